@@ -0,0 +1,196 @@
+use ra_db::{Cancelable, SyntaxDatabase};
+use ra_editor::find_node_at_offset;
+use ra_syntax::{AstNode, SyntaxNodeRef, TextRange, ast, SyntaxKind::*};
+use hir::{source_binder, Def, DefId, Expr, Function, Ty};
+
+use crate::{db::RootDatabase, FilePosition, FileRange, NavigationTarget};
+
+/// A function which calls, or is called by, the function of a call
+/// hierarchy request.
+#[derive(Debug)]
+pub struct CallItem {
+    pub target: NavigationTarget,
+    /// The names of the called function at the call sites. For incoming
+    /// calls, they are in the caller, for outgoing calls in the function of
+    /// the request.
+    pub call_sites: Vec<FileRange>,
+}
+
+/// The functions calling the function at `position`, which is either its
+/// name in the declaration or a reference to it. The callers are searched
+/// in all the crates of the workspace.
+pub(crate) fn incoming_calls(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Cancelable<Vec<CallItem>> {
+    if function_at(db, position)?.is_none() {
+        return Ok(Vec::new());
+    }
+    let mut calls = Calls::default();
+    for (file_id, range) in db.find_all_refs(position)? {
+        let file = db.source_file(file_id);
+        let name_ref = find_node_at_offset::<ast::NameRef>(file.syntax(), range.start())
+            .filter(|it| it.syntax().range() == range);
+        let name_ref = match name_ref {
+            Some(it) if is_call(it.syntax()) => it,
+            _ => continue,
+        };
+        let caller = source_binder::function_from_child_node(db, file_id, name_ref.syntax())?;
+        if let Some(caller) = caller {
+            calls.add(caller.def_id(), FileRange { file_id, range });
+        }
+    }
+    calls.finish(db)
+}
+
+/// The functions called by the function at `position`, which is either its
+/// name in the declaration or a reference to it. Calls are found in the body
+/// of the function, with the callees resolved by type inference.
+pub(crate) fn outgoing_calls(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Cancelable<Vec<CallItem>> {
+    let function = match function_at(db, position)? {
+        Some(it) => it,
+        None => return Ok(Vec::new()),
+    };
+    let (file_id, _) = function.def_id().source(db);
+    let fn_def = function.syntax(db);
+    let body = function.body(db)?;
+    let infer = function.infer(db)?;
+    let syntax_mapping = function.body_syntax_mapping(db)?;
+    let mut calls = Calls::default();
+    for (expr_id, expr) in body.exprs() {
+        let callee = match expr {
+            Expr::Call { callee, .. } => match &infer[*callee] {
+                Ty::FnDef { def_id, .. } => Some(*def_id),
+                _ => None,
+            },
+            Expr::MethodCall { .. } => infer.method_resolution(expr_id),
+            _ => None,
+        };
+        let callee = match callee {
+            Some(it) => it,
+            None => continue,
+        };
+        // tuple struct and enum variant constructors are not functions
+        match callee.resolve(db)? {
+            Def::Function(_) => (),
+            _ => continue,
+        }
+        let ptr = match syntax_mapping.expr_syntax(expr_id) {
+            Some(it) => it,
+            None => continue,
+        };
+        let call = fn_def
+            .borrowed()
+            .syntax()
+            .descendants()
+            .find(|it| it.range() == ptr.range() && it.kind() == ptr.kind());
+        let range = match call {
+            Some(call) => callee_name_range(call),
+            None => ptr.range(),
+        };
+        if let Some(frange) = file_id.original_file_range(db, range) {
+            calls.add(callee, frange);
+        }
+    }
+    calls.finish(db)
+}
+
+/// The function declared or referenced at `position`.
+fn function_at(db: &RootDatabase, position: FilePosition) -> Cancelable<Option<Function>> {
+    let (_, target) = ctry!(db.item_definition(position)?);
+    if target.kind != FN_DEF {
+        return Ok(None);
+    }
+    let file = db.source_file(target.file_id);
+    let fn_def = ctry!(file
+        .syntax()
+        .descendants()
+        .filter_map(ast::FnDef::cast)
+        .find(|it| it.syntax().range() == target.range));
+    source_binder::function_from_source(db, target.file_id, fn_def)
+}
+
+/// Whether `name_ref` is the name of the function of a call or of a method
+/// call, like `foo` in `foo()`, `a::foo()` or `x.foo()`.
+fn is_call(name_ref: SyntaxNodeRef) -> bool {
+    let parent = match name_ref.parent() {
+        Some(it) => it,
+        None => return false,
+    };
+    if parent.kind() == METHOD_CALL_EXPR {
+        return true;
+    }
+    let path_expr = parent
+        .ancestors()
+        .take_while(|it| it.kind() == PATH_SEGMENT || it.kind() == PATH)
+        .last()
+        .and_then(|path| path.parent())
+        .and_then(ast::PathExpr::cast);
+    let path_expr = match path_expr {
+        Some(it) => it,
+        None => return false,
+    };
+    // only the last segment of the path is the function
+    let is_last_segment = path_expr
+        .path()
+        .and_then(|it| it.segment())
+        .and_then(|it| it.name_ref())
+        .map(|it| it.syntax())
+        == Some(name_ref);
+    let is_callee = path_expr
+        .syntax()
+        .parent()
+        .and_then(ast::CallExpr::cast)
+        .and_then(|it| it.expr())
+        .map(|it| it.syntax())
+        == Some(path_expr.syntax());
+    is_last_segment && is_callee
+}
+
+/// For `a::foo(x)` and `y.foo(x)`, the range of `foo`.
+fn callee_name_range(call: SyntaxNodeRef) -> TextRange {
+    let name_ref = if let Some(call_expr) = ast::CallExpr::cast(call) {
+        match call_expr.expr() {
+            Some(ast::Expr::PathExpr(path_expr)) => path_expr
+                .path()
+                .and_then(|it| it.segment())
+                .and_then(|it| it.name_ref()),
+            _ => None,
+        }
+    } else {
+        ast::MethodCallExpr::cast(call).and_then(|it| it.name_ref())
+    };
+    match name_ref {
+        Some(it) => it.syntax().range(),
+        None => call.range(),
+    }
+}
+
+/// The call sites, grouped by the called or calling function, in the order
+/// of the first call.
+#[derive(Default)]
+struct Calls {
+    calls: Vec<(DefId, Vec<FileRange>)>,
+}
+
+impl Calls {
+    fn add(&mut self, def_id: DefId, call_site: FileRange) {
+        match self.calls.iter_mut().find(|(it, _)| *it == def_id) {
+            Some((_, call_sites)) => call_sites.push(call_site),
+            None => self.calls.push((def_id, vec![call_site])),
+        }
+    }
+
+    fn finish(self, db: &RootDatabase) -> Cancelable<Vec<CallItem>> {
+        let mut res = Vec::new();
+        for (def_id, call_sites) in self.calls {
+            if let Some(target) = NavigationTarget::from_def(db, def_id)? {
+                res.push(CallItem { target, call_sites });
+            }
+        }
+        Ok(res)
+    }
+}
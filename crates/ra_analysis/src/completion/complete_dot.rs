@@ -1,53 +1,80 @@
-use ra_syntax::ast::AstNode;
 use hir::{Ty, Def};
 
 use crate::Cancelable;
 use crate::completion::{CompletionContext, Completions, CompletionKind, CompletionItem, CompletionItemKind};
 
-/// Complete dot accesses, i.e. fields or methods (currently only fields).
+/// Complete dot accesses, i.e. fields or methods.
 pub(super) fn complete_dot(acc: &mut Completions, ctx: &CompletionContext) -> Cancelable<()> {
     let (function, receiver) = match (&ctx.function, ctx.dot_receiver) {
         (Some(function), Some(receiver)) => (function, receiver),
         _ => return Ok(()),
     };
     let infer_result = function.infer(ctx.db)?;
-    let receiver_ty = if let Some(ty) = infer_result.type_of_node(receiver.syntax()) {
-        ty
-    } else {
-        return Ok(());
+    let syntax_mapping = function.body_syntax_mapping(ctx.db)?;
+    let expr = match syntax_mapping.node_expr(receiver) {
+        Some(expr) => expr,
+        None => return Ok(()),
     };
+    let receiver_ty = infer_result[expr].clone();
     if !ctx.is_method_call {
-        complete_fields(acc, ctx, receiver_ty)?;
+        complete_fields(acc, ctx, receiver_ty.clone())?;
     }
+    complete_methods(acc, ctx, receiver_ty)?;
     Ok(())
 }
 
 fn complete_fields(acc: &mut Completions, ctx: &CompletionContext, receiver: Ty) -> Cancelable<()> {
-    // TODO: autoderef etc.
-    match receiver {
-        Ty::Adt { def_id, .. } => {
-            match def_id.resolve(ctx.db)? {
-                Def::Struct(s) => {
-                    let variant_data = s.variant_data(ctx.db)?;
-                    for field in variant_data.fields() {
-                        CompletionItem::new(CompletionKind::Reference, field.name().to_string())
+    for receiver in receiver.autoderef(ctx.db) {
+        match receiver {
+            Ty::Adt { def_id, .. } => {
+                match def_id.resolve(ctx.db)? {
+                    Def::Struct(s) => {
+                        let variant_data = s.variant_data(ctx.db)?;
+                        for field in variant_data.fields() {
+                            CompletionItem::new(
+                                CompletionKind::Reference,
+                                field.name().to_string(),
+                            )
                             .kind(CompletionItemKind::Field)
                             .add_to(acc);
+                        }
                     }
+                    // TODO unions
+                    _ => {}
                 }
-                // TODO unions
-                _ => {}
             }
-        }
-        Ty::Tuple(fields) => {
-            for (i, _ty) in fields.iter().enumerate() {
-                CompletionItem::new(CompletionKind::Reference, i.to_string())
-                    .kind(CompletionItemKind::Field)
+            Ty::Tuple(fields) => {
+                for (i, _ty) in fields.iter().enumerate() {
+                    CompletionItem::new(CompletionKind::Reference, i.to_string())
+                        .kind(CompletionItemKind::Field)
+                        .add_to(acc);
+                }
+            }
+            _ => {}
+        };
+    }
+    Ok(())
+}
+
+fn complete_methods(
+    acc: &mut Completions,
+    ctx: &CompletionContext,
+    receiver: Ty,
+) -> Cancelable<()> {
+    let krate = match ctx.module.as_ref().and_then(|it| it.krate(ctx.db)) {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    receiver.iterate_methods(ctx.db, &krate, |_ty, func| {
+        if let Some(sig) = func.signature_info(ctx.db) {
+            if sig.has_self_param {
+                CompletionItem::new(CompletionKind::Reference, sig.name)
+                    .kind(CompletionItemKind::Method)
                     .add_to(acc);
             }
         }
-        _ => {}
-    };
+        Ok(None::<()>)
+    })?;
     Ok(())
 }
 
@@ -72,6 +99,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_struct_field_completion_through_alias() {
+        check_ref_completion(
+            r"
+            struct A { the_field: u32 }
+            type B = A;
+            fn foo(b: B) {
+               b.<|>
+            }
+            ",
+            r#"the_field"#,
+        );
+    }
+
     #[test]
     fn test_struct_field_completion_self() {
         check_ref_completion(
@@ -83,7 +124,7 @@ mod tests {
                 }
             }
             ",
-            r#"the_field"#,
+            r#"the_field;foo"#,
         );
     }
 
@@ -99,4 +140,126 @@ mod tests {
             r#""#,
         );
     }
+
+    #[test]
+    fn test_method_completion() {
+        check_ref_completion(
+            r"
+            struct A {}
+            impl A {
+                fn the_method(&self) {}
+            }
+            fn foo(a: A) {
+               a.<|>
+            }
+            ",
+            r#"the_method"#,
+        );
+    }
+
+    #[test]
+    fn test_no_non_self_method() {
+        check_ref_completion(
+            r"
+            struct A {}
+            impl A {
+                fn the_method() {}
+            }
+            fn foo(a: A) {
+               a.<|>
+            }
+            ",
+            r#""#,
+        );
+    }
+
+    #[test]
+    fn test_field_completion_after_method_call() {
+        check_ref_completion(
+            r"
+            struct A { the_field: u32 }
+            struct B { a: A }
+            impl B {
+                fn get_a(&self) -> A { self.a }
+            }
+            fn foo(b: B) {
+               b.get_a().<|>
+            }
+            ",
+            r#"the_field"#,
+        );
+    }
+
+    #[test]
+    fn test_struct_field_completion_autoderef() {
+        check_ref_completion(
+            r"
+            struct A { the_field: u32 }
+            impl A {
+                fn foo(&self) {
+                    (&&self).<|>
+                }
+            }
+            ",
+            r#"the_field;foo"#,
+        );
+    }
+
+    #[test]
+    fn test_method_completion_autoderef() {
+        check_ref_completion(
+            r"
+            struct A {}
+            impl A {
+                fn the_method(&self) {}
+            }
+            fn foo(a: &&A) {
+               a.<|>
+            }
+            ",
+            r#"the_method"#,
+        );
+    }
+
+    #[test]
+    fn test_trait_method_completion() {
+        check_ref_completion(
+            r"
+            struct A {}
+            trait Trait {
+                fn the_method(&self);
+                fn provided_method(&self) {}
+                fn not_a_method();
+            }
+            trait OtherTrait {
+                fn other_method(&self);
+            }
+            impl Trait for A {
+                fn the_method(&self) {}
+                fn not_a_method() {}
+            }
+            fn foo(a: A) {
+               a.<|>
+            }
+            ",
+            r#"the_method;provided_method"#,
+        );
+    }
+
+    #[test]
+    fn test_completion_in_closure_param() {
+        check_ref_completion(
+            r"
+            struct A { the_field: u32 }
+            struct Iter;
+            impl Iter {
+                fn map<F: FnMut(A) -> B, B>(self, f: F) -> B {}
+            }
+            fn foo(iter: Iter) {
+               iter.map(|x| x.<|>)
+            }
+            ",
+            r#"the_field"#,
+        );
+    }
 }
@@ -13,9 +13,15 @@ pub(super) fn complete_path(acc: &mut Completions, ctx: &CompletionContext) -> C
         None => return Ok(()),
     };
     match def_id.resolve(ctx.db)? {
-        hir::Def::Module(module) => {
-            let module_scope = module.scope(ctx.db)?;
-            module_scope.entries().for_each(|(name, res)| {
+        hir::Def::Module(target) => {
+            let module_scope = target.scope(ctx.db)?;
+            let mut entries = module_scope
+                .entries()
+                .filter(|(_, res)| res.vis.is_visible_from(&target, module))
+                .collect::<Vec<_>>();
+            // the scope is a hash map, list the items in a stable order
+            entries.sort_by_key(|(name, _)| name.to_string());
+            entries.into_iter().for_each(|(name, res)| {
                 CompletionItem::new(CompletionKind::Reference, name.to_string())
                     .from_resolution(ctx, res)
                     .add_to(acc)
@@ -49,13 +55,64 @@ mod tests {
             use self::m::<|>;
 
             mod m {
-                struct Bar;
+                pub struct Bar;
             }
             ",
             "Bar",
         );
     }
 
+    #[test]
+    fn dont_complete_private_items_of_other_modules() {
+        check_reference_completion(
+            r"
+            use self::m::<|>;
+
+            mod m {
+                pub struct Bar;
+                pub(crate) struct Baz;
+                struct Private;
+                pub(super) fn spam() {}
+                mod inner {}
+            }
+            ",
+            "Bar;Baz;spam",
+        );
+    }
+
+    #[test]
+    fn completes_parent_items_in_module_of_file_not_reached_by_crate() {
+        check_reference_completion(
+            r"
+            //- /lib.rs
+            struct Bar;
+            //- /detached/child.rs
+            use super::<|>;
+            //- /detached/mod.rs
+            mod child;
+            struct Foo;
+            ",
+            "Foo;child",
+        );
+    }
+
+    #[test]
+    fn completes_items_from_macro_rules() {
+        check_reference_completion(
+            r"
+            use self::m::<|>;
+
+            mod m {
+                macro_rules! structs {
+                    ($($i:ident),*) => { $(pub struct $i;)* }
+                }
+                structs!(Foo, Bar);
+            }
+            ",
+            "Bar;Foo",
+        );
+    }
+
     #[test]
     fn completes_use_item_starting_with_crate() {
         check_reference_completion(
@@ -114,6 +171,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn completes_enum_variant_of_imported_enum() {
+        check_reference_completion(
+            "
+            //- /lib.rs
+            mod m { pub enum E { Foo, Bar { x: i32 } } }
+            use crate::m::E;
+            fn foo() { let _ = E::<|> }
+            ",
+            "Foo;Bar",
+        );
+    }
+
     #[test]
     fn dont_render_function_parens_in_use_item() {
         check_reference_completion(
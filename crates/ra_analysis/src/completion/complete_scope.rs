@@ -15,12 +15,13 @@ pub(super) fn complete_scope(acc: &mut Completions, ctx: &CompletionContext) ->
         None => return Ok(()),
     };
     if let Some(function) = &ctx.function {
-        let scopes = function.scopes(ctx.db);
+        let scopes = function.scopes(ctx.db)?;
         complete_fn(acc, &scopes, ctx.offset);
     }
 
     let module_scope = module.scope(ctx.db)?;
-    module_scope
+    // the scopes are hash maps, list the items in a stable order
+    let mut entries = module_scope
         .entries()
         .filter(|(_name, res)| {
             // Don't expose this item
@@ -32,28 +33,42 @@ pub(super) fn complete_scope(acc: &mut Completions, ctx: &CompletionContext) ->
                 }
             }
         })
-        .for_each(|(name, res)| {
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|(name, _)| name.to_string());
+    entries.into_iter().for_each(|(name, res)| {
+        CompletionItem::new(CompletionKind::Reference, name.to_string())
+            .from_resolution(ctx, res)
+            .add_to(acc)
+    });
+    if let Some(prelude) = module.prelude(ctx.db)? {
+        let prelude_scope = prelude.scope(ctx.db)?;
+        let mut entries = prelude_scope
+            .entries()
+            .filter(|(name, res)| {
+                res.vis == hir::Visibility::Public && module_scope.get(name).is_none()
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(name, _)| name.to_string());
+        entries.into_iter().for_each(|(name, res)| {
             CompletionItem::new(CompletionKind::Reference, name.to_string())
                 .from_resolution(ctx, res)
                 .add_to(acc)
         });
+    }
     Ok(())
 }
 
-fn complete_fn(acc: &mut Completions, scopes: &hir::FnScopes, offset: TextUnit) {
+fn complete_fn(acc: &mut Completions, scopes: &hir::ScopesWithSyntaxMapping, offset: TextUnit) {
     let mut shadowed = FxHashSet::default();
     scopes
         .scope_chain_for_offset(offset)
-        .flat_map(|scope| scopes.entries(scope).iter())
+        .flat_map(|scope| scopes.scopes.entries(scope).iter())
         .filter(|entry| shadowed.insert(entry.name()))
         .for_each(|entry| {
             CompletionItem::new(CompletionKind::Reference, entry.name().to_string())
                 .kind(CompletionItemKind::Binding)
                 .add_to(acc)
         });
-    if scopes.self_param.is_some() {
-        CompletionItem::new(CompletionKind::Reference, "self").add_to(acc);
-    }
 }
 
 #[cfg(test)]
@@ -120,7 +135,26 @@ mod tests {
                 <|>
             }
             ",
-            r#"quux "quux()$0";Foo;Baz"#,
+            r#"Baz;Foo;quux "quux()$0""#,
+        );
+    }
+
+    #[test]
+    fn completes_module_items_from_macros() {
+        check_reference_completion(
+            r"
+            macro_rules! foo {
+                ($i:ident) => { bar!($i); };
+            }
+            macro_rules! bar {
+                ($i:ident) => { struct $i; };
+            }
+            foo!(Foo);
+            fn quux() {
+                <|>
+            }
+            ",
+            r#"Foo;quux "quux()$0""#,
         );
     }
 
@@ -134,7 +168,33 @@ mod tests {
                 fn quux() { <|> }
             }
             ",
-            r#"quux "quux()$0";Bar"#,
+            r#"Bar;quux "quux()$0""#,
+        );
+    }
+
+    #[test]
+    fn completes_module_items_in_file_without_crate_root() {
+        check_reference_completion(
+            r"
+            //- /foo.rs
+            struct Foo;
+            fn f() { let _ = F<|> }
+            ",
+            r#"Foo;f "f()$0""#,
+        );
+    }
+
+    #[test]
+    fn completes_module_items_in_file_not_reached_by_crate() {
+        check_reference_completion(
+            r"
+            //- /lib.rs
+            struct Bar;
+            //- /detached.rs
+            struct Foo;
+            fn f() { let _ = F<|> }
+            ",
+            r#"Foo;f "f()$0""#,
         );
     }
 
@@ -177,8 +237,8 @@ mod tests {
             fn no_args() {}
             fn main() { no_<|> }
             ",
-            r#"no_args "no_args()$0"
-               main "main()$0""#,
+            r#"main "main()$0"
+               no_args "no_args()$0""#,
         );
         check_reference_completion(
             r"
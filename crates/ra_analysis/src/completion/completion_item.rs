@@ -30,8 +30,13 @@ pub enum CompletionItemKind {
     Struct,
     Enum,
     EnumVariant,
+    Trait,
+    TypeAlias,
+    Const,
+    Static,
     Binding,
     Field,
+    Method,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -137,6 +142,26 @@ impl Builder {
                 types: Some(hir::Def::Enum(..)),
                 ..
             } => CompletionItemKind::Enum,
+            PerNs {
+                types: Some(hir::Def::EnumVariant(..)),
+                ..
+            } => CompletionItemKind::EnumVariant,
+            PerNs {
+                types: Some(hir::Def::Trait(..)),
+                ..
+            } => CompletionItemKind::Trait,
+            PerNs {
+                types: Some(hir::Def::Type(..)),
+                ..
+            } => CompletionItemKind::TypeAlias,
+            PerNs {
+                values: Some(hir::Def::Const(..)),
+                ..
+            } => CompletionItemKind::Const,
+            PerNs {
+                values: Some(hir::Def::Static(..)),
+                ..
+            } => CompletionItemKind::Static,
             PerNs {
                 values: Some(hir::Def::Function(function)),
                 ..
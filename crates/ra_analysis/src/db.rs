@@ -85,6 +85,7 @@ salsa::database_storage! {
         impl ra_db::SyntaxDatabase {
             fn source_file() for ra_db::SourceFileQuery;
             fn file_lines() for ra_db::FileLinesQuery;
+            fn source_root_crates() for ra_db::SourceRootCratesQuery;
         }
         impl symbol_index::SymbolsDatabase {
             fn file_symbols() for symbol_index::FileSymbolsQuery;
@@ -93,19 +94,33 @@ salsa::database_storage! {
         impl hir::db::HirDatabase {
             fn hir_source_file() for hir::db::HirSourceFileQuery;
             fn expand_macro_invocation() for hir::db::ExpandMacroCallQuery;
+            fn crate_macros() for hir::db::CrateMacrosQuery;
             fn module_tree() for hir::db::ModuleTreeQuery;
             fn fn_scopes() for hir::db::FnScopesQuery;
+            fn fn_signature() for hir::db::FnSignatureQuery;
+            fn const_signature() for hir::db::ConstSignatureQuery;
+            fn static_signature() for hir::db::StaticSignatureQuery;
+            fn type_alias_ref() for hir::db::TypeAliasRefQuery;
+            fn body_hir() for hir::db::BodyHirQuery;
+            fn body_syntax_mapping() for hir::db::BodySyntaxMappingQuery;
             fn file_items() for hir::db::SourceFileItemsQuery;
             fn file_item() for hir::db::FileItemQuery;
             fn input_module_items() for hir::db::InputModuleItemsQuery;
+            fn private_path_segments() for hir::db::PrivatePathSegmentsQuery;
+            fn macro_expansion_items() for hir::db::MacroExpansionItemsQuery;
             fn item_map() for hir::db::ItemMapQuery;
             fn submodules() for hir::db::SubmodulesQuery;
             fn infer() for hir::db::InferQuery;
+            fn generic_params() for hir::db::GenericParamsQuery;
             fn type_for_def() for hir::db::TypeForDefQuery;
             fn type_for_field() for hir::db::TypeForFieldQuery;
             fn struct_data() for hir::db::StructDataQuery;
             fn enum_data() for hir::db::EnumDataQuery;
+            fn enum_variant_data() for hir::db::EnumVariantDataQuery;
             fn impls_in_module() for hir::db::ImplsInModuleQuery;
+            fn impls_in_crate() for hir::db::ImplsInCrateQuery;
+            fn implements() for hir::db::ImplementsQuery;
+            fn trait_data() for hir::db::TraitDataQuery;
         }
     }
 }
@@ -0,0 +1,223 @@
+use ra_db::{Cancelable, SyntaxDatabase};
+use ra_editor::find_node_at_offset;
+use ra_syntax::{ast, tokenize, AstNode, SyntaxKind, SyntaxKind::*};
+use hir::{HirFileId, MacroCallId, db::HirDatabase, source_binder};
+
+use crate::{db::RootDatabase, FilePosition};
+
+/// The result of the "expand macro" request: the name of the macro and its
+/// expansion, with all the nested macro calls expanded as well.
+#[derive(Debug)]
+pub struct ExpandedMacro {
+    pub name: String,
+    pub expansion: String,
+}
+
+pub(crate) fn expand_macro(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Cancelable<Option<ExpandedMacro>> {
+    let file = db.source_file(position.file_id);
+    let macro_call: ast::MacroCall = ctry!(find_node_at_offset(file.syntax(), position.offset));
+    // a definition doesn't expand to anything
+    if macro_call.macro_rules_name().is_some() {
+        return Ok(None);
+    }
+    let name = ctry!(macro_call.path()).syntax().text().to_string();
+    let macro_call_id = ctry!(source_binder::macro_call_from_node(
+        db,
+        position.file_id.into(),
+        macro_call
+    )?);
+    let expansion = ctry!(expand_macro_recursively(db, macro_call_id, 0)?);
+    Ok(Some(ExpandedMacro {
+        name,
+        expansion: insert_whitespaces(&expansion),
+    }))
+}
+
+fn expand_macro_recursively(
+    db: &RootDatabase,
+    macro_call_id: MacroCallId,
+    depth: u32,
+) -> Cancelable<Option<String>> {
+    let expansion = ctry!(db.expand_macro_invocation(macro_call_id));
+    let macro_file = HirFileId::from(macro_call_id);
+    let source_file = db.hir_source_file(macro_file);
+    let node = expansion.syntax_in(&source_file);
+    let node = node.borrowed();
+    let mut res = node.text().to_string();
+    if depth >= hir::MACRO_EXPANSION_DEPTH_LIMIT {
+        return Ok(Some(res));
+    }
+
+    let mut nested_expansions = Vec::new();
+    let macro_calls = node
+        .descendants()
+        .filter_map(ast::MacroCall::cast)
+        .filter(|it| it.macro_rules_name().is_none());
+    for macro_call in macro_calls {
+        let nested = match source_binder::macro_call_from_node(db, macro_file, macro_call)? {
+            Some(it) => it,
+            None => continue,
+        };
+        if let Some(text) = expand_macro_recursively(db, nested, depth + 1)? {
+            let range = macro_call.syntax().range() - node.range().start();
+            nested_expansions.push((range, text));
+        }
+    }
+    // replace from the end, so that the ranges of earlier calls stay valid
+    for (range, text) in nested_expansions.into_iter().rev() {
+        res.replace_range(range.start().to_usize()..range.end().to_usize(), &text);
+    }
+    Ok(Some(res))
+}
+
+/// Macros produce token trees, which don't remember the layout of the source,
+/// so expansions are formatted by putting the contents of `{}` and the
+/// statements ending with `;` on separate lines, and by removing the spaces
+/// around punctuation which are not needed.
+fn insert_whitespaces(text: &str) -> String {
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    let mut whitespace_before = false;
+    for token in tokenize(text) {
+        let len = token.len.to_usize();
+        let token_text = &text[offset..offset + len];
+        offset += len;
+        if token.kind == WHITESPACE {
+            whitespace_before = true;
+            continue;
+        }
+        tokens.push((token.kind, token_text, whitespace_before));
+        whitespace_before = false;
+    }
+
+    let mut res = String::new();
+    let mut indent = 0usize;
+    let mut delimiters: Vec<SyntaxKind> = Vec::new();
+    for (idx, &(kind, token_text, whitespace_before)) in tokens.iter().enumerate() {
+        let prev = idx.checked_sub(1).map(|it| tokens[it].0);
+        let next = tokens.get(idx + 1).map(|it| it.0);
+        if kind == R_CURLY && prev != Some(L_CURLY) {
+            indent = indent.saturating_sub(1);
+            new_line(&mut res, indent);
+        } else if whitespace_before && !at_line_start(&res) && needs_space(prev, kind, next) {
+            res.push(' ');
+        }
+        res.push_str(token_text);
+        match kind {
+            L_PAREN | L_BRACK => delimiters.push(kind),
+            R_PAREN | R_BRACK => {
+                delimiters.pop();
+            }
+            L_CURLY => {
+                delimiters.push(kind);
+                if next != Some(R_CURLY) {
+                    indent += 1;
+                    new_line(&mut res, indent);
+                }
+            }
+            R_CURLY => {
+                delimiters.pop();
+                match next {
+                    None | Some(COMMA) | Some(SEMI) | Some(DOT) | Some(QUESTION)
+                    | Some(R_PAREN) | Some(R_BRACK) | Some(R_CURLY) | Some(ELSE_KW) => (),
+                    Some(_) => new_line(&mut res, indent),
+                }
+            }
+            // `;` inside of `[u8; 4]` or `for (;;)` is not the end of a statement
+            SEMI => match delimiters.last() {
+                Some(L_PAREN) | Some(L_BRACK) => (),
+                _ if next.is_some() => new_line(&mut res, indent),
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+    res
+}
+
+fn needs_space(prev: Option<SyntaxKind>, kind: SyntaxKind, next: Option<SyntaxKind>) -> bool {
+    match (prev, kind) {
+        (_, COMMA) | (_, SEMI) | (_, COLON) | (_, QUESTION) | (_, R_PAREN) | (_, R_BRACK) => false,
+        (_, DOT) | (_, COLONCOLON) => false,
+        (Some(DOT), _) | (Some(COLONCOLON), _) | (Some(L_PAREN), _) | (Some(L_BRACK), _) => false,
+        // calls, `foo(..)` and `foo!(..)`
+        (Some(IDENT), L_PAREN) | (Some(EXCL), L_PAREN) | (Some(EXCL), L_BRACK) => false,
+        (Some(IDENT), EXCL) => next != Some(L_PAREN) && next != Some(L_BRACK),
+        _ => true,
+    }
+}
+
+fn new_line(buf: &mut String, indent: usize) {
+    while buf.ends_with(' ') {
+        buf.pop();
+    }
+    if !buf.is_empty() && !buf.ends_with('\n') {
+        buf.push('\n');
+    }
+    for _ in 0..indent {
+        buf.push_str("    ");
+    }
+}
+
+fn at_line_start(buf: &str) -> bool {
+    buf.rsplit('\n').next().unwrap().chars().all(|c| c == ' ')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_analysis::single_file_with_position;
+    use test_utils::assert_eq_text;
+
+    fn check_expand_macro(fixture: &str, name: &str, expansion: &str) {
+        let (analysis, position) = single_file_with_position(fixture);
+        let expanded = analysis.expand_macro(position).unwrap().unwrap();
+        assert_eq!(expanded.name, name);
+        assert_eq_text!(expansion.trim(), &expanded.expansion);
+    }
+
+    #[test]
+    fn expands_macro_rules() {
+        check_expand_macro(
+            "
+            macro_rules! foo {
+                ($i:ident) => { struct $i { x: u32, y: [u8; 4] } impl $i { fn new() -> $i { loop {} } } }
+            }
+            fo<|>o!(Foo);
+            ",
+            "foo",
+            "
+struct Foo {
+    x: u32, y: [u8; 4]
+}
+impl Foo {
+    fn new() -> Foo {
+        loop {}
+    }
+}",
+        );
+    }
+
+    #[test]
+    fn expands_nested_macro_calls() {
+        check_expand_macro(
+            "
+            macro_rules! foo {
+                () => { fn some_thing() -> u32 { let a = 0; a + 10 } }
+            }
+            macro_rules! bar {
+                () => { foo!(); }
+            }
+            bar!(<|>);
+            ",
+            "bar",
+            "
+fn some_thing() -> u32 {
+    let a = 0;
+    a + 10
+}",
+        );
+    }
+}
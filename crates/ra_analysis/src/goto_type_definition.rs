@@ -0,0 +1,53 @@
+use ra_db::{Cancelable, SyntaxDatabase};
+use ra_syntax::{AstNode, algo::find_leaf_at_offset, ast};
+use hir::{source_binder, Ty};
+
+use crate::{db::RootDatabase, FilePosition, NavigationTarget};
+
+/// Navigates to the struct or enum which is the type of the expression or
+/// the pattern at `position`. References are looked through, so `&Foo`
+/// leads to `Foo` as well.
+pub(crate) fn goto_type_definition(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Cancelable<Vec<NavigationTarget>> {
+    let mut ty = match type_at(db, position)? {
+        Some(it) => it,
+        None => return Ok(Vec::new()),
+    };
+    while let Ty::Ref(inner, _) = ty {
+        ty = Ty::clone(&inner);
+    }
+    let res = match ty {
+        Ty::Adt { def_id, .. } => NavigationTarget::from_def(db, def_id)?,
+        _ => None,
+    };
+    Ok(res.into_iter().collect())
+}
+
+/// The type of the innermost expression or pattern containing `position`.
+fn type_at(db: &RootDatabase, position: FilePosition) -> Cancelable<Option<Ty>> {
+    let file = db.source_file(position.file_id);
+    for leaf in find_leaf_at_offset(file.syntax(), position.offset) {
+        let fn_def = match leaf.ancestors().find_map(ast::FnDef::cast) {
+            Some(it) => it,
+            None => continue,
+        };
+        let function = ctry!(source_binder::function_from_source(
+            db,
+            position.file_id,
+            fn_def
+        )?);
+        let infer = function.infer(db)?;
+        let syntax_mapping = function.body_syntax_mapping(db)?;
+        let ty = leaf
+            .ancestors()
+            .take_while(|it| *it != fn_def.syntax())
+            .find_map(|it| infer.type_of_node(&syntax_mapping, it));
+        match ty {
+            Some(Ty::Unknown) | None => continue,
+            Some(ty) => return Ok(Some(ty.clone())),
+        }
+    }
+    Ok(None)
+}
@@ -48,7 +48,10 @@ pub(crate) fn type_of(db: &RootDatabase, frange: FileRange) -> Cancelable<Option
         parent_fn
     )?);
     let infer = function.infer(db)?;
-    Ok(infer.type_of_node(node).map(|t| t.to_string()))
+    let syntax_mapping = function.body_syntax_mapping(db)?;
+    Ok(infer
+        .type_of_node(&syntax_mapping, node)
+        .map(|ty| ty.to_string()))
 }
 
 // FIXME: this should not really use navigation target. Rather, approximatelly
@@ -123,6 +126,14 @@ impl NavigationTarget {
                 node.name()?.syntax().text().push_to(&mut string);
                 Some(string)
             })
+            .visit(|node: ast::EnumVariant| {
+                let enum_def = node.syntax().ancestors().find_map(ast::EnumDef::cast)?;
+                let mut string = String::new();
+                enum_def.name()?.syntax().text().push_to(&mut string);
+                string.push_str("::");
+                node.name()?.syntax().text().push_to(&mut string);
+                Some(string)
+            })
             .visit(|node: ast::TraitDef| {
                 let mut string = "trait ".to_string();
                 node.name()?.syntax().text().push_to(&mut string);
@@ -173,4 +184,19 @@ mod tests {
         assert_eq!(hover.range, TextRange::from_to(95.into(), 100.into()));
         assert_eq!(hover.info, "u32");
     }
+
+    #[test]
+    fn hover_shows_enum_variant() {
+        let (analysis, position) = single_file_with_position(
+            "
+            enum E { V1(u32), V2 }
+
+            fn main() {
+                let e = E::V<|>1(92);
+            }
+        ",
+        );
+        let hover = analysis.hover(position).unwrap().unwrap();
+        assert_eq!(hover.info, "```rust\nE::V1\n```");
+    }
 }
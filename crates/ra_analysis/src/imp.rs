@@ -1,22 +1,24 @@
 use std::sync::Arc;
 
 use salsa::Database;
+use rustc_hash::FxHashSet;
 
 use hir::{
     self, FnSignatureInfo, Problem, source_binder,
+    db::HirDatabase,
 };
-use ra_db::{FilesDatabase, SourceRoot, SourceRootId, SyntaxDatabase};
+use ra_db::{CrateGraph, FilesDatabase, SourceRoot, SourceRootId, SyntaxDatabase};
 use ra_editor::{self, find_node_at_offset, assists, LocalEdit, Severity};
 use ra_syntax::{
     ast::{self, ArgListOwner, Expr, NameOwner},
-    AstNode, SourceFileNode,
+    AstNode, SourceFileNode, SmolStr,
     SyntaxKind::*,
     SyntaxNodeRef, TextRange, TextUnit,
 };
 
 use crate::{
     AnalysisChange,
-    Cancelable, NavigationTarget,
+    Cancelable, NavigationTarget, LocalSyntaxPtr,
     CrateId, db, Diagnostic, FileId, FilePosition, FileRange, FileSystemEdit,
     Query, ReferenceResolution, RootChange, SourceChange, SourceFileEdit,
     symbol_index::{LibrarySymbolsQuery, FileSymbol},
@@ -26,6 +28,8 @@ impl db::RootDatabase {
     pub(crate) fn apply_change(&mut self, change: AnalysisChange) {
         log::info!("apply_change {:?}", change);
         // self.gc_syntax_trees();
+        let roots_changed = !change.new_roots.is_empty() || !change.roots_changed.is_empty();
+        let files_changed = !change.files_changed.is_empty();
         if !change.new_roots.is_empty() {
             let mut local_roots = Vec::clone(&self.local_roots());
             for (root_id, is_local) in change.new_roots {
@@ -58,12 +62,66 @@ impl db::RootDatabase {
             self.query_mut(ra_db::LibraryRootsQuery)
                 .set((), Arc::new(libraries));
         }
-        if let Some(crate_graph) = change.crate_graph {
+        // any edit can add or remove a `mod` declaration
+        if change.crate_graph.is_some() || roots_changed || files_changed {
+            self.set_crate_graph(change.crate_graph);
+        }
+    }
+
+    /// Sets the crate graph, with a detached crate for each local file which
+    /// none of the crates reaches, so that every file is analyzed.
+    fn set_crate_graph(&mut self, crate_graph: Option<CrateGraph>) {
+        if let Some(crate_graph) = crate_graph {
+            // the files reached by the new crates decide which ones are
+            // detached
+            self.query_mut(ra_db::CrateGraphQuery)
+                .set((), Arc::new(crate_graph));
+        }
+        let mut crate_graph = CrateGraph::clone(&self.crate_graph());
+        crate_graph.remove_detached_crates();
+        let reached = match self.local_crate_files(&crate_graph) {
+            Ok(it) => it,
+            // nothing is canceled while the changes are applied
+            Err(_) => return,
+        };
+        for &root_id in self.local_roots().iter() {
+            let source_root = self.source_root(root_id);
+            let mut file_ids = source_root.files.values().cloned().collect::<Vec<_>>();
+            file_ids.sort();
+            for file_id in file_ids {
+                if !reached.contains(&file_id) {
+                    crate_graph.add_detached_crate_root(file_id);
+                }
+            }
+        }
+        // editing a file usually keeps the crate graph as is, and setting it
+        // would invalidate everything
+        if crate_graph != *self.crate_graph() {
             self.query_mut(ra_db::CrateGraphQuery)
                 .set((), Arc::new(crate_graph))
         }
     }
 
+    /// The files of the modules of the crates `crate_graph` has in the local
+    /// roots.
+    fn local_crate_files(&self, crate_graph: &CrateGraph) -> Cancelable<FxHashSet<FileId>> {
+        let local_roots = self.local_roots();
+        let mut res = FxHashSet::default();
+        for crate_id in crate_graph.iter() {
+            let root_file = crate_graph.crate_root(crate_id);
+            if !local_roots.contains(&self.file_source_root(root_file)) {
+                continue;
+            }
+            let mut modules = Vec::new();
+            modules.extend(source_binder::module_from_file_id(self, root_file)?);
+            while let Some(module) = modules.pop() {
+                res.insert(module.file_id());
+                modules.extend(module.children().map(|(_name, it)| it));
+            }
+        }
+        Ok(res)
+    }
+
     fn apply_root_change(&mut self, root_id: SourceRootId, root_change: RootChange) {
         let mut source_root = SourceRoot::clone(&self.source_root(root_id));
         for add_file in root_change.added {
@@ -147,21 +205,9 @@ impl db::RootDatabase {
         let syntax = file.syntax();
         if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(syntax, position.offset) {
             let mut rr = ReferenceResolution::new(name_ref.syntax().range());
-            if let Some(fn_descr) =
-                source_binder::function_from_child_node(self, position.file_id, name_ref.syntax())?
-            {
-                let scope = fn_descr.scopes(self);
-                // First try to resolve the symbol locally
-                if let Some(entry) = scope.resolve_local_name(name_ref) {
-                    rr.resolves_to.push(NavigationTarget {
-                        file_id: position.file_id,
-                        name: entry.name().to_string().into(),
-                        range: entry.ptr().range(),
-                        kind: NAME,
-                        ptr: None,
-                    });
-                    return Ok(Some(rr));
-                };
+            if let Some(nav) = self.resolve_name_ref(position.file_id, name_ref)? {
+                rr.resolves_to.push(nav);
+                return Ok(Some(rr));
             }
             // If that fails try the index based approach.
             rr.resolves_to.extend(
@@ -196,6 +242,24 @@ impl db::RootDatabase {
                 }
             }
         }
+        // Inside of a macro call, look for the reference in the expansion.
+        if let Some((macro_file, offset)) = source_binder::descend_into_macros(self, position)? {
+            let expansion = self.hir_source_file(macro_file);
+            if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(expansion.syntax(), offset)
+            {
+                if let Some(frange) =
+                    macro_file.original_file_range(self, name_ref.syntax().range())
+                {
+                    let mut rr = ReferenceResolution::new(frange.range);
+                    rr.resolves_to.extend(
+                        self.index_resolve(name_ref)?
+                            .into_iter()
+                            .map(NavigationTarget::from_symbol),
+                    );
+                    return Ok(Some(rr));
+                }
+            }
+        }
         Ok(None)
     }
 
@@ -206,7 +270,7 @@ impl db::RootDatabase {
         let file = self.source_file(position.file_id);
         // Find the binding associated with the offset
         let (binding, descr) = match find_binding(self, &file, position)? {
-            None => return Ok(Vec::new()),
+            None => return self.find_all_item_refs(position),
             Some(it) => it,
         };
 
@@ -217,7 +281,7 @@ impl db::RootDatabase {
             .collect::<Vec<_>>();
         ret.extend(
             descr
-                .scopes(self)
+                .scopes(self)?
                 .find_all_refs(binding)
                 .into_iter()
                 .map(|ref_desc| (position.file_id, ref_desc.range)),
@@ -245,7 +309,7 @@ impl db::RootDatabase {
                 position.file_id,
                 name_ref.syntax(),
             )?);
-            let scope = descr.scopes(db);
+            let scope = descr.scopes(db)?;
             let resolved = ctry!(scope.resolve_local_name(name_ref));
             let resolved = resolved.ptr().resolve(source_file);
             let binding = ctry!(find_node_at_offset::<ast::BindPat>(
@@ -256,6 +320,108 @@ impl db::RootDatabase {
         }
     }
 
+    /// References to items can be anywhere in the workspace. The candidates
+    /// are the names with the same text in the local files, and those which
+    /// resolve to the item are its references.
+    fn find_all_item_refs(&self, position: FilePosition) -> Cancelable<Vec<(FileId, TextRange)>> {
+        let (name, target) = match self.item_definition(position)? {
+            Some(it) => it,
+            None => return Ok(Vec::new()),
+        };
+        // like for locals, the declaration comes first
+        let mut res = Vec::new();
+        res.extend(self.declaration_name(&target)?);
+        for &root in self.local_roots().iter() {
+            for &file_id in self.source_root(root).files.values() {
+                let text = self.file_text(file_id);
+                let file = self.source_file(file_id);
+                for (offset, _) in text.match_indices(name.as_str()) {
+                    let offset = TextUnit::from_usize(offset);
+                    let name_ref = match find_node_at_offset::<ast::NameRef>(file.syntax(), offset)
+                    {
+                        // not just a part of a longer name
+                        Some(it) if it.text() == name => it,
+                        _ => continue,
+                    };
+                    let resolved = match self.resolve_name_ref(file_id, name_ref)? {
+                        Some(it) => it,
+                        None => continue,
+                    };
+                    let is_target = |it: &NavigationTarget| {
+                        it.file_id == target.file_id && it.range == target.range
+                    };
+                    // `x` in `Foo { x }` is the local, but the field as well
+                    let is_field_ref = !is_target(&resolved)
+                        && resolved.kind == NAME
+                        && match self.resolve_named_field(file_id, name_ref)? {
+                            Some(field) => is_target(&field),
+                            None => false,
+                        };
+                    if is_target(&resolved) || is_field_ref {
+                        res.push((file_id, name_ref.syntax().range()));
+                    }
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// The name and the definition of the item at `position`, which can be
+    /// either a reference to the item or its declaration.
+    pub(crate) fn item_definition(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Option<(SmolStr, NavigationTarget)>> {
+        let file = self.source_file(position.file_id);
+        let syntax = file.syntax();
+        if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(syntax, position.offset) {
+            let target = ctry!(self.resolve_name_ref(position.file_id, name_ref)?);
+            return Ok(Some((name_ref.text(), target)));
+        }
+        let name = ctry!(find_node_at_offset::<ast::Name>(syntax, position.offset));
+        let item = ctry!(name.syntax().parent());
+        if let Some(module) = ast::Module::cast(item) {
+            if module.has_semi() {
+                let child_module = ctry!(source_binder::module_from_declaration(
+                    self,
+                    position.file_id,
+                    module
+                )?);
+                let target = ctry!(NavigationTarget::from_def(self, child_module.def_id(self))?);
+                return Ok(Some((name.text(), target)));
+            }
+        }
+        let target = NavigationTarget {
+            file_id: position.file_id,
+            name: name.text(),
+            range: item.range(),
+            kind: item.kind(),
+            ptr: None,
+        };
+        Ok(Some((name.text(), target)))
+    }
+
+    /// The name in the declaration of the item. For modules with a file of
+    /// their own, that's the name in `mod foo;`.
+    pub(crate) fn declaration_name(
+        &self,
+        target: &NavigationTarget,
+    ) -> Cancelable<Option<(FileId, TextRange)>> {
+        if target.kind == MODULE && target.range.is_empty() {
+            let module = ctry!(source_binder::module_from_file_id(self, target.file_id)?);
+            let (file_id, decl) = ctry!(module.parent_link_source(self));
+            let name = ctry!(decl.borrowed().name());
+            return Ok(Some((file_id, name.syntax().range())));
+        }
+        let file = self.source_file(target.file_id);
+        let item = ctry!(file
+            .syntax()
+            .descendants()
+            .find(|it| it.kind() == target.kind && it.range() == target.range));
+        let name = ctry!(item.children().find(|it| it.kind() == NAME));
+        Ok(Some((target.file_id, name.range())))
+    }
+
     pub(crate) fn diagnostics(&self, file_id: FileId) -> Cancelable<Vec<Diagnostic>> {
         let syntax = self.source_file(file_id);
 
@@ -269,7 +435,7 @@ impl db::RootDatabase {
             })
             .collect::<Vec<_>>();
         if let Some(m) = source_binder::module_from_file_id(self, file_id)? {
-            for (name_node, problem) in m.problems(self) {
+            for (name_node, problem) in m.problems(self)? {
                 let source_root = self.file_source_root(file_id);
                 let diag = match problem {
                     Problem::UnresolvedModule { candidate } => {
@@ -313,6 +479,12 @@ impl db::RootDatabase {
                             fix: Some(fix),
                         }
                     }
+                    Problem::PrivateItem { name } => Diagnostic {
+                        range: name_node.range(),
+                        message: format!("`{}` is private", name),
+                        severity: Severity::Error,
+                        fix: None,
+                    },
                 };
                 res.push(diag)
             }
@@ -397,25 +569,110 @@ impl db::RootDatabase {
         Ok(None)
     }
 
-    pub(crate) fn rename(
+    /// Resolves `name_ref` semantically: first as a local, then using type
+    /// inference and finally as a path in the module.
+    pub(crate) fn resolve_name_ref(
         &self,
-        position: FilePosition,
-        new_name: &str,
-    ) -> Cancelable<Vec<SourceFileEdit>> {
-        let res = self
-            .find_all_refs(position)?
-            .iter()
-            .map(|(file_id, text_range)| SourceFileEdit {
-                file_id: *file_id,
-                edit: {
-                    let mut builder = ra_text_edit::TextEditBuilder::default();
-                    builder.replace(*text_range, new_name.into());
-                    builder.finish()
-                },
-            })
-            .collect::<Vec<_>>();
-        Ok(res)
+        file_id: FileId,
+        name_ref: ast::NameRef,
+    ) -> Cancelable<Option<NavigationTarget>> {
+        if let Some(fn_descr) =
+            source_binder::function_from_child_node(self, file_id, name_ref.syntax())?
+        {
+            let scope = fn_descr.scopes(self)?;
+            // First try to resolve the symbol locally
+            if let Some(entry) = scope.resolve_local_name(name_ref) {
+                return Ok(Some(NavigationTarget {
+                    file_id,
+                    name: entry.name().to_string().into(),
+                    range: entry.ptr().range(),
+                    kind: NAME,
+                    ptr: None,
+                }));
+            };
+            // Then try methods and fields, which depend on the types
+            if let Some(nav) = self.resolve_by_inference(&fn_descr, name_ref)? {
+                return Ok(Some(nav));
+            }
+        }
+        if let Some(nav) = self.resolve_named_field(file_id, name_ref)? {
+            return Ok(Some(nav));
+        }
+        // Then try to resolve the path in the module
+        self.resolve_path(file_id, name_ref)
     }
+
+    /// Resolves methods, fields and associated functions (like `Foo::new`)
+    /// using the results of type inference.
+    fn resolve_by_inference(
+        &self,
+        function: &hir::Function,
+        name_ref: ast::NameRef,
+    ) -> Cancelable<Option<NavigationTarget>> {
+        let parent = ctry!(name_ref.syntax().parent());
+        let infer = function.infer(self)?;
+        let syntax_mapping = function.body_syntax_mapping(self)?;
+        if let Some(path_expr) = name_ref.syntax().ancestors().find_map(ast::PathExpr::cast) {
+            let last_name_ref = path_expr
+                .path()
+                .and_then(|it| it.segment())
+                .and_then(|it| it.name_ref());
+            if last_name_ref == Some(name_ref) {
+                let expr = ast::Expr::cast(path_expr.syntax()).unwrap();
+                let expr = ctry!(syntax_mapping.node_expr(expr));
+                let def_id = ctry!(infer.assoc_resolution(expr));
+                return NavigationTarget::from_def(self, def_id);
+            }
+        }
+        let expr = ctry!(ast::Expr::cast(parent).and_then(|it| syntax_mapping.node_expr(it)));
+        if parent.kind() == METHOD_CALL_EXPR {
+            let def_id = ctry!(infer.method_resolution(expr));
+            return NavigationTarget::from_def(self, def_id);
+        }
+        if parent.kind() == FIELD_EXPR {
+            let def_id = ctry!(infer.field_resolution(expr));
+            return NavigationTarget::from_field(self, def_id, &name_ref.text());
+        }
+        Ok(None)
+    }
+
+    /// Resolves the fields of struct literals, like `x` in `S { x: 1 }`.
+    fn resolve_named_field(
+        &self,
+        file_id: FileId,
+        name_ref: ast::NameRef,
+    ) -> Cancelable<Option<NavigationTarget>> {
+        let field = ctry!(name_ref.syntax().parent().and_then(ast::NamedField::cast));
+        let struct_lit = ctry!(field.syntax().ancestors().find_map(ast::StructLit::cast));
+        let path = ctry!(struct_lit.path().and_then(hir::Path::from_ast));
+        let module = ctry!(source_binder::module_from_child_node(
+            self,
+            file_id,
+            name_ref.syntax()
+        )?);
+        let def_id = ctry!(module.resolve_path(self, &path)?.take_types());
+        NavigationTarget::from_field(self, def_id, &name_ref.text())
+    }
+
+    /// Resolves the path which `name_ref` ends in the containing module.
+    fn resolve_path(
+        &self,
+        file_id: FileId,
+        name_ref: ast::NameRef,
+    ) -> Cancelable<Option<NavigationTarget>> {
+        // the innermost path is the one ending with `name_ref`
+        let path = ctry!(name_ref.syntax().ancestors().find_map(ast::Path::cast));
+        let path = ctry!(hir::Path::from_ast(path));
+        let module = ctry!(source_binder::module_from_child_node(
+            self,
+            file_id,
+            name_ref.syntax()
+        )?);
+        let resolved = module.resolve_path(self, &path)?;
+        let def_id = ctry!(resolved.take_types().or(resolved.take_values()));
+        NavigationTarget::from_def(self, def_id)
+    }
+
     fn index_resolve(&self, name_ref: ast::NameRef) -> Cancelable<Vec<FileSymbol>> {
         let name = name_ref.text();
         let mut query = Query::new(name.to_string());
@@ -425,6 +682,75 @@ impl db::RootDatabase {
     }
 }
 
+impl NavigationTarget {
+    pub(crate) fn from_def(
+        db: &db::RootDatabase,
+        def_id: hir::DefId,
+    ) -> Cancelable<Option<NavigationTarget>> {
+        let (file_id, node) = def_id.source(db);
+        let node = node.borrowed();
+        // modules with a file of their own are navigated to the start of the
+        // file, like for `mod foo;`
+        if node.kind() == SOURCE_FILE {
+            let module = match def_id.resolve(db)? {
+                hir::Def::Module(it) => it,
+                _ => return Ok(None),
+            };
+            let name = match module.name() {
+                Some(name) => name.to_string().into(),
+                None => "".into(),
+            };
+            return Ok(Some(NavigationTarget {
+                file_id: module.file_id(),
+                name,
+                range: TextRange::offset_len(0.into(), 0.into()),
+                kind: MODULE,
+                ptr: None,
+            }));
+        }
+        let frange = ctry!(file_id.original_file_range(db, node.range()));
+        let name = match node.children().find(|it| it.kind() == NAME) {
+            Some(name) => name.text().to_string().into(),
+            None => "".into(),
+        };
+        // items expanded from macros have no node in the original file
+        let ptr = if file_id == frange.file_id.into() {
+            Some(LocalSyntaxPtr::new(node))
+        } else {
+            None
+        };
+        Ok(Some(NavigationTarget {
+            file_id: frange.file_id,
+            name,
+            range: frange.range,
+            kind: node.kind(),
+            ptr,
+        }))
+    }
+
+    /// The field called `name` of the struct or enum variant `def_id`.
+    fn from_field(
+        db: &db::RootDatabase,
+        def_id: hir::DefId,
+        name: &SmolStr,
+    ) -> Cancelable<Option<NavigationTarget>> {
+        let (file_id, node) = def_id.source(db);
+        let field = ctry!(node
+            .borrowed()
+            .descendants()
+            .filter_map(ast::NamedFieldDef::cast)
+            .find(|it| it.name().map(|it| it.text()).as_ref() == Some(name)));
+        let frange = ctry!(file_id.original_file_range(db, field.syntax().range()));
+        Ok(Some(NavigationTarget {
+            file_id: frange.file_id,
+            name: name.clone(),
+            range: frange.range,
+            kind: NAMED_FIELD_DEF,
+            ptr: None,
+        }))
+    }
+}
+
 impl SourceChange {
     pub(crate) fn from_local_edit(file_id: FileId, edit: LocalEdit) -> SourceChange {
         let file_edit = SourceFileEdit {
@@ -0,0 +1,126 @@
+use ra_db::{Cancelable, FilesDatabase, SyntaxDatabase};
+use ra_editor::find_node_at_offset;
+use ra_syntax::{AstNode, ast, SyntaxKind::*};
+use hir::{db::HirDatabase, source_binder, Def, DefId, ImplBlock};
+
+use crate::{db::RootDatabase, FilePosition, NavigationTarget};
+
+/// Lists the impl blocks of the struct, the enum or the trait at `position`,
+/// which is either its name in the declaration or a reference to it. For
+/// types, both the inherent and the trait impls are listed. Impls are looked
+/// up in the crate of the definition and in the crates of the workspace, with
+/// the per crate index built from `impls_in_module`.
+pub(crate) fn goto_implementation(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Cancelable<Vec<NavigationTarget>> {
+    let def_id = match def_at(db, position)? {
+        Some(it) => it,
+        None => return Ok(Vec::new()),
+    };
+    let mut impl_blocks = Vec::new();
+    for krate in crates_to_search(db, def_id)? {
+        let crate_impls = db.impls_in_crate(krate)?;
+        match def_id.resolve(db)? {
+            Def::Struct(_) | Def::Enum(_) => {
+                let ty = db.type_for_def(def_id)?;
+                let inherent_impls = crate_impls.lookup_impl_blocks(db, &ty);
+                let trait_impls = crate_impls.lookup_trait_impl_blocks(db, &ty);
+                for impl_block in inherent_impls.chain(trait_impls) {
+                    impl_blocks.push(impl_block?);
+                }
+            }
+            Def::Trait(tr) => {
+                for it in crate_impls.lookup_impl_blocks_for_trait(db, &tr) {
+                    impl_blocks.push(it?.1);
+                }
+            }
+            _ => (),
+        }
+    }
+    let res = impl_blocks
+        .iter()
+        .filter_map(|it| NavigationTarget::from_impl_block(db, it).transpose())
+        .collect::<Cancelable<Vec<_>>>()?;
+    Ok(res)
+}
+
+/// The struct, enum or trait declared or referenced at `position`.
+fn def_at(db: &RootDatabase, position: FilePosition) -> Cancelable<Option<DefId>> {
+    let file = db.source_file(position.file_id);
+    let syntax = file.syntax();
+    if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(syntax, position.offset) {
+        let path = ctry!(name_ref.syntax().ancestors().find_map(ast::Path::cast));
+        let path = ctry!(hir::Path::from_ast(path));
+        let module = ctry!(source_binder::module_from_child_node(
+            db,
+            position.file_id,
+            name_ref.syntax()
+        )?);
+        return Ok(module.resolve_path(db, &path)?.take_types());
+    }
+    let name = ctry!(find_node_at_offset::<ast::Name>(syntax, position.offset));
+    let item = ctry!(name.syntax().parent());
+    match item.kind() {
+        STRUCT_DEF | ENUM_DEF | TRAIT_DEF => (),
+        _ => return Ok(None),
+    }
+    let module = ctry!(source_binder::module_from_child_node(
+        db,
+        position.file_id,
+        item
+    )?);
+    let scope = module.scope(db)?;
+    let def_id = scope
+        .entries()
+        .filter(|(entry_name, _)| entry_name.to_string() == name.text())
+        .filter_map(|(_, res)| res.def_id.take_types())
+        .find(|def_id| def_id.source(db).1.borrowed() == item);
+    Ok(def_id)
+}
+
+/// The crate of `def_id` and the crates of the workspace, each one once.
+fn crates_to_search(db: &RootDatabase, def_id: DefId) -> Cancelable<Vec<hir::Crate>> {
+    let mut res = Vec::new();
+    res.extend(def_id.module(db)?.krate(db));
+    let local_roots = db.local_roots();
+    let crate_graph = db.crate_graph();
+    for crate_id in crate_graph.iter() {
+        let root_file = crate_graph.crate_root(crate_id);
+        if !local_roots.contains(&db.file_source_root(root_file)) {
+            continue;
+        }
+        let krate = match source_binder::module_from_file_id(db, root_file)? {
+            Some(module) => module.krate(db),
+            None => None,
+        };
+        if let Some(krate) = krate {
+            if !res.contains(&krate) {
+                res.push(krate);
+            }
+        }
+    }
+    Ok(res)
+}
+
+impl NavigationTarget {
+    fn from_impl_block(
+        db: &RootDatabase,
+        impl_block: &ImplBlock,
+    ) -> Cancelable<Option<NavigationTarget>> {
+        let (file_id, node) = impl_block.source(db);
+        let node = node.borrowed();
+        let frange = ctry!(file_id.original_file_range(db, node.syntax().range()));
+        let name = match node.target_type() {
+            Some(ty) => ty.syntax().text().to_string().into(),
+            None => "".into(),
+        };
+        Ok(Some(NavigationTarget {
+            file_id: frange.file_id,
+            name,
+            range: frange.range,
+            kind: IMPL_BLOCK,
+            ptr: None,
+        }))
+    }
+}
@@ -22,6 +22,11 @@ mod runnables;
 mod extend_selection;
 mod syntax_highlighting;
 mod hover;
+mod expand_macro;
+mod rename;
+mod goto_type_definition;
+mod impls;
+mod call_hierarchy;
 
 use std::{fmt, sync::Arc};
 
@@ -37,6 +42,9 @@ use crate::symbol_index::{SymbolIndex, FileSymbol};
 pub use crate::{
     completion::{CompletionItem, CompletionItemKind, InsertText},
     runnables::{Runnable, RunnableKind},
+    expand_macro::ExpandedMacro,
+    rename::RenameError,
+    call_hierarchy::CallItem,
 };
 pub use ra_editor::{
     Fold, FoldKind, HighlightedRange, LineIndex, StructureNode, Severity
@@ -45,7 +53,7 @@ pub use hir::FnSignatureInfo;
 
 pub use ra_db::{
     Canceled, Cancelable, FilePosition, FileRange, LocalSyntaxPtr,
-    CrateGraph, CrateId, SourceRootId, FileId, SyntaxDatabase, FilesDatabase
+    CrateGraph, CrateId, CfgOptions, SourceRootId, FileId, SyntaxDatabase, FilesDatabase
 };
 
 #[derive(Default)]
@@ -352,6 +360,11 @@ impl Analysis {
         let file = self.db.source_file(file_id);
         ra_editor::syntax_tree(&file)
     }
+    /// Returns the expansion of the macro call at the position, with nested
+    /// macro calls expanded recursively. For debug purposes.
+    pub fn expand_macro(&self, position: FilePosition) -> Cancelable<Option<ExpandedMacro>> {
+        expand_macro::expand_macro(&self.db, position)
+    }
     /// Returns an edit to remove all newlines in the range, cleaning up minor
     /// stuff like trailing commas.
     pub fn join_lines(&self, frange: FileRange) -> SourceChange {
@@ -399,6 +412,26 @@ impl Analysis {
     ) -> Cancelable<Option<ReferenceResolution>> {
         self.db.approximately_resolve_symbol(position)
     }
+    /// Returns the definition of the type of the expression or the pattern at
+    /// point.
+    pub fn goto_type_definition(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Vec<NavigationTarget>> {
+        goto_type_definition::goto_type_definition(&self.db, position)
+    }
+    /// Returns the impl blocks of the type or the trait at point.
+    pub fn goto_implementation(&self, position: FilePosition) -> Cancelable<Vec<NavigationTarget>> {
+        impls::goto_implementation(&self.db, position)
+    }
+    /// Returns the functions which call the function at point.
+    pub fn incoming_calls(&self, position: FilePosition) -> Cancelable<Vec<CallItem>> {
+        call_hierarchy::incoming_calls(&self.db, position)
+    }
+    /// Returns the functions which are called by the function at point.
+    pub fn outgoing_calls(&self, position: FilePosition) -> Cancelable<Vec<CallItem>> {
+        call_hierarchy::outgoing_calls(&self.db, position)
+    }
     /// Finds all usages of the reference at point.
     pub fn find_all_refs(&self, position: FilePosition) -> Cancelable<Vec<(FileId, TextRange)>> {
         self.db.find_all_refs(position)
@@ -453,13 +486,13 @@ impl Analysis {
         hover::type_of(&*self.db, frange)
     }
     /// Returns the edit required to rename reference at the position to the new
-    /// name.
+    /// name, or the reason why it can't be renamed.
     pub fn rename(
         &self,
         position: FilePosition,
         new_name: &str,
-    ) -> Cancelable<Vec<SourceFileEdit>> {
-        self.db.rename(position, new_name)
+    ) -> Cancelable<Result<SourceChange, RenameError>> {
+        rename::rename(&self.db, position, new_name)
     }
 }
 
@@ -92,7 +92,7 @@ impl MockAnalysis {
             assert!(path.starts_with('/'));
             let path = RelativePathBuf::from_path(&path[1..]).unwrap();
             let file_id = file_map.add(path.clone());
-            if path == "/lib.rs" || path == "/main.rs" {
+            if path == "lib.rs" || path == "main.rs" {
                 crate_graph.add_crate_root(file_id);
             }
             change.add_file(source_root, file_id, path, Arc::new(contents));
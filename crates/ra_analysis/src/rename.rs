@@ -0,0 +1,277 @@
+use std::fmt;
+
+use ra_db::{Cancelable, FilesDatabase, SyntaxDatabase};
+use ra_editor::find_node_at_offset;
+use ra_syntax::{ast, tokenize, AstNode, SyntaxNodeRef, TextRange, TextUnit, SyntaxKind::*};
+use ra_text_edit::TextEditBuilder;
+use hir::source_binder;
+use relative_path::RelativePathBuf;
+use rustc_hash::FxHashMap;
+
+use crate::{
+    db::RootDatabase, FileId, FilePosition, FileSystemEdit, NavigationTarget, SourceChange,
+    SourceFileEdit,
+};
+
+/// Why a rename was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameError {
+    /// The new name is not an identifier, like `foo bar` or `fn`.
+    InvalidName(String),
+    /// There is nothing that can be renamed at the position.
+    NothingToRename,
+    /// The new name is already used by an item next to the renamed one.
+    AlreadyDefined(String),
+    /// The new name would shadow, or would be shadowed by, a local.
+    Shadowing(String),
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenameError::InvalidName(name) => write!(f, "`{}` is not a valid identifier", name),
+            RenameError::NothingToRename => write!(f, "nothing to rename"),
+            RenameError::AlreadyDefined(name) => write!(f, "`{}` is already defined", name),
+            RenameError::Shadowing(name) => write!(f, "`{}` would conflict with a local", name),
+        }
+    }
+}
+
+/// Renames the local or the item at `position` and all of its references in
+/// the workspace. Renaming a module with a file of its own also moves the
+/// file (and the directory with its submodules, if any).
+pub(crate) fn rename(
+    db: &RootDatabase,
+    position: FilePosition,
+    new_name: &str,
+) -> Cancelable<Result<SourceChange, RenameError>> {
+    if !is_valid_identifier(new_name) {
+        return Ok(Err(RenameError::InvalidName(new_name.to_string())));
+    }
+    let refs = db.find_all_refs(position)?;
+    let (_, target) = match db.item_definition(position)? {
+        Some(it) if !refs.is_empty() => it,
+        _ => return Ok(Err(RenameError::NothingToRename)),
+    };
+    let is_local = target.kind == NAME || target.kind == BIND_PAT;
+    if !is_local {
+        if let Some(err) = check_item_collision(db, &target, new_name)? {
+            return Ok(Err(err));
+        }
+    }
+    if let Some(err) = check_shadowing(db, &refs, is_local, new_name)? {
+        return Ok(Err(err));
+    }
+
+    let mut edits: FxHashMap<FileId, TextEditBuilder> = FxHashMap::default();
+    for (file_id, range) in refs {
+        // `Foo { x }` names both the field and the local, so the one which
+        // is not renamed is spelled out
+        let file = db.source_file(file_id);
+        let replacement = match find_node_at_range(file.syntax(), range) {
+            Some(node) if is_field_shorthand(node) && is_local => {
+                format!("{}: {}", node.text(), new_name)
+            }
+            Some(node) if is_field_shorthand(node) => format!("{}: {}", new_name, node.text()),
+            _ => new_name.to_string(),
+        };
+        edits
+            .entry(file_id)
+            .or_default()
+            .replace(range, replacement);
+    }
+    let source_file_edits = edits
+        .into_iter()
+        .map(|(file_id, edit)| SourceFileEdit {
+            file_id,
+            edit: edit.finish(),
+        })
+        .collect();
+    let file_system_edits = if target.kind == MODULE && target.range.is_empty() {
+        module_file_moves(db, target.file_id, new_name)
+    } else {
+        Vec::new()
+    };
+    Ok(Ok(SourceChange {
+        label: "rename".to_string(),
+        source_file_edits,
+        file_system_edits,
+        cursor_position: None,
+    }))
+}
+
+fn is_valid_identifier(name: &str) -> bool {
+    let tokens = tokenize(name);
+    tokens.len() == 1 && tokens[0].kind == IDENT && tokens[0].len == TextUnit::of_str(name)
+}
+
+/// Items can't be renamed to the name of another item of the same list,
+/// like another field of the struct, or of the same module.
+fn check_item_collision(
+    db: &RootDatabase,
+    target: &NavigationTarget,
+    new_name: &str,
+) -> Cancelable<Option<RenameError>> {
+    let (file_id, name_range) = ctry!(db.declaration_name(target)?);
+    let file = db.source_file(file_id);
+    let item = ctry!(find_node_at_range(file.syntax(), name_range).and_then(|it| it.parent()));
+    let list = ctry!(item.parent());
+    let already_defined = list
+        .children()
+        .filter(|it| *it != item)
+        .filter_map(|it| it.children().find(|it| it.kind() == NAME))
+        .any(|name| name.text() == new_name);
+    // items of modules can also collide with imports
+    let is_module_item =
+        list.kind() == SOURCE_FILE || list.parent().map(|it| it.kind()) == Some(MODULE);
+    let already_imported = if is_module_item {
+        match source_binder::module_from_child_node(db, file_id, item)? {
+            Some(module) => module
+                .scope(db)?
+                .entries()
+                .any(|(name, _)| name.to_string() == new_name),
+            None => false,
+        }
+    } else {
+        false
+    };
+    if already_defined || already_imported {
+        return Ok(Some(RenameError::AlreadyDefined(new_name.to_string())));
+    }
+    Ok(None)
+}
+
+/// The renamed references must not resolve to a different local afterwards:
+/// there must be no local with the new name where they are. Only the
+/// references which are resolved through the locals in scope can be
+/// captured, see `is_resolved_in_scope`. When renaming a local, it must also
+/// not shadow the uses of the new name in its function.
+fn check_shadowing(
+    db: &RootDatabase,
+    refs: &[(FileId, TextRange)],
+    is_local: bool,
+    new_name: &str,
+) -> Cancelable<Option<RenameError>> {
+    for &(file_id, range) in refs {
+        let file = db.source_file(file_id);
+        let node = match find_node_at_range(file.syntax(), range) {
+            Some(it) => it,
+            None => continue,
+        };
+        let function = match source_binder::function_from_child_node(db, file_id, node)? {
+            Some(it) => it,
+            None => continue,
+        };
+        let scopes = function.scopes(db)?;
+        let is_visible = is_resolved_in_scope(node, is_local)
+            && scopes
+                .scope_chain(node)
+                .flat_map(|scope| scopes.scopes.entries(scope).iter())
+                .any(|entry| entry.name().to_string() == new_name);
+        if is_visible {
+            return Ok(Some(RenameError::Shadowing(new_name.to_string())));
+        }
+        if is_local {
+            let fn_def = function.syntax(db);
+            let uses_new_name = fn_def
+                .borrowed()
+                .syntax()
+                .descendants()
+                .filter_map(ast::NameRef::cast)
+                .filter(|it| it.syntax().range().start() >= range.end())
+                .filter(|it| it.syntax().parent().map(|p| p.kind()) == Some(PATH_SEGMENT))
+                .any(|it| it.text() == new_name);
+            if uses_new_name {
+                return Ok(Some(RenameError::Shadowing(new_name.to_string())));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Whether the reference is resolved through the locals in scope, so that a
+/// local with the new name would capture it. These are the paths of a single
+/// segment in expressions, like `x` in `x + 1`, and, when renaming a local,
+/// the shorthands of struct literals, like `x` in `Foo { x }`. Fields and
+/// methods, like `a.x`, or qualified paths, like `m::x`, can't be captured.
+fn is_resolved_in_scope(node: SyntaxNodeRef, is_local: bool) -> bool {
+    if is_field_shorthand(node) {
+        return is_local;
+    }
+    let path = match node
+        .parent()
+        .and_then(|it| it.parent())
+        .and_then(ast::Path::cast)
+    {
+        Some(it) => it,
+        None => return false,
+    };
+    node.parent().map(|it| it.kind()) == Some(PATH_SEGMENT)
+        && path.qualifier().is_none()
+        && path.syntax().parent().map(|it| it.kind()) == Some(PATH_EXPR)
+}
+
+/// Whether `node` is the name of a struct literal field without an
+/// expression, like `x` in `Foo { x }`.
+fn is_field_shorthand(node: SyntaxNodeRef) -> bool {
+    match node.parent().and_then(ast::NamedField::cast) {
+        Some(field) => field.expr().is_none(),
+        None => false,
+    }
+}
+
+/// `foo.rs` is moved to `new_name.rs` and `foo/mod.rs` to `new_name/mod.rs`,
+/// together with the files of the submodules in the `foo` directory.
+fn module_file_moves(db: &RootDatabase, file_id: FileId, new_name: &str) -> Vec<FileSystemEdit> {
+    let source_root_id = db.file_source_root(file_id);
+    let path = db.file_relative_path(file_id);
+    let mut res = Vec::new();
+    let dir = if path.file_stem() == Some("mod") {
+        match path.parent() {
+            Some(it) => it.to_relative_path_buf(),
+            None => return res,
+        }
+    } else {
+        res.push(FileSystemEdit::MoveFile {
+            src: file_id,
+            dst_source_root: source_root_id,
+            dst_path: path.with_file_name(format!("{}.rs", new_name)),
+        });
+        match path.file_stem() {
+            Some(stem) => path.with_file_name(stem),
+            None => return res,
+        }
+    };
+    let new_dir = dir.with_file_name(new_name);
+    let source_root = db.source_root(source_root_id);
+    let mut moved_files = source_root
+        .files
+        .iter()
+        .filter_map(|(path, &file_id)| {
+            let rest = path.strip_prefix(&dir).ok()?;
+            Some((file_id, new_dir.join(rest)))
+        })
+        .collect::<Vec<(FileId, RelativePathBuf)>>();
+    moved_files.sort_by_key(|(file_id, _)| *file_id);
+    res.extend(
+        moved_files
+            .into_iter()
+            .map(|(file_id, dst_path)| FileSystemEdit::MoveFile {
+                src: file_id,
+                dst_source_root: source_root_id,
+                dst_path,
+            }),
+    );
+    res
+}
+
+fn find_node_at_range(syntax: SyntaxNodeRef, range: TextRange) -> Option<SyntaxNodeRef> {
+    let name_ref = find_node_at_offset::<ast::NameRef>(syntax, range.start())
+        .map(|it| it.syntax())
+        .filter(|it| it.range() == range);
+    name_ref.or_else(|| {
+        find_node_at_offset::<ast::Name>(syntax, range.start())
+            .map(|it| it.syntax())
+            .filter(|it| it.range() == range)
+    })
+}
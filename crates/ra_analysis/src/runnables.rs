@@ -1,6 +1,6 @@
 use itertools::Itertools;
 use ra_syntax::{
-    ast::{self, AstNode, NameOwner, ModuleItemOwner},
+    ast::{self, AstNode, AttrsOwner, NameOwner, ModuleItemOwner},
     TextRange, SyntaxNodeRef,
 };
 use ra_db::{Cancelable, SyntaxDatabase};
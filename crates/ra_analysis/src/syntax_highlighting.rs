@@ -1,6 +1,6 @@
-use ra_syntax::{ast, AstNode,};
 use ra_editor::HighlightedRange;
 use ra_db::SyntaxDatabase;
+use hir::db::HirDatabase;
 
 use crate::{
     db::RootDatabase,
@@ -10,23 +10,16 @@ use crate::{
 pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Cancelable<Vec<HighlightedRange>> {
     let source_file = db.source_file(file_id);
     let mut res = ra_editor::highlight(source_file.syntax());
-    for macro_call in source_file
-        .syntax()
-        .descendants()
-        .filter_map(ast::MacroCall::cast)
-    {
-        if let Some((off, exp)) = hir::MacroDef::ast_expand(macro_call) {
-            let mapped_ranges = ra_editor::highlight(exp.syntax().borrowed())
-                .into_iter()
-                .filter_map(|r| {
-                    let mapped_range = exp.map_range_back(r.range)?;
-                    let res = HighlightedRange {
-                        range: mapped_range + off,
-                        tag: r.tag,
-                    };
-                    Some(res)
+    for macro_call_id in hir::source_binder::macro_calls(db, file_id)? {
+        let macro_file = hir::HirFileId::from(macro_call_id);
+        let expansion = db.hir_source_file(macro_file);
+        for r in ra_editor::highlight(expansion.syntax()) {
+            if let Some(mapped_range) = macro_file.original_file_range(db, r.range) {
+                res.push(HighlightedRange {
+                    range: mapped_range.range,
+                    tag: r.tag,
                 });
-            res.extend(mapped_ranges);
+            }
         }
     }
     Ok(res)
@@ -69,6 +62,29 @@ mod tests {
         )
     }
 
+    #[test]
+    fn highlights_code_inside_nested_macros() {
+        let (analysis, file_id) = single_file(
+            "
+            fn main() {
+                vec![ctry!(foo(92))];
+            }
+            ",
+        );
+        let highlights = analysis.highlight(file_id).unwrap();
+        assert_eq_dbg(
+            r#"[HighlightedRange { range: [13; 15), tag: "keyword" },
+                HighlightedRange { range: [16; 20), tag: "function" },
+                HighlightedRange { range: [41; 45), tag: "macro" },
+                HighlightedRange { range: [56; 58), tag: "literal" },
+                HighlightedRange { range: [46; 51), tag: "macro" },
+                HighlightedRange { range: [56; 58), tag: "literal" },
+                HighlightedRange { range: [52; 55), tag: "text" },
+                HighlightedRange { range: [56; 58), tag: "literal" }]"#,
+            &highlights,
+        )
+    }
+
     // FIXME: this test is not really necessary: artifact of the inital hacky
     // macros implementation.
     #[test]
@@ -6,7 +6,7 @@ use test_utils::{assert_eq_dbg, assert_eq_text};
 
 use ra_analysis::{
     mock_analysis::{analysis_and_position, single_file, single_file_with_position, MockAnalysis},
-    AnalysisChange, CrateGraph, FileId, FnSignatureInfo, Query
+    AnalysisChange, CrateGraph, FileId, FilePosition, FnSignatureInfo, Query, RenameError
 };
 
 fn get_signature(text: &str) -> (FnSignatureInfo, Option<usize>) {
@@ -34,6 +34,112 @@ fn approximate_resolve_works_in_items() {
     );
 }
 
+#[test]
+fn approximate_resolve_works_inside_nested_macros() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        fn foo() {}
+        fn main() { vec![ctry!(fo<|>o())]; }
+    ",
+    );
+
+    let symbols = analysis.approximately_resolve_symbol(pos).unwrap().unwrap();
+    assert_eq_dbg(
+        r#"ReferenceResolution {
+            reference_range: [35; 38),
+            resolves_to: [NavigationTarget { file_id: FileId(1), name: "foo", kind: FN_DEF, range: [0; 11), ptr: Some(LocalSyntaxPtr { range: [0; 11), kind: FN_DEF }) }]
+        }"#,
+        &symbols,
+    );
+}
+
+#[test]
+fn approximate_resolve_works_for_enum_variants() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        enum E { V1 { x: u32 }, V2 }
+        fn foo(e: E) {
+            match e { E::V<|>1 { x } => (), _ => () }
+        }
+    ",
+    );
+
+    let symbols = analysis.approximately_resolve_symbol(pos).unwrap().unwrap();
+    assert_eq_dbg(
+        r#"ReferenceResolution {
+            reference_range: [61; 63),
+            resolves_to: [NavigationTarget { file_id: FileId(1), name: "V1", kind: ENUM_VARIANT, range: [9; 22), ptr: Some(LocalSyntaxPtr { range: [9; 22), kind: ENUM_VARIANT }) }]
+        }"#,
+        &symbols,
+    );
+}
+
+#[test]
+fn approximate_resolve_works_for_assoc_fns() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        struct A;
+        impl A { fn new() -> A { A } }
+        struct B;
+        impl B { fn new() -> B { B } }
+        fn main() { B::ne<|>w(); }
+    ",
+    );
+
+    let symbols = analysis.approximately_resolve_symbol(pos).unwrap().unwrap();
+    assert_eq_dbg(
+        r#"ReferenceResolution {
+            reference_range: [97; 100),
+            resolves_to: [NavigationTarget { file_id: FileId(1), name: "new", kind: FN_DEF, range: [60; 79), ptr: Some(LocalSyntaxPtr { range: [60; 79), kind: FN_DEF }) }]
+        }"#,
+        &symbols,
+    );
+}
+
+#[test]
+fn approximate_resolve_works_for_methods_and_fields() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        struct A { x: u32 }
+        impl A { fn frobnicate(&self) {} }
+        struct B { x: u32 }
+        impl B { fn frobnicate(&self) {} }
+        fn main(b: &B) { b.frob<|>nicate(); }
+    ",
+    );
+
+    let symbols = analysis.approximately_resolve_symbol(pos).unwrap().unwrap();
+    assert_eq_dbg(
+        r#"ReferenceResolution {
+            reference_range: [129; 139),
+            resolves_to: [NavigationTarget { file_id: FileId(1), name: "frobnicate", kind: FN_DEF, range: [84; 107), ptr: Some(LocalSyntaxPtr { range: [84; 107), kind: FN_DEF }) }]
+        }"#,
+        &symbols,
+    );
+
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        struct A { x: u32 }
+        struct B { x: u32 }
+        fn main(b: &B) { b.<|>x; }
+    ",
+    );
+
+    let symbols = analysis.approximately_resolve_symbol(pos).unwrap().unwrap();
+    assert_eq_dbg(
+        r#"ReferenceResolution {
+            reference_range: [59; 60),
+            resolves_to: [NavigationTarget { file_id: FileId(1), name: "x", kind: NAMED_FIELD_DEF, range: [31; 37), ptr: None }]
+        }"#,
+        &symbols,
+    );
+}
+
 #[test]
 fn test_resolve_module() {
     let (analysis, pos) = analysis_and_position(
@@ -98,6 +204,42 @@ fn test_unresolved_module_diagnostic_no_diag_for_inline_mode() {
     assert_eq_dbg(r#"[]"#, &diagnostics);
 }
 
+#[test]
+fn test_private_item_diagnostic() {
+    let (analysis, file_id) = single_file(
+        "
+use foo::Bar;
+use foo::Baz;
+mod foo { struct Bar; pub struct Baz; }
+",
+    );
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    assert_eq_dbg(
+        r#"[Diagnostic { message: "`Bar` is private", range: [10; 13), fix: None, severity: Error }]"#,
+        &diagnostics,
+    );
+}
+
+#[test]
+fn test_private_item_diagnostic_in_paths() {
+    let (analysis, file_id) = single_file(
+        "
+mod foo { struct Bar; pub struct Baz; fn spam() {} }
+fn f(x: foo::Bar, y: foo::Baz) {
+    foo::spam();
+    foo::Bar;
+}
+",
+    );
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    assert_eq_dbg(
+        r#"[Diagnostic { message: "`Bar` is private", range: [67; 70), fix: None, severity: Error },
+            Diagnostic { message: "`spam` is private", range: [96; 100), fix: None, severity: Error },
+            Diagnostic { message: "`Bar` is private", range: [113; 116), fix: None, severity: Error }]"#,
+        &diagnostics,
+    );
+}
+
 #[test]
 fn test_resolve_parent_module() {
     let (analysis, pos) = analysis_and_position(
@@ -158,6 +300,76 @@ fn test_resolve_crate_root() {
     assert_eq!(host.analysis().crate_for(mod_file).unwrap(), vec![crate_id]);
 }
 
+#[test]
+fn test_goto_type_definition() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        struct Foo;
+        struct Bar { foo: Foo }
+        fn main(bar: &Bar) { let foo = &bar.foo; fo<|>o; }
+    ",
+    );
+    assert_eq_dbg(
+        r#"[NavigationTarget { file_id: FileId(1), name: "Foo", kind: STRUCT_DEF, range: [0; 11), ptr: Some(LocalSyntaxPtr { range: [0; 11), kind: STRUCT_DEF }) }]"#,
+        &analysis.goto_type_definition(pos).unwrap(),
+    );
+
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        enum E { A }
+        struct S;
+        impl S { fn e(&self) -> E { E::A } }
+        fn main(s: S) { s.<|>e(); }
+    ",
+    );
+    assert_eq_dbg(
+        r#"[NavigationTarget { file_id: FileId(1), name: "E", kind: ENUM_DEF, range: [0; 12), ptr: Some(LocalSyntaxPtr { range: [0; 12), kind: ENUM_DEF }) }]"#,
+        &analysis.goto_type_definition(pos).unwrap(),
+    );
+}
+
+#[test]
+fn test_goto_implementation() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod a;
+        struct Fo<|>o;
+        impl Foo {}
+        trait T {}
+        impl T for Foo {}
+        struct Bar;
+        impl Bar {}
+        //- /a.rs
+        impl crate::Foo {}
+    ",
+    );
+    assert_eq_dbg(
+        r#"[NavigationTarget { file_id: FileId(1), name: "Foo", kind: IMPL_BLOCK, range: [19; 30), ptr: None },
+            NavigationTarget { file_id: FileId(2), name: "crate::Foo", kind: IMPL_BLOCK, range: [0; 18), ptr: None },
+            NavigationTarget { file_id: FileId(1), name: "Foo", kind: IMPL_BLOCK, range: [42; 59), ptr: None }]"#,
+        &analysis.goto_implementation(pos).unwrap(),
+    );
+
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        trait T {}
+        struct A;
+        impl <|>T for A {}
+        struct B;
+        impl T for B {}
+    ",
+    );
+    assert_eq_dbg(
+        r#"[NavigationTarget { file_id: FileId(1), name: "A", kind: IMPL_BLOCK, range: [21; 36), ptr: None },
+            NavigationTarget { file_id: FileId(1), name: "B", kind: IMPL_BLOCK, range: [47; 62), ptr: None }]"#,
+        &analysis.goto_implementation(pos).unwrap(),
+    );
+}
+
 #[test]
 fn test_fn_signature_two_args_first() {
     let (desc, param) = get_signature(
@@ -412,6 +624,67 @@ By default this method stops actor's `Context`."#
     );
 }
 
+#[test]
+fn test_incoming_and_outgoing_calls() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod a;
+        struct S;
+        impl S { fn baz(&self) {} }
+        fn f<|>oo(s: S) { bar(); s.baz(); bar(); }
+        fn bar() {}
+        fn caller() { foo(S); }
+        //- /a.rs
+        fn other() { super::foo(super::S); let _ = super::foo; }
+    ",
+    );
+    assert_eq_dbg(
+        r#"[CallItem { target: NavigationTarget { file_id: FileId(1), name: "caller", kind: FN_DEF, range: [97; 120), ptr: Some(LocalSyntaxPtr { range: [97; 120), kind: FN_DEF }) }, call_sites: [FileRange { file_id: FileId(1), range: [111; 114) }] }, CallItem { target: NavigationTarget { file_id: FileId(2), name: "other", kind: FN_DEF, range: [0; 56), ptr: Some(LocalSyntaxPtr { range: [0; 56), kind: FN_DEF }) }, call_sites: [FileRange { file_id: FileId(2), range: [20; 23) }] }]"#,
+        &analysis.incoming_calls(pos).unwrap(),
+    );
+    assert_eq_dbg(
+        r#"[CallItem { target: NavigationTarget { file_id: FileId(1), name: "bar", kind: FN_DEF, range: [85; 96), ptr: Some(LocalSyntaxPtr { range: [85; 96), kind: FN_DEF }) }, call_sites: [FileRange { file_id: FileId(1), range: [60; 63) }, FileRange { file_id: FileId(1), range: [76; 79) }] }, CallItem { target: NavigationTarget { file_id: FileId(1), name: "baz", kind: FN_DEF, range: [26; 42), ptr: Some(LocalSyntaxPtr { range: [26; 42), kind: FN_DEF }) }, call_sites: [FileRange { file_id: FileId(1), range: [69; 72) }] }]"#,
+        &analysis.outgoing_calls(pos).unwrap(),
+    );
+}
+
+#[test]
+fn test_calls_across_crates() {
+    let (mock, pos) = MockAnalysis::with_files_and_position(
+        "
+        //- /main.rs
+        fn main() { test_crate::foo(); }
+        //- /lib.rs
+        pub fn f<|>oo() {}
+    ",
+    );
+    let main_file = mock.id_of("/main.rs");
+    let lib_file = mock.id_of("/lib.rs");
+    let mut host = mock.analysis_host();
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_file);
+    let lib_crate = crate_graph.add_crate_root(lib_file);
+    crate_graph.add_dep(main_crate, "test_crate".into(), lib_crate);
+    let mut change = AnalysisChange::new();
+    change.set_crate_graph(crate_graph);
+    host.apply_change(change);
+    let analysis = host.analysis();
+
+    assert_eq_dbg(
+        r#"[CallItem { target: NavigationTarget { file_id: FileId(1), name: "main", kind: FN_DEF, range: [0; 32), ptr: Some(LocalSyntaxPtr { range: [0; 32), kind: FN_DEF }) }, call_sites: [FileRange { file_id: FileId(1), range: [24; 27) }] }]"#,
+        &analysis.incoming_calls(pos).unwrap(),
+    );
+    let main_pos = FilePosition {
+        file_id: main_file,
+        offset: 4.into(),
+    };
+    assert_eq_dbg(
+        r#"[CallItem { target: NavigationTarget { file_id: FileId(2), name: "foo", kind: FN_DEF, range: [0; 15), ptr: Some(LocalSyntaxPtr { range: [0; 15), kind: FN_DEF }) }, call_sites: [FileRange { file_id: FileId(1), range: [24; 27) }] }]"#,
+        &analysis.outgoing_calls(main_pos).unwrap(),
+    );
+}
+
 fn get_all_refs(text: &str) -> Vec<(FileId, TextRange)> {
     let (analysis, position) = single_file_with_position(text);
     analysis.find_all_refs(position).unwrap()
@@ -457,6 +730,76 @@ fn test_find_all_refs_for_fn_param() {
     let refs = get_all_refs(code);
     assert_eq!(refs.len(), 2);
 }
+
+#[test]
+fn test_find_all_refs_for_fn_across_files() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod foo;
+        fn main() { foo::fo<|>o(); }
+        //- /foo.rs
+        pub fn foo() {}
+        fn bar() { foo(); }
+    ",
+    );
+    let refs = analysis.find_all_refs(pos).unwrap();
+    assert_eq_dbg(
+        "[(FileId(2), [7; 10)), (FileId(1), [26; 29)), (FileId(2), [27; 30))]",
+        &refs,
+    );
+}
+
+#[test]
+fn test_find_all_refs_for_struct_and_fields() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        struct Foo { x: u32 }
+        fn f(a: Foo) -> u32 { let b = Foo { x<|>: 1 }; a.x + b.x }
+    ",
+    );
+    let refs = analysis.find_all_refs(pos).unwrap();
+    assert_eq_dbg("[(FileId(1), [13; 14)), (FileId(1), [58; 59)), (FileId(1), [68; 69)), (FileId(1), [74; 75))]", &refs);
+
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        struct Fo<|>o { x: u32 }
+        fn f(a: Foo) -> Foo { Foo { x: a.x } }
+    ",
+    );
+    let refs = analysis.find_all_refs(pos).unwrap();
+    assert_eq_dbg("[(FileId(1), [7; 10)), (FileId(1), [30; 33)), (FileId(1), [38; 41)), (FileId(1), [44; 47))]", &refs);
+}
+
+#[test]
+fn test_find_all_refs_for_modules_and_methods() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod foo;
+        fn f() { fo<|>o::bar(); }
+        //- /foo.rs
+        pub fn bar() {}
+    ",
+    );
+    let refs = analysis.find_all_refs(pos).unwrap();
+    assert_eq_dbg("[(FileId(1), [4; 7)), (FileId(1), [18; 21))]", &refs);
+
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        trait T { fn m(&self); }
+        struct S;
+        impl T for S { fn m(&self) {} }
+        fn f(s: S) { s.m<|>(); }
+    ",
+    );
+    let refs = analysis.find_all_refs(pos).unwrap();
+    assert_eq_dbg("[(FileId(1), [13; 14)), (FileId(1), [82; 83))]", &refs);
+}
+
 #[test]
 fn test_rename_for_local() {
     test_rename(
@@ -533,12 +876,166 @@ fn test_rename_for_mut_param() {
     );
 }
 
+#[test]
+fn test_rename_field_to_name_of_local() {
+    // `a.x` can't be captured by the local `b`
+    test_rename(
+        r#"
+    struct Foo { x<|>: u32 }
+    fn f(a: Foo) -> u32 {
+        let b = 1;
+        a.x + b
+    }"#,
+        "b",
+        r#"
+    struct Foo { b: u32 }
+    fn f(a: Foo) -> u32 {
+        let b = 1;
+        a.b + b
+    }"#,
+    );
+}
+
+#[test]
+fn test_rename_field_in_shorthand() {
+    test_rename(
+        r#"
+    struct Foo { x<|>: u32 }
+    fn f(x: u32) -> Foo {
+        Foo { x }
+    }"#,
+        "y",
+        r#"
+    struct Foo { y: u32 }
+    fn f(x: u32) -> Foo {
+        Foo { y: x }
+    }"#,
+    );
+}
+
+#[test]
+fn test_rename_local_in_shorthand() {
+    test_rename(
+        r#"
+    struct Foo { x: u32 }
+    fn f(x<|>: u32) -> Foo {
+        Foo { x }
+    }"#,
+        "y",
+        r#"
+    struct Foo { x: u32 }
+    fn f(y: u32) -> Foo {
+        Foo { x: y }
+    }"#,
+    );
+}
+
+#[test]
+fn test_rename_fn_across_files() {
+    let (analysis, position) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod foo;
+        fn main() { foo::fo<|>o(); }
+        //- /foo.rs
+        pub fn foo() {}
+        fn bar() { foo(); }
+    ",
+    );
+    let change = analysis.rename(position, "baz").unwrap().unwrap();
+    assert!(change.file_system_edits.is_empty());
+    let mut texts = change
+        .source_file_edits
+        .into_iter()
+        .map(|edit| {
+            let text = analysis.file_text(edit.file_id);
+            (edit.file_id, edit.edit.apply(&text))
+        })
+        .collect::<Vec<_>>();
+    texts.sort_by_key(|(file_id, _)| *file_id);
+    assert_eq_dbg(
+        r#"[(FileId(1), "mod foo;\nfn main() { foo::baz(); }\n"),
+            (FileId(2), "pub fn baz() {}\nfn bar() { baz(); }\n")]"#,
+        &texts,
+    );
+}
+
+#[test]
+fn test_rename_module_moves_its_files() {
+    let (analysis, position) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod fo<|>o;
+        fn main() { foo::bar::f(); }
+        //- /foo.rs
+        mod bar;
+        //- /foo/bar.rs
+        pub fn f() {}
+    ",
+    );
+    let change = analysis.rename(position, "baz").unwrap().unwrap();
+    assert_eq!(change.source_file_edits.len(), 1);
+    let edit = &change.source_file_edits[0];
+    assert_eq_text!(
+        "mod baz;\nfn main() { baz::bar::f(); }\n",
+        &edit.edit.apply(&analysis.file_text(edit.file_id))
+    );
+    assert_eq_dbg(
+        r#"[MoveFile { src: FileId(2), dst_source_root: SourceRootId(0), dst_path: "baz.rs" },
+            MoveFile { src: FileId(3), dst_source_root: SourceRootId(0), dst_path: "baz/bar.rs" }]"#,
+        &change.file_system_edits,
+    );
+}
+
+#[test]
+fn test_rename_refuses_invalid_names_and_conflicts() {
+    let rename_error = |text: &str, new_name: &str| {
+        let (analysis, position) = single_file_with_position(text);
+        analysis.rename(position, new_name).unwrap().unwrap_err()
+    };
+    assert_eq!(
+        rename_error("fn foo<|>() {}", "fn"),
+        RenameError::InvalidName("fn".to_string())
+    );
+    assert_eq!(
+        rename_error("fn foo<|>() {}", "a b"),
+        RenameError::InvalidName("a b".to_string())
+    );
+    assert_eq!(
+        rename_error("fn foo<|>() {} struct bar;", "bar"),
+        RenameError::AlreadyDefined("bar".to_string())
+    );
+    assert_eq!(
+        rename_error("struct S { x<|>: u32, y: u32 }", "y"),
+        RenameError::AlreadyDefined("y".to_string())
+    );
+    assert_eq!(
+        rename_error("fn foo<|>() {} fn main() { let bar = 1; foo(); }", "bar"),
+        RenameError::Shadowing("bar".to_string())
+    );
+    assert_eq!(
+        rename_error("fn bar() {} fn main() { let x<|> = 1; bar(); }", "bar"),
+        RenameError::Shadowing("bar".to_string())
+    );
+    assert_eq!(
+        rename_error("fn main() { let x = 1; let y<|> = 2; x + y; }", "x"),
+        RenameError::Shadowing("x".to_string())
+    );
+    assert_eq!(
+        rename_error(
+            "struct Foo { x: u32 } fn f(x<|>: u32) -> Foo { let y = 1; Foo { x } }",
+            "y"
+        ),
+        RenameError::Shadowing("y".to_string())
+    );
+}
+
 fn test_rename(text: &str, new_name: &str, expected: &str) {
     let (analysis, position) = single_file_with_position(text);
-    let edits = analysis.rename(position, new_name).unwrap();
+    let change = analysis.rename(position, new_name).unwrap().unwrap();
     let mut text_edit_bulder = ra_text_edit::TextEditBuilder::default();
     let mut file_id: Option<FileId> = None;
-    for edit in edits {
+    for edit in change.source_file_edits {
         file_id = Some(edit.file_id);
         for atom in edit.edit.as_atoms() {
             text_edit_bulder.replace(atom.delete, atom.insert.clone());
@@ -565,3 +1062,59 @@ pub trait HirDatabase: SyntaxDatabase {}
     assert_eq!(s.name(), "HirDatabase");
     assert_eq!(s.range(), TextRange::from_to(33.into(), 44.into()));
 }
+
+#[test]
+fn world_symbols_include_items_from_macro_rules() {
+    let (analysis, _) = single_file(
+        "
+macro_rules! structs {
+    ($($i:ident),*) => { $(struct $i;)* }
+}
+structs!(Foo, HirBar);
+    ",
+    );
+
+    let mut symbols = analysis.symbol_search(Query::new("HirBar".into())).unwrap();
+    let s = symbols.pop().unwrap();
+    assert_eq!(s.name(), "HirBar");
+    assert_eq!(s.range(), TextRange::from_to(82.into(), 88.into()));
+}
+
+#[test]
+fn world_symbols_include_items_from_nested_macro_calls() {
+    let (analysis, _) = single_file(
+        "
+macro_rules! structs {
+    ($($i:ident),*) => { $(struct $i;)* }
+}
+macro_rules! items {
+    ($($i:item)*) => { $($i)* }
+}
+items! { structs!(Foo, HirBar); }
+    ",
+    );
+
+    let mut symbols = analysis.symbol_search(Query::new("HirBar".into())).unwrap();
+    let s = symbols.pop().unwrap();
+    assert_eq!(s.name(), "HirBar");
+    assert_eq!(s.range(), TextRange::from_to(146.into(), 152.into()));
+}
+
+#[test]
+fn world_symbols_stop_at_recursive_macro_calls() {
+    let (analysis, _) = single_file(
+        "
+macro_rules! recursive {
+    ($i:ident) => { struct $i; recursive!($i); }
+}
+recursive!(HirBar);
+    ",
+    );
+
+    let symbols = analysis.symbol_search(Query::new("HirBar".into())).unwrap();
+    assert!(!symbols.is_empty());
+    for s in symbols {
+        assert_eq!(s.name(), "HirBar");
+        assert_eq!(s.range(), TextRange::from_to(88.into(), 94.into()));
+    }
+}
@@ -45,7 +45,6 @@ fn test_type_of_for_binding() {
     assert!(analysis.type_of(range).unwrap().is_none());
 }
 
-// FIXME: improve type_of to make this work
 #[test]
 fn test_type_of_for_expr_1() {
     let (analysis, range) = single_file_with_range(
@@ -57,10 +56,9 @@ fn test_type_of_for_expr_1() {
     );
 
     let type_name = analysis.type_of(range).unwrap().unwrap();
-    assert_eq!("[unknown]", &type_name);
+    assert_eq!("i32", &type_name);
 }
 
-// FIXME: improve type_of to make this work
 #[test]
 fn test_type_of_for_expr_2() {
     let (analysis, range) = single_file_with_range(
@@ -73,5 +71,5 @@ fn test_type_of_for_expr_2() {
     );
 
     let type_name = analysis.type_of(range).unwrap().unwrap();
-    assert_eq!("[unknown]", &type_name);
+    assert_eq!("i32", &type_name);
 }
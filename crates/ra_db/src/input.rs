@@ -39,7 +39,7 @@ pub struct SourceRoot {
 
 /// `CrateGraph` is a bit of information which turns a set of text files into a
 /// number of Rust crates. Each Crate is the `FileId` of it's root module, the
-/// set of cfg flags (not yet implemented) and the set of dependencies. Note
+/// set of cfg flags and the set of dependencies. Note
 /// that, due to cfg's, there might be several crates for a single `FileId`! As
 /// in the rust-lang proper, a crate does not have a name. Instead, names are
 /// specified on dependency edges. That is, a crate might be known under
@@ -56,17 +56,44 @@ pub struct CrateGraph {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CrateId(pub u32);
 
+/// The cfg flags a crate is compiled with: atoms, like `test` or `unix`, and
+/// key-value pairs, like `feature = "serde"` or `target_os = "linux"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CfgOptions {
+    atoms: FxHashSet<SmolStr>,
+    key_values: FxHashSet<(SmolStr, SmolStr)>,
+}
+
+impl CfgOptions {
+    pub fn insert_atom(&mut self, atom: SmolStr) {
+        self.atoms.insert(atom);
+    }
+    pub fn insert_key_value(&mut self, key: SmolStr, value: SmolStr) {
+        self.key_values.insert((key, value));
+    }
+    pub fn check_atom(&self, atom: &SmolStr) -> bool {
+        self.atoms.contains(atom)
+    }
+    pub fn check_key_value(&self, key: &SmolStr, value: &SmolStr) -> bool {
+        self.key_values.contains(&(key.clone(), value.clone()))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct CrateData {
     file_id: FileId,
+    cfg_options: CfgOptions,
     dependencies: Vec<Dependency>,
+    is_detached: bool,
 }
 
 impl CrateData {
-    fn new(file_id: FileId) -> CrateData {
+    fn new(file_id: FileId, cfg_options: CfgOptions) -> CrateData {
         CrateData {
             file_id,
+            cfg_options,
             dependencies: Vec::new(),
+            is_detached: false,
         }
     }
 
@@ -89,11 +116,30 @@ impl Dependency {
 
 impl CrateGraph {
     pub fn add_crate_root(&mut self, file_id: FileId) -> CrateId {
+        self.add_crate_root_with_cfg(file_id, CfgOptions::default())
+    }
+    pub fn add_crate_root_with_cfg(&mut self, file_id: FileId, cfg_options: CfgOptions) -> CrateId {
         let crate_id = CrateId(self.arena.len() as u32);
-        let prev = self.arena.insert(crate_id, CrateData::new(file_id));
+        let prev = self
+            .arena
+            .insert(crate_id, CrateData::new(file_id, cfg_options));
         assert!(prev.is_none());
         crate_id
     }
+    /// Adds a crate rooted at a file which none of the other crates reaches,
+    /// so that it is analyzed nevertheless. A detached crate is not a crate
+    /// for the build system: it has no dependencies, and it is not found by
+    /// `crate_id_for_crate_root`.
+    pub fn add_detached_crate_root(&mut self, file_id: FileId) -> CrateId {
+        let crate_id = self.add_crate_root(file_id);
+        self.arena.get_mut(&crate_id).unwrap().is_detached = true;
+        crate_id
+    }
+    /// Removes the detached crates. They are added after the other crates,
+    /// so the ids of the remaining crates don't change.
+    pub fn remove_detached_crates(&mut self) {
+        self.arena.retain(|_crate_id, data| !data.is_detached);
+    }
     pub fn add_dep(&mut self, from: CrateId, name: SmolStr, to: CrateId) {
         let mut visited = FxHashSet::default();
         if self.dfs_find(from, to, &mut visited) {
@@ -104,14 +150,23 @@ impl CrateGraph {
     pub fn is_empty(&self) -> bool {
         self.arena.is_empty()
     }
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = CrateId> + 'a {
+        self.arena.keys().map(|it| *it)
+    }
     pub fn crate_root(&self, crate_id: CrateId) -> FileId {
         self.arena[&crate_id].file_id
     }
+    pub fn is_detached(&self, crate_id: CrateId) -> bool {
+        self.arena[&crate_id].is_detached
+    }
+    pub fn cfg_options(&self, crate_id: CrateId) -> &CfgOptions {
+        &self.arena[&crate_id].cfg_options
+    }
     pub fn crate_id_for_crate_root(&self, file_id: FileId) -> Option<CrateId> {
         let (&crate_id, _) = self
             .arena
             .iter()
-            .find(|(_crate_id, data)| data.file_id == file_id)?;
+            .find(|(_crate_id, data)| data.file_id == file_id && !data.is_detached)?;
         Some(crate_id)
     }
     pub fn dependencies<'a>(
@@ -14,7 +14,7 @@ pub use crate::{
     cancelation::{Canceled, Cancelable},
     syntax_ptr::LocalSyntaxPtr,
     input::{
-        FilesDatabase, FileId, CrateId, SourceRoot, SourceRootId, CrateGraph, Dependency,
+        FilesDatabase, FileId, CrateId, SourceRoot, SourceRootId, CrateGraph, Dependency, CfgOptions,
         FileTextQuery, FileSourceRootQuery, SourceRootQuery, LocalRootsQuery, LibraryRootsQuery, CrateGraphQuery,
         FileRelativePathQuery
     },
@@ -53,6 +53,10 @@ salsa::query_group! {
         fn file_lines(file_id: FileId) -> Arc<LineIndex> {
             type FileLinesQuery;
         }
+        /// The crates whose root files are in the source root.
+        fn source_root_crates(id: SourceRootId) -> Arc<Vec<CrateId>> {
+            type SourceRootCratesQuery;
+        }
     }
 }
 
@@ -64,6 +68,15 @@ fn file_lines(db: &impl SyntaxDatabase, file_id: FileId) -> Arc<LineIndex> {
     let text = db.file_text(file_id);
     Arc::new(LineIndex::new(&*text))
 }
+fn source_root_crates(db: &impl SyntaxDatabase, id: SourceRootId) -> Arc<Vec<CrateId>> {
+    let crate_graph = db.crate_graph();
+    let mut res = crate_graph
+        .iter()
+        .filter(|&krate| db.file_source_root(crate_graph.crate_root(krate)) == id)
+        .collect::<Vec<_>>();
+    res.sort();
+    Arc::new(res)
+}
 
 #[derive(Clone, Copy, Debug)]
 pub struct FilePosition {
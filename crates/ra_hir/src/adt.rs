@@ -1,14 +1,19 @@
 use std::sync::Arc;
 
-use ra_db::Cancelable;
-use ra_syntax::ast::{self, NameOwner, StructFlavor};
+use ra_db::{Cancelable, CfgOptions};
+use ra_syntax::{
+    SyntaxNodeRef,
+    ast::{self, AstNode, NameOwner, StructFlavor},
+};
 
 use crate::{
-    DefId, Name, AsName,
+    DefId, DefLoc, DefKind, Name, AsName, SourceItemId, HirFileId,
     db::HirDatabase,
     type_ref::TypeRef,
+    cfg,
 };
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Struct {
     def_id: DefId,
 }
@@ -42,9 +47,9 @@ pub struct StructData {
 }
 
 impl StructData {
-    pub(crate) fn new(struct_def: ast::StructDef) -> StructData {
+    pub(crate) fn new(struct_def: ast::StructDef, cfg_options: &CfgOptions) -> StructData {
         let name = struct_def.name().map(|n| n.as_name());
-        let variant_data = VariantData::new(struct_def.flavor());
+        let variant_data = VariantData::new(struct_def.flavor(), cfg_options);
         let variant_data = Arc::new(variant_data);
         StructData { name, variant_data }
     }
@@ -58,6 +63,7 @@ impl StructData {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Enum {
     def_id: DefId,
 }
@@ -75,26 +81,40 @@ impl Enum {
         Ok(db.enum_data(self.def_id)?.name.clone())
     }
 
-    pub fn variants(&self, db: &impl HirDatabase) -> Cancelable<Vec<(Name, Arc<VariantData>)>> {
+    pub fn variants(&self, db: &impl HirDatabase) -> Cancelable<Vec<(Name, EnumVariant)>> {
         Ok(db.enum_data(self.def_id)?.variants.clone())
     }
+
+    pub fn variant(&self, db: &impl HirDatabase, name: &Name) -> Cancelable<Option<EnumVariant>> {
+        let res = db
+            .enum_data(self.def_id)?
+            .variants
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, variant)| variant.clone());
+        Ok(res)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EnumData {
     name: Option<Name>,
-    variants: Vec<(Name, Arc<VariantData>)>,
+    variants: Vec<(Name, EnumVariant)>,
 }
 
 impl EnumData {
-    pub(crate) fn new(enum_def: ast::EnumDef) -> Self {
+    pub(crate) fn new(db: &impl HirDatabase, def_loc: &DefLoc, enum_def: ast::EnumDef) -> Self {
         let name = enum_def.name().map(|n| n.as_name());
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(def_loc.krate);
         let variants = if let Some(evl) = enum_def.variant_list() {
             evl.variants()
+                .filter(|v| cfg::is_cfg_enabled(v.syntax(), cfg_options))
                 .map(|v| {
+                    let def_id = get_def_id(db, def_loc, v.syntax(), DefKind::EnumVariant);
                     (
                         v.name().map(|n| n.as_name()).unwrap_or_else(Name::missing),
-                        Arc::new(VariantData::new(v.flavor())),
+                        EnumVariant::new(def_id),
                     )
                 })
                 .collect()
@@ -105,6 +125,101 @@ impl EnumData {
     }
 }
 
+/// A variant of an enum. Like a struct, it lives in both namespaces: in the
+/// types namespace for struct literals and patterns, and in the values
+/// namespace as a constructor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumVariant {
+    def_id: DefId,
+}
+
+impl EnumVariant {
+    pub(crate) fn new(def_id: DefId) -> Self {
+        EnumVariant { def_id }
+    }
+
+    pub fn def_id(&self) -> DefId {
+        self.def_id
+    }
+
+    pub fn parent_enum(&self, db: &impl HirDatabase) -> Cancelable<Enum> {
+        Ok(db.enum_variant_data(self.def_id)?.parent_enum.clone())
+    }
+
+    pub fn name(&self, db: &impl HirDatabase) -> Cancelable<Option<Name>> {
+        Ok(db.enum_variant_data(self.def_id)?.name.clone())
+    }
+
+    pub fn variant_data(&self, db: &impl HirDatabase) -> Cancelable<Arc<VariantData>> {
+        Ok(db.enum_variant_data(self.def_id)?.variant_data.clone())
+    }
+
+    /// The file the variant is defined in, which may be a macro expansion,
+    /// and its syntax.
+    pub fn source(&self, db: &impl HirDatabase) -> (HirFileId, ast::EnumVariantNode) {
+        let def_loc = self.def_id.loc(db);
+        assert!(def_loc.kind == DefKind::EnumVariant);
+        let syntax = db.file_item(def_loc.source_item_id);
+        let node = ast::EnumVariant::cast(syntax.borrowed()).unwrap().owned();
+        (def_loc.source_item_id.file_id, node)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumVariantData {
+    name: Option<Name>,
+    variant_data: Arc<VariantData>,
+    parent_enum: Enum,
+}
+
+impl EnumVariantData {
+    pub(crate) fn new(
+        db: &impl HirDatabase,
+        def_loc: &DefLoc,
+        variant_def: ast::EnumVariant,
+    ) -> EnumVariantData {
+        let name = variant_def.name().map(|n| n.as_name());
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(def_loc.krate);
+        let variant_data = Arc::new(VariantData::new(variant_def.flavor(), cfg_options));
+        // variants are children of the enum's variant list
+        let enum_def = variant_def
+            .syntax()
+            .parent()
+            .and_then(|it| it.parent())
+            .and_then(ast::EnumDef::cast)
+            .expect("enum variant should be inside of an EnumDef");
+        let parent_enum = Enum::new(get_def_id(db, def_loc, enum_def.syntax(), DefKind::Enum));
+        EnumVariantData {
+            name,
+            variant_data,
+            parent_enum,
+        }
+    }
+}
+
+/// The `DefId` of an item which is in the same file and module as the item
+/// of `same_file_loc`.
+fn get_def_id(
+    db: &impl HirDatabase,
+    same_file_loc: &DefLoc,
+    node: SyntaxNodeRef,
+    expected_kind: DefKind,
+) -> DefId {
+    let file_id = same_file_loc.source_item_id.file_id;
+    let file_items = db.file_items(file_id);
+    let item_id = file_items.id_of(file_id, node);
+    let loc = DefLoc {
+        kind: expected_kind,
+        source_item_id: SourceItemId {
+            file_id,
+            item_id: Some(item_id),
+        },
+        ..same_file_loc.clone()
+    };
+    loc.id(db)
+}
+
 /// A single field of an enum variant or struct
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StructField {
@@ -130,11 +245,14 @@ pub enum VariantData {
 }
 
 impl VariantData {
-    pub fn new(flavor: StructFlavor) -> Self {
+    /// The fields disabled by a `#[cfg]` are left out; the names of the
+    /// fields of tuple structs are counted from the enabled ones.
+    pub fn new(flavor: StructFlavor, cfg_options: &CfgOptions) -> Self {
         match flavor {
             StructFlavor::Tuple(fl) => {
                 let fields = fl
                     .fields()
+                    .filter(|fd| cfg::is_cfg_enabled(fd.syntax(), cfg_options))
                     .enumerate()
                     .map(|(i, fd)| StructField {
                         name: Name::tuple_field_name(i),
@@ -146,6 +264,7 @@ impl VariantData {
             StructFlavor::Named(fl) => {
                 let fields = fl
                     .fields()
+                    .filter(|fd| cfg::is_cfg_enabled(fd.syntax(), cfg_options))
                     .map(|fd| StructField {
                         name: fd.name().map(|n| n.as_name()).unwrap_or_else(Name::missing),
                         type_ref: TypeRef::from_ast_opt(fd.type_ref()),
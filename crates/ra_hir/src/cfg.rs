@@ -0,0 +1,228 @@
+/// Conditional compilation: `#[cfg(..)]` and `#[cfg_attr(..)]` attributes are
+/// evaluated against the `CfgOptions` of the crate, which come from the
+/// `CrateGraph`.
+///
+/// Items which are disabled by a `#[cfg]` are not collected at all, as if they
+/// were not written, so that, for example, the `#[cfg(unix)]` and
+/// `#[cfg(windows)]` versions of the same function do not conflict.
+use ra_db::CfgOptions;
+use ra_macros::tt;
+use ra_syntax::{
+    SmolStr, SyntaxNodeRef,
+    ast::{self, AstNode},
+};
+
+/// A parsed `cfg` predicate, like `all(unix, feature = "serde")`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CfgExpr {
+    Invalid,
+    Atom(SmolStr),
+    KeyValue { key: SmolStr, value: SmolStr },
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Not(Box<CfgExpr>),
+}
+
+impl CfgExpr {
+    /// Returns `None` if the predicate is malformed and can't be evaluated.
+    fn fold(&self, cfg: &CfgOptions) -> Option<bool> {
+        let res = match self {
+            CfgExpr::Invalid => return None,
+            CfgExpr::Atom(name) => cfg.check_atom(name),
+            CfgExpr::KeyValue { key, value } => cfg.check_key_value(key, value),
+            CfgExpr::All(preds) => preds
+                .iter()
+                .try_fold(true, |s, it| Some(s && it.fold(cfg)?))?,
+            CfgExpr::Any(preds) => preds
+                .iter()
+                .try_fold(false, |s, it| Some(s || it.fold(cfg)?))?,
+            CfgExpr::Not(pred) => !pred.fold(cfg)?,
+        };
+        Some(res)
+    }
+}
+
+/// Whether an item is enabled, that is, all of its `#[cfg]` attributes
+/// evaluate to true. Predicates we fail to understand don't disable items.
+pub(crate) fn is_cfg_enabled(item: SyntaxNodeRef, cfg: &CfgOptions) -> bool {
+    attrs(item, cfg).iter().all(|attr| match attr.as_slice() {
+        [tt::TokenTree::Leaf(tt::Leaf::Ident(name)), tt::TokenTree::Subtree(args)]
+            if name.text == "cfg" =>
+        {
+            parse_cfg(&args.token_trees).fold(cfg) != Some(false)
+        }
+        _ => true,
+    })
+}
+
+/// Like `AttrsOwner::has_atom_attr`, but also sees the attributes of enabled
+/// `#[cfg_attr]`s.
+pub(crate) fn has_atom_attr(item: SyntaxNodeRef, cfg: &CfgOptions, atom: &str) -> bool {
+    attrs(item, cfg).iter().any(|attr| match attr.as_slice() {
+        [tt::TokenTree::Leaf(tt::Leaf::Ident(name))] => name.text == atom,
+        _ => false,
+    })
+}
+
+/// The contents of the attributes of an item, like `cfg(test)` for
+/// `#[cfg(test)]`. `#[cfg_attr(pred, a, b)]` is replaced by `a` and `b` if
+/// `pred` is true, and removed otherwise.
+fn attrs(item: SyntaxNodeRef, cfg: &CfgOptions) -> Vec<Vec<tt::TokenTree>> {
+    let mut res = Vec::new();
+    for attr in item.children().filter_map(ast::Attr::cast) {
+        let tt = match attr.value().and_then(ra_macros::ast_to_token_tree) {
+            Some((tt, _)) => tt,
+            None => continue,
+        };
+        push_attr(&mut res, tt.token_trees, cfg);
+    }
+    res
+}
+
+fn push_attr(acc: &mut Vec<Vec<tt::TokenTree>>, attr: Vec<tt::TokenTree>, cfg: &CfgOptions) {
+    let args = match attr.as_slice() {
+        [tt::TokenTree::Leaf(tt::Leaf::Ident(name)), tt::TokenTree::Subtree(args)]
+            if name.text == "cfg_attr" =>
+        {
+            args
+        }
+        _ => {
+            acc.push(attr);
+            return;
+        }
+    };
+    let mut parts = split_by_commas(&args.token_trees).into_iter();
+    let pred = match parts.next() {
+        Some(it) => parse_cfg(&it),
+        None => return,
+    };
+    if pred.fold(cfg) == Some(true) {
+        for attr in parts {
+            push_attr(acc, attr, cfg);
+        }
+    }
+}
+
+fn split_by_commas(tts: &[tt::TokenTree]) -> Vec<Vec<tt::TokenTree>> {
+    tts.split(is_comma)
+        .filter(|it| !it.is_empty())
+        .map(|it| it.to_vec())
+        .collect()
+}
+
+fn is_comma(tt: &tt::TokenTree) -> bool {
+    match tt {
+        tt::TokenTree::Leaf(tt::Leaf::Punct(punct)) => punct.char == ',',
+        _ => false,
+    }
+}
+
+/// Parses a predicate from the tokens of the predicate itself, like `unix`
+/// or `not(test)`.
+fn parse_cfg(tts: &[tt::TokenTree]) -> CfgExpr {
+    match tts {
+        [tt::TokenTree::Leaf(tt::Leaf::Ident(name))] => CfgExpr::Atom(name.text.clone()),
+        [tt::TokenTree::Leaf(tt::Leaf::Ident(key)), tt::TokenTree::Leaf(tt::Leaf::Punct(eq)), tt::TokenTree::Leaf(tt::Leaf::Literal(value))]
+            if eq.char == '=' =>
+        {
+            CfgExpr::KeyValue {
+                key: key.text.clone(),
+                value: value.text.trim_matches('"').into(),
+            }
+        }
+        [tt::TokenTree::Leaf(tt::Leaf::Ident(name)), tt::TokenTree::Subtree(args)] => {
+            let mut preds = split_by_commas(&args.token_trees)
+                .iter()
+                .map(|it| parse_cfg(it))
+                .collect::<Vec<_>>();
+            match name.text.as_str() {
+                "all" => CfgExpr::All(preds),
+                "any" => CfgExpr::Any(preds),
+                "not" if preds.len() == 1 => CfgExpr::Not(Box::new(preds.pop().unwrap())),
+                _ => CfgExpr::Invalid,
+            }
+        }
+        _ => CfgExpr::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_db::CfgOptions;
+    use ra_syntax::{SourceFileNode, AstNode, ast};
+
+    use super::{is_cfg_enabled, has_atom_attr};
+
+    fn check(cfg: &CfgOptions, item: &str, enabled: bool) {
+        let file = SourceFileNode::parse(item);
+        let item = file
+            .syntax()
+            .descendants()
+            .find_map(ast::FnDef::cast)
+            .unwrap();
+        assert_eq!(
+            is_cfg_enabled(item.syntax(), cfg),
+            enabled,
+            "{}",
+            item.syntax().text()
+        );
+    }
+
+    fn cfg_options() -> CfgOptions {
+        let mut cfg = CfgOptions::default();
+        cfg.insert_atom("unix".into());
+        cfg.insert_key_value("feature".into(), "serde".into());
+        cfg
+    }
+
+    #[test]
+    fn evaluates_cfg_predicates() {
+        let cfg = cfg_options();
+        check(&cfg, "fn f() {}", true);
+        check(&cfg, "#[cfg(unix)] fn f() {}", true);
+        check(&cfg, "#[cfg(windows)] fn f() {}", false);
+        check(&cfg, r#"#[cfg(feature = "serde")] fn f() {}"#, true);
+        check(&cfg, r#"#[cfg(feature = "std")] fn f() {}"#, false);
+        check(
+            &cfg,
+            r#"#[cfg(all(unix, feature = "serde"))] fn f() {}"#,
+            true,
+        );
+        check(
+            &cfg,
+            r#"#[cfg(all(unix, feature = "std"))] fn f() {}"#,
+            false,
+        );
+        check(
+            &cfg,
+            r#"#[cfg(any(windows, feature = "serde"))] fn f() {}"#,
+            true,
+        );
+        check(&cfg, "#[cfg(not(test))] fn f() {}", true);
+        check(&cfg, "#[cfg(unix)] #[cfg(test)] fn f() {}", false);
+        check(&cfg, "#[cfg(what = is, this)] fn f() {}", true);
+    }
+
+    #[test]
+    fn evaluates_cfg_attr() {
+        let cfg = cfg_options();
+        check(&cfg, "#[cfg_attr(unix, cfg(test))] fn f() {}", false);
+        check(&cfg, "#[cfg_attr(windows, cfg(test))] fn f() {}", true);
+        check(
+            &cfg,
+            "#[cfg_attr(unix, inline, cfg_attr(unix, cfg(test)))] fn f() {}",
+            false,
+        );
+
+        let file = SourceFileNode::parse(
+            "#[cfg_attr(unix, macro_use)] mod a {} #[cfg_attr(test, macro_use)] mod b {}",
+        );
+        let modules = file
+            .syntax()
+            .descendants()
+            .filter_map(ast::Module::cast)
+            .map(|it| has_atom_attr(it.syntax(), &cfg, "macro_use"))
+            .collect::<Vec<_>>();
+        assert_eq!(modules, vec![true, false]);
+    }
+}
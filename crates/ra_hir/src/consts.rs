@@ -0,0 +1,120 @@
+//! HIR for `const` and `static` items, both free and associated ones. Like
+//! functions, they have a declared type which doesn't depend on the
+//! initializer, so the type is all we need to know about them.
+
+use std::sync::Arc;
+
+use ra_db::Cancelable;
+use ra_syntax::ast::{self, AstNode, NameOwner};
+
+use crate::{
+    DefId, DefKind, Name, AsName, Module, ImplBlock,
+    db::HirDatabase,
+    type_ref::TypeRef,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Const {
+    def_id: DefId,
+}
+
+impl Const {
+    pub(crate) fn new(def_id: DefId) -> Const {
+        Const { def_id }
+    }
+
+    pub fn def_id(&self) -> DefId {
+        self.def_id
+    }
+
+    pub fn syntax(&self, db: &impl HirDatabase) -> ast::ConstDefNode {
+        let def_loc = self.def_id.loc(db);
+        assert!(def_loc.kind == DefKind::Const);
+        let syntax = db.file_item(def_loc.source_item_id);
+        ast::ConstDef::cast(syntax.borrowed()).unwrap().owned()
+    }
+
+    pub fn signature(&self, db: &impl HirDatabase) -> Arc<ConstSignature> {
+        db.const_signature(self.def_id)
+    }
+
+    pub fn module(&self, db: &impl HirDatabase) -> Cancelable<Module> {
+        self.def_id.module(db)
+    }
+
+    /// The containing impl block, if this is an associated const.
+    pub fn impl_block(&self, db: &impl HirDatabase) -> Cancelable<Option<ImplBlock>> {
+        self.def_id.impl_block(db)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Static {
+    def_id: DefId,
+}
+
+impl Static {
+    pub(crate) fn new(def_id: DefId) -> Static {
+        Static { def_id }
+    }
+
+    pub fn def_id(&self) -> DefId {
+        self.def_id
+    }
+
+    pub fn syntax(&self, db: &impl HirDatabase) -> ast::StaticDefNode {
+        let def_loc = self.def_id.loc(db);
+        assert!(def_loc.kind == DefKind::Static);
+        let syntax = db.file_item(def_loc.source_item_id);
+        ast::StaticDef::cast(syntax.borrowed()).unwrap().owned()
+    }
+
+    pub fn signature(&self, db: &impl HirDatabase) -> Arc<ConstSignature> {
+        db.static_signature(self.def_id)
+    }
+
+    pub fn module(&self, db: &impl HirDatabase) -> Cancelable<Module> {
+        self.def_id.module(db)
+    }
+}
+
+/// The declared name and type of a `const` or a `static`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstSignature {
+    name: Name,
+    type_ref: TypeRef,
+}
+
+impl ConstSignature {
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    pub fn type_ref(&self) -> &TypeRef {
+        &self.type_ref
+    }
+}
+
+pub(crate) fn const_signature(db: &impl HirDatabase, def_id: DefId) -> Arc<ConstSignature> {
+    let syntax = Const::new(def_id).syntax(db);
+    let node = syntax.borrowed();
+    Arc::new(ConstSignature {
+        name: node
+            .name()
+            .map(|n| n.as_name())
+            .unwrap_or_else(Name::missing),
+        type_ref: TypeRef::from_ast_opt(node.type_ref()),
+    })
+}
+
+pub(crate) fn static_signature(db: &impl HirDatabase, def_id: DefId) -> Arc<ConstSignature> {
+    let syntax = Static::new(def_id).syntax(db);
+    let node = syntax.borrowed();
+    Arc::new(ConstSignature {
+        name: node
+            .name()
+            .map(|n| n.as_name())
+            .unwrap_or_else(Name::missing),
+        type_ref: TypeRef::from_ast_opt(node.type_ref()),
+    })
+}
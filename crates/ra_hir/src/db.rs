@@ -1,19 +1,25 @@
 use std::sync::Arc;
 
 use ra_syntax::{SyntaxNode, SourceFileNode};
-use ra_db::{SourceRootId, LocationIntener, SyntaxDatabase, Cancelable};
+use ra_db::{CrateId, LocationIntener, LocalSyntaxPtr, SyntaxDatabase, Cancelable};
 
 use crate::{
     DefLoc, DefId, MacroCallLoc, MacroCallId, Name, HirFileId,
     SourceFileItems, SourceItemId,
     query_definitions,
-    FnScopes,
-    macros::MacroExpansion,
+    FnScopes, FnSignature,
+    macros::{MacroExpansion, CrateMacros},
+    expr::{Body, BodySyntaxMapping},
     module::{ModuleId, ModuleTree, ModuleSource,
     nameres::{ItemMap, InputModuleItems}},
-    ty::{InferenceResult, Ty},
-    adt::{StructData, EnumData},
+    ty::{InferenceResult, Ty, method_resolution::CrateImplBlocks, traits::Implementation},
+    adt::{StructData, EnumData, EnumVariantData},
+    traits::TraitData,
+    consts::ConstSignature,
+    type_ref::TypeRef,
+    Crate,
     impl_block::ModuleImplBlocks,
+    generics::GenericParams,
 };
 
 salsa::query_group! {
@@ -30,12 +36,46 @@ pub trait HirDatabase: SyntaxDatabase
         type ExpandMacroCallQuery;
         use fn crate::macros::expand_macro_invocation;
     }
+    fn crate_macros(krate: CrateId) -> Cancelable<Arc<CrateMacros>> {
+        type CrateMacrosQuery;
+        use fn crate::macros::crate_macros;
+    }
 
-    fn fn_scopes(def_id: DefId) -> Arc<FnScopes> {
+    fn fn_scopes(def_id: DefId) -> Cancelable<Arc<FnScopes>> {
         type FnScopesQuery;
         use fn query_definitions::fn_scopes;
     }
 
+    fn fn_signature(def_id: DefId) -> Arc<FnSignature> {
+        type FnSignatureQuery;
+        use fn crate::function::fn_signature;
+    }
+
+    fn const_signature(def_id: DefId) -> Arc<ConstSignature> {
+        type ConstSignatureQuery;
+        use fn crate::consts::const_signature;
+    }
+
+    fn static_signature(def_id: DefId) -> Arc<ConstSignature> {
+        type StaticSignatureQuery;
+        use fn crate::consts::static_signature;
+    }
+
+    fn type_alias_ref(def_id: DefId) -> Arc<TypeRef> {
+        type TypeAliasRefQuery;
+        use fn crate::type_alias::type_alias_ref;
+    }
+
+    fn body_hir(def_id: DefId) -> Cancelable<Arc<Body>> {
+        type BodyHirQuery;
+        use fn crate::expr::body_hir;
+    }
+
+    fn body_syntax_mapping(def_id: DefId) -> Cancelable<Arc<BodySyntaxMapping>> {
+        type BodySyntaxMappingQuery;
+        use fn crate::expr::body_syntax_mapping;
+    }
+
     fn struct_data(def_id: DefId) -> Cancelable<Arc<StructData>> {
         type StructDataQuery;
         use fn query_definitions::struct_data;
@@ -46,11 +86,26 @@ pub trait HirDatabase: SyntaxDatabase
         use fn query_definitions::enum_data;
     }
 
+    fn enum_variant_data(def_id: DefId) -> Cancelable<Arc<EnumVariantData>> {
+        type EnumVariantDataQuery;
+        use fn query_definitions::enum_variant_data;
+    }
+
+    fn trait_data(def_id: DefId) -> Cancelable<Arc<TraitData>> {
+        type TraitDataQuery;
+        use fn crate::traits::trait_data;
+    }
+
     fn infer(def_id: DefId) -> Cancelable<Arc<InferenceResult>> {
         type InferQuery;
         use fn crate::ty::infer;
     }
 
+    fn generic_params(def_id: DefId) -> Cancelable<Arc<GenericParams>> {
+        type GenericParamsQuery;
+        use fn crate::generics::generic_params;
+    }
+
     fn type_for_def(def_id: DefId) -> Cancelable<Ty> {
         type TypeForDefQuery;
         use fn crate::ty::type_for_def;
@@ -71,28 +126,46 @@ pub trait HirDatabase: SyntaxDatabase
         use fn query_definitions::file_item;
     }
 
-    fn submodules(source: ModuleSource) -> Cancelable<Arc<Vec<crate::module::imp::Submodule>>> {
+    fn submodules(krate: CrateId, source: ModuleSource) -> Cancelable<Arc<Vec<crate::module::imp::Submodule>>> {
         type SubmodulesQuery;
         use fn query_definitions::submodules;
     }
 
-    fn input_module_items(source_root_id: SourceRootId, module_id: ModuleId) -> Cancelable<Arc<InputModuleItems>> {
+    fn input_module_items(krate: CrateId, module_id: ModuleId) -> Cancelable<Arc<InputModuleItems>> {
         type InputModuleItemsQuery;
         use fn query_definitions::input_module_items;
     }
-    fn item_map(source_root_id: SourceRootId) -> Cancelable<Arc<ItemMap>> {
+    fn macro_expansion_items(macro_call_id: MacroCallId) -> Cancelable<Arc<InputModuleItems>> {
+        type MacroExpansionItemsQuery;
+        use fn query_definitions::macro_expansion_items;
+    }
+    fn item_map(krate: CrateId) -> Cancelable<Arc<ItemMap>> {
         type ItemMapQuery;
         use fn query_definitions::item_map;
     }
-    fn module_tree(source_root_id: SourceRootId) -> Cancelable<Arc<ModuleTree>> {
+    fn module_tree(krate: CrateId) -> Cancelable<Arc<ModuleTree>> {
         type ModuleTreeQuery;
         use fn crate::module::imp::module_tree;
     }
+    fn private_path_segments(krate: CrateId, module_id: ModuleId) -> Cancelable<Arc<Vec<(LocalSyntaxPtr, Name)>>> {
+        type PrivatePathSegmentsQuery;
+        use fn query_definitions::private_path_segments;
+    }
 
-    fn impls_in_module(source_root_id: SourceRootId, module_id: ModuleId) -> Cancelable<Arc<ModuleImplBlocks>> {
+    fn impls_in_module(krate: CrateId, module_id: ModuleId) -> Cancelable<Arc<ModuleImplBlocks>> {
         type ImplsInModuleQuery;
         use fn crate::impl_block::impls_in_module;
     }
+
+    fn impls_in_crate(krate: Crate) -> Cancelable<Arc<CrateImplBlocks>> {
+        type ImplsInCrateQuery;
+        use fn crate::ty::method_resolution::impls_in_crate;
+    }
+
+    fn implements(ty: Ty, trait_def_id: DefId) -> Cancelable<Option<Implementation>> {
+        type ImplementsQuery;
+        use fn crate::ty::traits::implements;
+    }
 }
 
 }
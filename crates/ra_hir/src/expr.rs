@@ -0,0 +1,980 @@
+//! HIR for function bodies. A body is lowered from the syntax tree into arenas
+//! of expressions and patterns, which are referred to by `ExprId` and `PatId`.
+//! Type inference and name resolution inside functions work on this
+//! representation; the `BodySyntaxMapping` records where each expression and
+//! pattern came from, so that IDE features can go back and forth between HIR
+//! and syntax.
+//!
+//! Some syntactic sugar is removed during lowering: `if let` becomes a `match`,
+//! and `while let` becomes a `loop` containing a `match`.
+
+use std::ops::Index;
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+
+use ra_arena::{Arena, RawId, impl_arena_id};
+use ra_db::{LocalSyntaxPtr, Cancelable};
+use ra_syntax::ast::{self, AstNode, LoopBodyOwner, ArgListOwner, NameOwner, LiteralFlavor};
+
+use crate::{
+    Path, Name, AsName, DefId, Def,
+    db::HirDatabase,
+    type_ref::{Mutability, TypeRef},
+    ty::primitive::{UncertainIntTy, UncertainFloatTy},
+};
+
+pub use ra_syntax::ast::{BinOp, PrefixOp as UnaryOp};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(RawId);
+impl_arena_id!(ExprId);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PatId(RawId);
+impl_arena_id!(PatId);
+
+/// The body of a function.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Body {
+    exprs: Arena<ExprId, Expr>,
+    pats: Arena<PatId, Pat>,
+    /// The patterns for the function's parameters, including `self`. While the
+    /// parameter types are part of the function signature, the patterns are
+    /// not (they don't change the external type of the function).
+    args: Vec<PatId>,
+    /// The `ExprId` of the actual body expression.
+    body_expr: ExprId,
+}
+
+/// A body together with the mapping between its expressions and patterns and
+/// the syntax nodes they were lowered from. Type inference etc. only depend on
+/// the `Body`, which doesn't contain any positions, so that they don't need to
+/// be recomputed when e.g. whitespace is typed inside the function.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BodySyntaxMapping {
+    body: Arc<Body>,
+    expr_syntax_mapping: FxHashMap<LocalSyntaxPtr, ExprId>,
+    expr_syntax_mapping_back: FxHashMap<ExprId, LocalSyntaxPtr>,
+    pat_syntax_mapping: FxHashMap<LocalSyntaxPtr, PatId>,
+    pat_syntax_mapping_back: FxHashMap<PatId, LocalSyntaxPtr>,
+}
+
+impl Body {
+    pub fn args(&self) -> &[PatId] {
+        &self.args
+    }
+
+    pub fn body_expr(&self) -> ExprId {
+        self.body_expr
+    }
+
+    pub fn exprs(&self) -> impl Iterator<Item = (ExprId, &Expr)> {
+        self.exprs.iter()
+    }
+
+    pub fn pats(&self) -> impl Iterator<Item = (PatId, &Pat)> {
+        self.pats.iter()
+    }
+}
+
+impl Index<ExprId> for Body {
+    type Output = Expr;
+
+    fn index(&self, expr: ExprId) -> &Expr {
+        &self.exprs[expr]
+    }
+}
+
+impl Index<PatId> for Body {
+    type Output = Pat;
+
+    fn index(&self, pat: PatId) -> &Pat {
+        &self.pats[pat]
+    }
+}
+
+impl BodySyntaxMapping {
+    pub fn body(&self) -> &Arc<Body> {
+        &self.body
+    }
+
+    pub fn expr_syntax(&self, expr: ExprId) -> Option<LocalSyntaxPtr> {
+        self.expr_syntax_mapping_back.get(&expr).cloned()
+    }
+
+    pub fn syntax_expr(&self, ptr: LocalSyntaxPtr) -> Option<ExprId> {
+        self.expr_syntax_mapping.get(&ptr).cloned()
+    }
+
+    pub fn node_expr(&self, node: ast::Expr) -> Option<ExprId> {
+        self.syntax_expr(LocalSyntaxPtr::new(node.syntax()))
+    }
+
+    pub fn pat_syntax(&self, pat: PatId) -> Option<LocalSyntaxPtr> {
+        self.pat_syntax_mapping_back.get(&pat).cloned()
+    }
+
+    pub fn syntax_pat(&self, ptr: LocalSyntaxPtr) -> Option<PatId> {
+        self.pat_syntax_mapping.get(&ptr).cloned()
+    }
+
+    pub fn node_pat(&self, node: ast::Pat) -> Option<PatId> {
+        self.syntax_pat(LocalSyntaxPtr::new(node.syntax()))
+    }
+}
+
+/// A literal. Only the information relevant for type inference is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Literal {
+    String,
+    ByteString,
+    Char,
+    Byte,
+    Bool,
+    Int(UncertainIntTy),
+    Float(UncertainFloatTy),
+}
+
+impl Literal {
+    fn from_ast(lit: ast::Literal) -> Literal {
+        match lit.flavor() {
+            LiteralFlavor::String => Literal::String,
+            LiteralFlavor::ByteString => Literal::ByteString,
+            LiteralFlavor::Char => Literal::Char,
+            LiteralFlavor::Byte => Literal::Byte,
+            LiteralFlavor::Bool => Literal::Bool,
+            LiteralFlavor::IntNumber { suffix } => Literal::Int(UncertainIntTy::from_suffix(
+                suffix.as_ref().map(|s| s.as_str()),
+            )),
+            LiteralFlavor::FloatNumber { suffix } => Literal::Float(UncertainFloatTy::from_suffix(
+                suffix.as_ref().map(|s| s.as_str()),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// This is produced if the syntax tree does not have a required expression piece.
+    Missing,
+    Path(Path),
+    If {
+        condition: ExprId,
+        then_branch: ExprId,
+        else_branch: Option<ExprId>,
+    },
+    Block {
+        statements: Vec<Statement>,
+        tail: Option<ExprId>,
+    },
+    Loop {
+        body: ExprId,
+    },
+    While {
+        condition: ExprId,
+        body: ExprId,
+    },
+    For {
+        iterable: ExprId,
+        pat: PatId,
+        body: ExprId,
+    },
+    Call {
+        callee: ExprId,
+        args: Vec<ExprId>,
+    },
+    MethodCall {
+        receiver: ExprId,
+        method_name: Name,
+        args: Vec<ExprId>,
+    },
+    Match {
+        expr: ExprId,
+        arms: Vec<MatchArm>,
+    },
+    Continue,
+    Break {
+        expr: Option<ExprId>,
+    },
+    Return {
+        expr: Option<ExprId>,
+    },
+    StructLit {
+        path: Option<Path>,
+        fields: Vec<StructLitField>,
+    },
+    Field {
+        expr: ExprId,
+        name: Name,
+    },
+    Try {
+        expr: ExprId,
+    },
+    Cast {
+        expr: ExprId,
+        type_ref: TypeRef,
+    },
+    Ref {
+        expr: ExprId,
+        mutability: Mutability,
+    },
+    UnaryOp {
+        expr: ExprId,
+        op: Option<UnaryOp>,
+    },
+    BinaryOp {
+        lhs: ExprId,
+        rhs: ExprId,
+        op: Option<BinOp>,
+    },
+    Index {
+        base: ExprId,
+        index: ExprId,
+    },
+    Range {
+        start: Option<ExprId>,
+        end: Option<ExprId>,
+        inclusive: bool,
+    },
+    Lambda {
+        args: Vec<PatId>,
+        arg_types: Vec<Option<TypeRef>>,
+        body: ExprId,
+    },
+    Tuple {
+        exprs: Vec<ExprId>,
+    },
+    Array(Array),
+    Literal(Literal),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Array {
+    ElementList(Vec<ExprId>),
+    /// `[initializer; repeat]`
+    Repeat {
+        initializer: ExprId,
+        repeat: ExprId,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchArm {
+    pub pats: Vec<PatId>,
+    pub guard: Option<ExprId>,
+    pub expr: ExprId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLitField {
+    pub name: Name,
+    pub expr: ExprId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Let {
+        pat: PatId,
+        type_ref: Option<TypeRef>,
+        initializer: Option<ExprId>,
+    },
+    Expr(ExprId),
+}
+
+impl Expr {
+    pub fn walk_child_exprs(&self, mut f: impl FnMut(ExprId)) {
+        match self {
+            Expr::Missing | Expr::Path(_) | Expr::Continue | Expr::Literal(_) => {}
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                f(*condition);
+                f(*then_branch);
+                if let Some(else_branch) = else_branch {
+                    f(*else_branch);
+                }
+            }
+            Expr::Block { statements, tail } => {
+                for stmt in statements {
+                    match stmt {
+                        Statement::Let { initializer, .. } => {
+                            if let Some(expr) = initializer {
+                                f(*expr);
+                            }
+                        }
+                        Statement::Expr(e) => f(*e),
+                    }
+                }
+                if let Some(expr) = tail {
+                    f(*expr);
+                }
+            }
+            Expr::Loop { body } => f(*body),
+            Expr::While { condition, body } => {
+                f(*condition);
+                f(*body);
+            }
+            Expr::For { iterable, body, .. } => {
+                f(*iterable);
+                f(*body);
+            }
+            Expr::Call { callee, args } => {
+                f(*callee);
+                for arg in args {
+                    f(*arg);
+                }
+            }
+            Expr::MethodCall { receiver, args, .. } => {
+                f(*receiver);
+                for arg in args {
+                    f(*arg);
+                }
+            }
+            Expr::Match { expr, arms } => {
+                f(*expr);
+                for arm in arms {
+                    if let Some(guard) = arm.guard {
+                        f(guard);
+                    }
+                    f(arm.expr);
+                }
+            }
+            Expr::Break { expr } | Expr::Return { expr } => {
+                if let Some(expr) = expr {
+                    f(*expr);
+                }
+            }
+            Expr::StructLit { fields, .. } => {
+                for field in fields {
+                    f(field.expr);
+                }
+            }
+            Expr::Field { expr, .. }
+            | Expr::Try { expr }
+            | Expr::Cast { expr, .. }
+            | Expr::Ref { expr, .. }
+            | Expr::UnaryOp { expr, .. } => f(*expr),
+            Expr::BinaryOp { lhs, rhs, .. } => {
+                f(*lhs);
+                f(*rhs);
+            }
+            Expr::Index { base, index } => {
+                f(*base);
+                f(*index);
+            }
+            Expr::Range { start, end, .. } => {
+                if let Some(start) = start {
+                    f(*start);
+                }
+                if let Some(end) = end {
+                    f(*end);
+                }
+            }
+            Expr::Lambda { body, .. } => f(*body),
+            Expr::Tuple { exprs } | Expr::Array(Array::ElementList(exprs)) => {
+                for expr in exprs {
+                    f(*expr);
+                }
+            }
+            Expr::Array(Array::Repeat {
+                initializer,
+                repeat,
+            }) => {
+                f(*initializer);
+                f(*repeat);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingAnnotation {
+    Unannotated,
+    Mutable,
+    Ref,
+    RefMut,
+}
+
+impl BindingAnnotation {
+    fn new(is_mutable: bool, is_ref: bool) -> BindingAnnotation {
+        match (is_mutable, is_ref) {
+            (true, true) => BindingAnnotation::RefMut,
+            (false, true) => BindingAnnotation::Ref,
+            (true, false) => BindingAnnotation::Mutable,
+            (false, false) => BindingAnnotation::Unannotated,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPat {
+    pub name: Name,
+    pub pat: PatId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pat {
+    /// This is produced if the syntax tree does not have a required pattern
+    /// piece, or for patterns we don't lower yet (ranges).
+    Missing,
+    /// `_`
+    Wild,
+    Tuple(Vec<PatId>),
+    Ref {
+        pat: PatId,
+        mutability: Mutability,
+    },
+    TupleStruct {
+        path: Option<Path>,
+        args: Vec<PatId>,
+    },
+    Struct {
+        path: Option<Path>,
+        args: Vec<FieldPat>,
+    },
+    Slice(Vec<PatId>),
+    Bind {
+        name: Name,
+        mode: BindingAnnotation,
+        subpat: Option<PatId>,
+    },
+    Path(Path),
+    Lit(Literal),
+}
+
+impl Pat {
+    pub fn walk_child_pats(&self, mut f: impl FnMut(PatId)) {
+        match self {
+            Pat::Missing | Pat::Wild | Pat::Path(_) | Pat::Lit(_) => {}
+            Pat::Bind { subpat, .. } => {
+                if let Some(subpat) = subpat {
+                    f(*subpat);
+                }
+            }
+            Pat::Tuple(args) | Pat::TupleStruct { args, .. } | Pat::Slice(args) => {
+                args.iter().cloned().for_each(f)
+            }
+            Pat::Ref { pat, .. } => f(*pat),
+            Pat::Struct { args, .. } => args.iter().map(|field| field.pat).for_each(f),
+        }
+    }
+}
+
+// Queries
+
+pub(crate) fn body_hir(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<Body>> {
+    Ok(Arc::clone(db.body_syntax_mapping(def_id)?.body()))
+}
+
+pub(crate) fn body_syntax_mapping(
+    db: &impl HirDatabase,
+    def_id: DefId,
+) -> Cancelable<Arc<BodySyntaxMapping>> {
+    let body_syntax_mapping = match def_id.resolve(db)? {
+        Def::Function(f) => collect_fn_body_syntax(f.syntax(db).borrowed()),
+        // TODO: consts and statics also have bodies
+        _ => panic!("trying to get body for item without body: {:?}", def_id),
+    };
+    Ok(Arc::new(body_syntax_mapping))
+}
+
+pub(crate) fn collect_fn_body_syntax(node: ast::FnDef) -> BodySyntaxMapping {
+    let mut collector = ExprCollector::new();
+    let mut args = Vec::new();
+    if let Some(param_list) = node.param_list() {
+        if let Some(self_param) = param_list.self_param() {
+            let self_param_syntax = self_param
+                .self_kw()
+                .map(|kw| kw.syntax())
+                .unwrap_or_else(|| self_param.syntax());
+            let pat = collector.alloc_pat(
+                Pat::Bind {
+                    name: Name::self_param(),
+                    mode: BindingAnnotation::Unannotated,
+                    subpat: None,
+                },
+                LocalSyntaxPtr::new(self_param_syntax),
+            );
+            args.push(pat);
+        }
+        for param in param_list.params() {
+            // parameters without a pattern still get a (missing) pattern, so
+            // that the patterns correspond to the parameter types
+            args.push(collector.collect_pat_opt(param.pat()));
+        }
+    }
+    let body_expr = collector.collect_block_opt(node.body());
+    collector.into_body_syntax_mapping(args, body_expr)
+}
+
+struct ExprCollector {
+    exprs: Arena<ExprId, Expr>,
+    pats: Arena<PatId, Pat>,
+    expr_syntax_mapping: FxHashMap<LocalSyntaxPtr, ExprId>,
+    expr_syntax_mapping_back: FxHashMap<ExprId, LocalSyntaxPtr>,
+    pat_syntax_mapping: FxHashMap<LocalSyntaxPtr, PatId>,
+    pat_syntax_mapping_back: FxHashMap<PatId, LocalSyntaxPtr>,
+}
+
+impl ExprCollector {
+    fn new() -> Self {
+        ExprCollector {
+            exprs: Arena::default(),
+            pats: Arena::default(),
+            expr_syntax_mapping: FxHashMap::default(),
+            expr_syntax_mapping_back: FxHashMap::default(),
+            pat_syntax_mapping: FxHashMap::default(),
+            pat_syntax_mapping_back: FxHashMap::default(),
+        }
+    }
+
+    fn alloc_expr(&mut self, expr: Expr, syntax_ptr: LocalSyntaxPtr) -> ExprId {
+        let id = self.exprs.alloc(expr);
+        self.expr_syntax_mapping.insert(syntax_ptr, id);
+        self.expr_syntax_mapping_back.insert(id, syntax_ptr);
+        id
+    }
+
+    fn alloc_pat(&mut self, pat: Pat, syntax_ptr: LocalSyntaxPtr) -> PatId {
+        let id = self.pats.alloc(pat);
+        self.pat_syntax_mapping.insert(syntax_ptr, id);
+        self.pat_syntax_mapping_back.insert(id, syntax_ptr);
+        id
+    }
+
+    fn empty_block(&mut self) -> ExprId {
+        self.exprs.alloc(Expr::Block {
+            statements: Vec::new(),
+            tail: None,
+        })
+    }
+
+    fn collect_expr(&mut self, expr: ast::Expr) -> ExprId {
+        let syntax_ptr = LocalSyntaxPtr::new(expr.syntax());
+        match expr {
+            ast::Expr::IfExpr(e) => {
+                if let Some(pat) = e.condition().and_then(|c| c.pat()) {
+                    // `if let` -- desugar to `match`
+                    let pat = self.collect_pat(pat);
+                    let match_expr =
+                        self.collect_expr_opt(e.condition().expect("checked above").expr());
+                    let then_branch = self.collect_block_opt(e.then_branch());
+                    let else_branch = e
+                        .else_branch()
+                        .map(|b| self.collect_block(b))
+                        .unwrap_or_else(|| self.empty_block());
+                    let placeholder_pat = self.pats.alloc(Pat::Wild);
+                    let arms = vec![
+                        MatchArm {
+                            pats: vec![pat],
+                            guard: None,
+                            expr: then_branch,
+                        },
+                        MatchArm {
+                            pats: vec![placeholder_pat],
+                            guard: None,
+                            expr: else_branch,
+                        },
+                    ];
+                    self.alloc_expr(
+                        Expr::Match {
+                            expr: match_expr,
+                            arms,
+                        },
+                        syntax_ptr,
+                    )
+                } else {
+                    let condition = self.collect_expr_opt(e.condition().and_then(|c| c.expr()));
+                    let then_branch = self.collect_block_opt(e.then_branch());
+                    let else_branch = e.else_branch().map(|b| self.collect_block(b));
+                    self.alloc_expr(
+                        Expr::If {
+                            condition,
+                            then_branch,
+                            else_branch,
+                        },
+                        syntax_ptr,
+                    )
+                }
+            }
+            ast::Expr::BlockExpr(e) => {
+                let block = self.collect_block_opt(e.block());
+                // make the block expr point to the block as well
+                self.expr_syntax_mapping.insert(syntax_ptr, block);
+                block
+            }
+            ast::Expr::LoopExpr(e) => {
+                let body = self.collect_block_opt(e.loop_body());
+                self.alloc_expr(Expr::Loop { body }, syntax_ptr)
+            }
+            ast::Expr::WhileExpr(e) => {
+                if let Some(pat) = e.condition().and_then(|c| c.pat()) {
+                    // `while let` -- desugar to `loop { match .. { pat => body, _ => break } }`
+                    let pat = self.collect_pat(pat);
+                    let match_expr =
+                        self.collect_expr_opt(e.condition().expect("checked above").expr());
+                    let body = self.collect_block_opt(e.loop_body());
+                    let placeholder_pat = self.pats.alloc(Pat::Wild);
+                    let break_expr = self.exprs.alloc(Expr::Break { expr: None });
+                    let arms = vec![
+                        MatchArm {
+                            pats: vec![pat],
+                            guard: None,
+                            expr: body,
+                        },
+                        MatchArm {
+                            pats: vec![placeholder_pat],
+                            guard: None,
+                            expr: break_expr,
+                        },
+                    ];
+                    let match_expr = self.exprs.alloc(Expr::Match {
+                        expr: match_expr,
+                        arms,
+                    });
+                    self.alloc_expr(Expr::Loop { body: match_expr }, syntax_ptr)
+                } else {
+                    let condition = self.collect_expr_opt(e.condition().and_then(|c| c.expr()));
+                    let body = self.collect_block_opt(e.loop_body());
+                    self.alloc_expr(Expr::While { condition, body }, syntax_ptr)
+                }
+            }
+            ast::Expr::ForExpr(e) => {
+                let iterable = self.collect_expr_opt(e.iterable());
+                let pat = self.collect_pat_opt(e.pat());
+                let body = self.collect_block_opt(e.loop_body());
+                self.alloc_expr(
+                    Expr::For {
+                        iterable,
+                        pat,
+                        body,
+                    },
+                    syntax_ptr,
+                )
+            }
+            ast::Expr::CallExpr(e) => {
+                let callee = self.collect_expr_opt(e.expr());
+                let args = self.collect_args(e.arg_list());
+                self.alloc_expr(Expr::Call { callee, args }, syntax_ptr)
+            }
+            ast::Expr::MethodCallExpr(e) => {
+                let receiver = self.collect_expr_opt(e.expr());
+                let args = self.collect_args(e.arg_list());
+                let method_name = e
+                    .name_ref()
+                    .map(|nr| nr.as_name())
+                    .unwrap_or_else(Name::missing);
+                self.alloc_expr(
+                    Expr::MethodCall {
+                        receiver,
+                        method_name,
+                        args,
+                    },
+                    syntax_ptr,
+                )
+            }
+            ast::Expr::MatchExpr(e) => {
+                let expr = self.collect_expr_opt(e.expr());
+                let mut arms = Vec::new();
+                for arm in e.match_arm_list().into_iter().flat_map(|l| l.arms()) {
+                    let pats = arm.pats().map(|p| self.collect_pat(p)).collect();
+                    let guard = arm
+                        .guard()
+                        .and_then(|g| g.expr())
+                        .map(|e| self.collect_expr(e));
+                    let expr = self.collect_expr_opt(arm.expr());
+                    arms.push(MatchArm { pats, guard, expr });
+                }
+                self.alloc_expr(Expr::Match { expr, arms }, syntax_ptr)
+            }
+            ast::Expr::PathExpr(e) => {
+                let path = e
+                    .path()
+                    .and_then(Path::from_ast)
+                    .map(Expr::Path)
+                    .unwrap_or(Expr::Missing);
+                self.alloc_expr(path, syntax_ptr)
+            }
+            ast::Expr::ContinueExpr(_e) => {
+                // TODO: labels
+                self.alloc_expr(Expr::Continue, syntax_ptr)
+            }
+            ast::Expr::BreakExpr(e) => {
+                let expr = e.expr().map(|e| self.collect_expr(e));
+                self.alloc_expr(Expr::Break { expr }, syntax_ptr)
+            }
+            ast::Expr::ParenExpr(e) => {
+                let inner = self.collect_expr_opt(e.expr());
+                // make the paren expr point to the inner expression as well
+                self.expr_syntax_mapping.insert(syntax_ptr, inner);
+                inner
+            }
+            ast::Expr::ReturnExpr(e) => {
+                let expr = e.expr().map(|e| self.collect_expr(e));
+                self.alloc_expr(Expr::Return { expr }, syntax_ptr)
+            }
+            ast::Expr::StructLit(e) => {
+                let path = e.path().and_then(Path::from_ast);
+                let mut fields = Vec::new();
+                for field in e.named_field_list().into_iter().flat_map(|l| l.fields()) {
+                    let name = field
+                        .name_ref()
+                        .map(|nr| nr.as_name())
+                        .unwrap_or_else(Name::missing);
+                    let expr = match (field.expr(), field.name_ref()) {
+                        (Some(e), _) => self.collect_expr(e),
+                        // shorthand fields like `S { a }` refer to the binding
+                        (None, Some(nr)) => self.alloc_expr(
+                            Expr::Path(Path::from(nr.as_name())),
+                            LocalSyntaxPtr::new(nr.syntax()),
+                        ),
+                        (None, None) => self.exprs.alloc(Expr::Missing),
+                    };
+                    fields.push(StructLitField { name, expr });
+                }
+                self.alloc_expr(Expr::StructLit { path, fields }, syntax_ptr)
+            }
+            ast::Expr::FieldExpr(e) => {
+                let expr = self.collect_expr_opt(e.expr());
+                let name = e
+                    .name_ref()
+                    .map(|nr| nr.as_name())
+                    .unwrap_or_else(Name::missing);
+                self.alloc_expr(Expr::Field { expr, name }, syntax_ptr)
+            }
+            ast::Expr::TryExpr(e) => {
+                // TODO: desugar to a `match` on `Try::into_result`
+                let expr = self.collect_expr_opt(e.expr());
+                self.alloc_expr(Expr::Try { expr }, syntax_ptr)
+            }
+            ast::Expr::CastExpr(e) => {
+                let expr = self.collect_expr_opt(e.expr());
+                let type_ref = TypeRef::from_ast_opt(e.type_ref());
+                self.alloc_expr(Expr::Cast { expr, type_ref }, syntax_ptr)
+            }
+            ast::Expr::RefExpr(e) => {
+                let expr = self.collect_expr_opt(e.expr());
+                let mutability = Mutability::from_mutable(e.is_mut());
+                self.alloc_expr(Expr::Ref { expr, mutability }, syntax_ptr)
+            }
+            ast::Expr::PrefixExpr(e) => {
+                let expr = self.collect_expr_opt(e.expr());
+                self.alloc_expr(Expr::UnaryOp { expr, op: e.op() }, syntax_ptr)
+            }
+            ast::Expr::LambdaExpr(e) => {
+                let mut args = Vec::new();
+                let mut arg_types = Vec::new();
+                for param in e.param_list().into_iter().flat_map(|l| l.params()) {
+                    args.push(self.collect_pat_opt(param.pat()));
+                    arg_types.push(param.type_ref().map(TypeRef::from_ast));
+                }
+                let body = self.collect_expr_opt(e.body());
+                self.alloc_expr(
+                    Expr::Lambda {
+                        args,
+                        arg_types,
+                        body,
+                    },
+                    syntax_ptr,
+                )
+            }
+            ast::Expr::BinExpr(e) => {
+                let lhs = self.collect_expr_opt(e.lhs());
+                let rhs = self.collect_expr_opt(e.rhs());
+                self.alloc_expr(
+                    Expr::BinaryOp {
+                        lhs,
+                        rhs,
+                        op: e.op(),
+                    },
+                    syntax_ptr,
+                )
+            }
+            ast::Expr::TupleExpr(e) => {
+                let exprs = e.exprs().map(|e| self.collect_expr(e)).collect();
+                self.alloc_expr(Expr::Tuple { exprs }, syntax_ptr)
+            }
+            ast::Expr::ArrayExpr(e) => {
+                let array = if e.is_repeat() {
+                    let mut exprs = e.exprs();
+                    let initializer = self.collect_expr_opt(exprs.next());
+                    let repeat = self.collect_expr_opt(exprs.next());
+                    Array::Repeat {
+                        initializer,
+                        repeat,
+                    }
+                } else {
+                    Array::ElementList(e.exprs().map(|e| self.collect_expr(e)).collect())
+                };
+                self.alloc_expr(Expr::Array(array), syntax_ptr)
+            }
+            ast::Expr::IndexExpr(e) => {
+                let base = self.collect_expr_opt(e.base());
+                let index = self.collect_expr_opt(e.index());
+                self.alloc_expr(Expr::Index { base, index }, syntax_ptr)
+            }
+            ast::Expr::RangeExpr(e) => {
+                let start = e.start().map(|e| self.collect_expr(e));
+                let end = e.end().map(|e| self.collect_expr(e));
+                let inclusive = e.is_inclusive();
+                self.alloc_expr(
+                    Expr::Range {
+                        start,
+                        end,
+                        inclusive,
+                    },
+                    syntax_ptr,
+                )
+            }
+            ast::Expr::Literal(e) => {
+                self.alloc_expr(Expr::Literal(Literal::from_ast(e)), syntax_ptr)
+            }
+            // these can't occur on their own, only as part of a match or
+            // struct literal
+            ast::Expr::MatchArmList(_)
+            | ast::Expr::MatchArm(_)
+            | ast::Expr::NamedFieldList(_)
+            | ast::Expr::NamedField(_) => self.alloc_expr(Expr::Missing, syntax_ptr),
+            // TODO: labels
+            ast::Expr::Label(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
+        }
+    }
+
+    fn collect_expr_opt(&mut self, expr: Option<ast::Expr>) -> ExprId {
+        if let Some(expr) = expr {
+            self.collect_expr(expr)
+        } else {
+            self.exprs.alloc(Expr::Missing)
+        }
+    }
+
+    fn collect_args(&mut self, arg_list: Option<ast::ArgList>) -> Vec<ExprId> {
+        arg_list
+            .into_iter()
+            .flat_map(|l| l.args())
+            .map(|e| self.collect_expr(e))
+            .collect()
+    }
+
+    fn collect_block(&mut self, block: ast::Block) -> ExprId {
+        let statements = block
+            .statements()
+            .map(|s| match s {
+                ast::Stmt::LetStmt(stmt) => {
+                    let pat = self.collect_pat_opt(stmt.pat());
+                    let type_ref = stmt.type_ref().map(TypeRef::from_ast);
+                    let initializer = stmt.initializer().map(|e| self.collect_expr(e));
+                    Statement::Let {
+                        pat,
+                        type_ref,
+                        initializer,
+                    }
+                }
+                ast::Stmt::ExprStmt(stmt) => Statement::Expr(self.collect_expr_opt(stmt.expr())),
+            })
+            .collect();
+        let tail = block.expr().map(|e| self.collect_expr(e));
+        self.alloc_expr(
+            Expr::Block { statements, tail },
+            LocalSyntaxPtr::new(block.syntax()),
+        )
+    }
+
+    fn collect_block_opt(&mut self, block: Option<ast::Block>) -> ExprId {
+        if let Some(block) = block {
+            self.collect_block(block)
+        } else {
+            self.exprs.alloc(Expr::Missing)
+        }
+    }
+
+    fn collect_pat(&mut self, pat: ast::Pat) -> PatId {
+        let syntax_ptr = LocalSyntaxPtr::new(pat.syntax());
+        let pattern = match pat {
+            ast::Pat::BindPat(bp) => {
+                let name = bp
+                    .name()
+                    .map(|nr| nr.as_name())
+                    .unwrap_or_else(Name::missing);
+                let mode = BindingAnnotation::new(bp.is_mutable(), bp.is_ref());
+                let subpat = bp.pat().map(|p| self.collect_pat(p));
+                Pat::Bind { name, mode, subpat }
+            }
+            ast::Pat::TupleStructPat(p) => {
+                let path = p.path().and_then(Path::from_ast);
+                let args = p.args().map(|p| self.collect_pat(p)).collect();
+                Pat::TupleStruct { path, args }
+            }
+            ast::Pat::RefPat(p) => {
+                let pat = self.collect_pat_opt(p.pat());
+                let mutability = Mutability::from_mutable(p.is_mut());
+                Pat::Ref { pat, mutability }
+            }
+            ast::Pat::PathPat(p) => match p.path().and_then(Path::from_ast) {
+                Some(path) => Pat::Path(path),
+                None => Pat::Missing,
+            },
+            ast::Pat::TuplePat(p) => Pat::Tuple(p.args().map(|p| self.collect_pat(p)).collect()),
+            ast::Pat::PlaceholderPat(_) => Pat::Wild,
+            ast::Pat::StructPat(p) => {
+                let path = p.path().and_then(Path::from_ast);
+                let mut args = Vec::new();
+                if let Some(field_pat_list) = p.field_pat_list() {
+                    for field_pat in field_pat_list.field_pats() {
+                        let name = field_pat
+                            .name()
+                            .map(|n| n.as_name())
+                            .unwrap_or_else(Name::missing);
+                        let pat = self.collect_pat_opt(field_pat.pat());
+                        args.push(FieldPat { name, pat });
+                    }
+                    // shorthand field patterns like `S { a, ref b }`
+                    for bind_pat in field_pat_list.bind_pats() {
+                        let name = bind_pat
+                            .name()
+                            .map(|n| n.as_name())
+                            .unwrap_or_else(Name::missing);
+                        let pat = self.collect_pat(ast::Pat::BindPat(bind_pat));
+                        args.push(FieldPat { name, pat });
+                    }
+                }
+                Pat::Struct { path, args }
+            }
+            ast::Pat::SlicePat(p) => Pat::Slice(p.args().map(|p| self.collect_pat(p)).collect()),
+            ast::Pat::LiteralPat(p) => match p.literal() {
+                Some(lit) => Pat::Lit(Literal::from_ast(lit)),
+                None => Pat::Missing,
+            },
+            // TODO: range patterns
+            ast::Pat::RangePat(_) | ast::Pat::FieldPatList(_) => Pat::Missing,
+        };
+        self.alloc_pat(pattern, syntax_ptr)
+    }
+
+    fn collect_pat_opt(&mut self, pat: Option<ast::Pat>) -> PatId {
+        if let Some(pat) = pat {
+            self.collect_pat(pat)
+        } else {
+            self.pats.alloc(Pat::Missing)
+        }
+    }
+
+    fn into_body_syntax_mapping(self, args: Vec<PatId>, body_expr: ExprId) -> BodySyntaxMapping {
+        let body = Body {
+            exprs: self.exprs,
+            pats: self.pats,
+            args,
+            body_expr,
+        };
+        BodySyntaxMapping {
+            body: Arc::new(body),
+            expr_syntax_mapping: self.expr_syntax_mapping,
+            expr_syntax_mapping_back: self.expr_syntax_mapping_back,
+            pat_syntax_mapping: self.pat_syntax_mapping,
+            pat_syntax_mapping_back: self.pat_syntax_mapping_back,
+        }
+    }
+}
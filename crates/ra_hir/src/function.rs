@@ -11,9 +11,15 @@ use ra_syntax::{
     ast::{self, AstNode, DocCommentsOwner, NameOwner},
 };
 
-use crate::{DefId, DefKind, HirDatabase, ty::InferenceResult, Module, Crate, impl_block::ImplBlock};
+use crate::{
+    DefId, DefKind, HirDatabase, Name, AsName, Module, Crate,
+    ty::InferenceResult,
+    impl_block::ImplBlock,
+    expr::{Body, BodySyntaxMapping},
+    type_ref::{TypeRef, Mutability},
+};
 
-pub use self::scope::FnScopes;
+pub use self::scope::{FnScopes, ScopesWithSyntaxMapping, ScopeEntryWithSyntax};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Function {
@@ -36,8 +42,25 @@ impl Function {
         ast::FnDef::cast(syntax.borrowed()).unwrap().owned()
     }
 
-    pub fn scopes(&self, db: &impl HirDatabase) -> Arc<FnScopes> {
-        db.fn_scopes(self.def_id)
+    pub fn body_syntax_mapping(&self, db: &impl HirDatabase) -> Cancelable<Arc<BodySyntaxMapping>> {
+        db.body_syntax_mapping(self.def_id)
+    }
+
+    pub fn body(&self, db: &impl HirDatabase) -> Cancelable<Arc<Body>> {
+        db.body_hir(self.def_id)
+    }
+
+    pub fn scopes(&self, db: &impl HirDatabase) -> Cancelable<ScopesWithSyntaxMapping> {
+        let scopes = db.fn_scopes(self.def_id)?;
+        let syntax_mapping = db.body_syntax_mapping(self.def_id)?;
+        Ok(ScopesWithSyntaxMapping {
+            scopes,
+            syntax_mapping,
+        })
+    }
+
+    pub fn signature(&self, db: &impl HirDatabase) -> Arc<FnSignature> {
+        db.fn_signature(self.def_id)
     }
 
     pub fn signature_info(&self, db: &impl HirDatabase) -> Option<FnSignatureInfo> {
@@ -63,12 +86,89 @@ impl Function {
     }
 }
 
+/// The declared signature of a function: its name, parameter types and return
+/// type. Unlike the syntax, this doesn't change when the body is edited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FnSignature {
+    name: Name,
+    /// The types of the parameters, starting with `self` if there is one.
+    args: Vec<TypeRef>,
+    ret_type: TypeRef,
+    has_self_param: bool,
+}
+
+impl FnSignature {
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    pub fn args(&self) -> &[TypeRef] {
+        &self.args
+    }
+
+    pub fn ret_type(&self) -> &TypeRef {
+        &self.ret_type
+    }
+
+    /// Whether the first parameter is `self`, i.e. whether this can be called
+    /// as a method.
+    pub fn has_self_param(&self) -> bool {
+        self.has_self_param
+    }
+}
+
+pub(crate) fn fn_signature(db: &impl HirDatabase, def_id: DefId) -> Arc<FnSignature> {
+    let func = Function::new(def_id);
+    let syntax = func.syntax(db);
+    let node = syntax.borrowed();
+    let name = node
+        .name()
+        .map(|n| n.as_name())
+        .unwrap_or_else(Name::missing);
+    let mut args = Vec::new();
+    let mut has_self_param = false;
+    if let Some(param_list) = node.param_list() {
+        if let Some(self_param) = param_list.self_param() {
+            let self_type = if let Some(type_ref) = self_param.type_ref() {
+                TypeRef::from_ast(type_ref)
+            } else {
+                let self_type = TypeRef::Path(Name::self_type().into());
+                match self_param.flavor() {
+                    ast::SelfParamFlavor::Owned => self_type,
+                    ast::SelfParamFlavor::Ref => {
+                        TypeRef::Reference(Box::new(self_type), Mutability::Shared)
+                    }
+                    ast::SelfParamFlavor::MutRef => {
+                        TypeRef::Reference(Box::new(self_type), Mutability::Mut)
+                    }
+                }
+            };
+            args.push(self_type);
+            has_self_param = true;
+        }
+        for param in param_list.params() {
+            args.push(TypeRef::from_ast_opt(param.type_ref()));
+        }
+    }
+    let ret_type = match node.ret_type().and_then(|rt| rt.type_ref()) {
+        Some(type_ref) => TypeRef::from_ast(type_ref),
+        None => TypeRef::unit(),
+    };
+    Arc::new(FnSignature {
+        name,
+        args,
+        ret_type,
+        has_self_param,
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct FnSignatureInfo {
     pub name: String,
     pub label: String,
     pub ret_type: Option<String>,
     pub params: Vec<String>,
+    pub has_self_param: bool,
     pub doc: Option<String>,
 }
 
@@ -125,12 +225,14 @@ impl FnSignatureInfo {
         }
 
         let params = FnSignatureInfo::param_list(node);
+        let has_self_param = node.param_list().and_then(|pl| pl.self_param()).is_some();
         let ret_type = node.ret_type().map(|r| r.syntax().text().to_string());
 
         Some(FnSignatureInfo {
             name,
             ret_type,
             params,
+            has_self_param,
             label: label.trim().to_owned(),
             doc,
         })
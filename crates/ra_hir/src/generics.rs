@@ -0,0 +1,191 @@
+//! Many kinds of items or constructs can have generic parameters: functions,
+//! structs, impls, traits, etc. This module provides a common HIR for these
+//! generic parameters. See also the `Generics` type and the `generics_of` query
+//! in rustc.
+
+use std::sync::Arc;
+
+use ra_db::Cancelable;
+use ra_syntax::{AstNode, ast::{self, NameOwner, TypeParamsOwner}};
+
+use crate::{
+    DefId, DefKind, Name, AsName, EnumVariant,
+    db::HirDatabase,
+    name::KnownName,
+    type_ref::TypeRef,
+};
+
+/// Data about a generic parameter (to a function, struct, impl, ...).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GenericParam {
+    pub(crate) idx: u32,
+    pub(crate) name: Name,
+    /// The signature of an `Fn`, `FnMut` or `FnOnce` bound on the parameter,
+    /// as a fn pointer type. Other bounds are not recorded yet.
+    pub(crate) fn_bound: Option<TypeRef>,
+}
+
+/// Data about the generic parameters of a function, struct, impl, etc.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct GenericParams {
+    /// The generic parameters of the parent item, e.g. the impl of a method.
+    /// The indices of our own parameters continue after those of the parent.
+    pub(crate) parent_params: Option<Arc<GenericParams>>,
+    pub(crate) params: Vec<GenericParam>,
+}
+
+impl GenericParams {
+    pub(crate) fn from_ast(
+        parent_params: Option<Arc<GenericParams>>,
+        node: Option<ast::TypeParamList>,
+    ) -> GenericParams {
+        let mut generics = GenericParams {
+            parent_params,
+            params: Vec::new(),
+        };
+        if let Some(params) = node {
+            generics.fill_params(params);
+        }
+        generics
+    }
+
+    /// The generic parameters of a trait. `Self` is treated like a parameter
+    /// of a parent, so that the trait's own parameters match up with the
+    /// arguments in paths like `Trait<A, B>`.
+    pub(crate) fn for_trait(node: Option<ast::TypeParamList>) -> GenericParams {
+        let self_param = GenericParam {
+            idx: 0,
+            name: Name::self_type(),
+            fn_bound: None,
+        };
+        let self_params = GenericParams {
+            parent_params: None,
+            params: vec![self_param],
+        };
+        GenericParams::from_ast(Some(Arc::new(self_params)), node)
+    }
+
+    fn fill_params(&mut self, params: ast::TypeParamList) {
+        let start = self.count_params_including_parent() as u32;
+        for (idx, type_param) in params.type_params().enumerate() {
+            let name = type_param
+                .name()
+                .map(|n| n.as_name())
+                .unwrap_or_else(Name::missing);
+            // TODO: bounds in where clauses
+            let fn_bound = type_param.bounds().find_map(fn_bound);
+            let param = GenericParam {
+                idx: start + idx as u32,
+                name,
+                fn_bound,
+            };
+            self.params.push(param);
+        }
+    }
+
+    /// Finds the parameter with the given name, looking in the parent's
+    /// parameters if it is not one of our own.
+    pub(crate) fn find_by_name(&self, name: &Name) -> Option<&GenericParam> {
+        self.params.iter().find(|p| &p.name == name).or_else(|| {
+            self.parent_params
+                .as_ref()
+                .and_then(|parent| parent.find_by_name(name))
+        })
+    }
+
+    /// Finds the parameter with the given index, looking in the parent's
+    /// parameters if it is not one of our own.
+    pub(crate) fn find_by_idx(&self, idx: u32) -> Option<&GenericParam> {
+        self.params.iter().find(|p| p.idx == idx).or_else(|| {
+            self.parent_params
+                .as_ref()
+                .and_then(|parent| parent.find_by_idx(idx))
+        })
+    }
+
+    pub(crate) fn count_parent_params(&self) -> usize {
+        self.parent_params
+            .as_ref()
+            .map(|p| p.count_params_including_parent())
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn count_params_including_parent(&self) -> usize {
+        self.count_parent_params() + self.params.len()
+    }
+
+    /// All parameters, including the parent's, ordered by index.
+    pub(crate) fn params_including_parent(&self) -> Vec<&GenericParam> {
+        let mut res = self
+            .parent_params
+            .as_ref()
+            .map(|p| p.params_including_parent())
+            .unwrap_or_default();
+        res.extend(self.params.iter());
+        res
+    }
+}
+
+/// If the bound is `Fn(A, B) -> R` (or `FnMut`, `FnOnce`), returns the fn
+/// pointer type `fn(A, B) -> R`.
+fn fn_bound(bound: ast::PathType) -> Option<TypeRef> {
+    let segment = bound.path()?.segment()?;
+    match segment.name_ref()?.as_name().as_known_name() {
+        Some(KnownName::Fn) | Some(KnownName::FnMut) | Some(KnownName::FnOnce) => {}
+        _ => return None,
+    }
+    let param_list = segment.param_list()?;
+    Some(TypeRef::fn_from_ast(Some(param_list), segment.ret_type()))
+}
+
+pub(crate) fn generic_params(
+    db: &impl HirDatabase,
+    def_id: DefId,
+) -> Cancelable<Arc<GenericParams>> {
+    let loc = def_id.loc(db);
+    let generics = match loc.kind {
+        DefKind::Function => {
+            let parent_params = match def_id.impl_block(db)? {
+                Some(impl_block) => Some(impl_block.generic_params()),
+                None => match def_id.containing_trait(db) {
+                    Some(tr) => Some(tr.generic_params(db)?),
+                    None => None,
+                },
+            };
+            let syntax = db.file_item(loc.source_item_id);
+            let type_params = ast::FnDef::cast(syntax.borrowed()).and_then(|f| f.type_param_list());
+            GenericParams::from_ast(parent_params, type_params)
+        }
+        DefKind::Struct => {
+            let syntax = db.file_item(loc.source_item_id);
+            let type_params =
+                ast::StructDef::cast(syntax.borrowed()).and_then(|s| s.type_param_list());
+            GenericParams::from_ast(None, type_params)
+        }
+        DefKind::Enum => {
+            let syntax = db.file_item(loc.source_item_id);
+            let type_params =
+                ast::EnumDef::cast(syntax.borrowed()).and_then(|e| e.type_param_list());
+            GenericParams::from_ast(None, type_params)
+        }
+        // variants use the parameters of their enum
+        DefKind::EnumVariant => {
+            let parent_enum = EnumVariant::new(def_id).parent_enum(db)?;
+            return db.generic_params(parent_enum.def_id());
+        }
+        DefKind::TypeAlias => {
+            let syntax = db.file_item(loc.source_item_id);
+            let type_params =
+                ast::TypeDef::cast(syntax.borrowed()).and_then(|t| t.type_param_list());
+            GenericParams::from_ast(None, type_params)
+        }
+        DefKind::Trait => {
+            let syntax = db.file_item(loc.source_item_id);
+            let type_params =
+                ast::TraitDef::cast(syntax.borrowed()).and_then(|t| t.type_param_list());
+            GenericParams::for_trait(type_params)
+        }
+        _ => GenericParams::default(),
+    };
+    Ok(Arc::new(generics))
+}
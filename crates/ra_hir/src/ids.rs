@@ -1,8 +1,14 @@
-use ra_db::{SourceRootId, LocationIntener, Cancelable, FileId};
-use ra_syntax::{SourceFileNode, SyntaxKind, SyntaxNode, SyntaxNodeRef, SourceFile, AstNode, ast};
+use ra_db::{CrateId, LocationIntener, Cancelable, FileId, FileRange};
+use ra_syntax::{
+    SourceFileNode, SyntaxKind, SyntaxNode, SyntaxNodeRef, SourceFile, AstNode, TextRange,
+    TextUnit, ast,
+};
 use ra_arena::{Arena, RawId, impl_arena_id};
 
-use crate::{HirDatabase, PerNs, ModuleId, Module, Def, Function, Struct, Enum, ImplBlock, Crate};
+use crate::{
+    HirDatabase, PerNs, ModuleId, Module, Def, Function, Struct, Enum, EnumVariant, Const, Static,
+    Trait, TypeAlias, ImplBlock, Crate,
+};
 
 /// hir makes a heavy use of ids: integer (u32) handlers to various things. You
 /// can think of id as a pointer (but without a lifetime) or a file descriptor
@@ -39,6 +45,25 @@ impl HirFileId {
         }
     }
 
+    /// Maps a range in this file to the range in the original source file it
+    /// was produced from. For nested macro calls, like `vec![format!(..)]`,
+    /// the range is mapped back through each expansion in turn. Returns `None`
+    /// if the text was produced by a macro rather than passed to it.
+    pub fn original_file_range(self, db: &impl HirDatabase, range: TextRange) -> Option<FileRange> {
+        let mut file_id = self;
+        let mut range = range;
+        loop {
+            let macro_call_id = match file_id.0 {
+                HirFileIdRepr::File(file_id) => return Some(FileRange { file_id, range }),
+                HirFileIdRepr::Macro(it) => it,
+            };
+            let expansion = db.expand_macro_invocation(macro_call_id)?;
+            let loc = macro_call_id.loc(db);
+            range = expansion.map_range_back(range)? + loc.arguments_offset(db)?;
+            file_id = loc.source_item_id.file_id;
+        }
+    }
+
     pub(crate) fn as_original_file(self) -> FileId {
         match self.0 {
             HirFileIdRepr::File(file_id) => file_id,
@@ -53,16 +78,15 @@ impl HirFileId {
         }
     }
 
+    /// The syntax of the file. A macro call which we can't expand produces no
+    /// code, so its file is empty.
     pub(crate) fn hir_source_file(db: &impl HirDatabase, file_id: HirFileId) -> SourceFileNode {
         match file_id.0 {
             HirFileIdRepr::File(file_id) => db.source_file(file_id),
-            HirFileIdRepr::Macro(m) => {
-                if let Some(exp) = db.expand_macro_invocation(m) {
-                    return exp.file();
-                }
-                // returning an empty string looks fishy...
-                SourceFileNode::parse("")
-            }
+            HirFileIdRepr::Macro(m) => match db.expand_macro_invocation(m) {
+                Some(exp) => exp.file(),
+                None => SourceFileNode::parse(""),
+            },
         }
     }
 }
@@ -93,9 +117,13 @@ ra_db::impl_numeric_id!(MacroCallId);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct MacroCallLoc {
-    pub(crate) source_root_id: SourceRootId,
+    pub(crate) krate: CrateId,
     pub(crate) module_id: ModuleId,
     pub(crate) source_item_id: SourceItemId,
+    /// The `macro_rules!` definition the call refers to, with the crate it
+    /// is defined in. It is resolved when the call is found, so that
+    /// expanding the call needs no name resolution (which can be canceled).
+    pub(crate) def: Option<(CrateId, SourceItemId)>,
 }
 
 impl MacroCallId {
@@ -108,13 +136,20 @@ impl MacroCallId {
 }
 
 impl MacroCallLoc {
-    #[allow(unused)]
     pub(crate) fn id(
         &self,
         db: &impl AsRef<LocationIntener<MacroCallLoc, MacroCallId>>,
     ) -> MacroCallId {
         db.as_ref().loc2id(&self)
     }
+
+    /// The start of the token tree of the call in the file containing it. The
+    /// source ranges of the `MacroExpansion` are relative to it.
+    pub(crate) fn arguments_offset(&self, db: &impl HirDatabase) -> Option<TextUnit> {
+        let syntax = db.file_item(self.source_item_id);
+        let macro_call = ast::MacroCall::cast(syntax.borrowed())?;
+        Some(macro_call.token_tree()?.syntax().range().start())
+    }
 }
 
 /// Def's are a core concept of hir. A `Def` is an Item (function, module, etc)
@@ -126,7 +161,7 @@ ra_db::impl_numeric_id!(DefId);
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct DefLoc {
     pub(crate) kind: DefKind,
-    pub(crate) source_root_id: SourceRootId,
+    pub(crate) krate: CrateId,
     pub(crate) module_id: ModuleId,
     pub(crate) source_item_id: SourceItemId,
 }
@@ -137,7 +172,11 @@ pub(crate) enum DefKind {
     Function,
     Struct,
     Enum,
-    Item,
+    EnumVariant,
+    Const,
+    Static,
+    Trait,
+    TypeAlias,
 
     StructCtor,
 }
@@ -151,7 +190,7 @@ impl DefId {
         let loc = self.loc(db);
         let res = match loc.kind {
             DefKind::Module => {
-                let module = Module::new(db, loc.source_root_id, loc.module_id)?;
+                let module = Module::new(db, loc.krate, loc.module_id)?;
                 Def::Module(module)
             }
             DefKind::Function => {
@@ -166,8 +205,27 @@ impl DefId {
                 let enum_def = Enum::new(self);
                 Def::Enum(enum_def)
             }
+            DefKind::EnumVariant => {
+                let enum_variant = EnumVariant::new(self);
+                Def::EnumVariant(enum_variant)
+            }
+            DefKind::Const => {
+                let const_def = Const::new(self);
+                Def::Const(const_def)
+            }
+            DefKind::Static => {
+                let static_def = Static::new(self);
+                Def::Static(static_def)
+            }
+            DefKind::Trait => {
+                let trait_def = Trait::new(self);
+                Def::Trait(trait_def)
+            }
+            DefKind::TypeAlias => {
+                let type_alias = TypeAlias::new(self);
+                Def::Type(type_alias)
+            }
             DefKind::StructCtor => Def::Item,
-            DefKind::Item => Def::Item,
         };
         Ok(res)
     }
@@ -175,7 +233,7 @@ impl DefId {
     /// For a module, returns that module; for any other def, returns the containing module.
     pub fn module(self, db: &impl HirDatabase) -> Cancelable<Module> {
         let loc = self.loc(db);
-        Module::new(db, loc.source_root_id, loc.module_id)
+        Module::new(db, loc.krate, loc.module_id)
     }
 
     /// Returns the containing crate.
@@ -183,12 +241,43 @@ impl DefId {
         Ok(self.module(db)?.krate(db))
     }
 
+    /// The syntax of the def: its item, or the whole file for the modules
+    /// which have a file of their own.
+    pub fn source(self, db: &impl HirDatabase) -> (HirFileId, SyntaxNode) {
+        let loc = self.loc(db);
+        let syntax = db.file_item(loc.source_item_id);
+        (loc.source_item_id.file_id, syntax)
+    }
+
     /// Returns the containing impl block, if this is an impl item.
     pub fn impl_block(self, db: &impl HirDatabase) -> Cancelable<Option<ImplBlock>> {
         let loc = self.loc(db);
-        let module_impls = db.impls_in_module(loc.source_root_id, loc.module_id)?;
+        let module_impls = db.impls_in_module(loc.krate, loc.module_id)?;
         Ok(ImplBlock::containing(module_impls, self))
     }
+
+    /// Returns the containing trait, if this is a trait item.
+    pub fn containing_trait(self, db: &impl HirDatabase) -> Option<Trait> {
+        let loc = self.loc(db);
+        let syntax = db.file_item(loc.source_item_id);
+        // trait items are direct children of the trait's item list
+        let trait_node = syntax
+            .borrowed()
+            .parent()
+            .and_then(|it| it.parent())
+            .and_then(ast::TraitDef::cast)?;
+        let file_items = db.file_items(loc.source_item_id.file_id);
+        let item_id = file_items.id_of_unchecked(trait_node.syntax());
+        let trait_loc = DefLoc {
+            kind: DefKind::Trait,
+            source_item_id: SourceItemId {
+                file_id: loc.source_item_id.file_id,
+                item_id: Some(item_id),
+            },
+            ..loc
+        };
+        Some(Trait::new(trait_loc.id(db)))
+    }
 }
 
 impl DefLoc {
@@ -204,11 +293,10 @@ impl DefKind {
             SyntaxKind::MODULE => PerNs::types(DefKind::Module),
             SyntaxKind::STRUCT_DEF => PerNs::both(DefKind::Struct, DefKind::StructCtor),
             SyntaxKind::ENUM_DEF => PerNs::types(DefKind::Enum),
-            // These define items, but don't have their own DefKinds yet:
-            SyntaxKind::TRAIT_DEF => PerNs::types(DefKind::Item),
-            SyntaxKind::TYPE_DEF => PerNs::types(DefKind::Item),
-            SyntaxKind::CONST_DEF => PerNs::values(DefKind::Item),
-            SyntaxKind::STATIC_DEF => PerNs::values(DefKind::Item),
+            SyntaxKind::TRAIT_DEF => PerNs::types(DefKind::Trait),
+            SyntaxKind::TYPE_DEF => PerNs::types(DefKind::TypeAlias),
+            SyntaxKind::CONST_DEF => PerNs::values(DefKind::Const),
+            SyntaxKind::STATIC_DEF => PerNs::values(DefKind::Static),
             _ => PerNs::none(),
         }
     }
@@ -250,6 +338,8 @@ impl SourceFileItems {
                 self.alloc(module_item.syntax().owned());
             } else if let Some(macro_call) = ast::MacroCall::cast(it) {
                 self.alloc(macro_call.syntax().owned());
+            } else if let Some(enum_variant) = ast::EnumVariant::cast(it) {
+                self.alloc(enum_variant.syntax().owned());
             }
         });
     }
@@ -2,15 +2,17 @@ use std::sync::Arc;
 use rustc_hash::FxHashMap;
 
 use ra_arena::{Arena, RawId, impl_arena_id};
-use ra_syntax::ast::{self, AstNode};
-use ra_db::{LocationIntener, Cancelable, SourceRootId};
+use ra_syntax::ast::{self, AstNode, TypeParamsOwner};
+use ra_db::{LocationIntener, Cancelable, CrateId, CfgOptions};
 
 use crate::{
-    DefId, DefLoc, DefKind, SourceItemId, SourceFileItems,
-    Module, Function,
+    DefId, DefLoc, DefKind, SourceItemId, SourceFileItems, HirFileId,
+    Module, Function, Const, TypeAlias,
     db::HirDatabase,
     type_ref::TypeRef,
     module::{ModuleSourceNode, ModuleId},
+    generics::GenericParams,
+    cfg,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -20,6 +22,13 @@ pub struct ImplBlock {
 }
 
 impl ImplBlock {
+    pub(crate) fn from_id(module_impl_blocks: Arc<ModuleImplBlocks>, impl_id: ImplId) -> ImplBlock {
+        ImplBlock {
+            module_impl_blocks,
+            impl_id,
+        }
+    }
+
     pub(crate) fn containing(
         module_impl_blocks: Arc<ModuleImplBlocks>,
         def_id: DefId,
@@ -46,6 +55,17 @@ impl ImplBlock {
     pub fn items(&self) -> &[ImplItem] {
         &self.impl_data().items
     }
+
+    pub fn generic_params(&self) -> Arc<GenericParams> {
+        self.impl_data().generic_params.clone()
+    }
+
+    pub fn source(&self, db: &impl HirDatabase) -> (HirFileId, ast::ImplBlockNode) {
+        let source_item_id = self.impl_data().source_item_id;
+        let syntax = db.file_item(source_item_id);
+        let node = ast::ImplBlock::cast(syntax.borrowed()).unwrap().owned();
+        (source_item_id.file_id, node)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -53,31 +73,35 @@ pub struct ImplData {
     target_trait: Option<TypeRef>,
     target_type: TypeRef,
     items: Vec<ImplItem>,
+    generic_params: Arc<GenericParams>,
+    source_item_id: SourceItemId,
 }
 
 impl ImplData {
     pub(crate) fn from_ast(
         db: &impl AsRef<LocationIntener<DefLoc, DefId>>,
         file_items: &SourceFileItems,
+        cfg_options: &CfgOptions,
         module: &Module,
         node: ast::ImplBlock,
     ) -> Self {
-        let target_trait = node.target_type().map(TypeRef::from_ast);
+        let target_trait = node.target_trait().map(TypeRef::from_ast);
         let target_type = TypeRef::from_ast_opt(node.target_type());
         let file_id = module.source().file_id();
         let items = if let Some(item_list) = node.item_list() {
             item_list
                 .impl_items()
+                .filter(|item_node| cfg::is_cfg_enabled(item_node.syntax(), cfg_options))
                 .map(|item_node| {
                     let kind = match item_node {
                         ast::ImplItem::FnDef(..) => DefKind::Function,
-                        ast::ImplItem::ConstDef(..) => DefKind::Item,
-                        ast::ImplItem::TypeDef(..) => DefKind::Item,
+                        ast::ImplItem::ConstDef(..) => DefKind::Const,
+                        ast::ImplItem::TypeDef(..) => DefKind::TypeAlias,
                     };
                     let item_id = file_items.id_of_unchecked(item_node.syntax());
                     let def_loc = DefLoc {
                         kind,
-                        source_root_id: module.source_root_id,
+                        krate: module.krate,
                         module_id: module.module_id,
                         source_item_id: SourceItemId {
                             file_id,
@@ -87,28 +111,42 @@ impl ImplData {
                     let def_id = def_loc.id(db);
                     match item_node {
                         ast::ImplItem::FnDef(..) => ImplItem::Method(Function::new(def_id)),
-                        ast::ImplItem::ConstDef(..) => ImplItem::Const(def_id),
-                        ast::ImplItem::TypeDef(..) => ImplItem::Type(def_id),
+                        ast::ImplItem::ConstDef(..) => ImplItem::Const(Const::new(def_id)),
+                        ast::ImplItem::TypeDef(..) => ImplItem::Type(TypeAlias::new(def_id)),
                     }
                 })
                 .collect()
         } else {
             Vec::new()
         };
+        let generic_params = Arc::new(GenericParams::from_ast(None, node.type_param_list()));
+        let source_item_id = SourceItemId {
+            file_id,
+            item_id: Some(file_items.id_of_unchecked(node.syntax())),
+        };
         ImplData {
             target_trait,
             target_type,
             items,
+            generic_params,
+            source_item_id,
         }
     }
+
+    pub fn target_trait(&self) -> Option<&TypeRef> {
+        self.target_trait.as_ref()
+    }
+
+    pub fn target_type(&self) -> &TypeRef {
+        &self.target_type
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ImplItem {
     Method(Function),
-    // these don't have their own types yet
-    Const(DefId),
-    Type(DefId),
+    Const(Const),
+    Type(TypeAlias),
     // Existential
 }
 
@@ -116,8 +154,8 @@ impl ImplItem {
     pub fn def_id(&self) -> DefId {
         match self {
             ImplItem::Method(f) => f.def_id(),
-            ImplItem::Const(def_id) => *def_id,
-            ImplItem::Type(def_id) => *def_id,
+            ImplItem::Const(c) => c.def_id(),
+            ImplItem::Type(t) => t.def_id(),
         }
     }
 }
@@ -132,10 +170,10 @@ impl_arena_id!(ImplId);
 /// types in any file; as long as the impl blocks in the file don't change, we
 /// don't need to do the second step again.
 ///
-/// (The second step does not yet exist currently.)
+/// The second step is done by `ty::method_resolution::CrateImplBlocks`.
 #[derive(Debug, PartialEq, Eq)]
 pub struct ModuleImplBlocks {
-    impls: Arena<ImplId, ImplData>,
+    pub(crate) impls: Arena<ImplId, ImplData>,
     impls_by_def: FxHashMap<DefId, ImplId>,
 }
 
@@ -155,9 +193,15 @@ impl ModuleImplBlocks {
         };
 
         let source_file_items = db.file_items(module.source().file_id());
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(module.krate);
 
         for impl_block_ast in node.children().filter_map(ast::ImplBlock::cast) {
-            let impl_block = ImplData::from_ast(db, &source_file_items, &module, impl_block_ast);
+            if !cfg::is_cfg_enabled(impl_block_ast.syntax(), cfg_options) {
+                continue;
+            }
+            let impl_block =
+                ImplData::from_ast(db, &source_file_items, cfg_options, &module, impl_block_ast);
             let id = self.impls.alloc(impl_block);
             for impl_item in &self.impls[id].items {
                 self.impls_by_def.insert(impl_item.def_id(), id);
@@ -170,11 +214,11 @@ impl ModuleImplBlocks {
 
 pub(crate) fn impls_in_module(
     db: &impl HirDatabase,
-    source_root_id: SourceRootId,
+    krate: CrateId,
     module_id: ModuleId,
 ) -> Cancelable<Arc<ModuleImplBlocks>> {
     let mut result = ModuleImplBlocks::new();
-    let module = Module::new(db, source_root_id, module_id)?;
+    let module = Module::new(db, krate, module_id)?;
     result.collect(db, module)?;
     Ok(Arc::new(result))
 }
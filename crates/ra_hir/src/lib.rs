@@ -23,15 +23,21 @@ pub mod source_binder;
 
 mod ids;
 mod macros;
+mod cfg;
 mod name;
 // can't use `crate` or `r#crate` here :(
 mod krate;
 mod module;
 mod function;
 mod adt;
+mod consts;
+mod type_alias;
 mod type_ref;
 mod ty;
 mod impl_block;
+mod generics;
+mod traits;
+mod expr;
 
 use crate::{
     db::HirDatabase,
@@ -40,15 +46,19 @@ use crate::{
 };
 
 pub use self::{
-    path::{Path, PathKind},
+    path::{Path, PathSegment, PathKind},
     name::Name,
     krate::Crate,
     ids::{HirFileId, DefId, DefLoc, MacroCallId, MacroCallLoc},
-    macros::{MacroDef, MacroInput, MacroExpansion},
-    module::{Module, ModuleId, Problem, nameres::{ItemMap, PerNs, Namespace}, ModuleScope, Resolution},
-    function::{Function, FnScopes},
-    adt::{Struct, Enum},
-    ty::Ty,
+    macros::{MacroDef, MacroInput, MacroExpansion, MACRO_EXPANSION_DEPTH_LIMIT},
+    module::{Module, ModuleId, Problem, nameres::{ItemMap, PerNs, Namespace}, ModuleScope, Resolution, Visibility},
+    function::{Function, FnSignature, FnScopes, ScopesWithSyntaxMapping, ScopeEntryWithSyntax},
+    adt::{Struct, Enum, EnumVariant},
+    consts::{Const, Static, ConstSignature},
+    type_alias::TypeAlias,
+    traits::{Trait, TraitData, TraitItem},
+    ty::{Ty, TraitRef, InferenceResult},
+    expr::{ExprId, PatId, Body, BodySyntaxMapping, Expr, Pat, Statement, MatchArm, Literal},
     impl_block::{ImplBlock, ImplItem},
 };
 
@@ -59,5 +69,10 @@ pub enum Def {
     Function(Function),
     Struct(Struct),
     Enum(Enum),
+    EnumVariant(EnumVariant),
+    Const(Const),
+    Static(Static),
+    Trait(Trait),
+    Type(TypeAlias),
     Item,
 }
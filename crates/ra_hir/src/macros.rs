@@ -5,19 +5,30 @@
 /// do we do that.
 ///
 /// When file-management question is resolved, all that is left is a token tree
-/// to token tree transformation plus hygent. We don't have either of thouse
-/// yet, so all macros are string based at the moment!
+/// to token tree transformation plus hygent. `macro_rules!` macros are
+/// expanded by `ra_macros`; the definitions are looked up in `CrateMacros`.
+/// A couple of macros we can't expand yet are hard-coded and string based.
 use std::sync::Arc;
 
-use ra_db::LocalSyntaxPtr;
+use rustc_hash::{FxHashMap, FxHashSet};
+use ra_db::{LocalSyntaxPtr, Cancelable, CrateId};
+use ra_macros::tt;
 use ra_syntax::{
-    TextRange, TextUnit, SourceFileNode, AstNode, SyntaxNode,
-    ast::{self, NameOwner},
+    TextRange, TextUnit, SourceFileNode, AstNode, SyntaxNode, SmolStr,
+    ast::{self, NameOwner, ModuleItemOwner, VisibilityOwner},
 };
 
-use crate::{HirDatabase, MacroCallId};
+use crate::{
+    HirDatabase, MacroCallId, Name, AsName, SourceItemId, Crate,
+    module::{ModuleId, ModuleTree, ModuleSourceNode},
+    cfg,
+};
+
+/// Stops the expansion of recursive macros.
+pub const MACRO_EXPANSION_DEPTH_LIMIT: u32 = 64;
 
-// Hard-coded defs for now :-(
+/// Macros we can't expand with `macro_rules!`: either we don't have the
+/// definition, or it's a procedural macro. Hard-coded for now :-(
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum MacroDef {
     CTry,
@@ -115,7 +126,17 @@ impl MacroDef {
             return None;
         }
         let src_range = TextRange::offset_len((pos as u32).into(), TextUnit::of_str(&trait_name));
-        let text = format!(r"trait {} {{ }}", trait_name);
+        // keep the visibility, so that the trait can be imported from other
+        // modules; the input is the trait wrapped in braces
+        let input_file = SourceFileNode::parse(input.text.get(1..)?);
+        let vis = input_file
+            .syntax()
+            .descendants()
+            .find_map(ast::TraitDef::cast)
+            .and_then(|it| it.visibility())
+            .map(|it| format!("{} ", it.syntax().text()))
+            .unwrap_or_default();
+        let text = format!(r"{}trait {} {{ }}", vis, trait_name);
         let file = SourceFileNode::parse(&text);
         let trait_def = file.syntax().descendants().find_map(ast::TraitDef::cast)?;
         let name = trait_def.name()?;
@@ -157,7 +178,12 @@ impl MacroExpansion {
     }
 
     pub fn syntax(&self) -> SyntaxNode {
-        self.ptr.resolve(&self.file())
+        self.syntax_in(&self.file())
+    }
+    /// Like `syntax`, but selects the expansion from an already parsed file,
+    /// like the `hir_source_file` of the macro call.
+    pub fn syntax_in(&self, file: &SourceFileNode) -> SyntaxNode {
+        self.ptr.resolve(file)
     }
     /// Maps range in the source code to the range in the expanded code.
     pub fn map_range_forward(&self, src_range: TextRange) -> Option<TextRange> {
@@ -185,6 +211,242 @@ impl MacroExpansion {
     }
 }
 
+/// A `macro_rules!` definition.
+#[derive(Debug, PartialEq, Eq)]
+struct MacroRulesDef {
+    name: Name,
+    source_item_id: SourceItemId,
+    offset: TextUnit,
+    /// Whether the definition has `#[macro_export]`.
+    exported: bool,
+}
+
+/// `macro_rules!` definitions of a crate. They are collected syntactically,
+/// without expanding macros and resolving names, because the items produced
+/// by macros depend on them.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CrateMacros {
+    per_module: FxHashMap<ModuleId, Vec<MacroRulesDef>>,
+    /// Modules with `#[macro_use]`, whose macros are visible in the parent
+    /// module as well.
+    macro_use: FxHashSet<ModuleId>,
+}
+
+impl CrateMacros {
+    /// Finds a definition in the module itself or in one of its `#[macro_use]`
+    /// children. Definitions before the `call` are preferred.
+    fn find_in_module(
+        &self,
+        module_tree: &ModuleTree,
+        module_id: ModuleId,
+        name: &Name,
+        call: Option<SourceItemId>,
+        call_offset: TextUnit,
+    ) -> Option<&MacroRulesDef> {
+        let defs = self.per_module.get(&module_id);
+        let mut candidates = defs
+            .iter()
+            .flat_map(|it| it.iter())
+            .filter(|it| it.name == *name);
+        let mut before_call = candidates.clone().filter(|it| {
+            Some(it.source_item_id.file_id) == call.map(|it| it.file_id) && it.offset < call_offset
+        });
+        if let Some(def) = before_call.next_back() {
+            return Some(def);
+        }
+        if let Some(def) = candidates.next() {
+            return Some(def);
+        }
+        module_id
+            .children(module_tree)
+            .filter(|(_, child)| self.macro_use.contains(child))
+            .find_map(|(_, child)| self.find_in_module(module_tree, child, name, None, call_offset))
+    }
+
+    fn find_exported(&self, name: &Name) -> Option<&MacroRulesDef> {
+        self.per_module
+            .values()
+            .flat_map(|it| it.iter())
+            .find(|it| it.exported && it.name == *name)
+    }
+}
+
+pub(crate) fn crate_macros(db: &impl HirDatabase, krate: CrateId) -> Cancelable<Arc<CrateMacros>> {
+    let module_tree = db.module_tree(krate)?;
+    let crate_graph = db.crate_graph();
+    let cfg_options = crate_graph.cfg_options(krate);
+    let mut res = CrateMacros::default();
+    for module_id in module_tree.modules() {
+        let source = module_id.source(&module_tree);
+        let file_id = source.file_id();
+        let file_items = db.file_items(file_id);
+        let source_node = source.resolve(db);
+        let items: Vec<ast::ItemOrMacro> = match &source_node {
+            ModuleSourceNode::SourceFile(it) => it.borrowed().items_with_macros().collect(),
+            ModuleSourceNode::Module(it) => it
+                .borrowed()
+                .item_list()
+                .map(|it| it.items_with_macros().collect())
+                .unwrap_or_else(Vec::new),
+        };
+        for item in items {
+            let syntax = match item {
+                ast::ItemOrMacro::Item(it) => it.syntax(),
+                ast::ItemOrMacro::Macro(it) => it.syntax(),
+            };
+            if !cfg::is_cfg_enabled(syntax, cfg_options) {
+                continue;
+            }
+            match item {
+                ast::ItemOrMacro::Item(ast::ModuleItem::Module(it)) => {
+                    if !cfg::has_atom_attr(it.syntax(), cfg_options, "macro_use") {
+                        continue;
+                    }
+                    let child = it
+                        .name()
+                        .and_then(|name| module_id.child(&module_tree, &name.as_name()));
+                    if let Some(child) = child {
+                        res.macro_use.insert(child);
+                    }
+                }
+                ast::ItemOrMacro::Item(_) => (),
+                ast::ItemOrMacro::Macro(macro_call) => {
+                    let name = match macro_call.macro_rules_name() {
+                        Some(it) => Name::new(it),
+                        None => continue,
+                    };
+                    let def = MacroRulesDef {
+                        name,
+                        source_item_id: SourceItemId {
+                            file_id,
+                            item_id: Some(file_items.id_of_unchecked(macro_call.syntax())),
+                        },
+                        offset: macro_call.syntax().range().start(),
+                        exported: cfg::has_atom_attr(
+                            macro_call.syntax(),
+                            cfg_options,
+                            "macro_export",
+                        ),
+                    };
+                    res.per_module.entry(module_id).or_default().push(def);
+                }
+            }
+        }
+    }
+    Ok(Arc::new(res))
+}
+
+/// Finds the `macro_rules!` definition a call refers to: first in the
+/// modules enclosing the call, then among the exported macros of the crate
+/// and its dependencies. Also returns the crate defining it.
+pub(crate) fn resolve_macro_rules(
+    db: &impl HirDatabase,
+    krate: CrateId,
+    module_id: ModuleId,
+    call: SourceItemId,
+    macro_call: ast::MacroCall,
+) -> Cancelable<Option<(CrateId, SourceItemId)>> {
+    let path = ctry!(macro_call.path());
+    let name = ctry!(ctry!(path.segment()).name_ref()).as_name();
+    let macros = db.crate_macros(krate)?;
+    if path.qualifier().is_none() {
+        let module_tree = db.module_tree(krate)?;
+        let call_offset = macro_call.syntax().range().start();
+        let mut module_id = Some(module_id);
+        let mut call = Some(call);
+        while let Some(id) = module_id {
+            if let Some(def) = macros.find_in_module(&module_tree, id, &name, call, call_offset) {
+                return Ok(Some((krate, def.source_item_id)));
+            }
+            module_id = id.parent(&module_tree);
+            call = None;
+        }
+    }
+    if let Some(def) = macros.find_exported(&name) {
+        return Ok(Some((krate, def.source_item_id)));
+    }
+    for dep in Crate::new(krate).dependencies(db) {
+        let macros = db.crate_macros(dep.krate.crate_id())?;
+        if let Some(def) = macros.find_exported(&name) {
+            return Ok(Some((dep.krate.crate_id(), def.source_item_id)));
+        }
+    }
+    Ok(None)
+}
+
+/// `def_dep` is the name of the dependency defining the macro, which `$crate`
+/// refers to; without it, `$crate` is the crate of the call.
+fn expand_macro_rules(
+    db: &impl HirDatabase,
+    def: SourceItemId,
+    def_dep: Option<Name>,
+    macro_call: ast::MacroCall,
+) -> Option<MacroExpansion> {
+    let def_syntax = db.file_item(def);
+    let def_call = ast::MacroCall::cast(def_syntax.borrowed())?;
+    let (def_tt, _) = ra_macros::ast_to_token_tree(def_call.token_tree()?)?;
+    let rules = ra_macros::MacroRules::parse(&def_tt)?;
+
+    let (input, token_map) = ra_macros::ast_to_token_tree(macro_call.token_tree()?)?;
+    let mut expansion = rules.expand(&input)?;
+    replace_dollar_crate(&mut expansion, &dollar_crate_path(def_dep));
+    let (text, ranges) = expansion.to_text();
+    let ranges_map = ranges
+        .into_iter()
+        .filter_map(|(id, range)| Some((token_map.relative_range_of(id)?, range)))
+        .collect();
+    let file = SourceFileNode::parse(&text);
+    let ptr = LocalSyntaxPtr::new(file.syntax());
+    Some(MacroExpansion {
+        text,
+        ranges_map,
+        ptr,
+    })
+}
+
+/// The tokens `$crate` stands for: `crate` for a macro of the calling crate,
+/// `::dep` for one of its dependency `dep`.
+fn dollar_crate_path(def_dep: Option<Name>) -> Vec<tt::TokenTree> {
+    let ident = |text: SmolStr| {
+        tt::Leaf::from(tt::Ident {
+            text,
+            id: tt::TokenId::unspecified(),
+        })
+        .into()
+    };
+    let colon = |spacing| {
+        tt::Leaf::from(tt::Punct {
+            char: ':',
+            spacing,
+            id: tt::TokenId::unspecified(),
+        })
+        .into()
+    };
+    match def_dep {
+        None => vec![ident("crate".into())],
+        Some(dep) => vec![
+            colon(tt::Spacing::Joint),
+            colon(tt::Spacing::Alone),
+            ident(dep.to_string().into()),
+        ],
+    }
+}
+
+fn replace_dollar_crate(subtree: &mut tt::Subtree, path: &[tt::TokenTree]) {
+    let token_trees = std::mem::replace(&mut subtree.token_trees, Vec::new());
+    for mut tt in token_trees {
+        match &mut tt {
+            tt::TokenTree::Leaf(tt::Leaf::Ident(it)) if it.text == "$crate" => {
+                subtree.token_trees.extend(path.iter().cloned());
+                continue;
+            }
+            tt::TokenTree::Subtree(it) => replace_dollar_crate(it, path),
+            _ => (),
+        }
+        subtree.token_trees.push(tt);
+    }
+}
+
 pub(crate) fn expand_macro_invocation(
     db: &impl HirDatabase,
     invoc: MacroCallId,
@@ -194,6 +456,18 @@ pub(crate) fn expand_macro_invocation(
     let syntax = syntax.borrowed();
     let macro_call = ast::MacroCall::cast(syntax).unwrap();
 
+    if let Some((def_krate, def)) = loc.def {
+        let def_dep = if def_krate == loc.krate {
+            None
+        } else {
+            Crate::new(loc.krate)
+                .dependencies(db)
+                .into_iter()
+                .find(|dep| dep.krate.crate_id() == def_krate)
+                .map(|dep| dep.name)
+        };
+        return expand_macro_rules(db, def, def_dep, macro_call).map(Arc::new);
+    }
     let (def, input) = MacroDef::from_call(macro_call)?;
     def.expand(input).map(Arc::new)
 }
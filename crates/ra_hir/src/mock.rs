@@ -1,12 +1,13 @@
 use std::sync::Arc;
 
 use parking_lot::Mutex;
+use rustc_hash::FxHashSet;
 use salsa::{self, Database};
 use ra_db::{LocationIntener, BaseDatabase, FilePosition, FileId, CrateGraph, SourceRoot, SourceRootId};
 use relative_path::RelativePathBuf;
 use test_utils::{parse_fixture, CURSOR_MARKER, extract_offset};
 
-use crate::{db, DefId, DefLoc, MacroCallId, MacroCallLoc};
+use crate::{db::{self, HirDatabase}, DefId, DefLoc, MacroCallId, MacroCallLoc};
 
 pub const WORKSPACE: SourceRootId = SourceRootId(0);
 
@@ -53,20 +54,48 @@ impl MockDatabase {
 
         let mut position = None;
         let mut source_root = SourceRoot::default();
+        let mut files = Vec::new();
         for entry in parse_fixture(fixture) {
-            if entry.text.contains(CURSOR_MARKER) {
+            let file_id = if entry.text.contains(CURSOR_MARKER) {
                 assert!(
                     position.is_none(),
                     "only one marker (<|>) per fixture is allowed"
                 );
-                position =
-                    Some(db.add_file_with_position(&mut source_root, &entry.meta, &entry.text));
+                let pos = db.add_file_with_position(&mut source_root, &entry.meta, &entry.text);
+                position = Some(pos);
+                pos.file_id
             } else {
-                db.add_file(&mut source_root, &entry.meta, &entry.text);
-            }
+                db.add_file(&mut source_root, &entry.meta, &entry.text)
+            };
+            files.push((entry.meta, file_id));
         }
+        // by default, the fixture is a single crate rooted at `/lib.rs` or
+        // `/main.rs`, or else at the first file
+        let crate_root = files
+            .iter()
+            .find(|(path, _)| path == "/lib.rs" || path == "/main.rs")
+            .or_else(|| files.first())
+            .map(|&(_, file_id)| file_id);
         db.query_mut(ra_db::SourceRootQuery)
             .set(WORKSPACE, Arc::new(source_root.clone()));
+        if let Some(crate_root) = crate_root {
+            let mut crate_graph = CrateGraph::default();
+            let krate = crate_graph.add_crate_root(crate_root);
+            db.set_crate_graph(crate_graph.clone());
+            // like the analysis, give each file the crate doesn't reach a
+            // detached crate
+            let module_tree = db.module_tree(krate).unwrap();
+            let reached = module_tree
+                .modules_with_sources()
+                .map(|(_id, src)| src.file_id().as_original_file())
+                .collect::<FxHashSet<_>>();
+            for &(_, file_id) in files.iter() {
+                if !reached.contains(&file_id) {
+                    crate_graph.add_detached_crate_root(file_id);
+                }
+            }
+            db.set_crate_graph(crate_graph);
+        }
         (db, source_root, position)
     }
 
@@ -191,23 +220,38 @@ salsa::database_storage! {
         impl ra_db::SyntaxDatabase {
             fn source_file() for ra_db::SourceFileQuery;
             fn file_lines() for ra_db::FileLinesQuery;
+            fn source_root_crates() for ra_db::SourceRootCratesQuery;
         }
         impl db::HirDatabase {
             fn hir_source_file() for db::HirSourceFileQuery;
             fn expand_macro_invocation() for db::ExpandMacroCallQuery;
+            fn crate_macros() for db::CrateMacrosQuery;
             fn module_tree() for db::ModuleTreeQuery;
             fn fn_scopes() for db::FnScopesQuery;
+            fn fn_signature() for db::FnSignatureQuery;
+            fn const_signature() for db::ConstSignatureQuery;
+            fn static_signature() for db::StaticSignatureQuery;
+            fn type_alias_ref() for db::TypeAliasRefQuery;
+            fn body_hir() for db::BodyHirQuery;
+            fn body_syntax_mapping() for db::BodySyntaxMappingQuery;
             fn file_items() for db::SourceFileItemsQuery;
             fn file_item() for db::FileItemQuery;
             fn input_module_items() for db::InputModuleItemsQuery;
+            fn private_path_segments() for db::PrivatePathSegmentsQuery;
+            fn macro_expansion_items() for db::MacroExpansionItemsQuery;
             fn item_map() for db::ItemMapQuery;
             fn submodules() for db::SubmodulesQuery;
             fn infer() for db::InferQuery;
+            fn generic_params() for db::GenericParamsQuery;
             fn type_for_def() for db::TypeForDefQuery;
             fn type_for_field() for db::TypeForFieldQuery;
             fn struct_data() for db::StructDataQuery;
             fn enum_data() for db::EnumDataQuery;
+            fn enum_variant_data() for db::EnumVariantDataQuery;
             fn impls_in_module() for db::ImplsInModuleQuery;
+            fn impls_in_crate() for db::ImplsInCrateQuery;
+            fn implements() for db::ImplementsQuery;
+            fn trait_data() for db::TraitDataQuery;
         }
     }
 }
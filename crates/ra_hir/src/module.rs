@@ -5,41 +5,41 @@ use std::sync::Arc;
 use log;
 
 use ra_syntax::{
-    algo::generate,
+    algo::{find_covering_node, generate},
     ast::{self, AstNode, NameOwner},
     SyntaxNode,
 };
 use ra_arena::{Arena, RawId, impl_arena_id};
-use ra_db::{SourceRootId, FileId, Cancelable};
+use ra_db::{CrateId, FileId, Cancelable};
 use relative_path::RelativePathBuf;
 
 use crate::{
     Def, DefKind, DefLoc, DefId,
-    Name, Path, PathKind, HirDatabase, SourceItemId, SourceFileItemId, Crate,
+    Name, Path, PathKind, PathSegment, HirDatabase, SourceItemId, SourceFileItemId, Crate,
     HirFileId,
 };
 
-pub use self::nameres::{ModuleScope, Resolution, Namespace, PerNs};
+pub use self::nameres::{ModuleScope, Resolution, Namespace, PerNs, Visibility};
 
 /// `Module` is API entry point to get all the information
 /// about a particular module.
 #[derive(Debug, Clone)]
 pub struct Module {
     tree: Arc<ModuleTree>,
-    pub(crate) source_root_id: SourceRootId,
+    pub(crate) krate: CrateId,
     pub(crate) module_id: ModuleId,
 }
 
 impl Module {
     pub(super) fn new(
         db: &impl HirDatabase,
-        source_root_id: SourceRootId,
+        krate: CrateId,
         module_id: ModuleId,
     ) -> Cancelable<Module> {
-        let module_tree = db.module_tree(source_root_id)?;
+        let module_tree = db.module_tree(krate)?;
         let res = Module {
             tree: module_tree,
-            source_root_id,
+            krate,
             module_id,
         };
         Ok(res)
@@ -87,12 +87,8 @@ impl Module {
     }
 
     /// Returns the crate this module is part of.
-    pub fn krate(&self, db: &impl HirDatabase) -> Option<Crate> {
-        let root_id = self.module_id.crate_root(&self.tree);
-        let file_id = root_id.source(&self.tree).file_id().as_original_file();
-        let crate_graph = db.crate_graph();
-        let crate_id = crate_graph.crate_id_for_crate_root(file_id)?;
-        Some(Crate::new(crate_id))
+    pub fn krate(&self, _db: &impl HirDatabase) -> Option<Crate> {
+        Some(Crate::new(self.krate))
     }
 
     /// Returns the all modules on the way to the root.
@@ -118,7 +114,7 @@ impl Module {
     pub fn def_id(&self, db: &impl HirDatabase) -> DefId {
         let def_loc = DefLoc {
             kind: DefKind::Module,
-            source_root_id: self.source_root_id,
+            krate: self.krate,
             module_id: self.module_id,
             source_item_id: self.module_id.source(&self.tree).0,
         };
@@ -136,51 +132,166 @@ impl Module {
 
     /// Returns a `ModuleScope`: a set of items, visible in this module.
     pub fn scope(&self, db: &impl HirDatabase) -> Cancelable<ModuleScope> {
-        let item_map = db.item_map(self.source_root_id)?;
+        let item_map = db.item_map(self.krate)?;
         let res = item_map.per_module[&self.module_id].clone();
         Ok(res)
     }
 
+    /// The prelude of the crate, whose names are in scope in this module
+    /// unless shadowed. `None` in modules under `#![no_implicit_prelude]`.
+    pub fn prelude(&self, db: &impl HirDatabase) -> Cancelable<Option<Module>> {
+        let item_map = db.item_map(self.krate)?;
+        if item_map.no_implicit_prelude.contains(&self.module_id) {
+            return Ok(None);
+        }
+        let def_id = ctry!(item_map.prelude);
+        match def_id.resolve(db)? {
+            Def::Module(it) => Ok(Some(it)),
+            _ => Ok(None),
+        }
+    }
+
     pub fn resolve_path(&self, db: &impl HirDatabase, path: &Path) -> Cancelable<PerNs<DefId>> {
-        let mut curr_per_ns = PerNs::types(
-            match path.kind {
-                PathKind::Crate => self.crate_root(),
-                PathKind::Self_ | PathKind::Plain => self.clone(),
-                PathKind::Super => {
-                    if let Some(p) = self.parent() {
-                        p
-                    } else {
-                        return Ok(PerNs::none());
-                    }
+        let (res, _) = self.resolve_path_with_private_segment(db, path)?;
+        Ok(res)
+    }
+
+    /// Like `resolve_path`, but also tells which of the segments of the path
+    /// was not resolved because its item is not visible from this module,
+    /// like `Private` in `m::Private`.
+    pub fn resolve_path_with_private_segment(
+        &self,
+        db: &impl HirDatabase,
+        path: &Path,
+    ) -> Cancelable<(PerNs<DefId>, Option<usize>)> {
+        let mut segments = &path.segments[..];
+        let start = match path.kind {
+            PathKind::Crate => self.crate_root(),
+            PathKind::Self_ => self.clone(),
+            // names which are not in the scope of the module come from the
+            // prelude
+            PathKind::Plain => match segments.first() {
+                Some(first) if self.scope(db)?.get(&first.name).is_none() => {
+                    self.prelude(db)?.unwrap_or_else(|| self.clone())
+                }
+                _ => self.clone(),
+            },
+            PathKind::Super => {
+                if let Some(p) = self.parent() {
+                    p
+                } else {
+                    return Ok((PerNs::none(), None));
                 }
             }
-            .def_id(db),
-        );
+            PathKind::Abs => {
+                // `::dep::path`: the first segment names a dependency crate
+                let (first, rest) = match segments.split_first() {
+                    Some(it) => it,
+                    None => return Ok((PerNs::none(), None)),
+                };
+                segments = rest;
+                match self.dependency_root(db, &first.name)? {
+                    Some(it) => it,
+                    None => return Ok((PerNs::none(), None)),
+                }
+            }
+        };
+        let skipped = path.segments.len() - segments.len();
+        let (res, private) = self.resolve_segments_with_private(db, start, segments)?;
+        Ok((res, private.map(|it| it + skipped)))
+    }
+
+    /// Resolves `segments` starting from the module `start`, considering
+    /// only the names visible from this module.
+    pub(crate) fn resolve_segments(
+        &self,
+        db: &impl HirDatabase,
+        start: Module,
+        segments: &[PathSegment],
+    ) -> Cancelable<PerNs<DefId>> {
+        let (res, _) = self.resolve_segments_with_private(db, start, segments)?;
+        Ok(res)
+    }
+
+    /// Like `resolve_segments`, with the index of the segment whose item is
+    /// not visible from this module, if that's why the resolution failed.
+    fn resolve_segments_with_private(
+        &self,
+        db: &impl HirDatabase,
+        start: Module,
+        segments: &[PathSegment],
+    ) -> Cancelable<(PerNs<DefId>, Option<usize>)> {
+        let mut curr_per_ns = PerNs::types(start.def_id(db));
 
-        let segments = &path.segments;
-        for name in segments.iter() {
+        for (i, segment) in segments.iter().enumerate() {
             let curr = if let Some(r) = curr_per_ns.as_ref().take(Namespace::Types) {
                 r
             } else {
-                return Ok(PerNs::none());
+                return Ok((PerNs::none(), None));
             };
             let module = match curr.resolve(db)? {
                 Def::Module(it) => it,
-                // TODO here would be the place to handle enum variants...
-                _ => return Ok(PerNs::none()),
+                // an enum variant is always the last segment of a path
+                Def::Enum(e) if i + 1 == segments.len() => {
+                    let res = match e.variant(db, &segment.name)? {
+                        Some(variant) => PerNs::both(variant.def_id(), variant.def_id()),
+                        None => PerNs::none(),
+                    };
+                    return Ok((res, None));
+                }
+                _ => return Ok((PerNs::none(), None)),
             };
             let scope = module.scope(db)?;
-            curr_per_ns = if let Some(r) = scope.get(&name) {
-                r.def_id
-            } else {
-                return Ok(PerNs::none());
+            curr_per_ns = match scope.get(&segment.name) {
+                Some(r) if r.vis.is_visible_from(&module, self) => r.def_id,
+                Some(_) => return Ok((PerNs::none(), Some(i))),
+                None => return Ok((PerNs::none(), None)),
             };
         }
-        Ok(curr_per_ns)
+        Ok((curr_per_ns, None))
     }
 
-    pub fn problems(&self, db: &impl HirDatabase) -> Vec<(SyntaxNode, Problem)> {
-        self.module_id.problems(&self.tree, db)
+    /// The root module of the dependency of this module's crate with the
+    /// given name.
+    pub(crate) fn dependency_root(
+        &self,
+        db: &impl HirDatabase,
+        name: &Name,
+    ) -> Cancelable<Option<Module>> {
+        let dep = Crate::new(self.krate)
+            .dependencies(db)
+            .into_iter()
+            .find(|dep| dep.name == *name);
+        match dep {
+            Some(dep) => dep.krate.root_module(db),
+            None => Ok(None),
+        }
+    }
+
+    pub fn problems(&self, db: &impl HirDatabase) -> Cancelable<Vec<(SyntaxNode, Problem)>> {
+        let mut res = self.module_id.problems(&self.tree, db);
+        let item_map = db.item_map(self.krate)?;
+        let file_id = self.source().file_id();
+        for (module_id, import, name) in item_map.private_imports.iter() {
+            if *module_id != self.module_id {
+                continue;
+            }
+            let source_item_id = SourceItemId {
+                file_id,
+                item_id: Some(import.file_item_id),
+            };
+            let item = db.file_item(source_item_id);
+            let range = import.relative_range + item.borrowed().range().start();
+            let node = find_covering_node(item.borrowed(), range).owned();
+            let problem = Problem::PrivateItem { name: name.clone() };
+            res.push((node, problem));
+        }
+        let file = db.hir_source_file(file_id);
+        for (ptr, name) in db.private_path_segments(self.krate, self.module_id)?.iter() {
+            let problem = Problem::PrivateItem { name: name.clone() };
+            res.push((ptr.resolve(&file), problem));
+        }
+        Ok(res)
     }
 
     pub(crate) fn source(&self) -> ModuleSource {
@@ -210,6 +321,14 @@ pub struct ModuleTree {
 }
 
 impl ModuleTree {
+    /// The crate root, the only module without a parent.
+    pub(crate) fn root(&self) -> Option<ModuleId> {
+        self.mods
+            .iter()
+            .find(|(_, m)| m.parent.is_none())
+            .map(|(id, _)| id)
+    }
+
     pub(crate) fn modules<'a>(&'a self) -> impl Iterator<Item = ModuleId> + 'a {
         self.mods.iter().map(|(id, _)| id)
     }
@@ -242,6 +361,10 @@ pub enum Problem {
         move_to: RelativePathBuf,
         candidate: RelativePathBuf,
     },
+    /// An import of an item which is not visible from the importing module.
+    PrivateItem {
+        name: Name,
+    },
 }
 
 impl ModuleId {
@@ -251,16 +374,35 @@ impl ModuleId {
     fn parent_link(self, tree: &ModuleTree) -> Option<LinkId> {
         tree.mods[self].parent
     }
-    fn parent(self, tree: &ModuleTree) -> Option<ModuleId> {
+    pub(crate) fn parent(self, tree: &ModuleTree) -> Option<ModuleId> {
         let link = self.parent_link(tree)?;
         Some(tree.links[link].owner)
     }
+    /// The `mod` item which declares this module, `None` for the crate root.
+    pub(crate) fn declaration(
+        self,
+        tree: &ModuleTree,
+        db: &impl HirDatabase,
+    ) -> Option<ast::ModuleNode> {
+        let link = self.parent_link(tree)?;
+        let name = &tree.links[link].name;
+        match link.owner(tree).source(tree).resolve(db) {
+            ModuleSourceNode::SourceFile(root) => imp::modules(root.borrowed())
+                .find(|(it, _)| it == name)
+                .map(|(_, it)| it.owned()),
+            ModuleSourceNode::Module(owner) => owner
+                .borrowed()
+                .item_list()
+                .and_then(|items| imp::modules(items).find(|(it, _)| it == name))
+                .map(|(_, it)| it.owned()),
+        }
+    }
     fn crate_root(self, tree: &ModuleTree) -> ModuleId {
         generate(Some(self), move |it| it.parent(tree))
             .last()
             .unwrap()
     }
-    fn child(self, tree: &ModuleTree, name: &Name) -> Option<ModuleId> {
+    pub(crate) fn child(self, tree: &ModuleTree, name: &Name) -> Option<ModuleId> {
         let link = tree.mods[self]
             .children
             .iter()
@@ -268,7 +410,10 @@ impl ModuleId {
             .find(|it| it.name == *name)?;
         Some(*link.points_to.first()?)
     }
-    fn children<'a>(self, tree: &'a ModuleTree) -> impl Iterator<Item = (Name, ModuleId)> + 'a {
+    pub(crate) fn children<'a>(
+        self,
+        tree: &'a ModuleTree,
+    ) -> impl Iterator<Item = (Name, ModuleId)> + 'a {
         tree.mods[self].children.iter().filter_map(move |&it| {
             let link = &tree.links[it];
             let module = *link.points_to.first()?;
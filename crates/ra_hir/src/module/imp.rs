@@ -2,9 +2,9 @@ use std::sync::Arc;
 
 use ra_syntax::ast::{self, NameOwner};
 use relative_path::RelativePathBuf;
-use rustc_hash::{FxHashMap, FxHashSet};
+use rustc_hash::FxHashSet;
 use arrayvec::ArrayVec;
-use ra_db::{SourceRoot, SourceRootId, Cancelable, FileId};
+use ra_db::{CrateId, Cancelable, FileId};
 
 use crate::{
     HirDatabase, Name, AsName,
@@ -44,51 +44,28 @@ pub(crate) fn modules<'a>(
         })
 }
 
-pub(crate) fn module_tree(
-    db: &impl HirDatabase,
-    source_root: SourceRootId,
-) -> Cancelable<Arc<ModuleTree>> {
+pub(crate) fn module_tree(db: &impl HirDatabase, krate: CrateId) -> Cancelable<Arc<ModuleTree>> {
     db.check_canceled()?;
-    let res = create_module_tree(db, source_root)?;
+    let res = create_module_tree(db, krate)?;
     Ok(Arc::new(res))
 }
 
-fn create_module_tree<'a>(
-    db: &impl HirDatabase,
-    source_root: SourceRootId,
-) -> Cancelable<ModuleTree> {
+fn create_module_tree(db: &impl HirDatabase, krate: CrateId) -> Cancelable<ModuleTree> {
     let mut tree = ModuleTree::default();
-
-    let mut roots = FxHashMap::default();
     let mut visited = FxHashSet::default();
 
-    let source_root = db.source_root(source_root);
-    for &file_id in source_root.files.values() {
-        let source = ModuleSource::new_file(file_id.into());
-        if visited.contains(&source) {
-            continue; // TODO: use explicit crate_roots here
-        }
-        assert!(!roots.contains_key(&file_id));
-        let module_id = build_subtree(
-            db,
-            &source_root,
-            &mut tree,
-            &mut visited,
-            &mut roots,
-            None,
-            source,
-        )?;
-        roots.insert(file_id, module_id);
-    }
+    let crate_graph = db.crate_graph();
+    let file_id = crate_graph.crate_root(krate);
+    let source = ModuleSource::new_file(file_id.into());
+    build_subtree(db, krate, &mut tree, &mut visited, None, source)?;
     Ok(tree)
 }
 
 fn build_subtree(
     db: &impl HirDatabase,
-    source_root: &SourceRoot,
+    krate: CrateId,
     tree: &mut ModuleTree,
     visited: &mut FxHashSet<ModuleSource>,
-    roots: &mut FxHashMap<FileId, ModuleId>,
     parent: Option<LinkId>,
     source: ModuleSource,
 ) -> Cancelable<ModuleId> {
@@ -98,7 +75,7 @@ fn build_subtree(
         parent,
         children: Vec::new(),
     });
-    for sub in db.submodules(source)?.iter() {
+    for sub in db.submodules(krate, source)?.iter() {
         let link = tree.push_link(LinkData {
             name: sub.name().clone(),
             owner: id,
@@ -109,36 +86,21 @@ fn build_subtree(
         let (points_to, problem) = match sub {
             Submodule::Declaration(name) => {
                 let (points_to, problem) = resolve_submodule(db, source, &name);
-                let points_to = points_to
-                    .into_iter()
-                    .map(|file_id| match roots.remove(&file_id) {
-                        Some(module_id) => {
-                            tree.mods[module_id].parent = Some(link);
-                            Ok(module_id)
-                        }
-                        None => build_subtree(
-                            db,
-                            source_root,
-                            tree,
-                            visited,
-                            roots,
-                            Some(link),
-                            ModuleSource::new_file(file_id.into()),
-                        ),
-                    })
-                    .collect::<Cancelable<Vec<_>>>()?;
-                (points_to, problem)
+                let mut modules = Vec::new();
+                for file_id in points_to {
+                    let source = ModuleSource::new_file(file_id.into());
+                    // don't loop on a module declaring itself, like `mod lib;`
+                    // in `lib.rs`
+                    if visited.contains(&source) {
+                        continue;
+                    }
+                    modules.push(build_subtree(db, krate, tree, visited, Some(link), source)?);
+                }
+                (modules, problem)
             }
             Submodule::Definition(_name, submodule_source) => {
-                let points_to = build_subtree(
-                    db,
-                    source_root,
-                    tree,
-                    visited,
-                    roots,
-                    Some(link),
-                    *submodule_source,
-                )?;
+                let points_to =
+                    build_subtree(db, krate, tree, visited, Some(link), *submodule_source)?;
                 (vec![points_to], None)
             }
         };
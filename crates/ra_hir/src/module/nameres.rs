@@ -5,7 +5,8 @@
 //!
 //! Like Rustc, we use an interative per-crate algorithm: we start with scopes
 //! containing only directly defined items, and then iteratively resolve
-//! imports.
+//! imports and expand macros. Macros can produce items and imports, which can
+//! in turn be needed to resolve other imports.
 //!
 //! To make this work nicely in the IDE scenarios, we place `InputModuleItems`
 //! in between raw syntax and name resolution. `InputModuleItems` are computed
@@ -13,33 +14,47 @@
 //! imports. The plain is to make `InputModuleItems` independent of local
 //! modifications (that is, typing inside a function shold not change IMIs),
 //! such that the results of name resolution can be preserved unless the module
-//! structure itself is modified.
+//! structure itself is modified. Macro calls are recorded in IMIs unexpanded,
+//! together with the `macro_rules!` definition they refer to; the items
+//! produced by a call are the IMIs of its expansion.
 use std::sync::Arc;
 
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use ra_syntax::{
-    TextRange,
+    TextRange, SyntaxNodeRef,
     SyntaxKind::{self, *},
-    ast::{self, AstNode}
+    algo::generate,
+    ast::{self, AstNode, NameOwner, VisibilityOwner},
 };
-use ra_db::{SourceRootId, Cancelable, FileId};
+use ra_db::{CrateId, Cancelable, FileId};
 
 use crate::{
-    HirFileId,
+    HirFileId, MacroCallId, MacroCallLoc,
     DefId, DefLoc, DefKind,
     SourceItemId, SourceFileItemId, SourceFileItems,
-    Path, PathKind,
-    HirDatabase, Crate,
+    Path, PathKind, PathSegment,
+    HirDatabase, Crate, Enum,
     Name, AsName,
     module::{Module, ModuleId, ModuleTree},
+    macros::{MACRO_EXPANSION_DEPTH_LIMIT, resolve_macro_rules},
+    cfg,
 };
 
 /// Item map is the result of the name resolution. Item map contains, for each
-/// module, the set of visible items.
-// FIXME: currenty we compute item map per source-root. We should do it per crate instead.
+/// module of a crate, the set of visible items.
 #[derive(Default, Debug, PartialEq, Eq)]
 pub struct ItemMap {
     pub per_module: FxHashMap<ModuleId, ModuleScope>,
+    /// Named imports which refer to an item not visible from the importing
+    /// module, together with the name of that item.
+    pub(crate) private_imports: Vec<(ModuleId, NamedImport, Name)>,
+    /// The module whose names are in scope in every module of the crate,
+    /// unless shadowed: `std::prelude::v1`, or `core::prelude::v1` in
+    /// `#![no_std]` crates.
+    pub(crate) prelude: Option<DefId>,
+    /// The modules the prelude is not in scope in: those with
+    /// `#![no_implicit_prelude]`, and their descendants.
+    pub(crate) no_implicit_prelude: FxHashSet<ModuleId>,
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Clone)]
@@ -66,6 +81,13 @@ impl ModuleScope {
 pub struct InputModuleItems {
     pub(crate) items: Vec<ModuleItem>,
     imports: Vec<Import>,
+    /// Item-position macro calls, expanded during name resolution.
+    pub(crate) macro_calls: Vec<MacroCallId>,
+    /// `#![no_std]` (or `#![no_core]`) of a file.
+    no_std: bool,
+    /// `#![no_implicit_prelude]` in the module, or `#[no_implicit_prelude]`
+    /// on its declaration.
+    no_implicit_prelude: bool,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -73,19 +95,57 @@ pub(crate) struct ModuleItem {
     pub(crate) id: SourceItemId,
     pub(crate) name: Name,
     kind: SyntaxKind,
-    vis: Vis,
+    vis: RawVisibility,
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum Vis {
-    // Priv,
-    Other,
+/// Visibility of an item or an import, as written in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RawVisibility {
+    /// No modifier, or `pub(self)`.
+    Private,
+    Public,
+    /// `pub(crate)`, or just `crate`.
+    Crate,
+    Super,
+    /// `pub(in path)`
+    In(Path),
+}
+
+impl RawVisibility {
+    fn from_ast(vis: Option<ast::Visibility>) -> RawVisibility {
+        let vis = match vis {
+            Some(it) => it,
+            None => return RawVisibility::Private,
+        };
+        match vis.kind() {
+            ast::VisibilityKind::In(path) => match Path::from_ast(path) {
+                Some(path) => RawVisibility::In(path),
+                None => RawVisibility::Private,
+            },
+            ast::VisibilityKind::PubCrate => RawVisibility::Crate,
+            ast::VisibilityKind::PubSuper => RawVisibility::Super,
+            ast::VisibilityKind::PubSelf => RawVisibility::Private,
+            ast::VisibilityKind::Pub => RawVisibility::Public,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Import {
     path: Path,
+    /// The name under which the import is visible, if it's not the last
+    /// segment of the path (`extern crate foo as bar;`).
+    alias: Option<Name>,
     kind: ImportKind,
+    vis: RawVisibility,
+}
+
+impl Import {
+    fn name(&self) -> Option<&Name> {
+        self.alias
+            .as_ref()
+            .or_else(|| self.path.segments.last().map(|segment| &segment.name))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -122,6 +182,54 @@ pub struct Resolution {
     pub def_id: PerNs<DefId>,
     /// ident by whitch this is imported into local scope.
     pub import: Option<NamedImport>,
+    /// Where the name can be used from.
+    pub vis: Visibility,
+}
+
+/// The set of modules a name can be used from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Visibility {
+    /// Everywhere, including other crates.
+    Public,
+    /// The given module of the same crate and its descendants.
+    Module(ModuleId),
+}
+
+impl Visibility {
+    /// Whether a name with this visibility in the scope of `owner` can be
+    /// used from `from`.
+    pub fn is_visible_from(self, owner: &Module, from: &Module) -> bool {
+        match self {
+            Visibility::Public => true,
+            Visibility::Module(_) => {
+                owner.krate == from.krate && self.is_visible_in(&from.tree, from.module_id)
+            }
+        }
+    }
+
+    fn is_visible_in(self, tree: &ModuleTree, module_id: ModuleId) -> bool {
+        match self {
+            Visibility::Public => true,
+            Visibility::Module(m) => {
+                generate(Some(module_id), |it| it.parent(tree)).any(|it| it == m)
+            }
+        }
+    }
+
+    /// The more restrictive of the two visibilities, assuming both include
+    /// some common module.
+    fn restrict(self, other: Visibility, tree: &ModuleTree) -> Visibility {
+        match (self, other) {
+            (Visibility::Public, it) | (it, Visibility::Public) => it,
+            (Visibility::Module(m1), Visibility::Module(m2)) => {
+                if Visibility::Module(m1).is_visible_in(tree, m2) {
+                    Visibility::Module(m2)
+                } else {
+                    Visibility::Module(m1)
+                }
+            }
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -211,7 +319,70 @@ impl<T> PerNs<T> {
 }
 
 impl InputModuleItems {
-    pub(crate) fn add_item(
+    /// Records the attributes of the module, which are the inner ones of its
+    /// file or item list, and the outer ones of its declaration.
+    pub(crate) fn add_attrs(
+        &mut self,
+        db: &impl HirDatabase,
+        krate: CrateId,
+        syntax: SyntaxNodeRef,
+    ) {
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(krate);
+        let has_attr = |atom| cfg::has_atom_attr(syntax, cfg_options, atom);
+        self.no_std |= has_attr("no_std") || has_attr("no_core");
+        self.no_implicit_prelude |= has_attr("no_implicit_prelude");
+    }
+
+    pub(crate) fn add_items<'a>(
+        &mut self,
+        db: &impl HirDatabase,
+        krate: CrateId,
+        module_id: ModuleId,
+        file_id: HirFileId,
+        items: impl Iterator<Item = ast::ItemOrMacro<'a>>,
+    ) -> Cancelable<()> {
+        let file_items = db.file_items(file_id);
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(krate);
+        for item in items {
+            let syntax = match item {
+                ast::ItemOrMacro::Item(it) => it.syntax(),
+                ast::ItemOrMacro::Macro(it) => it.syntax(),
+            };
+            if !cfg::is_cfg_enabled(syntax, cfg_options) {
+                continue;
+            }
+            match item {
+                ast::ItemOrMacro::Item(it) => {
+                    self.add_item(file_id, &file_items, it);
+                }
+                ast::ItemOrMacro::Macro(macro_call) => {
+                    // definitions are collected by `crate_macros`
+                    if macro_call.macro_rules_name().is_some() {
+                        continue;
+                    }
+                    let item_id = file_items.id_of_unchecked(macro_call.syntax());
+                    let source_item_id = SourceItemId {
+                        file_id,
+                        item_id: Some(item_id),
+                    };
+                    let def =
+                        resolve_macro_rules(db, krate, module_id, source_item_id, macro_call)?;
+                    let loc = MacroCallLoc {
+                        krate,
+                        module_id,
+                        source_item_id,
+                        def,
+                    };
+                    self.macro_calls.push(loc.id(db));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_item(
         &mut self,
         file_id: HirFileId,
         file_items: &SourceFileItems,
@@ -237,9 +408,7 @@ impl InputModuleItems {
                 // impls don't define items
             }
             ast::ModuleItem::UseItem(it) => self.add_use_item(file_items, it),
-            ast::ModuleItem::ExternCrateItem(_) => {
-                // TODO
-            }
+            ast::ModuleItem::ExternCrateItem(it) => self.add_extern_crate_item(file_items, it)?,
             ast::ModuleItem::ConstDef(it) => {
                 self.items.push(ModuleItem::new(file_id, file_items, it)?)
             }
@@ -256,6 +425,7 @@ impl InputModuleItems {
     fn add_use_item(&mut self, file_items: &SourceFileItems, item: ast::UseItem) {
         let file_item_id = file_items.id_of_unchecked(item.syntax());
         let start_offset = item.syntax().range().start();
+        let vis = RawVisibility::from_ast(item.visibility());
         Path::expand_use_item(item, |path, range| {
             let kind = match range {
                 None => ImportKind::Glob,
@@ -264,20 +434,56 @@ impl InputModuleItems {
                     relative_range: range - start_offset,
                 }),
             };
-            self.imports.push(Import { kind, path })
+            self.imports.push(Import {
+                kind,
+                path,
+                alias: None,
+                vis: vis.clone(),
+            })
         })
     }
+
+    fn add_extern_crate_item(
+        &mut self,
+        file_items: &SourceFileItems,
+        item: ast::ExternCrateItem,
+    ) -> Option<()> {
+        let name = item.name()?;
+        let alias = item.alias().and_then(|it| it.name());
+        let file_item_id = file_items.id_of_unchecked(item.syntax());
+        let range = alias.unwrap_or(name).syntax().range();
+        let kind = ImportKind::Named(NamedImport {
+            file_item_id,
+            relative_range: range - item.syntax().range().start(),
+        });
+        let path = Path {
+            kind: PathKind::Abs,
+            segments: vec![PathSegment {
+                name: name.as_name(),
+                args_and_bindings: None,
+            }],
+        };
+        let alias = alias.map(|it| it.as_name());
+        let vis = RawVisibility::from_ast(item.visibility());
+        self.imports.push(Import {
+            path,
+            alias,
+            kind,
+            vis,
+        });
+        Some(())
+    }
 }
 
 impl ModuleItem {
     fn new<'a>(
         file_id: HirFileId,
         file_items: &SourceFileItems,
-        item: impl ast::NameOwner<'a>,
+        item: impl ast::NameOwner<'a> + ast::VisibilityOwner<'a>,
     ) -> Option<ModuleItem> {
         let name = item.name()?.as_name();
         let kind = item.syntax().kind();
-        let vis = Vis::Other;
+        let vis = RawVisibility::from_ast(item.visibility());
         let item_id = Some(file_items.id_of_unchecked(item.syntax()));
         let id = SourceItemId { file_id, item_id };
         let res = ModuleItem {
@@ -293,11 +499,34 @@ impl ModuleItem {
 pub(crate) struct Resolver<'a, DB> {
     db: &'a DB,
     input: &'a FxHashMap<ModuleId, Arc<InputModuleItems>>,
-    source_root: SourceRootId,
+    krate: CrateId,
     module_tree: Arc<ModuleTree>,
+    /// Imports of each module: the ones from the input, followed by the ones
+    /// produced by macros.
+    imports: FxHashMap<ModuleId, Vec<Import>>,
+    /// Named imports which don't need to be looked at again, as
+    /// `(module, index of the import)`.
+    processed_imports: FxHashSet<(ModuleId, usize)>,
+    /// Macro calls which are not expanded yet, with the depth of expansion.
+    pending_macro_calls: Vec<(ModuleId, MacroCallId, u32)>,
+    /// Whether the crate is `#![no_std]`, which hides the implicit `std`.
+    no_std: bool,
     result: ItemMap,
 }
 
+/// The outcome of resolving the path of an import.
+enum ImportResolution {
+    /// Some segment is not (yet) in scope; resolving other imports might
+    /// change that.
+    Unresolved,
+    /// The definitions the path resolves to, and how visible the last
+    /// segment is.
+    Resolved(PerNs<DefId>, Visibility),
+    /// The segment with the given name is not visible from the importing
+    /// module.
+    Private(Name),
+}
+
 impl<'a, DB> Resolver<'a, DB>
 where
     DB: HirDatabase,
@@ -305,26 +534,44 @@ where
     pub(crate) fn new(
         db: &'a DB,
         input: &'a FxHashMap<ModuleId, Arc<InputModuleItems>>,
-        source_root: SourceRootId,
+        krate: CrateId,
         module_tree: Arc<ModuleTree>,
     ) -> Resolver<'a, DB> {
         Resolver {
             db,
             input,
-            source_root,
+            krate,
             module_tree,
+            imports: FxHashMap::default(),
+            processed_imports: FxHashSet::default(),
+            pending_macro_calls: Vec::new(),
+            no_std: false,
             result: ItemMap::default(),
         }
     }
 
     pub(crate) fn resolve(mut self) -> Cancelable<ItemMap> {
+        self.resolve_prelude()?;
         for (&module_id, items) in self.input.iter() {
             self.populate_module(module_id, Arc::clone(items))?;
+            self.imports.insert(module_id, items.imports.clone());
+            self.pending_macro_calls
+                .extend(items.macro_calls.iter().map(|&call| (module_id, call, 0)));
         }
 
-        for &module_id in self.input.keys() {
+        // An import can bring into scope names needed by other imports, and a
+        // macro can produce both items and imports, so iterate until we reach
+        // a fixed point. This terminates because scopes only ever grow and
+        // the depth of macro expansion is limited.
+        loop {
             self.db.check_canceled()?;
-            self.resolve_imports(module_id)?;
+            let mut changed = self.expand_macros()?;
+            for &module_id in self.input.keys() {
+                changed |= self.resolve_imports(module_id)?;
+            }
+            if !changed {
+                break;
+            }
         }
         Ok(self.result)
     }
@@ -337,166 +584,414 @@ where
         let mut module_items = ModuleScope::default();
 
         // Populate extern crates prelude
-        {
-            let root_id = module_id.crate_root(&self.module_tree);
-            let file_id = root_id.source(&self.module_tree).file_id();
-            let crate_graph = self.db.crate_graph();
-            if let Some(crate_id) = crate_graph.crate_id_for_crate_root(file_id.as_original_file())
-            {
-                let krate = Crate::new(crate_id);
-                for dep in krate.dependencies(self.db) {
-                    if let Some(module) = dep.krate.root_module(self.db)? {
-                        let def_id = module.def_id(self.db);
-                        self.add_module_item(
-                            &mut module_items,
-                            dep.name.clone(),
-                            PerNs::types(def_id),
-                        );
-                    }
-                }
-            };
+        for dep in Crate::new(self.krate).dependencies(self.db) {
+            if self.no_std && dep.name == Name::std() {
+                continue;
+            }
+            if let Some(module) = dep.krate.root_module(self.db)? {
+                let def_id = module.def_id(self.db);
+                let resolution = Resolution {
+                    def_id: PerNs::types(def_id),
+                    import: None,
+                    vis: Visibility::Module(module_id),
+                };
+                module_items.items.insert(dep.name.clone(), resolution);
+            }
         }
         for import in input.imports.iter() {
-            if let Some(name) = import.path.segments.iter().last() {
-                if let ImportKind::Named(import) = import.kind {
+            if let Some(name) = import.name() {
+                if let ImportKind::Named(ptr) = import.kind {
                     module_items.items.insert(
                         name.clone(),
                         Resolution {
                             def_id: PerNs::none(),
-                            import: Some(import),
+                            import: Some(ptr),
+                            vis: self.resolve_visibility(module_id, &import.vis),
                         },
                     );
                 }
             }
         }
+        self.result.per_module.insert(module_id, module_items);
+
         // Populate explicitly declared items, except modules
         for item in input.items.iter() {
-            if item.kind == MODULE {
-                continue;
-            }
-            // depending on the item kind, the location can define something in
-            // the values namespace, the types namespace, or both
-            let kind = DefKind::for_syntax_kind(item.kind);
-            let def_id = kind.map(|k| {
-                let def_loc = DefLoc {
-                    kind: k,
-                    source_root_id: self.source_root,
-                    module_id,
-                    source_item_id: item.id,
-                };
-                def_loc.id(self.db)
-            });
+            self.add_item(module_id, item);
+        }
+
+        // Populate modules
+        let module_tree = Arc::clone(&self.module_tree);
+        for (name, child_id) in module_id.children(&module_tree) {
+            let vis = input
+                .items
+                .iter()
+                .find(|item| item.kind == MODULE && item.name == name)
+                .map(|item| self.resolve_visibility(module_id, &item.vis))
+                .unwrap_or(Visibility::Module(module_id));
             let resolution = Resolution {
-                def_id,
+                def_id: PerNs::types(self.module_def_id(child_id)),
                 import: None,
+                vis,
             };
-            module_items.items.insert(item.name.clone(), resolution);
+            self.update(module_id, |items| {
+                items.items.insert(name, resolution);
+            });
         }
+        Ok(())
+    }
 
-        // Populate modules
-        for (name, module_id) in module_id.children(&self.module_tree) {
-            let def_loc = DefLoc {
-                kind: DefKind::Module,
-                source_root_id: self.source_root,
-                module_id,
-                source_item_id: module_id.source(&self.module_tree).0,
+    /// Looks at the attributes of the crate root to find out which of the
+    /// implicit `std` and `core` dependencies provides the prelude.
+    fn resolve_prelude(&mut self) -> Cancelable<()> {
+        let root = match self.module_tree.root() {
+            Some(it) => &self.input[&it],
+            None => return Ok(()),
+        };
+        self.no_std = root.no_std;
+        // modules come before their children
+        for module_id in self.module_tree.modules() {
+            let parent_has_none = match module_id.parent(&self.module_tree) {
+                Some(parent) => self.result.no_implicit_prelude.contains(&parent),
+                None => false,
             };
-            let def_id = def_loc.id(self.db);
-            self.add_module_item(&mut module_items, name, PerNs::types(def_id));
+            if parent_has_none || self.input[&module_id].no_implicit_prelude {
+                self.result.no_implicit_prelude.insert(module_id);
+            }
+        }
+        let dep_name = if self.no_std {
+            Name::core()
+        } else {
+            Name::std()
+        };
+        let dep = Crate::new(self.krate)
+            .dependencies(self.db)
+            .into_iter()
+            .find(|dep| dep.name == dep_name);
+        let dep_root = match dep {
+            Some(dep) => dep.krate.root_module(self.db)?,
+            None => None,
+        };
+        if let Some(dep_root) = dep_root {
+            let path = Path {
+                kind: PathKind::Plain,
+                segments: ["prelude", "v1"]
+                    .iter()
+                    .map(|&it| PathSegment {
+                        name: Name::new(it.into()),
+                        args_and_bindings: None,
+                    })
+                    .collect(),
+            };
+            let prelude = dep_root.resolve_path(self.db, &path)?.take_types();
+            self.result.prelude = prelude.filter(|it| it.loc(self.db).kind == DefKind::Module);
         }
-
-        self.result.per_module.insert(module_id, module_items);
         Ok(())
     }
 
-    fn add_module_item(&self, module_items: &mut ModuleScope, name: Name, def_id: PerNs<DefId>) {
+    /// Expands the pending macro calls, adding the produced items to the
+    /// scopes of the calling modules. Returns whether anything was expanded.
+    fn expand_macros(&mut self) -> Cancelable<bool> {
+        let pending = std::mem::replace(&mut self.pending_macro_calls, Vec::new());
+        let changed = !pending.is_empty();
+        for (module_id, macro_call_id, depth) in pending {
+            let expansion = self.db.macro_expansion_items(macro_call_id)?;
+            for item in expansion.items.iter() {
+                self.add_item(module_id, item);
+            }
+            self.imports
+                .get_mut(&module_id)
+                .unwrap()
+                .extend(expansion.imports.iter().cloned());
+            if depth < MACRO_EXPANSION_DEPTH_LIMIT {
+                self.pending_macro_calls.extend(
+                    expansion
+                        .macro_calls
+                        .iter()
+                        .map(|&call| (module_id, call, depth + 1)),
+                );
+            }
+        }
+        Ok(changed)
+    }
+
+    fn add_item(&mut self, module_id: ModuleId, item: &ModuleItem) {
+        if item.kind == MODULE {
+            return;
+        }
+        // depending on the item kind, the location can define something in
+        // the values namespace, the types namespace, or both
+        let kind = DefKind::for_syntax_kind(item.kind);
+        let def_id = kind.map(|k| {
+            let def_loc = DefLoc {
+                kind: k,
+                krate: self.krate,
+                module_id,
+                source_item_id: item.id,
+            };
+            def_loc.id(self.db)
+        });
         let resolution = Resolution {
             def_id,
             import: None,
+            vis: self.resolve_visibility(module_id, &item.vis),
+        };
+        self.update(module_id, |items| {
+            items.items.insert(item.name.clone(), resolution);
+        });
+    }
+
+    fn module_def_id(&self, module_id: ModuleId) -> DefId {
+        let def_loc = DefLoc {
+            kind: DefKind::Module,
+            krate: self.krate,
+            module_id,
+            source_item_id: module_id.source(&self.module_tree).0,
         };
-        module_items.items.insert(name, resolution);
+        def_loc.id(self.db)
     }
 
-    fn resolve_imports(&mut self, module_id: ModuleId) -> Cancelable<()> {
-        for import in self.input[&module_id].imports.iter() {
-            self.resolve_import(module_id, import)?;
+    fn resolve_visibility(&self, module_id: ModuleId, vis: &RawVisibility) -> Visibility {
+        let tree = &self.module_tree;
+        match vis {
+            RawVisibility::Private => Visibility::Module(module_id),
+            RawVisibility::Public => Visibility::Public,
+            RawVisibility::Crate => Visibility::Module(module_id.crate_root(tree)),
+            RawVisibility::Super => Visibility::Module(module_id.parent(tree).unwrap_or(module_id)),
+            RawVisibility::In(path) => {
+                // `pub(in path)` can only name an ancestor module, so we
+                // don't need the item map to resolve the path
+                let start = match path.kind {
+                    PathKind::Self_ => Some(module_id),
+                    PathKind::Super => module_id.parent(tree),
+                    PathKind::Plain | PathKind::Crate | PathKind::Abs => {
+                        Some(module_id.crate_root(tree))
+                    }
+                };
+                let target = path
+                    .segments
+                    .iter()
+                    .fold(start, |m, segment| m?.child(tree, &segment.name));
+                Visibility::Module(target.unwrap_or(module_id))
+            }
+        }
+    }
+
+    /// Returns whether any import changed the item map.
+    fn resolve_imports(&mut self, module_id: ModuleId) -> Cancelable<bool> {
+        let mut changed = false;
+        let imports = self.imports[&module_id].clone();
+        for (idx, import) in imports.iter().enumerate() {
+            if self.processed_imports.contains(&(module_id, idx)) {
+                continue;
+            }
+            changed |= self.resolve_import(module_id, idx, import)?;
+        }
+        Ok(changed)
+    }
+
+    fn resolve_import(
+        &mut self,
+        module_id: ModuleId,
+        idx: usize,
+        import: &Import,
+    ) -> Cancelable<bool> {
+        let (def_id, def_vis) = match self.resolve_import_path(module_id, &import.path)? {
+            ImportResolution::Unresolved => return Ok(false),
+            ImportResolution::Private(name) => {
+                self.processed_imports.insert((module_id, idx));
+                if let ImportKind::Named(ptr) = import.kind {
+                    // FIXME: `NamedImport` can't point into macro expansions
+                    if !self.is_from_macro(module_id, idx) {
+                        self.result.private_imports.push((module_id, ptr, name));
+                    }
+                }
+                return Ok(false);
+            }
+            ImportResolution::Resolved(def_id, def_vis) => (def_id, def_vis),
+        };
+        let vis = self.resolve_visibility(module_id, &import.vis);
+        match import.kind {
+            ImportKind::Glob => self.resolve_glob_import(module_id, def_id, def_vis, vis),
+            ImportKind::Named(ptr) => {
+                self.processed_imports.insert((module_id, idx));
+                let name = match import.name() {
+                    Some(it) => it.clone(),
+                    None => return Ok(false),
+                };
+                // FIXME: `NamedImport` can't point into macro expansions
+                let import = if self.is_from_macro(module_id, idx) {
+                    None
+                } else {
+                    Some(ptr)
+                };
+                let res = Resolution {
+                    def_id,
+                    import,
+                    vis,
+                };
+                self.update(module_id, |items| {
+                    items.items.insert(name, res);
+                });
+                Ok(true)
+            }
         }
-        Ok(())
     }
 
-    fn resolve_import(&mut self, module_id: ModuleId, import: &Import) -> Cancelable<()> {
-        let ptr = match import.kind {
-            ImportKind::Glob => return Ok(()),
-            ImportKind::Named(ptr) => ptr,
+    /// Adds the names of the module `def_id` which are visible from
+    /// `module_id` to its scope, unless they are shadowed by other items. For
+    /// an enum, these are its variants, which are as visible as the enum
+    /// (`def_vis`).
+    fn resolve_glob_import(
+        &mut self,
+        module_id: ModuleId,
+        def_id: PerNs<DefId>,
+        def_vis: Visibility,
+        vis: Visibility,
+    ) -> Cancelable<bool> {
+        let def_id = match def_id.take_types() {
+            Some(it) => it,
+            None => return Ok(false),
+        };
+        let entries = match def_id.loc(self.db) {
+            DefLoc {
+                kind: DefKind::Module,
+                krate,
+                module_id: target_id,
+                ..
+            } => {
+                if krate == self.krate {
+                    self.result.per_module[&target_id]
+                        .items
+                        .iter()
+                        .filter(|(_, res)| res.vis.is_visible_in(&self.module_tree, module_id))
+                        .map(|(name, res)| (name.clone(), res.def_id, res.vis))
+                        .collect::<Vec<_>>()
+                } else {
+                    Module::new(self.db, krate, target_id)?
+                        .scope(self.db)?
+                        .entries()
+                        .filter(|(_, res)| res.vis == Visibility::Public)
+                        .map(|(name, res)| (name.clone(), res.def_id, res.vis))
+                        .collect::<Vec<_>>()
+                }
+            }
+            DefLoc {
+                kind: DefKind::Enum,
+                ..
+            } => Enum::new(def_id)
+                .variants(self.db)?
+                .into_iter()
+                .map(|(name, variant)| {
+                    let def_id = PerNs::both(variant.def_id(), variant.def_id());
+                    (name, def_id, def_vis)
+                })
+                .collect::<Vec<_>>(),
+            _ => return Ok(false),
         };
+        let module_tree = Arc::clone(&self.module_tree);
+        let mut changed = false;
+        self.update(module_id, |items| {
+            for (name, def_id, item_vis) in entries {
+                if def_id.is_none() || items.items.contains_key(&name) {
+                    continue;
+                }
+                let res = Resolution {
+                    def_id,
+                    import: None,
+                    vis: vis.restrict(item_vis, &module_tree),
+                };
+                items.items.insert(name, res);
+                changed = true;
+            }
+        });
+        Ok(changed)
+    }
 
-        let mut curr: ModuleId = match import.path.kind {
+    fn resolve_import_path(
+        &self,
+        module_id: ModuleId,
+        path: &Path,
+    ) -> Cancelable<ImportResolution> {
+        let start = match path.kind {
             PathKind::Plain | PathKind::Self_ => module_id,
             PathKind::Super => {
                 match module_id.parent(&self.module_tree) {
                     Some(it) => it,
                     // TODO: error
-                    None => return Ok(()),
+                    None => return Ok(ImportResolution::Unresolved),
                 }
             }
             PathKind::Crate => module_id.crate_root(&self.module_tree),
+            PathKind::Abs => {
+                // the path starts in a dependency, so there's nothing to
+                // resolve iteratively in this crate
+                let module = Module::new(self.db, self.krate, module_id)?;
+                let def_id = module.resolve_path(self.db, path)?;
+                return Ok(resolved(def_id, Visibility::Public));
+            }
         };
 
-        for (i, name) in import.path.segments.iter().enumerate() {
-            let is_last = i == import.path.segments.len() - 1;
-
-            let def_id = match self.result.per_module[&curr].items.get(name) {
-                Some(res) if !res.def_id.is_none() => res.def_id,
-                _ => return Ok(()),
+        let mut curr_per_ns = PerNs::types(self.module_def_id(start));
+        let mut curr_vis = Visibility::Public;
+        for (i, segment) in path.segments.iter().enumerate() {
+            let curr_def_id = curr_per_ns.take_types();
+            let curr = match curr_def_id.map(|it| it.loc(self.db)) {
+                Some(DefLoc {
+                    kind: DefKind::Module,
+                    krate,
+                    module_id: target_module_id,
+                    ..
+                }) => {
+                    if krate == self.krate {
+                        target_module_id
+                    } else {
+                        let module = Module::new(self.db, self.krate, module_id)?;
+                        let target = Module::new(self.db, krate, target_module_id)?;
+                        let def_id =
+                            module.resolve_segments(self.db, target, &path.segments[i..])?;
+                        return Ok(resolved(def_id, Visibility::Public));
+                    }
+                }
+                // `use Enum::Variant`; variants have no items of their own
+                Some(DefLoc {
+                    kind: DefKind::Enum,
+                    ..
+                }) if i + 1 == path.segments.len() => {
+                    let e = Enum::new(curr_def_id.unwrap());
+                    let def_id = match e.variant(self.db, &segment.name)? {
+                        Some(variant) => PerNs::both(variant.def_id(), variant.def_id()),
+                        None => PerNs::none(),
+                    };
+                    return Ok(resolved(def_id, curr_vis));
+                }
+                _ => return Ok(ImportResolution::Unresolved),
             };
-
-            if !is_last {
-                let type_def_id = if let Some(d) = def_id.take(Namespace::Types) {
-                    d
-                } else {
-                    return Ok(());
-                };
-                curr = match type_def_id.loc(self.db) {
-                    DefLoc {
-                        kind: DefKind::Module,
-                        module_id: target_module_id,
-                        source_root_id,
-                        ..
-                    } => {
-                        if source_root_id == self.source_root {
-                            target_module_id
-                        } else {
-                            let module = Module::new(self.db, source_root_id, target_module_id)?;
-                            let path = Path {
-                                segments: import.path.segments[i + 1..].iter().cloned().collect(),
-                                kind: PathKind::Crate,
-                            };
-                            let def_id = module.resolve_path(self.db, &path)?;
-                            if !def_id.is_none() {
-                                self.update(module_id, |items| {
-                                    let res = Resolution {
-                                        def_id: def_id,
-                                        import: Some(ptr),
-                                    };
-                                    items.items.insert(name.clone(), res);
-                                })
-                            }
-                            return Ok(());
-                        }
+            curr_per_ns = match self.result.per_module[&curr].items.get(&segment.name) {
+                Some(res) if !res.def_id.is_none() => {
+                    if !res.vis.is_visible_in(&self.module_tree, module_id) {
+                        return Ok(ImportResolution::Private(segment.name.clone()));
                     }
-                    _ => return Ok(()),
+                    curr_vis = res.vis;
+                    res.def_id
                 }
+                _ => return Ok(ImportResolution::Unresolved),
+            };
+        }
+        return Ok(ImportResolution::Resolved(curr_per_ns, curr_vis));
+
+        fn resolved(def_id: PerNs<DefId>, vis: Visibility) -> ImportResolution {
+            if def_id.is_none() {
+                ImportResolution::Unresolved
             } else {
-                self.update(module_id, |items| {
-                    let res = Resolution {
-                        def_id: def_id,
-                        import: Some(ptr),
-                    };
-                    items.items.insert(name.clone(), res);
-                })
+                ImportResolution::Resolved(def_id, vis)
             }
         }
-        Ok(())
+    }
+
+    /// Whether the import was produced by a macro, rather than written in
+    /// the module.
+    fn is_from_macro(&self, module_id: ModuleId, idx: usize) -> bool {
+        idx >= self.input[&module_id].imports.len()
     }
 
     fn update(&mut self, module_id: ModuleId, f: impl FnOnce(&mut ModuleScope)) {
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use salsa::Database;
-use ra_db::{FilesDatabase, CrateGraph};
+use ra_db::{CrateGraph, CfgOptions};
 use relative_path::RelativePath;
 use test_utils::assert_eq_text;
 
@@ -13,12 +13,11 @@ use crate::{
 
 fn item_map(fixture: &str) -> (Arc<hir::ItemMap>, hir::ModuleId) {
     let (db, pos) = MockDatabase::with_position(fixture);
-    let source_root = db.file_source_root(pos.file_id);
     let module = hir::source_binder::module_from_position(&db, pos)
         .unwrap()
         .unwrap();
     let module_id = module.module_id;
-    (db.item_map(source_root).unwrap(), module_id)
+    (db.item_map(module.krate).unwrap(), module_id)
 }
 
 fn check_module_item_map(map: &hir::ItemMap, module_id: hir::ModuleId, expected: &str) {
@@ -92,7 +91,7 @@ fn item_map_contains_items_from_expansions() {
 
         //- /foo/bar.rs
         salsa::query_group! {
-            trait Baz {}
+            pub trait Baz {}
         }
     ",
     );
@@ -106,6 +105,167 @@ fn item_map_contains_items_from_expansions() {
     );
 }
 
+#[test]
+fn item_map_contains_items_from_macro_rules() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        #[macro_use]
+        mod macros;
+        mod foo;
+
+        use crate::foo::{Bar, Baz, baz};
+
+        macro_rules! structs {
+            ($($i:ident),*) => { $(pub struct $i;)* }
+        }
+
+        structs!(Foo, Spam);
+        <|>
+
+        //- /macros.rs
+        macro_rules! fns {
+            ($($i:ident),*) => { $(pub fn $i() {})* }
+        }
+
+        //- /foo.rs
+        structs! { Bar, Baz }
+        fns! { baz }
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Bar: t v
+            Baz: t v
+            Foo: t v
+            Spam: t v
+            baz: v
+            foo: t
+            macros: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_for_files_not_reached_by_crate() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        struct Foo;
+
+        //- /detached/child.rs
+        pub struct Baz;
+
+        //- /detached/mod.rs
+        mod child;
+        use self::child::Baz;
+        struct Bar;
+        <|>
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Bar: t v
+            Baz: t v
+            child: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_expands_macros_to_a_fixed_point() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+
+        use crate::foo::{Bar, Baz, spam};
+        <|>
+
+        //- /foo.rs
+        macro_rules! structs {
+            ($($i:ident),*) => { $(pub struct $i;)* }
+        }
+        macro_rules! nested {
+            ($s:ident, $f:ident) => { structs!($s); pub use self::inner::$f; };
+        }
+        macro_rules! recursive {
+            () => { recursive!(); }
+        }
+
+        nested!(Bar, spam);
+        structs!(Baz);
+        recursive!();
+        pub mod inner {
+            pub fn spam() {}
+        }
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Bar: t v
+            Baz: t v
+            foo: t
+            spam: v
+        ",
+    );
+}
+
+#[test]
+fn item_map_contains_items_from_exported_macro_rules() {
+    let (item_map, module_id) = item_map_with_dep(
+        "
+        //- /main.rs
+        test_crate::structs!(Foo);
+
+        //- /lib.rs
+        #[macro_export]
+        macro_rules! structs {
+            ($i:ident) => { struct $i; }
+        }
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Foo: t v
+            test_crate: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_resolves_dollar_crate_in_crate_of_macro() {
+    let (item_map, module_id) = item_map_with_dep(
+        "
+        //- /main.rs
+        test_crate::imports!();
+
+        //- /lib.rs
+        pub struct Foo;
+        #[macro_export]
+        macro_rules! imports {
+            () => { use $crate::Foo; }
+        }
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Foo: t v
+            test_crate: t
+        ",
+    );
+}
+
 #[test]
 fn item_map_using_self() {
     let (item_map, module_id) = item_map(
@@ -130,6 +290,212 @@ fn item_map_using_self() {
     );
 }
 
+#[test]
+fn item_map_glob_imports() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        use self::foo::*;
+        <|>
+
+        //- /foo.rs
+        pub mod bar;
+        pub use self::bar::*;
+        pub struct Baz;
+        struct Private;
+        pub(crate) fn spam() {}
+
+        //- /foo/bar.rs
+        pub struct Bar;
+        pub(super) struct Restricted;
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Bar: t v
+            Baz: t v
+            bar: t
+            foo: t
+            spam: v
+        ",
+    );
+}
+
+#[test]
+fn item_map_glob_import_does_not_shadow_items() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        use self::foo::*;
+        fn Baz() {}
+        <|>
+
+        //- /foo.rs
+        pub struct Baz;
+        pub fn quux() {}
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: v
+            foo: t
+            quux: v
+        ",
+    );
+}
+
+#[test]
+fn item_map_glob_import_from_parent() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        struct Baz;
+        fn quux() {}
+
+        //- /foo.rs
+        use super::*;
+        <|>
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            foo: t
+            quux: v
+        ",
+    );
+}
+
+#[test]
+fn item_map_enum_variant_imports() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        use self::foo::E::V1;
+        use self::foo::Option::*;
+        use self::foo::E::V3;
+        <|>
+
+        //- /foo.rs
+        pub enum E { V1, V2 }
+        pub enum Option<T> { None, Some(T) }
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            None: t v
+            Some: t v
+            V1: t v
+            V3: _
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_query_group_trait_keeps_visibility() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        use self::foo::*;
+        <|>
+
+        //- /foo.rs
+        salsa::query_group! {
+            pub(crate) trait Database: BaseDatabase {
+                fn query(key: u32) -> u32 {
+                    type Query;
+                }
+            }
+        }
+        salsa::query_group! {
+            trait PrivateDatabase: BaseDatabase {}
+        }
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Database: t
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_enum_glob_reexport_keeps_enum_visibility() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        use self::foo::*;
+        <|>
+
+        //- /foo.rs
+        pub use self::Private::*;
+        pub use self::Public::*;
+        enum Private { A }
+        pub enum Public { B }
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            B: t v
+            Public: t
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_private_imports_are_not_resolved() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        use self::foo::{Private, Public, InParent};
+        use self::foo::inner::InCrate;
+        <|>
+
+        //- /foo.rs
+        struct Private;
+        pub struct Public;
+        pub(super) struct InParent;
+        pub(crate) mod inner {
+            pub(in crate::foo) struct InFoo;
+            pub(crate) struct InCrate;
+        }
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            InCrate: t v
+            InParent: t v
+            Private: _
+            Public: t v
+            foo: t
+        ",
+    );
+}
+
 #[test]
 fn item_map_across_crates() {
     let (mut db, sr) = MockDatabase::with_files(
@@ -151,23 +517,318 @@ fn item_map_across_crates() {
 
     db.set_crate_graph(crate_graph);
 
-    let source_root = db.file_source_root(main_id);
     let module = hir::source_binder::module_from_file_id(&db, main_id)
         .unwrap()
         .unwrap();
     let module_id = module.module_id;
-    let item_map = db.item_map(source_root).unwrap();
+    let item_map = db.item_map(module.krate).unwrap();
+
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            test_crate: t
+        ",
+    );
+}
+
+/// Makes `/main.rs` a crate depending on `/lib.rs` under the name
+/// `test_crate`, and returns the item map for the root of the main crate.
+fn item_map_with_dep(fixture: &str) -> (Arc<hir::ItemMap>, hir::ModuleId) {
+    let (mut db, sr) = MockDatabase::with_files(fixture);
+    let main_id = sr.files[RelativePath::new("/main.rs")];
+    let lib_id = sr.files[RelativePath::new("/lib.rs")];
+
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_id);
+    let lib_crate = crate_graph.add_crate_root(lib_id);
+    crate_graph.add_dep(main_crate, "test_crate".into(), lib_crate);
+
+    db.set_crate_graph(crate_graph);
+
+    let module = hir::source_binder::module_from_file_id(&db, main_id)
+        .unwrap()
+        .unwrap();
+    (db.item_map(module.krate).unwrap(), module.module_id)
+}
+
+#[test]
+fn item_map_across_crates_nested_path() {
+    let (item_map, module_id) = item_map_with_dep(
+        "
+        //- /main.rs
+        use test_crate::foo::Baz;
+
+        //- /lib.rs
+        pub mod foo;
+
+        //- /foo.rs
+        pub struct Baz;
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            test_crate: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_across_crates_absolute_path() {
+    let (item_map, module_id) = item_map_with_dep(
+        "
+        //- /main.rs
+        mod test_crate {}
+        use ::test_crate::Baz;
+
+        //- /lib.rs
+        pub struct Baz;
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            test_crate: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_extern_crate_alias() {
+    let (item_map, module_id) = item_map_with_dep(
+        "
+        //- /main.rs
+        extern crate test_crate as dep;
+        use dep::Baz;
 
+        //- /lib.rs
+        pub struct Baz;
+    ",
+    );
     check_module_item_map(
         &item_map,
         module_id,
         "
             Baz: t v
+            dep: t
             test_crate: t
         ",
     );
 }
 
+#[test]
+fn item_map_skips_items_disabled_by_cfg() {
+    let (mut db, sr) = MockDatabase::with_files(
+        r#"
+        //- /lib.rs
+        mod foo;
+        #[cfg(test)]
+        mod tests;
+        #[cfg_attr(feature = "std", macro_use)]
+        mod macros;
+
+        use crate::foo::{Bar, Baz};
+
+        #[cfg(feature = "std")]
+        pub fn std_only() {}
+        #[cfg(not(feature = "std"))]
+        pub fn no_std_only() {}
+
+        structs!(Quux);
+
+        //- /foo.rs
+        #[cfg(feature = "std")]
+        pub struct Bar;
+        #[cfg(not(feature = "std"))]
+        pub fn Bar() {}
+        #[cfg(test)]
+        pub struct Baz;
+
+        //- /tests.rs
+        pub struct Tests;
+
+        //- /macros.rs
+        macro_rules! structs {
+            ($($i:ident),*) => { $(pub struct $i;)* }
+        }
+        #[cfg(test)]
+        macro_rules! structs {
+            ($($i:ident),*) => { $(pub fn $i() {})* }
+        }
+    "#,
+    );
+    let lib_id = sr.files[RelativePath::new("/lib.rs")];
+    let mut cfg_options = CfgOptions::default();
+    cfg_options.insert_key_value("feature".into(), "std".into());
+    let mut crate_graph = CrateGraph::default();
+    crate_graph.add_crate_root_with_cfg(lib_id, cfg_options);
+    db.set_crate_graph(crate_graph);
+
+    let module = hir::source_binder::module_from_file_id(&db, lib_id)
+        .unwrap()
+        .unwrap();
+    let item_map = db.item_map(module.krate).unwrap();
+    check_module_item_map(
+        &item_map,
+        module.module_id,
+        "
+            Bar: t v
+            Baz: _
+            Quux: t v
+            foo: t
+            macros: t
+            std_only: v
+        ",
+    );
+}
+
+/// Resolves the names in the root of `/main.rs`, a crate which depends on
+/// `std` (`/std.rs`) and `core` (`/core.rs`), and returns what they resolve
+/// to, together with the item map of the root.
+fn resolve_with_prelude(main: &str, names: &[&str]) -> (Arc<hir::ItemMap>, hir::ModuleId, String) {
+    resolve_with_prelude_in(main, &[], names)
+}
+
+/// Like `resolve_with_prelude`, but resolves the names in the module at
+/// `module_path` from the root.
+fn resolve_with_prelude_in(
+    main: &str,
+    module_path: &[&str],
+    names: &[&str],
+) -> (Arc<hir::ItemMap>, hir::ModuleId, String) {
+    let fixture = format!(
+        "
+        //- /main.rs
+        {}
+
+        //- /std.rs
+        pub mod prelude {{
+            pub mod v1 {{
+                pub use core::Option;
+                pub struct Vec;
+            }}
+        }}
+
+        //- /core.rs
+        pub enum Option {{}}
+        pub mod prelude {{
+            pub mod v1 {{
+                pub use crate::Option;
+            }}
+        }}
+        ",
+        main
+    );
+    let (mut db, sr) = MockDatabase::with_files(&fixture);
+    let main_id = sr.files[RelativePath::new("/main.rs")];
+    let std_id = sr.files[RelativePath::new("/std.rs")];
+    let core_id = sr.files[RelativePath::new("/core.rs")];
+
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_id);
+    let std_crate = crate_graph.add_crate_root(std_id);
+    let core_crate = crate_graph.add_crate_root(core_id);
+    crate_graph.add_dep(std_crate, "core".into(), core_crate);
+    crate_graph.add_dep(main_crate, "std".into(), std_crate);
+    crate_graph.add_dep(main_crate, "core".into(), core_crate);
+    db.set_crate_graph(crate_graph);
+
+    let mut module = hir::source_binder::module_from_file_id(&db, main_id)
+        .unwrap()
+        .unwrap();
+    for &name in module_path {
+        module = module.child(&hir::Name::new(name.into())).unwrap();
+    }
+    let resolutions = names
+        .iter()
+        .map(|&name| {
+            let path = hir::Path {
+                kind: hir::PathKind::Plain,
+                segments: vec![hir::PathSegment {
+                    name: hir::Name::new(name.into()),
+                    args_and_bindings: None,
+                }],
+            };
+            let resolved = module
+                .resolve_path(&db, &path)
+                .unwrap()
+                .take_types()
+                .map(|it| it.loc(&db).krate);
+            let krate = match resolved {
+                None => "_",
+                Some(it) if it == main_crate => "main",
+                Some(it) if it == std_crate => "std",
+                Some(_) => "core",
+            };
+            format!("{}: {}", name, krate)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    (
+        db.item_map(module.krate).unwrap(),
+        module.module_id,
+        resolutions,
+    )
+}
+
+#[test]
+fn std_prelude() {
+    let (item_map, module_id, resolutions) =
+        resolve_with_prelude("struct Vec;", &["Option", "Vec", "std", "core"]);
+    assert_eq_text!(
+        "Option: core\nVec: main\nstd: std\ncore: core",
+        &resolutions
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Vec: t v
+            core: t
+            std: t
+        ",
+    );
+}
+
+#[test]
+fn no_std_uses_core_prelude() {
+    let (item_map, module_id, resolutions) =
+        resolve_with_prelude("#![no_std]", &["Option", "Vec", "std"]);
+    assert_eq_text!("Option: core\nVec: _\nstd: _", &resolutions);
+    check_module_item_map(&item_map, module_id, "core: t");
+}
+
+#[test]
+fn no_implicit_prelude() {
+    let (_, _, resolutions) = resolve_with_prelude("#![no_implicit_prelude]", &["Option", "std"]);
+    assert_eq_text!("Option: _\nstd: std", &resolutions);
+}
+
+#[test]
+fn no_implicit_prelude_in_module() {
+    let main = "
+        mod foo {
+            #![no_implicit_prelude]
+            mod bar {}
+        }
+        #[no_implicit_prelude]
+        mod baz {}
+        mod quux {}
+    ";
+    let resolve = |module_path: &[&str]| resolve_with_prelude_in(main, module_path, &["Option"]).2;
+    assert_eq_text!("Option: _", &resolve(&["foo"]));
+    assert_eq_text!("Option: _", &resolve(&["foo", "bar"]));
+    assert_eq_text!("Option: _", &resolve(&["baz"]));
+    assert_eq_text!("Option: core", &resolve(&["quux"]));
+    assert_eq_text!("Option: core", &resolve(&[]));
+}
+
 #[test]
 fn typing_inside_a_function_should_not_invalidate_item_map() {
     let (mut db, pos) = MockDatabase::with_position(
@@ -189,10 +850,13 @@ fn typing_inside_a_function_should_not_invalidate_item_map() {
         }
         ",
     );
-    let source_root = db.file_source_root(pos.file_id);
+    let krate = hir::source_binder::module_from_position(&db, pos)
+        .unwrap()
+        .unwrap()
+        .krate;
     {
         let events = db.log_executed(|| {
-            db.item_map(source_root).unwrap();
+            db.item_map(krate).unwrap();
         });
         assert!(format!("{:?}", events).contains("item_map"))
     }
@@ -211,7 +875,7 @@ fn typing_inside_a_function_should_not_invalidate_item_map() {
 
     {
         let events = db.log_executed(|| {
-            db.item_map(source_root).unwrap();
+            db.item_map(krate).unwrap();
         });
         assert!(
             !format!("{:?}", events).contains("item_map"),
@@ -240,10 +904,13 @@ fn typing_inside_a_function_inside_a_macro_should_not_invalidate_item_map() {
         pub struct Baz;
     ",
     );
-    let source_root = db.file_source_root(pos.file_id);
+    let krate = hir::source_binder::module_from_position(&db, pos)
+        .unwrap()
+        .unwrap()
+        .krate;
     {
         let events = db.log_executed(|| {
-            db.item_map(source_root).unwrap();
+            db.item_map(krate).unwrap();
         });
         assert!(format!("{:?}", events).contains("item_map"))
     }
@@ -262,7 +929,7 @@ fn typing_inside_a_function_inside_a_macro_should_not_invalidate_item_map() {
 
     {
         let events = db.log_executed(|| {
-            db.item_map(source_root).unwrap();
+            db.item_map(krate).unwrap();
         });
         assert!(
             !format!("{:?}", events).contains("item_map"),
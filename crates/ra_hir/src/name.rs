@@ -23,7 +23,7 @@ impl fmt::Debug for Name {
 }
 
 impl Name {
-    fn new(text: SmolStr) -> Name {
+    pub(crate) fn new(text: SmolStr) -> Name {
         Name { text }
     }
 
@@ -35,6 +35,22 @@ impl Name {
         Name::new(idx.to_string().into())
     }
 
+    pub(crate) fn self_param() -> Name {
+        Name::new("self".into())
+    }
+
+    pub(crate) fn self_type() -> Name {
+        Name::new("Self".into())
+    }
+
+    pub(crate) fn std() -> Name {
+        Name::new("std".into())
+    }
+
+    pub(crate) fn core() -> Name {
+        Name::new("core".into())
+    }
+
     pub(crate) fn as_known_name(&self) -> Option<KnownName> {
         let name = match self.text.as_str() {
             "isize" => KnownName::Isize,
@@ -51,7 +67,13 @@ impl Name {
             "u128" => KnownName::U128,
             "f32" => KnownName::F32,
             "f64" => KnownName::F64,
+            "bool" => KnownName::Bool,
+            "char" => KnownName::Char,
+            "str" => KnownName::Str,
             "Self" => KnownName::Self_,
+            "Fn" => KnownName::Fn,
+            "FnMut" => KnownName::FnMut,
+            "FnOnce" => KnownName::FnOnce,
             _ => return None,
         };
         Some(name)
@@ -104,5 +126,13 @@ pub(crate) enum KnownName {
     F32,
     F64,
 
+    Bool,
+    Char,
+    Str,
+
     Self_,
+
+    Fn,
+    FnMut,
+    FnOnce,
 }
@@ -1,11 +1,35 @@
+use std::sync::Arc;
+
 use ra_syntax::{ast, AstNode, TextRange};
 
-use crate::{Name, AsName};
+use crate::{Name, AsName, type_ref::TypeRef};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Path {
     pub kind: PathKind,
-    pub segments: Vec<Name>,
+    pub segments: Vec<PathSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PathSegment {
+    pub name: Name,
+    pub args_and_bindings: Option<Arc<GenericArgs>>,
+}
+
+/// Generic arguments to a path segment (e.g. the `i32` in `Option<i32>`). This
+/// can (in the future) also include bindings of associated types, like in
+/// `Iterator<Item = Foo>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GenericArgs {
+    pub args: Vec<GenericArg>,
+    // someday also bindings
+}
+
+/// A single generic argument.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GenericArg {
+    Type(TypeRef),
+    // or lifetime...
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -14,6 +38,8 @@ pub enum PathKind {
     Self_,
     Super,
     Crate,
+    /// `::foo`; the first segment is an extern crate
+    Abs,
 }
 
 impl Path {
@@ -30,8 +56,21 @@ impl Path {
         let mut segments = Vec::new();
         loop {
             let segment = path.segment()?;
+            if segment.has_colon_colon() {
+                kind = PathKind::Abs;
+            }
             match segment.kind()? {
-                ast::PathSegmentKind::Name(name) => segments.push(name.as_name()),
+                ast::PathSegmentKind::Name(name) => {
+                    let args = segment
+                        .type_arg_list()
+                        .and_then(GenericArgs::from_ast)
+                        .map(Arc::new);
+                    let segment = PathSegment {
+                        name: name.as_name(),
+                        args_and_bindings: args,
+                    };
+                    segments.push(segment);
+                }
                 ast::PathSegmentKind::CrateKw => {
                     kind = PathKind::Crate;
                     break;
@@ -80,7 +119,35 @@ impl Path {
         if self.kind != PathKind::Plain || self.segments.len() > 1 {
             return None;
         }
-        self.segments.first()
+        self.segments.first().map(|s| &s.name)
+    }
+}
+
+impl GenericArgs {
+    fn from_ast(node: ast::TypeArgList) -> Option<GenericArgs> {
+        let mut args = Vec::new();
+        for type_arg in node.type_args() {
+            let type_ref = TypeRef::from_ast_opt(type_arg.type_ref());
+            args.push(GenericArg::Type(type_ref));
+        }
+        // lifetimes and assoc type args ignored for now
+        if !args.is_empty() {
+            Some(GenericArgs { args })
+        } else {
+            None
+        }
+    }
+}
+
+impl From<Name> for Path {
+    fn from(name: Name) -> Path {
+        Path {
+            kind: PathKind::Plain,
+            segments: vec![PathSegment {
+                name,
+                args_and_bindings: None,
+            }],
+        }
     }
 }
 
@@ -136,16 +203,23 @@ fn convert_path(prefix: Option<Path>, path: ast::Path) -> Option<Path> {
     let prefix = if let Some(qual) = path.qualifier() {
         Some(convert_path(prefix, qual)?)
     } else {
-        None
+        prefix
     };
     let segment = path.segment()?;
     let res = match segment.kind()? {
         ast::PathSegmentKind::Name(name) => {
             let mut res = prefix.unwrap_or_else(|| Path {
-                kind: PathKind::Plain,
+                kind: if segment.has_colon_colon() {
+                    PathKind::Abs
+                } else {
+                    PathKind::Plain
+                },
                 segments: Vec::with_capacity(1),
             });
-            res.segments.push(name.as_name());
+            res.segments.push(PathSegment {
+                name: name.as_name(),
+                args_and_bindings: None, // no type args in use
+            });
             res
         }
         ast::PathSegmentKind::CrateKw => {
@@ -6,28 +6,28 @@ use std::{
 use rustc_hash::FxHashMap;
 use ra_syntax::{
     AstNode, SyntaxNode,
+    SyntaxKind::{PATH_EXPR, PATH_TYPE, STRUCT_LIT},
     ast::{self, NameOwner, ModuleItemOwner}
 };
-use ra_db::{SourceRootId, Cancelable,};
+use ra_db::{CrateId, Cancelable, CfgOptions, LocalSyntaxPtr};
 
 use crate::{
-    SourceFileItems, SourceItemId, DefKind, Function, DefId, Name, AsName, HirFileId,
-    MacroCallLoc,
+    SourceFileItems, SourceItemId, DefKind, DefId, Name, AsName, HirFileId, MacroCallId, Path,
     db::HirDatabase,
     function::FnScopes,
     module::{
-        ModuleSource, ModuleSourceNode, ModuleId,
+        Module, ModuleSource, ModuleSourceNode, ModuleId,
         imp::Submodule,
         nameres::{InputModuleItems, ItemMap, Resolver},
     },
-    adt::{StructData, EnumData},
+    adt::{StructData, EnumData, EnumVariantData},
+    cfg,
 };
 
-pub(super) fn fn_scopes(db: &impl HirDatabase, def_id: DefId) -> Arc<FnScopes> {
-    let function = Function::new(def_id);
-    let syntax = function.syntax(db);
-    let res = FnScopes::new(syntax.borrowed());
-    Arc::new(res)
+pub(super) fn fn_scopes(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<FnScopes>> {
+    let body = db.body_hir(def_id)?;
+    let res = FnScopes::new(body);
+    Ok(Arc::new(res))
 }
 
 pub(super) fn struct_data(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<StructData>> {
@@ -36,7 +36,12 @@ pub(super) fn struct_data(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Ar
     let syntax = db.file_item(def_loc.source_item_id);
     let struct_def =
         ast::StructDef::cast(syntax.borrowed()).expect("struct def should point to StructDef node");
-    Ok(Arc::new(StructData::new(struct_def.borrowed())))
+    let crate_graph = db.crate_graph();
+    let cfg_options = crate_graph.cfg_options(def_loc.krate);
+    Ok(Arc::new(StructData::new(
+        struct_def.borrowed(),
+        cfg_options,
+    )))
 }
 
 pub(super) fn enum_data(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<EnumData>> {
@@ -45,7 +50,23 @@ pub(super) fn enum_data(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<
     let syntax = db.file_item(def_loc.source_item_id);
     let enum_def =
         ast::EnumDef::cast(syntax.borrowed()).expect("enum def should point to EnumDef node");
-    Ok(Arc::new(EnumData::new(enum_def.borrowed())))
+    Ok(Arc::new(EnumData::new(db, &def_loc, enum_def.borrowed())))
+}
+
+pub(super) fn enum_variant_data(
+    db: &impl HirDatabase,
+    def_id: DefId,
+) -> Cancelable<Arc<EnumVariantData>> {
+    let def_loc = def_id.loc(db);
+    assert!(def_loc.kind == DefKind::EnumVariant);
+    let syntax = db.file_item(def_loc.source_item_id);
+    let variant_def = ast::EnumVariant::cast(syntax.borrowed())
+        .expect("enum variant def should point to EnumVariant node");
+    Ok(Arc::new(EnumVariantData::new(
+        db,
+        &def_loc,
+        variant_def.borrowed(),
+    )))
 }
 
 pub(super) fn file_items(db: &impl HirDatabase, file_id: HirFileId) -> Arc<SourceFileItems> {
@@ -64,26 +85,33 @@ pub(super) fn file_item(db: &impl HirDatabase, source_item_id: SourceItemId) ->
 
 pub(crate) fn submodules(
     db: &impl HirDatabase,
+    krate: CrateId,
     source: ModuleSource,
 ) -> Cancelable<Arc<Vec<Submodule>>> {
     db.check_canceled()?;
     let file_id = source.file_id();
+    let crate_graph = db.crate_graph();
+    let cfg_options = crate_graph.cfg_options(krate);
     let submodules = match source.resolve(db) {
-        ModuleSourceNode::SourceFile(it) => collect_submodules(db, file_id, it.borrowed()),
+        ModuleSourceNode::SourceFile(it) => {
+            collect_submodules(db, cfg_options, file_id, it.borrowed())
+        }
         ModuleSourceNode::Module(it) => it
             .borrowed()
             .item_list()
-            .map(|it| collect_submodules(db, file_id, it))
+            .map(|it| collect_submodules(db, cfg_options, file_id, it))
             .unwrap_or_else(Vec::new),
     };
     return Ok(Arc::new(submodules));
 
     fn collect_submodules<'a>(
         db: &impl HirDatabase,
+        cfg_options: &CfgOptions,
         file_id: HirFileId,
         root: impl ast::ModuleItemOwner<'a>,
     ) -> Vec<Submodule> {
         modules(root)
+            .filter(|(_, m)| cfg::is_cfg_enabled(m.syntax(), cfg_options))
             .map(|(name, m)| {
                 if m.has_semi() {
                     Submodule::Declaration(name)
@@ -112,70 +140,124 @@ pub(crate) fn modules<'a>(
 
 pub(super) fn input_module_items(
     db: &impl HirDatabase,
-    source_root_id: SourceRootId,
+    krate: CrateId,
     module_id: ModuleId,
 ) -> Cancelable<Arc<InputModuleItems>> {
-    let module_tree = db.module_tree(source_root_id)?;
+    let module_tree = db.module_tree(krate)?;
     let source = module_id.source(&module_tree);
     let file_id = source.file_id();
-    let file_items = db.file_items(file_id);
-    let fill = |acc: &mut InputModuleItems, items: &mut Iterator<Item = ast::ItemOrMacro>| {
-        for item in items {
-            match item {
-                ast::ItemOrMacro::Item(it) => {
-                    acc.add_item(file_id, &file_items, it);
-                }
-                ast::ItemOrMacro::Macro(macro_call) => {
-                    let item_id = file_items.id_of_unchecked(macro_call.syntax());
-                    let loc = MacroCallLoc {
-                        source_root_id,
-                        module_id,
-                        source_item_id: SourceItemId {
-                            file_id,
-                            item_id: Some(item_id),
-                        },
-                    };
-                    let id = loc.id(db);
-                    let file_id = HirFileId::from(id);
-                    let file_items = db.file_items(file_id);
-                    //FIXME: expand recursively
-                    for item in db.hir_source_file(file_id).borrowed().items() {
-                        acc.add_item(file_id, &file_items, item);
-                    }
-                }
-            }
-        }
-    };
-
     let mut res = InputModuleItems::default();
     match source.resolve(db) {
-        ModuleSourceNode::SourceFile(it) => fill(&mut res, &mut it.borrowed().items_with_macros()),
+        ModuleSourceNode::SourceFile(it) => {
+            if let Some(decl) = module_id.declaration(&module_tree, db) {
+                res.add_attrs(db, krate, decl.borrowed().syntax());
+            }
+            res.add_attrs(db, krate, it.borrowed().syntax());
+            res.add_items(
+                db,
+                krate,
+                module_id,
+                file_id,
+                it.borrowed().items_with_macros(),
+            )?
+        }
         ModuleSourceNode::Module(it) => {
+            res.add_attrs(db, krate, it.borrowed().syntax());
             if let Some(item_list) = it.borrowed().item_list() {
-                fill(&mut res, &mut item_list.items_with_macros())
+                res.add_attrs(db, krate, item_list.syntax());
+                res.add_items(db, krate, module_id, file_id, item_list.items_with_macros())?
             }
         }
     };
     Ok(Arc::new(res))
 }
 
-pub(super) fn item_map(
+pub(super) fn macro_expansion_items(
     db: &impl HirDatabase,
-    source_root: SourceRootId,
-) -> Cancelable<Arc<ItemMap>> {
+    macro_call_id: MacroCallId,
+) -> Cancelable<Arc<InputModuleItems>> {
+    let loc = macro_call_id.loc(db);
+    let file_id = HirFileId::from(macro_call_id);
+    let source_file = db.hir_source_file(file_id);
+    let mut res = InputModuleItems::default();
+    res.add_items(
+        db,
+        loc.krate,
+        loc.module_id,
+        file_id,
+        source_file.borrowed().items_with_macros(),
+    )?;
+    Ok(Arc::new(res))
+}
+
+pub(super) fn item_map(db: &impl HirDatabase, krate: CrateId) -> Cancelable<Arc<ItemMap>> {
     let start = Instant::now();
-    let module_tree = db.module_tree(source_root)?;
+    let module_tree = db.module_tree(krate)?;
     let input = module_tree
         .modules()
         .map(|id| {
-            let items = db.input_module_items(source_root, id)?;
+            let items = db.input_module_items(krate, id)?;
             Ok((id, items))
         })
         .collect::<Cancelable<FxHashMap<_, _>>>()?;
 
-    let resolver = Resolver::new(db, &input, source_root, module_tree);
+    let resolver = Resolver::new(db, &input, krate, module_tree);
     let res = resolver.resolve()?;
     let elapsed = start.elapsed();
     log::info!("item_map: {:?}", elapsed);
     Ok(Arc::new(res))
 }
+
+/// The segments of the paths of expressions and types in the module which
+/// name items not visible there, like `Private` in `m::Private`.
+pub(super) fn private_path_segments(
+    db: &impl HirDatabase,
+    krate: CrateId,
+    module_id: ModuleId,
+) -> Cancelable<Arc<Vec<(LocalSyntaxPtr, Name)>>> {
+    let module = Module::new(db, krate, module_id)?;
+    let source_node = module.source().resolve(db);
+    let (syntax, inline_module) = match &source_node {
+        ModuleSourceNode::SourceFile(it) => (it.borrowed().syntax(), None),
+        ModuleSourceNode::Module(it) => (it.borrowed().syntax(), Some(it.borrowed().syntax())),
+    };
+    let mut res = Vec::new();
+    for path in syntax.descendants().filter_map(ast::Path::cast) {
+        // the qualifiers are resolved together with the whole path
+        match path.syntax().parent().map(|it| it.kind()) {
+            Some(PATH_EXPR) | Some(PATH_TYPE) | Some(STRUCT_LIT) => (),
+            _ => continue,
+        }
+        // the paths of inline submodules belong to those
+        let owner = path
+            .syntax()
+            .ancestors()
+            .filter_map(ast::Module::cast)
+            .find(|it| !it.has_semi())
+            .map(|it| it.syntax());
+        if owner != inline_module {
+            continue;
+        }
+        let hir_path = match Path::from_ast(path) {
+            Some(it) => it,
+            None => continue,
+        };
+        let idx = match module.resolve_path_with_private_segment(db, &hir_path)?.1 {
+            Some(it) => it,
+            None => continue,
+        };
+        // the named segments of the path, like those of `hir::Path`
+        let mut name_refs = Vec::new();
+        let mut curr = Some(path);
+        while let Some(path) = curr {
+            name_refs.extend(path.segment().and_then(|it| it.name_ref()));
+            curr = path.qualifier();
+        }
+        name_refs.reverse();
+        if let Some(name_ref) = name_refs.get(idx) {
+            let name = hir_path.segments[idx].name.clone();
+            res.push((LocalSyntaxPtr::new(name_ref.syntax()), name));
+        }
+    }
+    Ok(Arc::new(res))
+}
@@ -8,13 +8,15 @@
 use ra_db::{FileId, FilePosition, Cancelable};
 use ra_editor::find_node_at_offset;
 use ra_syntax::{
-    SmolStr, TextRange, SyntaxNodeRef,
+    SmolStr, TextRange, TextUnit, SyntaxNodeRef,
     ast::{self, AstNode, NameOwner},
+    algo::visit::{visitor, Visitor},
 };
 
 use crate::{
-    HirDatabase, Module, Function, SourceItemId,
+    HirDatabase, Module, Function, SourceItemId, HirFileId, MacroCallId, MacroCallLoc,
     module::ModuleSource,
+    macros::{MACRO_EXPANSION_DEPTH_LIMIT, resolve_macro_rules},
     DefKind, DefLoc, AsName,
 };
 
@@ -79,12 +81,37 @@ fn module_from_source(
     module_source: ModuleSource,
 ) -> Cancelable<Option<Module>> {
     let source_root_id = db.file_source_root(module_source.file_id().as_original_file());
-    let module_tree = db.module_tree(source_root_id)?;
-    let m = module_tree
-        .modules_with_sources()
-        .find(|(_id, src)| src == &module_source);
-    let module_id = ctry!(m).0;
-    Ok(Some(Module::new(db, source_root_id, module_id)?))
+    // A file can belong to several crates, like a module which is shared via
+    // `#[path]` or a crate root which is a module of another crate as well.
+    // The crate the module is the closest to the root of wins. Detached
+    // crates, which are only there for the files no crate reaches, come last,
+    // and their roots are no real crate roots: there, the crate the module is
+    // the farthest from the root of wins.
+    let crate_graph = db.crate_graph();
+    let mut res: Option<((bool, isize), Module)> = None;
+    for &krate in db.source_root_crates(source_root_id).iter() {
+        let module_tree = db.module_tree(krate)?;
+        let m = module_tree
+            .modules_with_sources()
+            .find(|(_id, src)| src == &module_source);
+        if let Some((module_id, _)) = m {
+            let module = Module::new(db, krate, module_id)?;
+            let depth = module.path_to_root().len() as isize;
+            let rank = if crate_graph.is_detached(krate) {
+                (true, -depth)
+            } else {
+                (false, depth)
+            };
+            let is_better = match &res {
+                Some((it, _)) => rank < *it,
+                None => true,
+            };
+            if is_better {
+                res = Some((rank, module));
+            }
+        }
+    }
+    Ok(res.map(|(_, module)| module))
 }
 
 pub fn function_from_source(
@@ -111,7 +138,7 @@ pub fn function_from_module(
     };
     let def_loc = DefLoc {
         kind: DefKind::Function,
-        source_root_id: module.source_root_id,
+        krate: module.krate,
         module_id: module.module_id,
         source_item_id,
     };
@@ -127,6 +154,108 @@ pub fn function_from_child_node(
     function_from_source(db, file_id, fn_def)
 }
 
+/// Locates the macro call by its syntax. For macro-expansion files, the call
+/// must come from `db.hir_source_file(file_id)`.
+pub fn macro_call_from_node(
+    db: &impl HirDatabase,
+    file_id: HirFileId,
+    macro_call: ast::MacroCall,
+) -> Cancelable<Option<MacroCallId>> {
+    let (krate, module_id) = match file_id.as_macro_call_id() {
+        // calls produced by a macro belong to the module of the outer call
+        Some(outer) => {
+            let loc = outer.loc(db);
+            (loc.krate, loc.module_id)
+        }
+        None => {
+            let module =
+                module_from_child_node(db, file_id.as_original_file(), macro_call.syntax())?;
+            let module = ctry!(module);
+            (module.krate, module.module_id)
+        }
+    };
+    let file_items = db.file_items(file_id);
+    let source_item_id = SourceItemId {
+        file_id,
+        item_id: Some(file_items.id_of(file_id, macro_call.syntax())),
+    };
+    let def = resolve_macro_rules(db, krate, module_id, source_item_id, macro_call)?;
+    let loc = MacroCallLoc {
+        krate,
+        module_id,
+        source_item_id,
+        def,
+    };
+    Ok(Some(loc.id(db)))
+}
+
+/// Returns all macro calls of the file, in any position, together with the
+/// calls in their expansions, outer calls first.
+pub fn macro_calls(db: &impl HirDatabase, file_id: FileId) -> Cancelable<Vec<MacroCallId>> {
+    let mut res = Vec::new();
+    let mut files = vec![(HirFileId::from(file_id), 0)];
+    while let Some((file_id, depth)) = files.pop() {
+        let source_file = db.hir_source_file(file_id);
+        let macro_calls = source_file
+            .syntax()
+            .descendants()
+            .filter_map(ast::MacroCall::cast)
+            .filter(|it| it.macro_rules_name().is_none());
+        for macro_call in macro_calls {
+            if let Some(macro_call_id) = macro_call_from_node(db, file_id, macro_call)? {
+                res.push(macro_call_id);
+                if depth < MACRO_EXPANSION_DEPTH_LIMIT {
+                    files.push((macro_call_id.into(), depth + 1));
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Finds the innermost macro expansion which contains the code at `position`,
+/// looking through nested macro calls. Returns the expansion file and the
+/// offset in it, or `None` if the position is not inside a macro call.
+pub fn descend_into_macros(
+    db: &impl HirDatabase,
+    position: FilePosition,
+) -> Cancelable<Option<(HirFileId, TextUnit)>> {
+    let mut file_id = HirFileId::from(position.file_id);
+    let mut offset = position.offset;
+    for _ in 0..MACRO_EXPANSION_DEPTH_LIMIT {
+        let source_file = db.hir_source_file(file_id);
+        let macro_call = match find_node_at_offset::<ast::MacroCall>(source_file.syntax(), offset) {
+            Some(it) => it,
+            None => break,
+        };
+        let position_range = TextRange::offset_len(offset, 0.into());
+        let token_tree = match macro_call.token_tree() {
+            Some(it) if position_range.is_subrange(&it.syntax().range()) => it,
+            _ => break,
+        };
+        let macro_call_id = match macro_call_from_node(db, file_id, macro_call)? {
+            Some(it) => it,
+            None => break,
+        };
+        let expansion = match db.expand_macro_invocation(macro_call_id) {
+            Some(it) => it,
+            None => break,
+        };
+        let src_range = position_range - token_tree.syntax().range().start();
+        match expansion.map_range_forward(src_range) {
+            Some(dst_range) => {
+                file_id = macro_call_id.into();
+                offset = dst_range.start();
+            }
+            None => break,
+        }
+    }
+    if file_id.as_macro_call_id().is_none() {
+        return Ok(None);
+    }
+    Ok(Some((file_id, offset)))
+}
+
 pub fn macro_symbols(
     db: &impl HirDatabase,
     file_id: FileId,
@@ -135,27 +264,30 @@ pub fn macro_symbols(
         Some(it) => it,
         None => return Ok(Vec::new()),
     };
-    let items = db.input_module_items(module.source_root_id, module.module_id)?;
+    let items = db.input_module_items(module.krate, module.module_id)?;
     let mut res = Vec::new();
 
-    for macro_call_id in items
-        .items
-        .iter()
-        .filter_map(|it| it.id.file_id.as_macro_call_id())
-    {
-        if let Some(exp) = db.expand_macro_invocation(macro_call_id) {
-            let loc = macro_call_id.loc(db);
-            let syntax = db.file_item(loc.source_item_id);
-            let syntax = syntax.borrowed();
-            let macro_call = ast::MacroCall::cast(syntax).unwrap();
-            let off = macro_call.token_tree().unwrap().syntax().range().start();
-            let file = exp.file();
-            for trait_def in file.syntax().descendants().filter_map(ast::TraitDef::cast) {
-                if let Some(name) = trait_def.name() {
-                    let dst_range = name.syntax().range();
-                    if let Some(src_range) = exp.map_range_back(dst_range) {
-                        res.push((name.text(), src_range + off))
-                    }
+    // only the names written in this file can be mapped back, but nested
+    // calls can pass them on
+    let mut macro_call_ids: Vec<_> = items.macro_calls.iter().map(|&it| (it, 0)).collect();
+    let mut idx = 0;
+    while idx < macro_call_ids.len() {
+        let (macro_call_id, depth) = macro_call_ids[idx];
+        if depth < MACRO_EXPANSION_DEPTH_LIMIT {
+            let nested = db.macro_expansion_items(macro_call_id)?;
+            macro_call_ids.extend(nested.macro_calls.iter().map(|&it| (it, depth + 1)));
+        }
+        idx += 1;
+    }
+
+    for (macro_call_id, _) in macro_call_ids {
+        let macro_file = HirFileId::from(macro_call_id);
+        let source_file = db.hir_source_file(macro_file);
+        for name in source_file.syntax().descendants().filter_map(item_name) {
+            let dst_range = name.syntax().range();
+            if let Some(src_range) = macro_file.original_file_range(db, dst_range) {
+                if src_range.file_id == file_id {
+                    res.push((name.text(), src_range.range))
                 }
             }
         }
@@ -163,3 +295,19 @@ pub fn macro_symbols(
 
     Ok(res)
 }
+
+fn item_name(node: SyntaxNodeRef) -> Option<ast::Name> {
+    fn name<'a, N: NameOwner<'a>>(node: N) -> Option<ast::Name<'a>> {
+        node.name()
+    }
+    visitor()
+        .visit(name::<ast::FnDef>)
+        .visit(name::<ast::StructDef>)
+        .visit(name::<ast::EnumDef>)
+        .visit(name::<ast::TraitDef>)
+        .visit(name::<ast::Module>)
+        .visit(name::<ast::TypeDef>)
+        .visit(name::<ast::ConstDef>)
+        .visit(name::<ast::StaticDef>)
+        .accept(node)?
+}
@@ -0,0 +1,137 @@
+//! HIR for trait definitions. Impls of traits are collected together with the
+//! inherent impls, see `ty::method_resolution::CrateImplBlocks`; whether a
+//! type implements a trait is answered by the `implements` query.
+
+use std::sync::Arc;
+
+use ra_db::Cancelable;
+use ra_syntax::ast::{self, AstNode, NameOwner};
+
+use crate::{
+    DefId, DefLoc, DefKind, SourceItemId, Name, AsName, Function, Const, TypeAlias,
+    db::HirDatabase,
+    generics::GenericParams,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Trait {
+    def_id: DefId,
+}
+
+impl Trait {
+    pub(crate) fn new(def_id: DefId) -> Self {
+        Trait { def_id }
+    }
+
+    pub fn def_id(&self) -> DefId {
+        self.def_id
+    }
+
+    pub fn name(&self, db: &impl HirDatabase) -> Cancelable<Option<Name>> {
+        Ok(db.trait_data(self.def_id)?.name.clone())
+    }
+
+    pub fn trait_data(&self, db: &impl HirDatabase) -> Cancelable<Arc<TraitData>> {
+        db.trait_data(self.def_id)
+    }
+
+    /// The generic parameters of the trait. The first one is always the
+    /// implicit `Self` parameter.
+    pub fn generic_params(&self, db: &impl HirDatabase) -> Cancelable<Arc<GenericParams>> {
+        db.generic_params(self.def_id)
+    }
+
+    /// Finds the method with the given name, including provided methods.
+    pub fn method(&self, db: &impl HirDatabase, name: &Name) -> Cancelable<Option<Function>> {
+        let trait_data = db.trait_data(self.def_id)?;
+        let method = trait_data
+            .methods()
+            .find(|(method_name, _)| *method_name == name)
+            .map(|(_, f)| f.clone());
+        Ok(method)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraitData {
+    name: Option<Name>,
+    items: Vec<(Name, TraitItem)>,
+}
+
+impl TraitData {
+    fn new(db: &impl HirDatabase, def_loc: &DefLoc, node: ast::TraitDef) -> TraitData {
+        let name = node.name().map(|n| n.as_name());
+        let file_items = db.file_items(def_loc.source_item_id.file_id);
+        let items = node
+            .item_list()
+            .into_iter()
+            .flat_map(|it| it.impl_items())
+            .map(|item_node| {
+                let (kind, name) = match item_node {
+                    ast::ImplItem::FnDef(it) => (DefKind::Function, it.name()),
+                    ast::ImplItem::ConstDef(it) => (DefKind::Const, it.name()),
+                    ast::ImplItem::TypeDef(it) => (DefKind::TypeAlias, it.name()),
+                };
+                let name = name.map(|n| n.as_name()).unwrap_or_else(Name::missing);
+                let item_id = file_items.id_of_unchecked(item_node.syntax());
+                let item_loc = DefLoc {
+                    kind,
+                    source_item_id: SourceItemId {
+                        file_id: def_loc.source_item_id.file_id,
+                        item_id: Some(item_id),
+                    },
+                    ..*def_loc
+                };
+                let def_id = item_loc.id(db);
+                let item = match item_node {
+                    ast::ImplItem::FnDef(..) => TraitItem::Method(Function::new(def_id)),
+                    ast::ImplItem::ConstDef(..) => TraitItem::Const(Const::new(def_id)),
+                    ast::ImplItem::TypeDef(..) => TraitItem::Type(TypeAlias::new(def_id)),
+                };
+                (name, item)
+            })
+            .collect();
+        TraitData { name, items }
+    }
+
+    pub fn name(&self) -> Option<&Name> {
+        self.name.as_ref()
+    }
+
+    pub fn items(&self) -> &[(Name, TraitItem)] {
+        &self.items
+    }
+
+    pub fn methods(&self) -> impl Iterator<Item = (&Name, &Function)> {
+        self.items.iter().filter_map(|(name, item)| match item {
+            TraitItem::Method(f) => Some((name, f)),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraitItem {
+    Method(Function),
+    Const(Const),
+    Type(TypeAlias),
+}
+
+impl TraitItem {
+    pub fn def_id(&self) -> DefId {
+        match self {
+            TraitItem::Method(f) => f.def_id(),
+            TraitItem::Const(c) => c.def_id(),
+            TraitItem::Type(t) => t.def_id(),
+        }
+    }
+}
+
+pub(crate) fn trait_data(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<TraitData>> {
+    let def_loc = def_id.loc(db);
+    assert!(def_loc.kind == DefKind::Trait);
+    let syntax = db.file_item(def_loc.source_item_id);
+    let trait_def =
+        ast::TraitDef::cast(syntax.borrowed()).expect("trait def should point to TraitDef node");
+    Ok(Arc::new(TraitData::new(db, &def_loc, trait_def)))
+}
@@ -13,29 +13,46 @@
 //! the union-find implementation from the `ena` crate, which is extracted from
 //! rustc.
 
-mod primitive;
+mod autoderef;
+pub(crate) mod primitive;
+mod op;
+pub(crate) mod method_resolution;
+pub(crate) mod traits;
 #[cfg(test)]
 mod tests;
 
 use std::sync::Arc;
+use std::borrow::Cow;
+use std::ops::Index;
 use std::{fmt, mem};
 
 use log;
 use rustc_hash::FxHashMap;
 use ena::unify::{InPlaceUnificationTable, UnifyKey, UnifyValue, NoError};
 
-use ra_db::{LocalSyntaxPtr, Cancelable};
+use ra_db::Cancelable;
 use ra_syntax::{
-    ast::{self, AstNode, LoopBodyOwner, ArgListOwner, PrefixOp},
-    SyntaxNodeRef
+    SyntaxNodeRef,
+    ast::{self, AstNode},
 };
 
 use crate::{
-    Def, DefId, FnScopes, Module, Function, Struct, Enum, Path, Name, AsName, ImplBlock,
+    Def, DefId, FnScopes, Module, Function, Struct, Enum, EnumVariant, Const, Static, TypeAlias,
+    Path, PathSegment, Name, ImplBlock, FnSignature,
     db::HirDatabase,
+    adt::VariantData,
     type_ref::{TypeRef, Mutability},
     name::KnownName,
+    generics::GenericParams,
+    path::GenericArg,
+    expr::{
+        Body, BodySyntaxMapping, Expr, Literal, ExprId, PatId, Pat, UnaryOp, Statement,
+        BindingAnnotation, Array,
+    },
 };
+use self::primitive::{UncertainIntTy, UncertainFloatTy};
+
+pub use self::traits::TraitRef;
 
 /// The ID of a type variable.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
@@ -94,13 +111,34 @@ impl UnifyValue for TypeVarValue {
     }
 }
 
-/// The kinds of placeholders we need during type inference. Currently, we only
-/// have type variables; in the future, we will probably also need int and float
-/// variables, for inference of literal values (e.g. `100` could be one of
+/// The kinds of placeholders we need during type inference. There's separate
+/// values for general types, and for integer and float variables. The latter
+/// two are used for inference of literal values (e.g. `100` could be one of
 /// several integer types).
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum InferTy {
     TypeVar(TypeVarId),
+    IntVar(TypeVarId),
+    FloatVar(TypeVarId),
+}
+
+impl InferTy {
+    fn to_inner(&self) -> TypeVarId {
+        match self {
+            InferTy::TypeVar(ty) | InferTy::IntVar(ty) | InferTy::FloatVar(ty) => *ty,
+        }
+    }
+
+    /// The type a variable of this kind defaults to if nothing else is known
+    /// about it: `i32` for integers, `f64` for floats.
+    fn fallback_value(&self) -> Ty {
+        match self {
+            InferTy::TypeVar(..) => Ty::Unknown,
+            InferTy::IntVar(..) => Ty::Int(primitive::IntTy::I32),
+            InferTy::FloatVar(..) => Ty::Float(primitive::FloatTy::F64),
+        }
+    }
 }
 
 /// When inferring an expression, we propagate downward whatever type hint we
@@ -153,14 +191,17 @@ pub enum Ty {
         def_id: DefId,
         /// The name, for displaying.
         name: Name,
-        // later we'll need generic substitutions here
+        /// Substitutions for the generic parameters of the type.
+        substs: Substs,
     },
 
     /// The pointee of a string slice. Written as `str`.
     Str,
 
-    // An array with the given length. Written as `[T; n]`.
-    // Array(Ty, ty::Const),
+    /// An array with the given length. Written as `[T; n]`. (We don't track
+    /// the length yet.)
+    Array(Arc<Ty>),
+
     /// The pointee of an array slice.  Written as `[T]`.
     Slice(Arc<Ty>),
 
@@ -171,6 +212,20 @@ pub enum Ty {
     /// `&'a mut T` or `&'a T`.
     Ref(Arc<Ty>, Mutability),
 
+    /// The definition of a function. This is the type of e.g. `foo` in
+    /// `fn foo() {}` -- it is distinct from `Ty::FnPtr`, and just coerces to
+    /// it. Tuple variant constructors like `Some` have such types as well.
+    FnDef {
+        /// The DefId of the function or enum variant.
+        def_id: DefId,
+        /// The name, for displaying.
+        name: Name,
+        /// The signature, in terms of the function's generic parameters.
+        sig: Arc<FnSig>,
+        /// Substitutions for the generic parameters of the function.
+        substs: Substs,
+    },
+
     /// A pointer to a function.  Written as `fn() -> i32`.
     ///
     /// For example the type of `bar` here:
@@ -181,15 +236,19 @@ pub enum Ty {
     /// ```
     FnPtr(Arc<FnSig>),
 
-    // rustc has a separate type for each function, which just coerces to the
-    // above function pointer type. Once we implement generics, we will probably
-    // need this as well.
+    /// The anonymous type of a closure. Used to represent the type of
+    /// `|a| a`.
+    Closure {
+        /// The DefId of the function containing the closure.
+        def_id: DefId,
+        /// The closure expression in the body of that function.
+        expr: ExprId,
+        /// The parameter and return types of the closure.
+        sig: Arc<FnSig>,
+    },
 
     // A trait, defined with `dyn trait`.
     // Dynamic(),
-    // The anonymous type of a closure. Used to represent the type of
-    // `|a| a`.
-    // Closure(DefId, ClosureSubsts<'tcx>),
 
     // The anonymous type of a generator. Used to represent the type of
     // `|a| yield a`.
@@ -210,9 +269,15 @@ pub enum Ty {
 
     // Opaque (`impl Trait`) type found in a return type.
     // Opaque(DefId, Substs),
+    /// A type parameter; for example, `T` in `fn f<T>(x: T) {}`.
+    Param {
+        /// The index of the parameter (starting with parameters from the
+        /// surrounding impl, then the current function).
+        idx: u32,
+        /// The name of the parameter, for displaying.
+        name: Name,
+    },
 
-    // A type parameter; for example, `T` in `fn f<T>(x: T) {}
-    // Param(ParamTy),
     /// A type variable used during type checking. Not to be confused with a
     /// type parameter.
     Infer(InferTy),
@@ -225,6 +290,25 @@ pub enum Ty {
     Unknown,
 }
 
+/// A list of substitutions for generic parameters.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Substs(Arc<[Ty]>);
+
+impl Substs {
+    pub fn empty() -> Substs {
+        Substs(Arc::new([]))
+    }
+
+    fn walk_mut(&mut self, f: &mut impl FnMut(&mut Ty)) {
+        // Without an Arc::make_mut_slice, we can't avoid the clone here:
+        let mut v: Vec<_> = self.0.iter().cloned().collect();
+        for t in &mut v {
+            t.walk_mut(f);
+        }
+        self.0 = v.into();
+    }
+}
+
 /// A function signature.
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct FnSig {
@@ -232,11 +316,25 @@ pub struct FnSig {
     output: Ty,
 }
 
+impl FnSig {
+    fn subst(&self, substs: &Substs) -> FnSig {
+        FnSig {
+            input: self
+                .input
+                .iter()
+                .map(|ty| ty.clone().subst(substs))
+                .collect(),
+            output: self.output.clone().subst(substs),
+        }
+    }
+}
+
 impl Ty {
     pub(crate) fn from_hir(
         db: &impl HirDatabase,
         module: &Module,
         impl_block: Option<&ImplBlock>,
+        generics: &GenericParams,
         type_ref: &TypeRef,
     ) -> Cancelable<Self> {
         Ok(match type_ref {
@@ -244,29 +342,32 @@ impl Ty {
             TypeRef::Tuple(inner) => {
                 let inner_tys = inner
                     .iter()
-                    .map(|tr| Ty::from_hir(db, module, impl_block, tr))
+                    .map(|tr| Ty::from_hir(db, module, impl_block, generics, tr))
                     .collect::<Cancelable<Vec<_>>>()?;
                 Ty::Tuple(inner_tys.into())
             }
-            TypeRef::Path(path) => Ty::from_hir_path(db, module, impl_block, path)?,
+            TypeRef::Path(path) => Ty::from_hir_path(db, module, impl_block, generics, path)?,
             TypeRef::RawPtr(inner, mutability) => {
-                let inner_ty = Ty::from_hir(db, module, impl_block, inner)?;
+                let inner_ty = Ty::from_hir(db, module, impl_block, generics, inner)?;
                 Ty::RawPtr(Arc::new(inner_ty), *mutability)
             }
-            TypeRef::Array(_inner) => Ty::Unknown, // TODO
+            TypeRef::Array(inner) => {
+                let inner_ty = Ty::from_hir(db, module, impl_block, generics, inner)?;
+                Ty::Array(Arc::new(inner_ty))
+            }
             TypeRef::Slice(inner) => {
-                let inner_ty = Ty::from_hir(db, module, impl_block, inner)?;
+                let inner_ty = Ty::from_hir(db, module, impl_block, generics, inner)?;
                 Ty::Slice(Arc::new(inner_ty))
             }
             TypeRef::Reference(inner, mutability) => {
-                let inner_ty = Ty::from_hir(db, module, impl_block, inner)?;
+                let inner_ty = Ty::from_hir(db, module, impl_block, generics, inner)?;
                 Ty::Ref(Arc::new(inner_ty), *mutability)
             }
             TypeRef::Placeholder => Ty::Unknown,
             TypeRef::Fn(params) => {
                 let mut inner_tys = params
                     .iter()
-                    .map(|tr| Ty::from_hir(db, module, impl_block, tr))
+                    .map(|tr| Ty::from_hir(db, module, impl_block, generics, tr))
                     .collect::<Cancelable<Vec<_>>>()?;
                 let return_ty = inner_tys
                     .pop()
@@ -285,10 +386,11 @@ impl Ty {
         db: &impl HirDatabase,
         module: &Module,
         impl_block: Option<&ImplBlock>,
+        generics: &GenericParams,
         type_ref: Option<&TypeRef>,
     ) -> Cancelable<Self> {
         type_ref
-            .map(|t| Ty::from_hir(db, module, impl_block, t))
+            .map(|t| Ty::from_hir(db, module, impl_block, generics, t))
             .unwrap_or(Ok(Ty::Unknown))
     }
 
@@ -296,6 +398,7 @@ impl Ty {
         db: &impl HirDatabase,
         module: &Module,
         impl_block: Option<&ImplBlock>,
+        generics: &GenericParams,
         path: &Path,
     ) -> Cancelable<Self> {
         if let Some(name) = path.as_ident() {
@@ -305,8 +408,29 @@ impl Ty {
                 return Ok(Ty::Uint(uint_ty));
             } else if let Some(float_ty) = primitive::FloatTy::from_name(name) {
                 return Ok(Ty::Float(float_ty));
-            } else if name.as_known_name() == Some(KnownName::Self_) {
-                return Ty::from_hir_opt(db, module, None, impl_block.map(|i| i.target_type()));
+            } else if name.as_known_name() == Some(KnownName::Bool) {
+                return Ok(Ty::Bool);
+            } else if name.as_known_name() == Some(KnownName::Char) {
+                return Ok(Ty::Char);
+            } else if name.as_known_name() == Some(KnownName::Str) {
+                return Ok(Ty::Str);
+            } else if name.as_known_name() == Some(KnownName::Self_) && impl_block.is_some() {
+                // the impl's generic parameters are also the first parameters
+                // of its items, so we can just use them here (in traits, `Self`
+                // is a generic parameter, handled below)
+                let impl_generics = impl_block.map(|i| i.generic_params()).unwrap_or_default();
+                return Ty::from_hir_opt(
+                    db,
+                    module,
+                    None,
+                    &impl_generics,
+                    impl_block.map(|i| i.target_type()),
+                );
+            } else if let Some(generic_param) = generics.find_by_name(name) {
+                return Ok(Ty::Param {
+                    idx: generic_param.idx,
+                    name: generic_param.name.clone(),
+                });
             }
         }
 
@@ -317,27 +441,49 @@ impl Ty {
             return Ok(Ty::Unknown);
         };
         let ty = db.type_for_def(resolved)?;
-        Ok(ty)
-    }
-
-    // TODO: These should not be necessary long-term, since everything will work on HIR
-    pub(crate) fn from_ast_opt(
-        db: &impl HirDatabase,
-        module: &Module,
-        impl_block: Option<&ImplBlock>,
-        node: Option<ast::TypeRef>,
-    ) -> Cancelable<Self> {
-        node.map(|n| Ty::from_ast(db, module, impl_block, n))
-            .unwrap_or(Ok(Ty::Unknown))
+        let substs = Ty::substs_from_path(db, module, impl_block, generics, path, resolved)?;
+        match resolved.resolve(db)? {
+            // the type of an alias is the aliased type, in terms of the
+            // alias' parameters
+            Def::Type(_) => Ok(ty.subst(&substs)),
+            _ => Ok(ty.apply_substs(substs)),
+        }
     }
 
-    pub(crate) fn from_ast(
+    /// Collect generic arguments from a path into a `Substs`. See also
+    /// `create_substs_for_ast_path` and `def_to_ty` in rustc.
+    fn substs_from_path(
         db: &impl HirDatabase,
         module: &Module,
         impl_block: Option<&ImplBlock>,
-        node: ast::TypeRef,
-    ) -> Cancelable<Self> {
-        Ty::from_hir(db, module, impl_block, &TypeRef::from_ast(node))
+        outer_generics: &GenericParams,
+        path: &Path,
+        resolved: DefId,
+    ) -> Cancelable<Substs> {
+        let mut substs = Vec::new();
+        let def_generics = db.generic_params(resolved)?;
+        let parent_param_count = def_generics.count_parent_params();
+        substs.extend((0..parent_param_count).map(|_| Ty::Unknown));
+        let last = path
+            .segments
+            .last()
+            .expect("path should have at least one segment");
+        if let Some(generic_args) = &last.args_and_bindings {
+            for arg in generic_args.args.iter().take(def_generics.params.len()) {
+                match arg {
+                    GenericArg::Type(type_ref) => {
+                        let ty = Ty::from_hir(db, module, impl_block, outer_generics, type_ref)?;
+                        substs.push(ty);
+                    }
+                }
+            }
+        }
+        // add placeholders for args that were not provided
+        // TODO: handle defaults
+        for _ in substs.len()..def_generics.count_params_including_parent() {
+            substs.push(Ty::Unknown);
+        }
+        Ok(Substs(substs.into()))
     }
 
     pub fn unit() -> Self {
@@ -347,7 +493,7 @@ impl Ty {
     fn walk_mut(&mut self, f: &mut impl FnMut(&mut Ty)) {
         f(self);
         match self {
-            Ty::Slice(t) => Arc::make_mut(t).walk_mut(f),
+            Ty::Slice(t) | Ty::Array(t) => Arc::make_mut(t).walk_mut(f),
             Ty::RawPtr(t, _) => Arc::make_mut(t).walk_mut(f),
             Ty::Ref(t, _) => Arc::make_mut(t).walk_mut(f),
             Ty::Tuple(ts) => {
@@ -358,14 +504,17 @@ impl Ty {
                 }
                 *ts = v.into();
             }
-            Ty::FnPtr(sig) => {
+            Ty::FnPtr(sig) | Ty::Closure { sig, .. } => {
                 let sig_mut = Arc::make_mut(sig);
                 for input in &mut sig_mut.input {
                     input.walk_mut(f);
                 }
                 sig_mut.output.walk_mut(f);
             }
-            Ty::Adt { .. } => {} // need to walk type parameters later
+            // the signature is in terms of the parameters, so we only need to
+            // walk the substitutions
+            Ty::FnDef { substs, .. } => substs.walk_mut(f),
+            Ty::Adt { substs, .. } => substs.walk_mut(f),
             _ => {}
         }
     }
@@ -377,6 +526,65 @@ impl Ty {
         });
         self
     }
+
+    /// If this is a type with type parameters (an ADT or function), replaces
+    /// the `Substs` for these type parameters with the given ones. (So e.g. if
+    /// `self` is `Option<_>` and the substs contain `u32`, we'll have
+    /// `Option<u32>` afterwards.)
+    pub fn apply_substs(self, substs: Substs) -> Ty {
+        match self {
+            Ty::Adt { def_id, name, .. } => Ty::Adt {
+                def_id,
+                name,
+                substs,
+            },
+            Ty::FnDef {
+                def_id, name, sig, ..
+            } => Ty::FnDef {
+                def_id,
+                name,
+                sig,
+                substs,
+            },
+            _ => self,
+        }
+    }
+
+    /// Replaces type parameters in this type using the given `Substs`. (So e.g.
+    /// if `self` is `&[T]`, where type parameter T has index 0, and the
+    /// `Substs` contain `u32` at index 0, we'll have `&[u32]` afterwards.)
+    pub fn subst(self, substs: &Substs) -> Ty {
+        self.fold(&mut |ty| match ty {
+            Ty::Param { idx, name } => {
+                if (idx as usize) < substs.0.len() {
+                    substs.0[idx as usize].clone()
+                } else {
+                    // type parameter that is not substituted (e.g. because
+                    // we only have the substs of the parent); leave it as is
+                    Ty::Param { idx, name }
+                }
+            }
+            ty => ty,
+        })
+    }
+
+    /// Returns the type parameters of this type if it has some (i.e. is an ADT
+    /// or function); so if `self` is `Option<u32>`, this returns the `u32`.
+    fn substs(&self) -> Option<Substs> {
+        match self {
+            Ty::Adt { substs, .. } | Ty::FnDef { substs, .. } => Some(substs.clone()),
+            _ => None,
+        }
+    }
+
+    /// Replaces inference variables by `Ty::Unknown`, e.g. to use the type as
+    /// a query key.
+    pub(crate) fn without_inference_vars(self) -> Ty {
+        self.fold(&mut |ty| match ty {
+            Ty::Infer(..) => Ty::Unknown,
+            ty => ty,
+        })
+    }
 }
 
 impl fmt::Display for Ty {
@@ -389,6 +597,7 @@ impl fmt::Display for Ty {
             Ty::Float(t) => write!(f, "{}", t.ty_to_string()),
             Ty::Str => write!(f, "str"),
             Ty::Slice(t) => write!(f, "[{}]", t),
+            Ty::Array(t) => write!(f, "[{};_]", t),
             Ty::RawPtr(t, m) => write!(f, "*{}{}", m.as_keyword_for_ptr(), t),
             Ty::Ref(t, m) => write!(f, "&{}{}", m.as_keyword_for_ref(), t),
             Ty::Never => write!(f, "!"),
@@ -406,7 +615,38 @@ impl fmt::Display for Ty {
                 }
                 write!(f, ") -> {}", sig.output)
             }
-            Ty::Adt { name, .. } => write!(f, "{}", name),
+            Ty::FnDef {
+                name, substs, sig, ..
+            } => {
+                let sig = sig.subst(substs);
+                write!(f, "fn {}(", name)?;
+                for t in &sig.input {
+                    write!(f, "{},", t)?;
+                }
+                write!(f, ") -> {}", sig.output)
+            }
+            Ty::Closure { sig, .. } => {
+                write!(f, "|")?;
+                for t in &sig.input {
+                    write!(f, "{},", t)?;
+                }
+                write!(f, "| -> {}", sig.output)
+            }
+            Ty::Adt { name, substs, .. } => {
+                write!(f, "{}", name)?;
+                if !substs.0.is_empty() {
+                    write!(f, "<")?;
+                    for (i, t) in substs.0.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}", t)?;
+                    }
+                    write!(f, ">")?;
+                }
+                Ok(())
+            }
+            Ty::Param { name, .. } => write!(f, "{}", name),
             Ty::Unknown => write!(f, "[unknown]"),
             Ty::Infer(..) => write!(f, "_"),
         }
@@ -415,45 +655,128 @@ impl fmt::Display for Ty {
 
 // Functions returning declared types for items
 
-/// Compute the declared type of a function. This should not need to look at the
-/// function body (but currently uses the function AST, so does anyway - TODO).
+/// Compute the declared type of a function. This does not need to look at the
+/// function body.
 fn type_for_fn(db: &impl HirDatabase, f: Function) -> Cancelable<Ty> {
-    let syntax = f.syntax(db);
+    let signature = f.signature(db);
     let module = f.module(db)?;
     let impl_block = f.impl_block(db)?;
-    let node = syntax.borrowed();
-    // TODO we ignore type parameters for now
-    let input = node
-        .param_list()
-        .map(|pl| {
-            pl.params()
-                .map(|p| Ty::from_ast_opt(db, &module, impl_block.as_ref(), p.type_ref()))
-                .collect()
-        })
-        .unwrap_or_else(|| Ok(Vec::new()))?;
-    let output = if let Some(type_ref) = node.ret_type().and_then(|rt| rt.type_ref()) {
-        Ty::from_ast(db, &module, impl_block.as_ref(), type_ref)?
-    } else {
-        Ty::unit()
-    };
+    let generics = db.generic_params(f.def_id())?;
+    let input = signature
+        .args()
+        .iter()
+        .map(|tr| Ty::from_hir(db, &module, impl_block.as_ref(), &generics, tr))
+        .collect::<Cancelable<Vec<_>>>()?;
+    let output = Ty::from_hir(
+        db,
+        &module,
+        impl_block.as_ref(),
+        &generics,
+        signature.ret_type(),
+    )?;
     let sig = FnSig { input, output };
-    Ok(Ty::FnPtr(Arc::new(sig)))
+    Ok(Ty::FnDef {
+        def_id: f.def_id(),
+        name: signature.name().clone(),
+        sig: Arc::new(sig),
+        substs: make_substs(&generics),
+    })
+}
+
+/// The substitutions that map each generic parameter to itself; so the
+/// resulting types refer to the parameters (e.g. `Option<T>` inside `Option`).
+fn make_substs(generics: &GenericParams) -> Substs {
+    Substs(
+        generics
+            .params_including_parent()
+            .into_iter()
+            .map(|p| Ty::Param {
+                idx: p.idx,
+                name: p.name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .into(),
+    )
 }
 
 fn type_for_struct(db: &impl HirDatabase, s: Struct) -> Cancelable<Ty> {
+    let generics = db.generic_params(s.def_id())?;
     Ok(Ty::Adt {
         def_id: s.def_id(),
         name: s.name(db)?.unwrap_or_else(Name::missing),
+        substs: make_substs(&generics),
     })
 }
 
 pub fn type_for_enum(db: &impl HirDatabase, s: Enum) -> Cancelable<Ty> {
+    let generics = db.generic_params(s.def_id())?;
     Ok(Ty::Adt {
         def_id: s.def_id(),
         name: s.name(db)?.unwrap_or_else(Name::missing),
+        substs: make_substs(&generics),
+    })
+}
+
+/// Unit and struct variants have the type of their enum, while tuple variants
+/// are constructor functions returning it, like `fn Some(T) -> Option<T>`.
+fn type_for_enum_variant(db: &impl HirDatabase, ev: EnumVariant) -> Cancelable<Ty> {
+    let enum_ty = type_for_enum(db, ev.parent_enum(db)?)?;
+    let fields = match &*ev.variant_data(db)? {
+        VariantData::Tuple(fields) => fields.clone(),
+        VariantData::Struct(_) | VariantData::Unit => return Ok(enum_ty),
+    };
+    let module = ev.def_id().module(db)?;
+    let generics = db.generic_params(ev.def_id())?;
+    let input = fields
+        .iter()
+        .map(|field| Ty::from_hir(db, &module, None, &generics, field.type_ref()))
+        .collect::<Cancelable<Vec<_>>>()?;
+    let sig = FnSig {
+        input,
+        output: enum_ty,
+    };
+    Ok(Ty::FnDef {
+        def_id: ev.def_id(),
+        name: ev.name(db)?.unwrap_or_else(Name::missing),
+        sig: Arc::new(sig),
+        substs: make_substs(&generics),
     })
 }
 
+fn type_for_const(db: &impl HirDatabase, c: Const) -> Cancelable<Ty> {
+    let signature = c.signature(db);
+    let module = c.module(db)?;
+    let impl_block = c.impl_block(db)?;
+    let generics = db.generic_params(c.def_id())?;
+    Ty::from_hir(
+        db,
+        &module,
+        impl_block.as_ref(),
+        &generics,
+        signature.type_ref(),
+    )
+}
+
+fn type_for_static(db: &impl HirDatabase, s: Static) -> Cancelable<Ty> {
+    let signature = s.signature(db);
+    let module = s.module(db)?;
+    let generics = db.generic_params(s.def_id())?;
+    Ty::from_hir(db, &module, None, &generics, signature.type_ref())
+}
+
+/// The aliased type, which is what the alias expands to. Cyclic aliases
+/// would expand forever, so they are left unknown.
+fn type_for_type_alias(db: &impl HirDatabase, t: TypeAlias) -> Cancelable<Ty> {
+    if t.is_cyclic(db)? {
+        return Ok(Ty::Unknown);
+    }
+    let type_ref = t.type_ref(db);
+    let module = t.module(db)?;
+    let impl_block = t.def_id().impl_block(db)?;
+    let generics = db.generic_params(t.def_id())?;
+    Ty::from_hir(db, &module, impl_block.as_ref(), &generics, &type_ref)
+}
+
 pub(super) fn type_for_def(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Ty> {
     let def = def_id.resolve(db)?;
     match def {
@@ -464,6 +787,14 @@ pub(super) fn type_for_def(db: &impl HirDatabase, def_id: DefId) -> Cancelable<T
         Def::Function(f) => type_for_fn(db, f),
         Def::Struct(s) => type_for_struct(db, s),
         Def::Enum(e) => type_for_enum(db, e),
+        Def::EnumVariant(ev) => type_for_enum_variant(db, ev),
+        Def::Const(c) => type_for_const(db, c),
+        Def::Static(s) => type_for_static(db, s),
+        Def::Type(t) => type_for_type_alias(db, t),
+        Def::Trait(..) => {
+            log::debug!("trying to get type for trait {:?}", def_id);
+            Ok(Ty::Unknown)
+        }
         Def::Item => {
             log::debug!("trying to get type for item of unknown type {:?}", def_id);
             Ok(Ty::Unknown)
@@ -471,6 +802,8 @@ pub(super) fn type_for_def(db: &impl HirDatabase, def_id: DefId) -> Cancelable<T
     }
 }
 
+/// The declared type of a field of a struct or enum variant, in terms of the
+/// generic parameters of the struct or enum.
 pub(super) fn type_for_field(db: &impl HirDatabase, def_id: DefId, field: Name) -> Cancelable<Ty> {
     let def = def_id.resolve(db)?;
     let variant_data = match def {
@@ -478,96 +811,194 @@ pub(super) fn type_for_field(db: &impl HirDatabase, def_id: DefId, field: Name)
             let variant_data = s.variant_data(db)?;
             variant_data
         }
+        Def::EnumVariant(ev) => ev.variant_data(db)?,
         // TODO: unions
-        // TODO: enum variants
-        _ => panic!(
-            "trying to get type for field in non-struct/variant {:?}",
-            def_id
-        ),
+        _ => {
+            log::debug!(
+                "trying to get type for field in non-struct/variant {:?}",
+                def_id
+            );
+            return Ok(Ty::Unknown);
+        }
     };
     let module = def_id.module(db)?;
     let impl_block = def_id.impl_block(db)?;
+    let generics = db.generic_params(def_id)?;
     let type_ref = if let Some(tr) = variant_data.get_field_type_ref(&field) {
         tr
     } else {
         return Ok(Ty::Unknown);
     };
-    Ty::from_hir(db, &module, impl_block.as_ref(), &type_ref)
+    Ty::from_hir(db, &module, impl_block.as_ref(), &generics, &type_ref)
 }
 
 /// The result of type inference: A mapping from expressions and patterns to types.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct InferenceResult {
-    type_of: FxHashMap<LocalSyntaxPtr, Ty>,
+    type_of_expr: FxHashMap<ExprId, Ty>,
+    type_of_pat: FxHashMap<PatId, Ty>,
+    method_resolutions: FxHashMap<ExprId, DefId>,
+    field_resolutions: FxHashMap<ExprId, DefId>,
+    assoc_resolutions: FxHashMap<ExprId, DefId>,
 }
 
 impl InferenceResult {
-    /// Returns the type of the given syntax node, if it was inferred. Will
-    /// return `None` for syntax nodes not in the inferred function or not
-    /// pointing to an expression/pattern, `Some(Ty::Unknown)` for
-    /// expressions/patterns that could not be inferred.
-    pub fn type_of_node(&self, node: SyntaxNodeRef) -> Option<Ty> {
-        self.type_of.get(&LocalSyntaxPtr::new(node)).cloned()
+    /// The method called by a method call expression.
+    pub fn method_resolution(&self, expr: ExprId) -> Option<DefId> {
+        self.method_resolutions.get(&expr).cloned()
+    }
+
+    /// The struct or enum variant whose field is accessed by a field
+    /// expression. For tuples, there is none.
+    pub fn field_resolution(&self, expr: ExprId) -> Option<DefId> {
+        self.field_resolutions.get(&expr).cloned()
+    }
+
+    /// The associated function a path expression like `Foo::new` refers to.
+    pub fn assoc_resolution(&self, expr: ExprId) -> Option<DefId> {
+        self.assoc_resolutions.get(&expr).cloned()
+    }
+
+    /// The type of an expression or a pattern of the body, given by its
+    /// syntax node.
+    pub fn type_of_node(
+        &self,
+        syntax_mapping: &BodySyntaxMapping,
+        node: SyntaxNodeRef,
+    ) -> Option<&Ty> {
+        if let Some(expr) = ast::Expr::cast(node).and_then(|it| syntax_mapping.node_expr(it)) {
+            Some(&self[expr])
+        } else if let Some(pat) = ast::Pat::cast(node).and_then(|it| syntax_mapping.node_pat(it)) {
+            Some(&self[pat])
+        } else {
+            None
+        }
     }
 }
 
+impl Index<ExprId> for InferenceResult {
+    type Output = Ty;
+
+    fn index(&self, expr: ExprId) -> &Ty {
+        self.type_of_expr.get(&expr).unwrap_or(&Ty::Unknown)
+    }
+}
+
+impl Index<PatId> for InferenceResult {
+    type Output = Ty;
+
+    fn index(&self, pat: PatId) -> &Ty {
+        self.type_of_pat.get(&pat).unwrap_or(&Ty::Unknown)
+    }
+}
+
+/// A `loop`, `while` or `for` that a `break` can refer to.
+#[derive(Clone, Debug)]
+struct BreakableContext {
+    /// Whether there is a `break` for this loop; otherwise, a `loop` never
+    /// finishes.
+    may_break: bool,
+    /// The type of the values given to `break`.
+    break_ty: Ty,
+}
+
 /// The inference context contains all information needed during type inference.
 #[derive(Clone, Debug)]
 struct InferenceContext<'a, D: HirDatabase> {
     db: &'a D,
+    /// The function whose body is being inferred.
+    def_id: DefId,
+    body: Arc<Body>,
     scopes: Arc<FnScopes>,
-    /// The self param for the current method, if it exists.
-    self_param: Option<LocalSyntaxPtr>,
     module: Module,
     impl_block: Option<ImplBlock>,
+    /// The generic parameters of the function being inferred (including those
+    /// of the surrounding impl).
+    generics: Arc<GenericParams>,
     var_unification_table: InPlaceUnificationTable<TypeVarId>,
-    type_of: FxHashMap<LocalSyntaxPtr, Ty>,
+    type_of_expr: FxHashMap<ExprId, Ty>,
+    type_of_pat: FxHashMap<PatId, Ty>,
+    method_resolutions: FxHashMap<ExprId, DefId>,
+    field_resolutions: FxHashMap<ExprId, DefId>,
+    assoc_resolutions: FxHashMap<ExprId, DefId>,
     /// The return type of the function being inferred.
     return_ty: Ty,
+    /// The loops we are currently in, innermost last.
+    breakables: Vec<BreakableContext>,
 }
 
 impl<'a, D: HirDatabase> InferenceContext<'a, D> {
     fn new(
         db: &'a D,
+        def_id: DefId,
+        body: Arc<Body>,
         scopes: Arc<FnScopes>,
         module: Module,
         impl_block: Option<ImplBlock>,
+        generics: Arc<GenericParams>,
     ) -> Self {
         InferenceContext {
-            type_of: FxHashMap::default(),
+            type_of_expr: FxHashMap::default(),
+            type_of_pat: FxHashMap::default(),
+            method_resolutions: FxHashMap::default(),
+            field_resolutions: FxHashMap::default(),
+            assoc_resolutions: FxHashMap::default(),
             var_unification_table: InPlaceUnificationTable::new(),
-            self_param: None,       // set during parameter typing
             return_ty: Ty::Unknown, // set in collect_fn_signature
+            breakables: Vec::new(),
             db,
+            def_id,
+            body,
             scopes,
             module,
             impl_block,
+            generics,
         }
     }
 
     fn resolve_all(mut self) -> InferenceResult {
-        let mut types = mem::replace(&mut self.type_of, FxHashMap::default());
-        for ty in types.values_mut() {
+        let mut expr_types = mem::replace(&mut self.type_of_expr, FxHashMap::default());
+        for ty in expr_types.values_mut() {
+            let resolved = self.resolve_ty_completely(mem::replace(ty, Ty::Unknown));
+            *ty = resolved;
+        }
+        let mut pat_types = mem::replace(&mut self.type_of_pat, FxHashMap::default());
+        for ty in pat_types.values_mut() {
             let resolved = self.resolve_ty_completely(mem::replace(ty, Ty::Unknown));
             *ty = resolved;
         }
-        InferenceResult { type_of: types }
+        InferenceResult {
+            type_of_expr: expr_types,
+            type_of_pat: pat_types,
+            method_resolutions: self.method_resolutions,
+            field_resolutions: self.field_resolutions,
+            assoc_resolutions: self.assoc_resolutions,
+        }
     }
 
-    fn write_ty(&mut self, node: SyntaxNodeRef, ty: Ty) {
-        self.type_of.insert(LocalSyntaxPtr::new(node), ty);
+    fn write_expr_ty(&mut self, expr: ExprId, ty: Ty) {
+        self.type_of_expr.insert(expr, ty);
     }
 
-    fn make_ty(&self, type_ref: &TypeRef) -> Cancelable<Ty> {
-        Ty::from_hir(self.db, &self.module, self.impl_block.as_ref(), type_ref)
+    fn write_pat_ty(&mut self, pat: PatId, ty: Ty) {
+        self.type_of_pat.insert(pat, ty);
     }
 
-    fn make_ty_opt(&self, type_ref: Option<&TypeRef>) -> Cancelable<Ty> {
-        Ty::from_hir_opt(self.db, &self.module, self.impl_block.as_ref(), type_ref)
+    fn make_ty(&self, type_ref: &TypeRef) -> Cancelable<Ty> {
+        Ty::from_hir(
+            self.db,
+            &self.module,
+            self.impl_block.as_ref(),
+            &self.generics,
+            type_ref,
+        )
     }
 
     fn unify(&mut self, ty1: &Ty, ty2: &Ty) -> bool {
-        match (ty1, ty2) {
+        // try to resolve type vars first
+        let ty1 = self.resolve_ty_shallow(ty1);
+        let ty2 = self.resolve_ty_shallow(ty2);
+        match (&*ty1, &*ty2) {
             (Ty::Unknown, ..) => true,
             (.., Ty::Unknown) => true,
             (Ty::Bool, _)
@@ -579,25 +1010,65 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             | (Ty::Float(..), Ty::Float(..)) => ty1 == ty2,
             (
                 Ty::Adt {
-                    def_id: def_id1, ..
+                    def_id: def_id1,
+                    substs: substs1,
+                    ..
                 },
                 Ty::Adt {
-                    def_id: def_id2, ..
+                    def_id: def_id2,
+                    substs: substs2,
+                    ..
+                },
+            )
+            | (
+                Ty::FnDef {
+                    def_id: def_id1,
+                    substs: substs1,
+                    ..
                 },
-            ) if def_id1 == def_id2 => true,
-            (Ty::Slice(t1), Ty::Slice(t2)) => self.unify(t1, t2),
+                Ty::FnDef {
+                    def_id: def_id2,
+                    substs: substs2,
+                    ..
+                },
+            ) if def_id1 == def_id2 => self.unify_substs(substs1, substs2),
+            (Ty::Param { idx: idx1, .. }, Ty::Param { idx: idx2, .. }) => idx1 == idx2,
+            (Ty::Slice(t1), Ty::Slice(t2)) | (Ty::Array(t1), Ty::Array(t2)) => self.unify(t1, t2),
             (Ty::RawPtr(t1, m1), Ty::RawPtr(t2, m2)) if m1 == m2 => self.unify(t1, t2),
             (Ty::Ref(t1, m1), Ty::Ref(t2, m2)) if m1 == m2 => self.unify(t1, t2),
             (Ty::FnPtr(sig1), Ty::FnPtr(sig2)) if sig1 == sig2 => true,
+            (
+                Ty::Closure {
+                    def_id: def_id1,
+                    expr: expr1,
+                    sig: sig1,
+                },
+                Ty::Closure {
+                    def_id: def_id2,
+                    expr: expr2,
+                    sig: sig2,
+                },
+            ) if def_id1 == def_id2 && expr1 == expr2 => self.unify_sigs(sig1, sig2),
             (Ty::Tuple(ts1), Ty::Tuple(ts2)) if ts1.len() == ts2.len() => ts1
                 .iter()
                 .zip(ts2.iter())
                 .all(|(t1, t2)| self.unify(t1, t2)),
-            (Ty::Infer(InferTy::TypeVar(tv1)), Ty::Infer(InferTy::TypeVar(tv2))) => {
+            (Ty::Infer(InferTy::TypeVar(tv1)), Ty::Infer(InferTy::TypeVar(tv2)))
+            | (Ty::Infer(InferTy::IntVar(tv1)), Ty::Infer(InferTy::IntVar(tv2)))
+            | (Ty::Infer(InferTy::FloatVar(tv1)), Ty::Infer(InferTy::FloatVar(tv2))) => {
+                // both type vars are unknown since we tried to resolve them
                 self.var_unification_table.union(*tv1, *tv2);
                 true
             }
-            (Ty::Infer(InferTy::TypeVar(tv)), other) | (other, Ty::Infer(InferTy::TypeVar(tv))) => {
+            (Ty::Infer(InferTy::TypeVar(tv)), other)
+            | (other, Ty::Infer(InferTy::TypeVar(tv)))
+            | (Ty::Infer(InferTy::IntVar(tv)), other @ Ty::Int(_))
+            | (other @ Ty::Int(_), Ty::Infer(InferTy::IntVar(tv)))
+            | (Ty::Infer(InferTy::IntVar(tv)), other @ Ty::Uint(_))
+            | (other @ Ty::Uint(_), Ty::Infer(InferTy::IntVar(tv)))
+            | (Ty::Infer(InferTy::FloatVar(tv)), other @ Ty::Float(_))
+            | (other @ Ty::Float(_), Ty::Infer(InferTy::FloatVar(tv))) => {
+                // the type var is unknown since we tried to resolve it
                 self.var_unification_table
                     .union_value(*tv, TypeVarValue::Known(other.clone()));
                 true
@@ -606,12 +1077,43 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         }
     }
 
+    fn unify_substs(&mut self, substs1: &Substs, substs2: &Substs) -> bool {
+        substs1.0.len() == substs2.0.len()
+            && substs1
+                .0
+                .iter()
+                .zip(substs2.0.iter())
+                .all(|(t1, t2)| self.unify(t1, t2))
+    }
+
+    fn unify_sigs(&mut self, sig1: &FnSig, sig2: &FnSig) -> bool {
+        sig1.input.len() == sig2.input.len()
+            && sig1
+                .input
+                .iter()
+                .zip(sig2.input.iter())
+                .all(|(t1, t2)| self.unify(t1, t2))
+            && self.unify(&sig1.output, &sig2.output)
+    }
+
     fn new_type_var(&mut self) -> Ty {
         Ty::Infer(InferTy::TypeVar(
             self.var_unification_table.new_key(TypeVarValue::Unknown),
         ))
     }
 
+    fn new_integer_var(&mut self) -> Ty {
+        Ty::Infer(InferTy::IntVar(
+            self.var_unification_table.new_key(TypeVarValue::Unknown),
+        ))
+    }
+
+    fn new_float_var(&mut self) -> Ty {
+        Ty::Infer(InferTy::FloatVar(
+            self.var_unification_table.new_key(TypeVarValue::Unknown),
+        ))
+    }
+
     /// Replaces Ty::Unknown by a new type var, so we can maybe still infer it.
     fn insert_type_vars_shallow(&mut self, ty: Ty) -> Ty {
         match ty {
@@ -630,67 +1132,150 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
     /// known type.
     fn resolve_ty_as_possible(&mut self, ty: Ty) -> Ty {
         ty.fold(&mut |ty| match ty {
-            Ty::Infer(InferTy::TypeVar(tv)) => {
-                if let Some(known_ty) = self.var_unification_table.probe_value(tv).known() {
+            Ty::Infer(tv) => {
+                let inner = tv.to_inner();
+                if let Some(known_ty) = self.var_unification_table.probe_value(inner).known() {
                     // known_ty may contain other variables that are known by now
                     self.resolve_ty_as_possible(known_ty.clone())
                 } else {
-                    Ty::Infer(InferTy::TypeVar(tv))
+                    Ty::Infer(tv)
                 }
             }
             _ => ty,
         })
     }
 
+    /// If `ty` is a type variable with known type, returns that type;
+    /// otherwise, return ty.
+    fn resolve_ty_shallow<'b>(&mut self, ty: &'b Ty) -> Cow<'b, Ty> {
+        match ty {
+            Ty::Infer(tv) => {
+                match self
+                    .var_unification_table
+                    .probe_value(tv.to_inner())
+                    .known()
+                {
+                    // known_ty may itself be a type variable that is known by now
+                    Some(known_ty) => {
+                        Cow::Owned(self.resolve_ty_shallow(&known_ty.clone()).into_owned())
+                    }
+                    None => Cow::Borrowed(ty),
+                }
+            }
+            _ => Cow::Borrowed(ty),
+        }
+    }
+
     /// Resolves the type completely; type variables without known type are
-    /// replaced by Ty::Unknown.
+    /// replaced by Ty::Unknown, integer and float variables by their default
+    /// types.
     fn resolve_ty_completely(&mut self, ty: Ty) -> Ty {
         ty.fold(&mut |ty| match ty {
-            Ty::Infer(InferTy::TypeVar(tv)) => {
-                if let Some(known_ty) = self.var_unification_table.probe_value(tv).known() {
+            Ty::Infer(tv) => {
+                let inner = tv.to_inner();
+                if let Some(known_ty) = self.var_unification_table.probe_value(inner).known() {
                     // known_ty may contain other variables that are known by now
                     self.resolve_ty_completely(known_ty.clone())
                 } else {
-                    Ty::Unknown
+                    tv.fallback_value()
                 }
             }
             _ => ty,
         })
     }
 
-    fn infer_path_expr(&mut self, expr: ast::PathExpr) -> Cancelable<Option<Ty>> {
-        let ast_path = ctry!(expr.path());
-        let path = ctry!(Path::from_ast(ast_path));
-        if path.is_ident() {
+    fn infer_path_expr(&mut self, expr: ExprId, path: &Path) -> Cancelable<Option<Ty>> {
+        if path.is_ident() || path.is_self() {
             // resolve locally
-            let name = ctry!(ast_path.segment().and_then(|s| s.name_ref()));
-            if let Some(scope_entry) = self.scopes.resolve_local_name(name) {
-                let ty = ctry!(self.type_of.get(&scope_entry.ptr()));
+            let name = path.as_ident().cloned().unwrap_or_else(Name::self_param);
+            if let Some(scope_entry) = self.scopes.resolve_local_name(expr, name) {
+                let ty = ctry!(self.type_of_pat.get(&scope_entry.pat()));
                 let ty = self.resolve_ty_as_possible(ty.clone());
                 return Ok(Some(ty));
             };
-        } else if path.is_self() {
-            // resolve `self` param
-            let self_param = ctry!(self.self_param);
-            let ty = ctry!(self.type_of.get(&self_param));
-            let ty = self.resolve_ty_as_possible(ty.clone());
-            return Ok(Some(ty));
         };
 
         // resolve in module
-        let resolved = ctry!(self.module.resolve_path(self.db, &path)?.take_values());
+        let resolved = match self.module.resolve_path(self.db, path)?.take_values() {
+            Some(it) => it,
+            None => return self.infer_assoc_path_expr(expr, path),
+        };
         let ty = self.db.type_for_def(resolved)?;
+        let substs = Ty::substs_from_path(
+            self.db,
+            &self.module,
+            self.impl_block.as_ref(),
+            &self.generics,
+            path,
+            resolved,
+        )?;
+        let ty = ty.apply_substs(substs);
         let ty = self.insert_type_vars(ty);
         Ok(Some(ty))
     }
 
-    fn resolve_variant(&self, path: Option<ast::Path>) -> Cancelable<(Ty, Option<DefId>)> {
-        let path = if let Some(path) = path.and_then(Path::from_ast) {
+    /// Finds one of the structs of `core::ops`, like `Range`, which are the
+    /// types of range expressions. Crates which only see `std` get them
+    /// through its re-export of `ops`.
+    fn resolve_ops_struct(&self, name: &str) -> Cancelable<Option<Struct>> {
+        let krate = match self.module.dependency_root(self.db, &Name::core())? {
+            Some(it) => Some(it),
+            None => self.module.dependency_root(self.db, &Name::std())?,
+        };
+        let krate = ctry!(krate);
+        let segments = ["ops", name]
+            .iter()
+            .map(|&it| PathSegment {
+                name: Name::new(it.into()),
+                args_and_bindings: None,
+            })
+            .collect::<Vec<_>>();
+        let def_id = ctry!(self
+            .module
+            .resolve_segments(self.db, krate, &segments)?
+            .take_types());
+        match def_id.resolve(self.db)? {
+            Def::Struct(s) => Ok(Some(s)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Paths like `Foo::new` refer to associated functions: the qualifier is
+    /// a type, in whose impls the function is looked up.
+    fn infer_assoc_path_expr(&mut self, expr: ExprId, path: &Path) -> Cancelable<Option<Ty>> {
+        let (last, qualifier) = ctry!(path.segments.split_last());
+        if qualifier.is_empty() {
+            return Ok(None);
+        }
+        let qualifier = Path {
+            kind: path.kind,
+            segments: qualifier.to_vec(),
+        };
+        let qualifier_ty = Ty::from_hir_path(
+            self.db,
+            &self.module,
+            self.impl_block.as_ref(),
+            &self.generics,
+            &qualifier,
+        )?;
+        let krate = ctry!(self.module.krate(self.db));
+        let def_id = ctry!(qualifier_ty.lookup_assoc_fn(self.db, &krate, &last.name)?);
+        self.assoc_resolutions.insert(expr, def_id);
+        // like for methods, all the generic parameters are inferred
+        let ty = self.db.type_for_def(def_id)?;
+        let substs = ty.substs().unwrap_or_else(Substs::empty);
+        let unknown = vec![Ty::Unknown; substs.0.len()];
+        let ty = ty.apply_substs(Substs(unknown.into()));
+        Ok(Some(self.insert_type_vars(ty)))
+    }
+
+    fn resolve_variant(&mut self, path: Option<&Path>) -> Cancelable<(Ty, Option<DefId>)> {
+        let path = if let Some(path) = path {
             path
         } else {
             return Ok((Ty::Unknown, None));
         };
-        let def_id = if let Some(def_id) = self.module.resolve_path(self.db, &path)?.take_types() {
+        let def_id = if let Some(def_id) = self.module.resolve_path(self.db, path)?.take_types() {
             def_id
         } else {
             return Ok((Ty::Unknown, None));
@@ -698,337 +1283,732 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         Ok(match def_id.resolve(self.db)? {
             Def::Struct(s) => {
                 let ty = type_for_struct(self.db, s)?;
+                let substs = Ty::substs_from_path(
+                    self.db,
+                    &self.module,
+                    self.impl_block.as_ref(),
+                    &self.generics,
+                    path,
+                    def_id,
+                )?;
+                let ty = self.insert_type_vars(ty.apply_substs(substs));
+                (ty, Some(def_id))
+            }
+            Def::EnumVariant(ev) => {
+                let ty = type_for_enum(self.db, ev.parent_enum(self.db)?)?;
+                let substs = Ty::substs_from_path(
+                    self.db,
+                    &self.module,
+                    self.impl_block.as_ref(),
+                    &self.generics,
+                    path,
+                    def_id,
+                )?;
+                let ty = self.insert_type_vars(ty.apply_substs(substs));
                 (ty, Some(def_id))
             }
             _ => (Ty::Unknown, None),
         })
     }
 
-    fn infer_expr_opt(
+    /// For a trait method, the types of the trait's parameters (including
+    /// `Self`) according to the impl that applies to the receiver type.
+    fn trait_substs_for_method(
+        &self,
+        method: DefId,
+        receiver_ty: &Ty,
+    ) -> Cancelable<Option<Substs>> {
+        let tr = ctry!(method.containing_trait(self.db));
+        let receiver_ty = receiver_ty.clone().without_inference_vars();
+        let implementation = ctry!(self.db.implements(receiver_ty, tr.def_id())?);
+        Ok(Some(implementation.trait_ref.substs))
+    }
+
+    /// The signature of a value of the given type when it is called: this
+    /// works for fn items and pointers, closures, and type parameters with an
+    /// `Fn*` bound.
+    fn callable_sig(&mut self, ty: &Ty) -> Cancelable<Option<FnSig>> {
+        let sig = match self.resolve_ty_as_possible(ty.clone()) {
+            Ty::FnPtr(sig) | Ty::Closure { sig, .. } => FnSig::clone(&sig),
+            Ty::FnDef { sig, substs, .. } => sig.subst(&substs),
+            Ty::Param { idx, .. } => {
+                let generics = Arc::clone(&self.generics);
+                let bound = ctry!(generics.find_by_idx(idx).and_then(|p| p.fn_bound.as_ref()));
+                let bound_ty = self.make_ty(bound)?;
+                match self.insert_type_vars(bound_ty) {
+                    Ty::FnPtr(sig) => FnSig::clone(&sig),
+                    _ => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(sig))
+    }
+
+    /// For each parameter of the called function, if its type is a type
+    /// parameter with an `Fn*` bound, the bound's signature as a fn pointer
+    /// type. A closure passed for that parameter is inferred against it, so
+    /// that the closure's parameters get their types.
+    fn expected_closure_tys(&mut self, callee_ty: &Ty) -> Cancelable<Vec<Option<Ty>>> {
+        let (def_id, sig, substs) = match callee_ty {
+            Ty::FnDef {
+                def_id,
+                sig,
+                substs,
+                ..
+            } => (*def_id, sig, substs),
+            _ => return Ok(Vec::new()),
+        };
+        let module = def_id.module(self.db)?;
+        let impl_block = def_id.impl_block(self.db)?;
+        let generics = self.db.generic_params(def_id)?;
+        let mut closure_tys = Vec::with_capacity(sig.input.len());
+        for param_ty in sig.input.iter() {
+            let bound = match param_ty {
+                Ty::Param { idx, .. } => {
+                    generics.find_by_idx(*idx).and_then(|p| p.fn_bound.as_ref())
+                }
+                _ => None,
+            };
+            let closure_ty = match bound {
+                Some(bound) => {
+                    let ty = Ty::from_hir(self.db, &module, impl_block.as_ref(), &generics, bound)?;
+                    Some(self.insert_type_vars(ty.subst(substs)))
+                }
+                None => None,
+            };
+            closure_tys.push(closure_ty);
+        }
+        Ok(closure_tys)
+    }
+
+    fn infer_call_args(
         &mut self,
-        expr: Option<ast::Expr>,
-        expected: &Expectation,
-    ) -> Cancelable<Ty> {
-        if let Some(e) = expr {
-            self.infer_expr(e, expected)
-        } else {
-            Ok(Ty::Unknown)
+        args: &[ExprId],
+        param_tys: &[Ty],
+        closure_tys: &[Option<Ty>],
+    ) -> Cancelable<()> {
+        for (i, arg) in args.iter().enumerate() {
+            let param_ty = param_tys.get(i).cloned().unwrap_or(Ty::Unknown);
+            match closure_tys.get(i) {
+                Some(Some(closure_ty)) => {
+                    let arg_ty =
+                        self.infer_expr(*arg, &Expectation::has_type(closure_ty.clone()))?;
+                    self.unify(&arg_ty, &param_ty);
+                }
+                _ => {
+                    self.infer_expr(*arg, &Expectation::has_type(param_ty))?;
+                }
+            }
         }
+        Ok(())
     }
 
-    fn infer_expr(&mut self, expr: ast::Expr, expected: &Expectation) -> Cancelable<Ty> {
-        let ty = match expr {
-            ast::Expr::IfExpr(e) => {
-                if let Some(condition) = e.condition() {
-                    let expected = if condition.pat().is_none() {
-                        Expectation::has_type(Ty::Bool)
-                    } else {
-                        Expectation::none()
+    /// Infers the types for a pattern matching a value of type `expected`, and
+    /// records the types of all the bindings in it. Returns the type of the
+    /// matched value.
+    fn infer_pat(&mut self, pat: PatId, expected: &Ty) -> Cancelable<Ty> {
+        let body = Arc::clone(&self.body); // avoid borrow checker problem
+        let expected = self.resolve_ty_as_possible(expected.clone());
+        let ty = match &body[pat] {
+            Pat::Tuple(args) => {
+                let expected_tys = match &expected {
+                    Ty::Tuple(tys) => tys.clone(),
+                    _ => Arc::new([]),
+                };
+                // TODO: handle `..` in the middle of the pattern
+                let mut tys = Vec::new();
+                for (i, sub_pat) in args.iter().enumerate() {
+                    let expected_ty = expected_tys.get(i).cloned().unwrap_or(Ty::Unknown);
+                    tys.push(self.infer_pat(*sub_pat, &expected_ty)?);
+                }
+                Ty::Tuple(tys.into())
+            }
+            Pat::Ref { pat, mutability } => {
+                let expected_inner = match &expected {
+                    Ty::Ref(inner, m) if m == mutability => Ty::clone(inner),
+                    _ => Ty::Unknown,
+                };
+                let inner_ty = self.infer_pat(*pat, &expected_inner)?;
+                Ty::Ref(Arc::new(inner_ty), *mutability)
+            }
+            Pat::TupleStruct { path, args } => {
+                let (ty, def_id) = self.resolve_variant(path.as_ref())?;
+                let substs = ty.substs().unwrap_or_else(Substs::empty);
+                for (i, sub_pat) in args.iter().enumerate() {
+                    let field_ty = match def_id {
+                        Some(def_id) => self
+                            .db
+                            .type_for_field(def_id, Name::tuple_field_name(i))?
+                            .subst(&substs),
+                        None => Ty::Unknown,
+                    };
+                    self.infer_pat(*sub_pat, &field_ty)?;
+                }
+                ty
+            }
+            Pat::Struct { path, args } => {
+                let (ty, def_id) = self.resolve_variant(path.as_ref())?;
+                let substs = ty.substs().unwrap_or_else(Substs::empty);
+                for field_pat in args {
+                    let field_ty = match def_id {
+                        Some(def_id) => self
+                            .db
+                            .type_for_field(def_id, field_pat.name.clone())?
+                            .subst(&substs),
+                        None => Ty::Unknown,
                     };
-                    self.infer_expr_opt(condition.expr(), &expected)?;
-                    // TODO write type for pat
+                    self.infer_pat(field_pat.pat, &field_ty)?;
+                }
+                ty
+            }
+            Pat::Slice(args) => {
+                let elem_ty = match &expected {
+                    Ty::Array(t) | Ty::Slice(t) => Ty::clone(t),
+                    _ => Ty::Unknown,
                 };
-                let if_ty = self.infer_block_opt(e.then_branch(), expected)?;
-                if let Some(else_branch) = e.else_branch() {
-                    self.infer_block(else_branch, expected)?;
+                for sub_pat in args {
+                    self.infer_pat(*sub_pat, &elem_ty)?;
+                }
+                match &expected {
+                    Ty::Array(_) | Ty::Slice(_) => expected.clone(),
+                    _ => Ty::Unknown,
+                }
+            }
+            Pat::Bind { mode, subpat, .. } => {
+                let ty = match subpat {
+                    Some(sub_pat) => self.infer_pat(*sub_pat, &expected)?,
+                    None => expected.clone(),
+                };
+                let ty = self.insert_type_vars_shallow(ty);
+                self.unify(&ty, &expected);
+                let ty = self.resolve_ty_as_possible(ty);
+                // the type of the binding itself; `ref` bindings borrow from
+                // the matched value
+                let bound_ty = match mode {
+                    BindingAnnotation::Ref => Ty::Ref(Arc::new(ty.clone()), Mutability::Shared),
+                    BindingAnnotation::RefMut => Ty::Ref(Arc::new(ty.clone()), Mutability::Mut),
+                    BindingAnnotation::Unannotated | BindingAnnotation::Mutable => ty.clone(),
+                };
+                self.write_pat_ty(pat, bound_ty);
+                return Ok(ty);
+            }
+            Pat::Path(path) => self.resolve_variant(Some(path))?.0,
+            Pat::Lit(lit) => self.literal_ty(lit),
+            Pat::Wild | Pat::Missing => Ty::Unknown,
+        };
+        // use a new type variable if we got Ty::Unknown here
+        let ty = self.insert_type_vars_shallow(ty);
+        self.unify(&ty, &expected);
+        let ty = self.resolve_ty_as_possible(ty);
+        self.write_pat_ty(pat, ty.clone());
+        Ok(ty)
+    }
+
+    fn infer_expr(&mut self, tgt_expr: ExprId, expected: &Expectation) -> Cancelable<Ty> {
+        let body = Arc::clone(&self.body); // avoid borrow checker problem
+        let ty = match &body[tgt_expr] {
+            Expr::Missing => Ty::Unknown,
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.infer_expr(*condition, &Expectation::has_type(Ty::Bool))?;
+                let then_ty = self.infer_expr(*then_branch, expected)?;
+                if let Some(else_branch) = else_branch {
+                    self.infer_expr(*else_branch, expected)?;
                 } else {
                     // no else branch -> unit
                     self.unify(&expected.ty, &Ty::unit()); // actually coerce
                 }
-                if_ty
+                then_ty
             }
-            ast::Expr::BlockExpr(e) => self.infer_block_opt(e.block(), expected)?,
-            ast::Expr::LoopExpr(e) => {
-                self.infer_block_opt(e.loop_body(), &Expectation::has_type(Ty::unit()))?;
-                // TODO never, or the type of the break param
-                Ty::Unknown
+            Expr::Block { statements, tail } => self.infer_block(statements, *tail, expected)?,
+            Expr::Loop { body } => {
+                let break_ty = self.new_type_var();
+                self.breakables.push(BreakableContext {
+                    may_break: false,
+                    break_ty,
+                });
+                self.infer_expr(*body, &Expectation::has_type(Ty::unit()))?;
+                let ctx = self.breakables.pop().expect("breakable stack broken");
+                if ctx.may_break {
+                    ctx.break_ty
+                } else {
+                    Ty::Never
+                }
             }
-            ast::Expr::WhileExpr(e) => {
-                if let Some(condition) = e.condition() {
-                    let expected = if condition.pat().is_none() {
-                        Expectation::has_type(Ty::Bool)
-                    } else {
-                        Expectation::none()
-                    };
-                    self.infer_expr_opt(condition.expr(), &expected)?;
-                    // TODO write type for pat
-                };
-                self.infer_block_opt(e.loop_body(), &Expectation::has_type(Ty::unit()))?;
-                // TODO always unit?
+            Expr::While { condition, body } => {
+                self.infer_expr(*condition, &Expectation::has_type(Ty::Bool))?;
+                self.infer_loop_body(*body)?;
                 Ty::unit()
             }
-            ast::Expr::ForExpr(e) => {
-                let _iterable_ty = self.infer_expr_opt(e.iterable(), &Expectation::none());
-                if let Some(_pat) = e.pat() {
-                    // TODO write type for pat
-                }
-                self.infer_block_opt(e.loop_body(), &Expectation::has_type(Ty::unit()))?;
-                // TODO always unit?
+            Expr::For {
+                iterable,
+                pat,
+                body,
+            } => {
+                let _iterable_ty = self.infer_expr(*iterable, &Expectation::none())?;
+                // TODO the item type of the iterable, via IntoIterator
+                self.infer_pat(*pat, &Ty::Unknown)?;
+                self.infer_loop_body(*body)?;
                 Ty::unit()
             }
-            ast::Expr::LambdaExpr(e) => {
-                let _body_ty = self.infer_expr_opt(e.body(), &Expectation::none())?;
-                Ty::Unknown
+            Expr::Lambda {
+                args,
+                arg_types,
+                body,
+            } => {
+                // the parameter and return types come from annotations, or
+                // else from the signature the closure is expected to have
+                let expected_sig = self.callable_sig(&expected.ty)?;
+                let mut input = Vec::with_capacity(args.len());
+                for (i, (arg_pat, arg_type)) in args.iter().zip(arg_types.iter()).enumerate() {
+                    let expected_ty = expected_sig
+                        .as_ref()
+                        .and_then(|sig| sig.input.get(i).cloned())
+                        .unwrap_or(Ty::Unknown);
+                    let expected_ty = if let Some(type_ref) = arg_type {
+                        let ty = self.make_ty(type_ref)?;
+                        let ty = self.insert_type_vars(ty);
+                        self.unify(&ty, &expected_ty);
+                        ty
+                    } else {
+                        expected_ty
+                    };
+                    input.push(self.infer_pat(*arg_pat, &expected_ty)?);
+                }
+                let ret_ty = match expected_sig {
+                    Some(sig) => sig.output,
+                    None => Ty::Unknown,
+                };
+                let ret_ty = self.insert_type_vars_shallow(ret_ty);
+                // `break` inside a closure can't refer to loops outside of it
+                let breakables = mem::replace(&mut self.breakables, Vec::new());
+                self.infer_expr(*body, &Expectation::has_type(ret_ty.clone()))?;
+                self.breakables = breakables;
+                let sig = FnSig {
+                    input,
+                    output: self.resolve_ty_as_possible(ret_ty),
+                };
+                Ty::Closure {
+                    def_id: self.def_id,
+                    expr: tgt_expr,
+                    sig: Arc::new(sig),
+                }
             }
-            ast::Expr::CallExpr(e) => {
-                let callee_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                let (arg_tys, ret_ty) = match &callee_ty {
-                    Ty::FnPtr(sig) => (&sig.input[..], sig.output.clone()),
-                    _ => {
+            Expr::Call { callee, args } => {
+                let callee_ty = self.infer_expr(*callee, &Expectation::none())?;
+                let (param_tys, ret_ty) = match self.callable_sig(&callee_ty)? {
+                    Some(sig) => (sig.input, sig.output),
+                    None => {
                         // not callable
                         // TODO report an error?
-                        (&[][..], Ty::Unknown)
+                        (Vec::new(), Ty::Unknown)
+                    }
+                };
+                let closure_tys = self.expected_closure_tys(&callee_ty)?;
+                self.infer_call_args(args, &param_tys, &closure_tys)?;
+                ret_ty
+            }
+            Expr::MethodCall {
+                receiver,
+                method_name,
+                args,
+            } => {
+                let receiver_ty = self.infer_expr(*receiver, &Expectation::none())?;
+                let receiver_ty = self.resolve_ty_as_possible(receiver_ty);
+                let resolved = match self.module.krate(self.db) {
+                    Some(krate) => {
+                        receiver_ty
+                            .clone()
+                            .lookup_method(self.db, &krate, method_name)?
                     }
+                    None => None,
                 };
-                if let Some(arg_list) = e.arg_list() {
-                    for (i, arg) in arg_list.args().enumerate() {
-                        self.infer_expr(
-                            arg,
-                            &Expectation::has_type(arg_tys.get(i).cloned().unwrap_or(Ty::Unknown)),
-                        )?;
+                let (derefed_receiver_ty, method_ty) = match resolved {
+                    Some((ty, def_id)) => {
+                        self.method_resolutions.insert(tgt_expr, def_id);
+                        (ty, self.db.type_for_def(def_id)?)
                     }
+                    None => (Ty::Unknown, Ty::Unknown),
+                };
+                // The generic parameters of the method (including those of
+                // the impl) are all inferred; they get determined by unifying
+                // with the receiver and argument types. For trait methods, the
+                // parameters of the trait are known from the impl, though.
+                let method_ty = match method_ty {
+                    Ty::FnDef { def_id, .. } => {
+                        let substs = method_ty.substs().unwrap_or_else(Substs::empty);
+                        let trait_substs = self
+                            .trait_substs_for_method(def_id, &derefed_receiver_ty)?
+                            .unwrap_or_else(Substs::empty);
+                        let fresh = (0..substs.0.len())
+                            .map(|i| trait_substs.0.get(i).cloned().unwrap_or(Ty::Unknown))
+                            .collect::<Vec<_>>();
+                        let ty = method_ty.apply_substs(Substs(fresh.into()));
+                        self.insert_type_vars(ty)
+                    }
+                    ty => self.insert_type_vars(ty),
+                };
+                let sig = match &method_ty {
+                    Ty::FnPtr(sig) => Some(FnSig::clone(sig)),
+                    Ty::FnDef { sig, substs, .. } => Some(sig.subst(substs)),
+                    _ => None,
+                };
+                let (expected_receiver_ty, param_tys, ret_ty) = match sig {
+                    Some(mut sig) => {
+                        if !sig.input.is_empty() {
+                            let receiver_ty = sig.input.remove(0);
+                            (receiver_ty, sig.input, sig.output)
+                        } else {
+                            (Ty::Unknown, Vec::new(), sig.output)
+                        }
+                    }
+                    None => (Ty::Unknown, Vec::new(), Ty::Unknown),
+                };
+                let mut closure_tys = self.expected_closure_tys(&method_ty)?;
+                if !closure_tys.is_empty() {
+                    // the receiver
+                    closure_tys.remove(0);
                 }
+                // Apply autoref so the below unification works correctly
+                let actual_receiver_ty = match &expected_receiver_ty {
+                    Ty::Ref(_, mutability) => Ty::Ref(Arc::new(derefed_receiver_ty), *mutability),
+                    _ => derefed_receiver_ty,
+                };
+                self.unify(&expected_receiver_ty, &actual_receiver_ty);
+                self.infer_call_args(args, &param_tys, &closure_tys)?;
                 ret_ty
             }
-            ast::Expr::MethodCallExpr(e) => {
-                let _receiver_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                if let Some(arg_list) = e.arg_list() {
-                    for arg in arg_list.args() {
-                        // TODO unify / expect argument type
-                        self.infer_expr(arg, &Expectation::none())?;
+            Expr::Match { expr, arms } => {
+                let input_ty = self.infer_expr(*expr, &Expectation::none())?;
+                // all arms need to have the same type, which is also the type
+                // of the whole match
+                let result_ty = match expected.ty {
+                    Ty::Unknown => self.new_type_var(),
+                    _ => expected.ty.clone(),
+                };
+                for arm in arms {
+                    for pat in &arm.pats {
+                        self.infer_pat(*pat, &input_ty)?;
+                    }
+                    if let Some(guard) = arm.guard {
+                        self.infer_expr(guard, &Expectation::has_type(Ty::Bool))?;
                     }
+                    self.infer_expr(arm.expr, &Expectation::has_type(result_ty.clone()))?;
                 }
-                Ty::Unknown
+                result_ty
             }
-            ast::Expr::MatchExpr(e) => {
-                let _ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                if let Some(match_arm_list) = e.match_arm_list() {
-                    for arm in match_arm_list.arms() {
-                        // TODO type the bindings in pat
-                        // TODO type the guard
-                        let _ty = self.infer_expr_opt(arm.expr(), &Expectation::none())?;
-                    }
-                    // TODO unify all the match arm types
-                    Ty::Unknown
+            Expr::Path(p) => self.infer_path_expr(tgt_expr, p)?.unwrap_or(Ty::Unknown),
+            Expr::Continue => Ty::Never,
+            Expr::Break { expr } => {
+                let break_ty = match self.breakables.last() {
+                    Some(ctx) => ctx.break_ty.clone(),
+                    None => Ty::Unknown,
+                };
+                if let Some(expr) = expr {
+                    self.infer_expr(*expr, &Expectation::has_type(break_ty))?;
                 } else {
-                    Ty::Unknown
+                    self.unify(&break_ty, &Ty::unit());
+                }
+                if let Some(ctx) = self.breakables.last_mut() {
+                    ctx.may_break = true;
                 }
-            }
-            ast::Expr::TupleExpr(_e) => Ty::Unknown,
-            ast::Expr::ArrayExpr(_e) => Ty::Unknown,
-            ast::Expr::PathExpr(e) => self.infer_path_expr(e)?.unwrap_or(Ty::Unknown),
-            ast::Expr::ContinueExpr(_e) => Ty::Never,
-            ast::Expr::BreakExpr(_e) => Ty::Never,
-            ast::Expr::ParenExpr(e) => self.infer_expr_opt(e.expr(), expected)?,
-            ast::Expr::Label(_e) => Ty::Unknown,
-            ast::Expr::ReturnExpr(e) => {
-                // TODO expect return type of function
-                self.infer_expr_opt(e.expr(), &Expectation::none())?;
                 Ty::Never
             }
-            ast::Expr::MatchArmList(_) | ast::Expr::MatchArm(_) | ast::Expr::MatchGuard(_) => {
-                // Can this even occur outside of a match expression?
-                Ty::Unknown
+            Expr::Return { expr } => {
+                if let Some(expr) = expr {
+                    // TODO expect return type of function
+                    self.infer_expr(*expr, &Expectation::none())?;
+                }
+                Ty::Never
             }
-            ast::Expr::StructLit(e) => {
-                let (ty, def_id) = self.resolve_variant(e.path())?;
-                if let Some(nfl) = e.named_field_list() {
-                    for field in nfl.fields() {
-                        let field_ty = if let (Some(def_id), Some(nr)) = (def_id, field.name_ref())
-                        {
-                            self.db.type_for_field(def_id, nr.as_name())?
-                        } else {
-                            Ty::Unknown
-                        };
-                        self.infer_expr_opt(field.expr(), &Expectation::has_type(field_ty))?;
-                    }
+            Expr::StructLit { path, fields } => {
+                let (ty, def_id) = self.resolve_variant(path.as_ref())?;
+                let substs = ty.substs().unwrap_or_else(Substs::empty);
+                for field in fields {
+                    let field_ty = if let Some(def_id) = def_id {
+                        self.db
+                            .type_for_field(def_id, field.name.clone())?
+                            .subst(&substs)
+                    } else {
+                        Ty::Unknown
+                    };
+                    self.infer_expr(field.expr, &Expectation::has_type(field_ty))?;
                 }
                 ty
             }
-            ast::Expr::NamedFieldList(_) | ast::Expr::NamedField(_) => {
-                // Can this even occur outside of a struct literal?
-                Ty::Unknown
+            Expr::Index { base, index } => {
+                let base_ty = self.infer_expr(*base, &Expectation::none())?;
+                let _index_ty = self.infer_expr(*index, &Expectation::none())?;
+                let base_ty = self.resolve_ty_as_possible(base_ty);
+                // TODO: the Index trait; ranges as index
+                base_ty
+                    .autoderef(self.db)
+                    .find_map(|ty| match ty {
+                        Ty::Array(t) | Ty::Slice(t) => Some(Ty::clone(&t)),
+                        _ => None,
+                    })
+                    .unwrap_or(Ty::Unknown)
             }
-            ast::Expr::IndexExpr(_e) => Ty::Unknown,
-            ast::Expr::FieldExpr(e) => {
-                let receiver_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                if let Some(nr) = e.name_ref() {
-                    let ty = match receiver_ty {
+            Expr::Field { expr, name } => {
+                let receiver_ty = self.infer_expr(*expr, &Expectation::none())?;
+                let receiver_ty = self.resolve_ty_as_possible(receiver_ty);
+                let mut ty = Ty::Unknown;
+                for derefed_ty in receiver_ty.autoderef(self.db) {
+                    let field_ty = match derefed_ty {
                         Ty::Tuple(fields) => {
-                            let i = nr.text().parse::<usize>().ok();
+                            let i = name.to_string().parse::<usize>().ok();
                             i.and_then(|i| fields.get(i).cloned())
-                                .unwrap_or(Ty::Unknown)
                         }
-                        Ty::Adt { def_id, .. } => self.db.type_for_field(def_id, nr.as_name())?,
-                        _ => Ty::Unknown,
+                        Ty::Adt { def_id, substs, .. } => {
+                            match self.db.type_for_field(def_id, name.clone())? {
+                                Ty::Unknown => None,
+                                ty => {
+                                    self.field_resolutions.insert(tgt_expr, def_id);
+                                    Some(ty.subst(&substs))
+                                }
+                            }
+                        }
+                        _ => None,
                     };
-                    self.insert_type_vars(ty)
-                } else {
-                    Ty::Unknown
+                    if let Some(field_ty) = field_ty {
+                        ty = field_ty;
+                        break;
+                    }
                 }
+                self.insert_type_vars(ty)
             }
-            ast::Expr::TryExpr(e) => {
-                let _inner_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
+            Expr::Try { expr } => {
+                let _inner_ty = self.infer_expr(*expr, &Expectation::none())?;
                 Ty::Unknown
             }
-            ast::Expr::CastExpr(e) => {
-                let _inner_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                let cast_ty = Ty::from_ast_opt(
-                    self.db,
-                    &self.module,
-                    self.impl_block.as_ref(),
-                    e.type_ref(),
-                )?;
+            Expr::Cast { expr, type_ref } => {
+                let _inner_ty = self.infer_expr(*expr, &Expectation::none())?;
+                let cast_ty = self.make_ty(type_ref)?;
                 let cast_ty = self.insert_type_vars(cast_ty);
                 // TODO do the coercion...
                 cast_ty
             }
-            ast::Expr::RefExpr(e) => {
+            Expr::Ref { expr, mutability } => {
                 // TODO pass the expectation down
-                let inner_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                let m = Mutability::from_mutable(e.is_mut());
+                let inner_ty = self.infer_expr(*expr, &Expectation::none())?;
                 // TODO reference coercions etc.
-                Ty::Ref(Arc::new(inner_ty), m)
+                Ty::Ref(Arc::new(inner_ty), *mutability)
+            }
+            Expr::UnaryOp { expr, op } => {
+                let inner_ty = self.infer_expr(*expr, &Expectation::none())?;
+                match op {
+                    Some(UnaryOp::Deref) => {
+                        // TODO Deref::deref
+                        inner_ty.builtin_deref(true).unwrap_or(Ty::Unknown)
+                    }
+                    Some(op) => {
+                        let inner_ty = self.resolve_ty_as_possible(inner_ty);
+                        op::prefix_op_return_ty(*op, inner_ty)
+                    }
+                    None => Ty::Unknown,
+                }
+            }
+            Expr::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let mut elem_ty = match start {
+                    Some(start) => self.infer_expr(*start, &Expectation::none())?,
+                    None => self.new_type_var(),
+                };
+                if let Some(end) = end {
+                    elem_ty = self.infer_expr(*end, &Expectation::has_type(elem_ty))?;
+                }
+                let (name, substs) = match (start, end, inclusive) {
+                    (None, None, _) => ("RangeFull", Vec::new()),
+                    (Some(_), None, _) => ("RangeFrom", vec![elem_ty]),
+                    (None, Some(_), false) => ("RangeTo", vec![elem_ty]),
+                    (None, Some(_), true) => ("RangeToInclusive", vec![elem_ty]),
+                    (Some(_), Some(_), false) => ("Range", vec![elem_ty]),
+                    (Some(_), Some(_), true) => ("RangeInclusive", vec![elem_ty]),
+                };
+                match self.resolve_ops_struct(name)? {
+                    Some(s) => type_for_struct(self.db, s)?.apply_substs(Substs(substs.into())),
+                    None => Ty::Unknown,
+                }
             }
-            ast::Expr::PrefixExpr(e) => {
-                let inner_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                match e.op() {
-                    Some(PrefixOp::Deref) => {
-                        match inner_ty {
-                            // builtin deref:
-                            Ty::Ref(ref_inner, _) => (*ref_inner).clone(),
-                            Ty::RawPtr(ptr_inner, _) => (*ptr_inner).clone(),
-                            // TODO Deref::deref
-                            _ => Ty::Unknown,
+            Expr::BinaryOp { lhs, rhs, op } => {
+                let lhs_ty = self.infer_expr(*lhs, &Expectation::none())?;
+                match op {
+                    Some(op) => {
+                        let lhs_ty = self.resolve_ty_as_possible(lhs_ty);
+                        let rhs_expected = op::binary_op_rhs_expectation(*op, lhs_ty.clone());
+                        self.infer_expr(*rhs, &Expectation::has_type(rhs_expected))?;
+                        let lhs_ty = self.resolve_ty_as_possible(lhs_ty);
+                        op::binary_op_return_ty(*op, lhs_ty)
+                    }
+                    None => {
+                        self.infer_expr(*rhs, &Expectation::none())?;
+                        Ty::Unknown
+                    }
+                }
+            }
+            Expr::Tuple { exprs } => {
+                let expected_tys = match self.resolve_ty_as_possible(expected.ty.clone()) {
+                    Ty::Tuple(tys) => tys,
+                    _ => Arc::new([]),
+                };
+                let mut tys = Vec::new();
+                for (i, expr) in exprs.iter().enumerate() {
+                    let expected_ty = expected_tys.get(i).cloned().unwrap_or(Ty::Unknown);
+                    tys.push(self.infer_expr(*expr, &Expectation::has_type(expected_ty))?);
+                }
+                Ty::Tuple(tys.into())
+            }
+            Expr::Array(array) => {
+                let elem_ty = match self.resolve_ty_as_possible(expected.ty.clone()) {
+                    Ty::Array(t) | Ty::Slice(t) => Ty::clone(&t),
+                    _ => self.new_type_var(),
+                };
+                match array {
+                    Array::ElementList(exprs) => {
+                        for expr in exprs {
+                            self.infer_expr(*expr, &Expectation::has_type(elem_ty.clone()))?;
                         }
                     }
-                    _ => Ty::Unknown,
+                    Array::Repeat {
+                        initializer,
+                        repeat,
+                    } => {
+                        self.infer_expr(*initializer, &Expectation::has_type(elem_ty.clone()))?;
+                        let usize_ty = Ty::Uint(primitive::UintTy::Usize);
+                        self.infer_expr(*repeat, &Expectation::has_type(usize_ty))?;
+                    }
                 }
+                Ty::Array(Arc::new(elem_ty))
             }
-            ast::Expr::RangeExpr(_e) => Ty::Unknown,
-            ast::Expr::BinExpr(_e) => Ty::Unknown,
-            ast::Expr::Literal(_e) => Ty::Unknown,
+            Expr::Literal(lit) => self.literal_ty(lit),
         };
         // use a new type variable if we got Ty::Unknown here
         let ty = self.insert_type_vars_shallow(ty);
         self.unify(&ty, &expected.ty);
-        self.write_ty(expr.syntax(), ty.clone());
+        self.write_expr_ty(tgt_expr, ty.clone());
         Ok(ty)
     }
 
-    fn infer_block_opt(
-        &mut self,
-        node: Option<ast::Block>,
-        expected: &Expectation,
-    ) -> Cancelable<Ty> {
-        if let Some(b) = node {
-            self.infer_block(b, expected)
-        } else {
-            Ok(Ty::Unknown)
+    /// Infers the body of a `while` or `for` loop; `break`s in it can't have
+    /// a value.
+    fn infer_loop_body(&mut self, body: ExprId) -> Cancelable<()> {
+        self.breakables.push(BreakableContext {
+            may_break: false,
+            break_ty: Ty::unit(),
+        });
+        self.infer_expr(body, &Expectation::has_type(Ty::unit()))?;
+        self.breakables.pop().expect("breakable stack broken");
+        Ok(())
+    }
+
+    fn literal_ty(&mut self, lit: &Literal) -> Ty {
+        match lit {
+            Literal::Bool => Ty::Bool,
+            Literal::String => Ty::Ref(Arc::new(Ty::Str), Mutability::Shared),
+            Literal::ByteString => {
+                let byte_ty = Ty::Uint(primitive::UintTy::U8);
+                let slice_ty = Ty::Slice(Arc::new(byte_ty));
+                Ty::Ref(Arc::new(slice_ty), Mutability::Shared)
+            }
+            Literal::Byte => Ty::Uint(primitive::UintTy::U8),
+            Literal::Char => Ty::Char,
+            Literal::Int(ty) => match ty {
+                UncertainIntTy::Signed(int_ty) => Ty::Int(*int_ty),
+                UncertainIntTy::Unsigned(uint_ty) => Ty::Uint(*uint_ty),
+                UncertainIntTy::Unknown => self.new_integer_var(),
+            },
+            Literal::Float(ty) => match ty {
+                UncertainFloatTy::Known(float_ty) => Ty::Float(*float_ty),
+                UncertainFloatTy::Unknown => self.new_float_var(),
+            },
         }
     }
 
-    fn infer_block(&mut self, node: ast::Block, expected: &Expectation) -> Cancelable<Ty> {
-        for stmt in node.statements() {
+    fn infer_block(
+        &mut self,
+        statements: &[Statement],
+        tail: Option<ExprId>,
+        expected: &Expectation,
+    ) -> Cancelable<Ty> {
+        for stmt in statements {
             match stmt {
-                ast::Stmt::LetStmt(stmt) => {
-                    let decl_ty = Ty::from_ast_opt(
+                Statement::Let {
+                    pat,
+                    type_ref,
+                    initializer,
+                } => {
+                    let decl_ty = Ty::from_hir_opt(
                         self.db,
                         &self.module,
                         self.impl_block.as_ref(),
-                        stmt.type_ref(),
+                        &self.generics,
+                        type_ref.as_ref(),
                     )?;
                     let decl_ty = self.insert_type_vars(decl_ty);
-                    let ty = if let Some(expr) = stmt.initializer() {
-                        let expr_ty = self.infer_expr(expr, &Expectation::has_type(decl_ty))?;
-                        expr_ty
+                    let ty = if let Some(expr) = initializer {
+                        self.infer_expr(*expr, &Expectation::has_type(decl_ty))?
                     } else {
                         decl_ty
                     };
 
-                    if let Some(pat) = stmt.pat() {
-                        self.write_ty(pat.syntax(), ty);
-                    };
+                    self.infer_pat(*pat, &ty)?;
                 }
-                ast::Stmt::ExprStmt(expr_stmt) => {
-                    self.infer_expr_opt(expr_stmt.expr(), &Expectation::none())?;
+                Statement::Expr(expr) => {
+                    self.infer_expr(*expr, &Expectation::none())?;
                 }
             }
         }
-        let ty = if let Some(expr) = node.expr() {
+        let ty = if let Some(expr) = tail {
             self.infer_expr(expr, expected)?
         } else {
             Ty::unit()
         };
-        self.write_ty(node.syntax(), ty.clone());
         Ok(ty)
     }
 
-    fn collect_fn_signature(&mut self, node: ast::FnDef) -> Cancelable<()> {
-        if let Some(param_list) = node.param_list() {
-            if let Some(self_param) = param_list.self_param() {
-                let self_type = if let Some(type_ref) = self_param.type_ref() {
-                    let ty = self.make_ty(&TypeRef::from_ast(type_ref))?;
-                    self.insert_type_vars(ty)
-                } else {
-                    // TODO this should be handled by desugaring during HIR conversion
-                    let ty = self.make_ty_opt(self.impl_block.as_ref().map(|i| i.target_type()))?;
-                    let ty = match self_param.flavor() {
-                        ast::SelfParamFlavor::Owned => ty,
-                        ast::SelfParamFlavor::Ref => Ty::Ref(Arc::new(ty), Mutability::Shared),
-                        ast::SelfParamFlavor::MutRef => Ty::Ref(Arc::new(ty), Mutability::Mut),
-                    };
-                    self.insert_type_vars(ty)
-                };
-                if let Some(self_kw) = self_param.self_kw() {
-                    let self_param = LocalSyntaxPtr::new(self_kw.syntax());
-                    self.self_param = Some(self_param);
-                    self.type_of.insert(self_param, self_type);
-                }
-            }
-            for param in param_list.params() {
-                let pat = if let Some(pat) = param.pat() {
-                    pat
-                } else {
-                    continue;
-                };
-                let ty = if let Some(type_ref) = param.type_ref() {
-                    let ty = self.make_ty(&TypeRef::from_ast(type_ref))?;
-                    self.insert_type_vars(ty)
-                } else {
-                    // missing type annotation
-                    self.new_type_var()
-                };
-                self.type_of.insert(LocalSyntaxPtr::new(pat.syntax()), ty);
-            }
+    fn collect_fn_signature(&mut self, signature: &FnSignature) -> Cancelable<()> {
+        let body = Arc::clone(&self.body); // avoid borrow checker problem
+        for (type_ref, pat) in signature.args().iter().zip(body.args()) {
+            let ty = self.make_ty(type_ref)?;
+            let ty = self.insert_type_vars(ty);
+            self.infer_pat(*pat, &ty)?;
         }
-
-        self.return_ty = if let Some(type_ref) = node.ret_type().and_then(|n| n.type_ref()) {
-            let ty = self.make_ty(&TypeRef::from_ast(type_ref))?;
+        self.return_ty = {
+            let ty = self.make_ty(signature.ret_type())?;
             self.insert_type_vars(ty)
-        } else {
-            Ty::unit()
         };
+        Ok(())
+    }
 
+    fn infer_body(&mut self) -> Cancelable<()> {
+        self.infer_expr(
+            self.body.body_expr(),
+            &Expectation::has_type(self.return_ty.clone()),
+        )?;
         Ok(())
     }
 }
 
 pub fn infer(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<InferenceResult>> {
     let function = Function::new(def_id); // TODO: consts also need inference
-    let scopes = function.scopes(db);
+    let body = function.body(db)?;
+    let scopes = db.fn_scopes(def_id)?;
     let module = function.module(db)?;
     let impl_block = function.impl_block(db)?;
-    let mut ctx = InferenceContext::new(db, scopes, module, impl_block);
+    let generics = db.generic_params(def_id)?;
+    let mut ctx = InferenceContext::new(db, def_id, body, scopes, module, impl_block, generics);
 
-    let syntax = function.syntax(db);
-    let node = syntax.borrowed();
+    let signature = function.signature(db);
+    ctx.collect_fn_signature(&signature)?;
 
-    ctx.collect_fn_signature(node)?;
-
-    if let Some(block) = node.body() {
-        ctx.infer_block(block, &Expectation::has_type(ctx.return_ty.clone()))?;
-    }
+    ctx.infer_body()?;
 
     Ok(Arc::new(ctx.resolve_all()))
 }
@@ -0,0 +1,31 @@
+//! In certain situations, rust automatically inserts derefs as necessary: For
+//! example, field accesses `foo.bar` still work when `foo` is actually a
+//! reference to a type with the field `bar`. This is an approximation of the
+//! logic in rustc (which lives in librustc_typeck/check/autoderef.rs).
+
+use ra_syntax::algo::generate;
+
+use crate::HirDatabase;
+use super::Ty;
+
+impl Ty {
+    /// Iterates over the possible derefs of `ty`, starting with `ty` itself.
+    pub fn autoderef<'a>(self, db: &'a impl HirDatabase) -> impl Iterator<Item = Ty> + 'a {
+        generate(Some(self), move |ty| ty.autoderef_step(db))
+    }
+
+    fn autoderef_step(&self, _db: &impl HirDatabase) -> Option<Ty> {
+        // TODO Deref::deref
+        self.builtin_deref(false)
+    }
+
+    /// The type resulting from a built-in deref of `self`. Raw pointers can only
+    /// be dereferenced explicitly (i.e. by `*ptr`), never by autoderef.
+    pub(crate) fn builtin_deref(&self, explicit: bool) -> Option<Ty> {
+        match self {
+            Ty::Ref(t, _) => Some(Ty::clone(t)),
+            Ty::RawPtr(t, _) if explicit => Some(Ty::clone(t)),
+            _ => None,
+        }
+    }
+}
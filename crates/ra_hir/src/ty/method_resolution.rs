@@ -0,0 +1,299 @@
+//! This module is concerned with finding methods that a given type provides.
+//! For details about how this works in rustc, see the method lookup page in the
+//! [rustc guide](https://rust-lang.github.io/rustc-guide/method-lookup.html)
+//! and the corresponding code mostly in librustc_typeck/check/method/probe.rs.
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+
+use ra_db::{Cancelable, CrateId};
+
+use crate::{
+    HirDatabase, DefId, Function, Module, Crate, Name, ImplBlock, ImplItem, Trait,
+    module::ModuleId, impl_block::ImplId,
+};
+use super::{Ty, TraitRef, primitive::{IntTy, UintTy, FloatTy}};
+
+/// This is used as a key for indexing impls.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TyFingerprint {
+    Adt(DefId),
+    Bool,
+    Char,
+    Int(IntTy),
+    Uint(UintTy),
+    Float(FloatTy),
+    Str,
+}
+
+impl TyFingerprint {
+    /// Creates a TyFingerprint for looking up an impl. Only certain types can
+    /// have impls: if we have some `struct S`, we can have an `impl S`, but not
+    /// `impl &S`. Hence, this will return `None` for reference types and such.
+    fn for_impl(ty: &Ty) -> Option<TyFingerprint> {
+        match ty {
+            Ty::Adt { def_id, .. } => Some(TyFingerprint::Adt(*def_id)),
+            Ty::Bool => Some(TyFingerprint::Bool),
+            Ty::Char => Some(TyFingerprint::Char),
+            Ty::Int(t) => Some(TyFingerprint::Int(*t)),
+            Ty::Uint(t) => Some(TyFingerprint::Uint(*t)),
+            Ty::Float(t) => Some(TyFingerprint::Float(*t)),
+            Ty::Str => Some(TyFingerprint::Str),
+            _ => None,
+        }
+    }
+}
+
+/// The impl blocks of a whole crate: inherent impls indexed by the type they
+/// are for, and trait impls indexed by the trait. This is the second step of
+/// impl collection described on `ModuleImplBlocks`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CrateImplBlocks {
+    /// To make sense of the ModuleIds, we need the crate.
+    krate: CrateId,
+    impls: FxHashMap<TyFingerprint, Vec<(ModuleId, ImplId)>>,
+    impls_by_trait: FxHashMap<Trait, Vec<(ModuleId, ImplId)>>,
+    /// The trait impls again, indexed by the type they are for.
+    trait_impls_by_ty: FxHashMap<TyFingerprint, Vec<(ModuleId, ImplId)>>,
+}
+
+impl CrateImplBlocks {
+    pub fn lookup_impl_blocks<'a>(
+        &'a self,
+        db: &'a impl HirDatabase,
+        ty: &Ty,
+    ) -> impl Iterator<Item = Cancelable<ImplBlock>> + 'a {
+        let fingerprint = TyFingerprint::for_impl(ty);
+        let impls = fingerprint.and_then(|f| self.impls.get(&f));
+        self.impl_blocks(db, impls)
+    }
+
+    /// The impls of traits for the given type in this crate.
+    pub fn lookup_trait_impl_blocks<'a>(
+        &'a self,
+        db: &'a impl HirDatabase,
+        ty: &Ty,
+    ) -> impl Iterator<Item = Cancelable<ImplBlock>> + 'a {
+        let fingerprint = TyFingerprint::for_impl(ty);
+        let impls = fingerprint.and_then(|f| self.trait_impls_by_ty.get(&f));
+        self.impl_blocks(db, impls)
+    }
+
+    fn impl_blocks<'a>(
+        &'a self,
+        db: &'a impl HirDatabase,
+        impls: Option<&'a Vec<(ModuleId, ImplId)>>,
+    ) -> impl Iterator<Item = Cancelable<ImplBlock>> + 'a {
+        impls
+            .into_iter()
+            .flat_map(|i| i.iter())
+            .map(move |(module_id, impl_id)| {
+                let module_impl_blocks = db.impls_in_module(self.krate, *module_id)?;
+                Ok(ImplBlock::from_id(module_impl_blocks, *impl_id))
+            })
+    }
+
+    /// The impls of the given trait in this crate, with the modules they are
+    /// in.
+    pub fn lookup_impl_blocks_for_trait<'a>(
+        &'a self,
+        db: &'a impl HirDatabase,
+        tr: &Trait,
+    ) -> impl Iterator<Item = Cancelable<(Module, ImplBlock)>> + 'a {
+        self.impls_by_trait
+            .get(tr)
+            .into_iter()
+            .flat_map(|i| i.iter())
+            .map(move |(module_id, impl_id)| {
+                let module = Module::new(db, self.krate, *module_id)?;
+                let module_impl_blocks = db.impls_in_module(self.krate, *module_id)?;
+                Ok((module, ImplBlock::from_id(module_impl_blocks, *impl_id)))
+            })
+    }
+
+    /// All traits that have impls in this crate.
+    pub fn implemented_traits<'a>(&'a self) -> impl Iterator<Item = Trait> + 'a {
+        self.impls_by_trait.keys().cloned()
+    }
+
+    fn collect_recursive(&mut self, db: &impl HirDatabase, module: Module) -> Cancelable<()> {
+        let module_id = module.module_id;
+        let module_impl_blocks = db.impls_in_module(self.krate, module_id)?;
+
+        for (impl_id, impl_data) in module_impl_blocks.impls.iter() {
+            let impl_block = ImplBlock::from_id(Arc::clone(&module_impl_blocks), impl_id);
+            if impl_data.target_trait().is_some() {
+                if let Some(trait_ref) = TraitRef::for_impl(db, &module, &impl_block)? {
+                    if let Some(self_ty_fp) = TyFingerprint::for_impl(trait_ref.self_ty()) {
+                        self.trait_impls_by_ty
+                            .entry(self_ty_fp)
+                            .or_default()
+                            .push((module_id, impl_id));
+                    }
+                    self.impls_by_trait
+                        .entry(trait_ref.trait_)
+                        .or_default()
+                        .push((module_id, impl_id));
+                }
+                continue;
+            }
+            let target_ty = Ty::from_hir(
+                db,
+                &module,
+                Some(&impl_block),
+                &impl_block.generic_params(),
+                impl_data.target_type(),
+            )?;
+            if let Some(target_ty_fp) = TyFingerprint::for_impl(&target_ty) {
+                self.impls
+                    .entry(target_ty_fp)
+                    .or_default()
+                    .push((module_id, impl_id));
+            }
+        }
+
+        for (_name, child) in module.children() {
+            self.collect_recursive(db, child)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn impls_in_crate(
+    db: &impl HirDatabase,
+    krate: Crate,
+) -> Cancelable<Arc<CrateImplBlocks>> {
+    let mut crate_impl_blocks = CrateImplBlocks {
+        krate: krate.crate_id(),
+        impls: FxHashMap::default(),
+        impls_by_trait: FxHashMap::default(),
+        trait_impls_by_ty: FxHashMap::default(),
+    };
+    if let Some(module) = krate.root_module(db)? {
+        crate_impl_blocks.collect_recursive(db, module)?;
+    }
+    Ok(Arc::new(crate_impl_blocks))
+}
+
+pub(super) fn def_crate(db: &impl HirDatabase, ty: &Ty) -> Cancelable<Option<Crate>> {
+    match ty {
+        Ty::Adt { def_id, .. } => def_id.krate(db),
+        _ => Ok(None),
+    }
+}
+
+/// The crates whose impls can provide methods of `ty` when called from
+/// `krate`: the crate of the type, and `krate` with all its (transitive)
+/// dependencies. The latter covers trait impls for primitives or foreign
+/// types, like `impl MyTrait for u32` in the calling crate.
+fn crates_to_search(db: &impl HirDatabase, krate: &Crate, ty: &Ty) -> Cancelable<Vec<Crate>> {
+    let mut res = Vec::new();
+    res.extend(def_crate(db, ty)?);
+    let mut stack = vec![krate.clone()];
+    while let Some(krate) = stack.pop() {
+        if res.contains(&krate) {
+            continue;
+        }
+        stack.extend(krate.dependencies(db).into_iter().map(|dep| dep.krate));
+        res.push(krate);
+    }
+    Ok(res)
+}
+
+impl Ty {
+    /// Look up the method with the given name, returning the actual autoderefed
+    /// receiver type (but without autoref applied yet). Only methods taking
+    /// `self` are considered.
+    pub fn lookup_method(
+        self,
+        db: &impl HirDatabase,
+        krate: &Crate,
+        name: &Name,
+    ) -> Cancelable<Option<(Ty, DefId)>> {
+        self.iterate_methods(db, krate, |ty, f| {
+            let sig = f.signature(db);
+            let is_match = sig.has_self_param() && sig.name() == name;
+            Ok(if is_match {
+                Some((ty.clone(), f.def_id()))
+            } else {
+                None
+            })
+        })
+    }
+
+    /// Look up the associated function with the given name, for paths like
+    /// `Foo::new`. Unlike for method calls, functions without `self` are
+    /// considered as well.
+    pub(crate) fn lookup_assoc_fn(
+        self,
+        db: &impl HirDatabase,
+        krate: &Crate,
+        name: &Name,
+    ) -> Cancelable<Option<DefId>> {
+        self.iterate_methods(db, krate, |_ty, f| {
+            Ok(if f.signature(db).name() == name {
+                Some(f.def_id())
+            } else {
+                None
+            })
+        })
+    }
+
+    /// Calls `callback` for each method of this type and of the types it
+    /// autoderefs to, until it returns `Some`. For each type, inherent methods
+    /// come before trait methods. `krate` is the crate of the call, whose
+    /// impls and those of its dependencies are considered.
+    // This would be nicer if it just returned an iterator, but that's really
+    // complicated with all the cancelable operations
+    pub fn iterate_methods<T>(
+        self,
+        db: &impl HirDatabase,
+        krate: &Crate,
+        mut callback: impl FnMut(&Ty, &Function) -> Cancelable<Option<T>>,
+    ) -> Cancelable<Option<T>> {
+        // For method calls, rust first does any number of autoderef, and then one
+        // autoref (i.e. when the method takes &self or &mut self). We just ignore
+        // the autoref currently -- when we find a method matching the given name,
+        // we assume it fits.
+        for derefed_ty in self.autoderef(db) {
+            if TyFingerprint::for_impl(&derefed_ty).is_none() {
+                continue;
+            }
+            let mut traits = Vec::new();
+            for krate in crates_to_search(db, krate, &derefed_ty)? {
+                let impls = db.impls_in_crate(krate)?;
+                for impl_block in impls.lookup_impl_blocks(db, &derefed_ty) {
+                    let impl_block = impl_block?;
+                    for item in impl_block.items() {
+                        if let ImplItem::Method(f) = item {
+                            if let Some(result) = callback(&derefed_ty, f)? {
+                                return Ok(Some(result));
+                            }
+                        }
+                    }
+                }
+                for tr in impls.implemented_traits() {
+                    if !traits.contains(&tr) {
+                        traits.push(tr);
+                    }
+                }
+            }
+
+            // TODO: only traits that are in scope should be considered
+            let query_ty = derefed_ty.clone().without_inference_vars();
+            for tr in traits {
+                if db.implements(query_ty.clone(), tr.def_id())?.is_none() {
+                    continue;
+                }
+                let trait_data = tr.trait_data(db)?;
+                for (_name, f) in trait_data.methods() {
+                    if let Some(result) = callback(&derefed_ty, f)? {
+                        return Ok(Some(result));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
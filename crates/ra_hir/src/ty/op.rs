@@ -0,0 +1,129 @@
+//! Typing of built-in operators on primitive types. Operators on other types
+//! go through the `std::ops` traits, which we don't handle yet.
+
+use ra_syntax::ast::{BinOp, PrefixOp};
+
+use super::{InferTy, Ty};
+
+/// The type the right-hand side of a binary operator is expected to have,
+/// given the type of the left-hand side.
+pub(super) fn binary_op_rhs_expectation(op: BinOp, lhs_ty: Ty) -> Ty {
+    match op {
+        BinOp::BooleanAnd | BinOp::BooleanOr => Ty::Bool,
+        BinOp::Assignment
+        | BinOp::EqualityTest
+        | BinOp::NegatedEqualityTest
+        | BinOp::LesserEqualTest
+        | BinOp::GreaterEqualTest
+        | BinOp::LesserTest
+        | BinOp::GreaterTest => lhs_ty,
+        // the right-hand side of a shift can be any integer type
+        BinOp::LeftShift | BinOp::RightShift | BinOp::ShlAssign | BinOp::ShrAssign => Ty::Unknown,
+        BinOp::Addition
+        | BinOp::Subtraction
+        | BinOp::Multiplication
+        | BinOp::Division
+        | BinOp::Remainder
+        | BinOp::BitwiseAnd
+        | BinOp::BitwiseOr
+        | BinOp::BitwiseXor
+        | BinOp::AddAssign
+        | BinOp::SubAssign
+        | BinOp::MulAssign
+        | BinOp::DivAssign
+        | BinOp::BitAndAssign
+        | BinOp::BitOrAssign
+        | BinOp::BitXorAssign => {
+            if is_builtin_arithmetic(op, &lhs_ty) {
+                lhs_ty
+            } else {
+                Ty::Unknown
+            }
+        }
+    }
+}
+
+/// The result type of a binary operator, given the (already unified) type of
+/// its left-hand side.
+pub(super) fn binary_op_return_ty(op: BinOp, lhs_ty: Ty) -> Ty {
+    match op {
+        BinOp::BooleanAnd
+        | BinOp::BooleanOr
+        | BinOp::EqualityTest
+        | BinOp::NegatedEqualityTest
+        | BinOp::LesserEqualTest
+        | BinOp::GreaterEqualTest
+        | BinOp::LesserTest
+        | BinOp::GreaterTest => Ty::Bool,
+        BinOp::Assignment
+        | BinOp::AddAssign
+        | BinOp::SubAssign
+        | BinOp::MulAssign
+        | BinOp::DivAssign
+        | BinOp::ShlAssign
+        | BinOp::ShrAssign
+        | BinOp::BitAndAssign
+        | BinOp::BitOrAssign
+        | BinOp::BitXorAssign => Ty::unit(),
+        BinOp::Addition
+        | BinOp::Subtraction
+        | BinOp::Multiplication
+        | BinOp::Division
+        | BinOp::Remainder
+        | BinOp::LeftShift
+        | BinOp::RightShift
+        | BinOp::BitwiseAnd
+        | BinOp::BitwiseOr
+        | BinOp::BitwiseXor => {
+            if is_builtin_arithmetic(op, &lhs_ty) {
+                lhs_ty
+            } else {
+                Ty::Unknown
+            }
+        }
+    }
+}
+
+/// The result type of a built-in prefix operator (other than deref).
+pub(super) fn prefix_op_return_ty(op: PrefixOp, inner_ty: Ty) -> Ty {
+    match (op, &inner_ty) {
+        (PrefixOp::Neg, Ty::Int(..))
+        | (PrefixOp::Neg, Ty::Float(..))
+        | (PrefixOp::Neg, Ty::Infer(InferTy::IntVar(..)))
+        | (PrefixOp::Neg, Ty::Infer(InferTy::FloatVar(..)))
+        | (PrefixOp::Not, Ty::Bool)
+        | (PrefixOp::Not, Ty::Int(..))
+        | (PrefixOp::Not, Ty::Uint(..))
+        | (PrefixOp::Not, Ty::Infer(InferTy::IntVar(..))) => inner_ty,
+        _ => Ty::Unknown,
+    }
+}
+
+const BITWISE_OPS: &[BinOp] = &[
+    BinOp::BitwiseAnd,
+    BinOp::BitwiseOr,
+    BinOp::BitwiseXor,
+    BinOp::BitAndAssign,
+    BinOp::BitOrAssign,
+    BinOp::BitXorAssign,
+];
+
+const SHIFT_OPS: &[BinOp] = &[
+    BinOp::LeftShift,
+    BinOp::RightShift,
+    BinOp::ShlAssign,
+    BinOp::ShrAssign,
+];
+
+/// Whether `op` is a built-in operation for operands of type `ty`, i.e.
+/// arithmetic on numbers, or bitwise operations on integers and booleans.
+fn is_builtin_arithmetic(op: BinOp, ty: &Ty) -> bool {
+    let is_bitwise = BITWISE_OPS.contains(&op);
+    let is_shift = SHIFT_OPS.contains(&op);
+    match ty {
+        Ty::Int(..) | Ty::Uint(..) | Ty::Infer(InferTy::IntVar(..)) => true,
+        Ty::Float(..) | Ty::Infer(InferTy::FloatVar(..)) => !is_bitwise && !is_shift,
+        Ty::Bool => is_bitwise,
+        _ => false,
+    }
+}
@@ -47,6 +47,18 @@ impl IntTy {
             _ => None,
         }
     }
+
+    pub fn from_suffix(suffix: &str) -> Option<IntTy> {
+        match suffix {
+            "isize" => Some(IntTy::Isize),
+            "i8" => Some(IntTy::I8),
+            "i16" => Some(IntTy::I16),
+            "i32" => Some(IntTy::I32),
+            "i64" => Some(IntTy::I64),
+            "i128" => Some(IntTy::I128),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Copy)]
@@ -82,6 +94,18 @@ impl UintTy {
             _ => None,
         }
     }
+
+    pub fn from_suffix(suffix: &str) -> Option<UintTy> {
+        match suffix {
+            "usize" => Some(UintTy::Usize),
+            "u8" => Some(UintTy::U8),
+            "u16" => Some(UintTy::U16),
+            "u32" => Some(UintTy::U32),
+            "u64" => Some(UintTy::U64),
+            "u128" => Some(UintTy::U128),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Debug for UintTy {
@@ -129,4 +153,48 @@ impl FloatTy {
             _ => None,
         }
     }
+
+    pub fn from_suffix(suffix: &str) -> Option<FloatTy> {
+        match suffix {
+            "f32" => Some(FloatTy::F32),
+            "f64" => Some(FloatTy::F64),
+            _ => None,
+        }
+    }
+}
+
+/// The type of an integer literal, as far as it is determined by its suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UncertainIntTy {
+    Unknown,
+    Signed(IntTy),
+    Unsigned(UintTy),
+}
+
+impl UncertainIntTy {
+    pub fn from_suffix(suffix: Option<&str>) -> UncertainIntTy {
+        if let Some(int_ty) = suffix.and_then(IntTy::from_suffix) {
+            UncertainIntTy::Signed(int_ty)
+        } else if let Some(uint_ty) = suffix.and_then(UintTy::from_suffix) {
+            UncertainIntTy::Unsigned(uint_ty)
+        } else {
+            UncertainIntTy::Unknown
+        }
+    }
+}
+
+/// The type of a float literal, as far as it is determined by its suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UncertainFloatTy {
+    Unknown,
+    Known(FloatTy),
+}
+
+impl UncertainFloatTy {
+    pub fn from_suffix(suffix: Option<&str>) -> UncertainFloatTy {
+        match suffix.and_then(FloatTy::from_suffix) {
+            Some(float_ty) => UncertainFloatTy::Known(float_ty),
+            None => UncertainFloatTy::Unknown,
+        }
+    }
 }
@@ -1,15 +1,15 @@
 use std::fmt::Write;
 use std::path::{PathBuf, Path};
 use std::fs;
+use std::sync::Arc;
 
-use ra_db::{SyntaxDatabase};
+use salsa::Database;
+use ra_db::{SyntaxDatabase, FileId, CrateGraph, CfgOptions};
+use relative_path::RelativePath;
 use ra_syntax::ast::{self, AstNode};
 use test_utils::{project_dir, assert_eq_text, read_text};
 
-use crate::{
-    source_binder,
-    mock::MockDatabase,
-};
+use crate::{source_binder, mock::MockDatabase};
 
 // These tests compare the inference results for all expressions in a file
 // against snapshots of the current results. If you change something and these
@@ -56,7 +56,7 @@ fn infer_paths() {
 fn a() -> u32 { 1 }
 
 mod b {
-    fn c() -> u32 { 1 }
+    pub fn c() -> u32 { 1 }
 }
 
 fn test() {
@@ -153,8 +153,564 @@ impl S {
     );
 }
 
+#[test]
+fn infer_method_call() {
+    check_inference(
+        r#"
+struct A { x: u32 }
+struct B;
+
+impl A {
+    fn new() -> A { A { x: 1 } }
+    fn by_ref(&self) -> B { B }
+    fn by_mut_ref(&mut self, b: B) -> &A { self }
+    fn by_value(self) -> u32 { self.x }
+}
+
+fn test(a: A, b: &A) {
+    a.by_ref();
+    a.by_mut_ref(a.by_ref());
+    a.by_value();
+    b.by_ref();
+    a.by_mut_ref(B).x;
+    a.unknown_method();
+}
+"#,
+        "0008_method.txt",
+    );
+}
+
+#[test]
+fn infer_field_autoderef() {
+    check_inference(
+        r#"
+struct A {
+    b: B,
+}
+struct B;
+
+fn test1(a: A) {
+    let a1 = a;
+    a1.b;
+    let a2 = &a;
+    a2.b;
+    let a3 = &mut a;
+    a3.b;
+    let a4 = &&&&&&&a;
+    a4.b;
+    let a5 = &mut &&mut &&mut a;
+    a5.b;
+}
+
+fn test2(a1: *const A, a2: *mut A) {
+    a1.b;
+    a2.b;
+    (*a1).b;
+}
+"#,
+        "0009_field_autoderef.txt",
+    );
+}
+
+#[test]
+fn infer_struct_generics() {
+    check_inference(
+        r#"
+struct A<T> {
+    x: T,
+}
+
+fn test(a1: A<u32>, i: i32) {
+    a1.x;
+    let a2 = A { x: i };
+    a2.x;
+    let a3 = A::<i128> { x: 1 };
+    a3.x;
+}
+"#,
+        "0010_struct_generics.txt",
+    );
+}
+
+#[test]
+fn infer_function_generics() {
+    check_inference(
+        r#"
+fn id<T>(t: T) -> T { t }
+
+fn test(a: u32, b: i128, c: u64) {
+    id(a);
+    id::<i128>(b);
+    let x: u64 = id(c);
+}
+"#,
+        "0011_function_generics.txt",
+    );
+}
+
+#[test]
+fn infer_impl_generics() {
+    check_inference(
+        r#"
+struct A<T1, T2> {
+    x: T1,
+    y: T2,
+}
+impl<Y, X> A<X, Y> {
+    fn x(self) -> X {
+        self.x
+    }
+    fn y(self) -> Y {
+        self.y
+    }
+    fn z<T>(self, t: T) -> (X, Y, T) {
+        (self.x, self.y, t)
+    }
+}
+
+fn test(x: u64, y: i64, z: i128) {
+    let a = A { x: x, y: y };
+    a.x();
+    a.y();
+    a.z(z);
+}
+"#,
+        "0012_impl_generics.txt",
+    );
+}
+
+#[test]
+fn infer_literals() {
+    check_inference(
+        r#"
+fn test() {
+    5i32;
+    5u8;
+    5;
+    5.0;
+    5e3f32;
+    "hello";
+    b"bytes";
+    'c';
+    b'b';
+    true;
+    let a: u64 = 1;
+    let b: f32 = 1.0;
+}
+"#,
+        "0013_literals.txt",
+    );
+}
+
+#[test]
+fn infer_binary_and_prefix_ops() {
+    check_inference(
+        r#"
+fn test(x: u32, f: f32, b: bool) {
+    let a = 1 + 2;
+    x + 1;
+    1 + x;
+    f * 2.0;
+    x == 1;
+    x < 2;
+    b && true;
+    x << 2u8;
+    x & 1;
+    b | false;
+    -f;
+    !b;
+    !x;
+    let mut c = 1;
+    c += x;
+    f + x;
+}
+"#,
+        "0014_ops.txt",
+    );
+}
+
+#[test]
+fn infer_tuples_arrays_and_ranges() {
+    check_inference(
+        r#"
+fn test(x: u32, s: &[u8], a: [i64; 2]) {
+    let t = (x, 1, "s");
+    let t2: (u8, f32) = (1, 1.0);
+    let arr = [1, 2, 3];
+    let arr2 = [x; 10];
+    let arr3: [u16; 2] = [1, 2];
+    arr[0];
+    s[x];
+    a[1];
+    0..x;
+    ..1;
+}
+"#,
+        "0015_tuples_arrays.txt",
+    );
+}
+
+#[test]
+fn infer_pattern() {
+    check_inference(
+        r#"
+struct A<T> {
+    x: T,
+    y: u32,
+}
+struct B(u64, bool);
+
+fn test(x: &i32, a: A<i64>, t: (u8, &str)) {
+    let y = x;
+    let &z = x;
+    let a1 = z;
+    let (c, d) = (1, "hello");
+    let (e, f) = t;
+    let A { x: g, y } = a;
+    let A { ref x, .. } = a;
+    let B(h, i) = B(1, true);
+    let [j, k] = [1u16, 2];
+    let ref mut l = y;
+    let m @ (n, o) = t;
+
+    if let (p, "s") = t {
+        p;
+    }
+    while let B(q, r) = B(2, false) {
+        q;
+    }
+    for s in unknown {
+        s;
+    }
+
+    let lambda = |a: u64, b, c: i32| { a + b; c };
+}
+"#,
+        "0016_patterns.txt",
+    );
+}
+
+#[test]
+fn infer_match() {
+    check_inference(
+        r#"
+enum E { V1, V2 }
+struct S(u32, u32);
+
+fn test(x: u32, s: S, e: E) {
+    let a = match x {
+        0 => 1,
+        n if n > 2 => x,
+        _ => 3,
+    };
+    let b = match s {
+        S(1, y) => y,
+        S(z, _) => z,
+    };
+    match e {
+        E::V1 => "one",
+        E::V2 => "two",
+    };
+}
+"#,
+        "0017_match.txt",
+    );
+}
+
+#[test]
+fn infer_enum_variants() {
+    check_inference(
+        r#"
+enum Option<T> { None, Some(T) }
+enum E { V1 { x: u32 }, V2(bool) }
+use self::Option::*;
+
+fn test(e: E) {
+    let a = Option::Some(1u8);
+    let b = Some("x");
+    let c: Option<i64> = None;
+    let d = E::V1 { x: 1 };
+    match e {
+        E::V1 { x } => x,
+        E::V2(y) => 0,
+    };
+    match a {
+        Option::Some(z) => z,
+        Option::None => 0,
+    };
+}
+"#,
+        "0020_enum_variants.txt",
+    );
+}
+
+#[test]
+fn infer_consts_statics_and_aliases() {
+    check_inference(
+        r#"
+struct S<T> { x: T }
+type Alias = S<u32>;
+type Pair<T> = (T, S<T>);
+type Cyclic = Vec<Cyclic>;
+const C: u32 = 1;
+static ST: &str = "x";
+
+fn test(a: Alias, p: Pair<i64>, c: Cyclic) {
+    a.x;
+    p;
+    c;
+    C;
+    ST;
+}
+"#,
+        "0021_consts_statics_aliases.txt",
+    );
+}
+
+#[test]
+fn infer_assoc_fn_paths() {
+    check_inference(
+        r#"
+struct A;
+impl A {
+    fn new() -> A { A }
+}
+struct G<T> { x: T }
+impl<T> G<T> {
+    fn wrap(x: T) -> G<T> { G { x } }
+}
+
+fn test() {
+    let a = A::new();
+    let g = G::wrap(1u8);
+}
+"#,
+        "0022_assoc_fn_paths.txt",
+    );
+}
+
+#[test]
+fn infer_trait_method() {
+    check_inference(
+        r#"
+trait Trait<T> {
+    fn method(&self) -> T;
+    fn same(self) -> Self;
+    fn provided(&self) -> u8 { 0 }
+}
+struct S;
+struct W<U> { inner: U }
+
+impl Trait<u32> for S {
+    fn method(&self) -> u32 { 1 }
+    fn same(self) -> S { self }
+}
+impl<U> Trait<U> for W<U> {
+    fn method(&self) -> U { self.inner }
+    fn same(self) -> W<U> { self }
+}
+
+fn test(s: S, w: W<i64>) {
+    s.same();
+    s.provided();
+    w.same();
+    w.provided();
+    s.method();
+    w.method();
+}
+"#,
+        "0018_trait_method.txt",
+    );
+}
+
+#[test]
+fn infer_closures() {
+    check_inference(
+        r#"
+struct A { field: u32 }
+struct Iter;
+
+impl Iter {
+    fn map<F: Fn(A) -> U, U>(self, f: F) -> U {}
+}
+
+fn apply(f: fn(i64) -> bool) {}
+fn call<F: FnOnce(u8) -> T, T>(f: F) -> T { f(1) }
+
+fn test(iter: Iter) {
+    let id = |x: u16| x;
+    id(1);
+    let b = |x| x;
+    b(1u32);
+    iter.map(|a| a.field);
+    apply(|x| x > 0);
+    call(|x| x);
+    let unit = || {};
+}
+"#,
+        "0019_closures.txt",
+    );
+}
+
+#[test]
+fn infer_ranges() {
+    check_inference_with_core(
+        r#"
+//- /main.rs
+fn test(x: u32, i: i64) {
+    let r = 0..x;
+    let from = i..;
+    let to = ..1u8;
+    let full = ..;
+    let incl = 1..=i;
+    let to_incl = ..=x;
+}
+
+//- /core.rs
+pub mod ops {
+    pub struct Range<Idx> { pub start: Idx, pub end: Idx }
+    pub struct RangeFrom<Idx> { pub start: Idx }
+    pub struct RangeTo<Idx> { pub end: Idx }
+    pub struct RangeFull;
+    pub struct RangeInclusive<Idx> { start: Idx, end: Idx }
+    pub struct RangeToInclusive<Idx> { pub end: Idx }
+}
+"#,
+        "0023_ranges.txt",
+    );
+}
+
+#[test]
+fn infer_methods_of_primitives_and_foreign_types() {
+    check_inference_with_core(
+        r#"
+//- /main.rs
+use core::Vec;
+
+trait MyTrait {
+    fn my_method(&self) -> i64;
+}
+
+impl MyTrait for u32 {
+    fn my_method(&self) -> i64 { 1 }
+}
+
+impl<T> MyTrait for Vec<T> {
+    fn my_method(&self) -> i64 { 2 }
+}
+
+fn test(x: u32, v: Vec<u8>) {
+    x.my_method();
+    v.my_method();
+    x.count_ones();
+    v.len();
+}
+
+//- /core.rs
+pub struct Vec<T> { buf: T }
+
+impl<T> Vec<T> {
+    pub fn len(&self) -> usize { 0 }
+}
+
+impl u32 {
+    pub fn count_ones(self) -> u32 { 0 }
+}
+"#,
+        "0024_methods_of_primitives_and_foreign_types.txt",
+    );
+}
+
+#[test]
+fn infer_cfg_disabled_duplicates() {
+    let content = r#"
+struct S {
+    #[cfg(feature = "a")]
+    field: u32,
+    #[cfg(not(feature = "a"))]
+    field: i8,
+}
+enum E {
+    #[cfg(feature = "a")]
+    V(u32),
+    #[cfg(not(feature = "a"))]
+    V(i8),
+}
+impl S {
+    #[cfg(not(feature = "a"))]
+    fn x(&self) -> i8 { 0 }
+    #[cfg(feature = "a")]
+    fn x(&self) -> u32 { 0 }
+}
+
+fn test(s: S) {
+    s.x();
+    s.field;
+    E::V;
+}
+"#;
+    let (mut db, _, file_id) = MockDatabase::with_single_file(content);
+    let mut cfg_options = CfgOptions::default();
+    cfg_options.insert_key_value("feature".into(), "a".into());
+    let mut crate_graph = CrateGraph::default();
+    crate_graph.add_crate_root_with_cfg(file_id, cfg_options);
+    db.set_crate_graph(crate_graph);
+    let result = infer_file(&db, file_id);
+    check_inference_result(content, result, "0025_cfg_disabled_duplicates.txt");
+}
+
+#[test]
+fn typing_whitespace_inside_a_function_should_not_invalidate_types() {
+    let (mut db, pos) = MockDatabase::with_position(
+        "
+        //- /lib.rs
+        fn foo() -> i32 {
+            <|>1 + 1
+        }
+    ",
+    );
+    let source_file = db.source_file(pos.file_id);
+    let fn_def = source_file
+        .syntax()
+        .descendants()
+        .find_map(ast::FnDef::cast)
+        .unwrap();
+    let func = source_binder::function_from_source(&db, pos.file_id, fn_def)
+        .unwrap()
+        .unwrap();
+    {
+        let events = db.log_executed(|| {
+            func.infer(&db).unwrap();
+        });
+        assert!(format!("{:?}", events).contains("infer"))
+    }
+
+    let new_text = "
+        fn foo() -> i32 {
+            1
+            +
+            1
+        }
+    "
+    .to_string();
+
+    db.query_mut(ra_db::FileTextQuery)
+        .set(pos.file_id, Arc::new(new_text));
+
+    {
+        let events = db.log_executed(|| {
+            func.infer(&db).unwrap();
+        });
+        assert!(!format!("{:?}", events).contains("infer"), "{:#?}", events)
+    }
+}
+
 fn infer(content: &str) -> String {
     let (db, _, file_id) = MockDatabase::with_single_file(content);
+    infer_file(&db, file_id)
+}
+
+fn infer_file(db: &MockDatabase, file_id: FileId) -> String {
     let source_file = db.source_file(file_id);
     let mut acc = String::new();
     for fn_def in source_file
@@ -162,11 +718,28 @@ fn infer(content: &str) -> String {
         .descendants()
         .filter_map(ast::FnDef::cast)
     {
-        let func = source_binder::function_from_source(&db, file_id, fn_def)
+        let func = source_binder::function_from_source(db, file_id, fn_def)
             .unwrap()
             .unwrap();
-        let inference_result = func.infer(&db).unwrap();
-        for (syntax_ptr, ty) in &inference_result.type_of {
+        let inference_result = func.infer(db).unwrap();
+        let body_syntax_mapping = func.body_syntax_mapping(db).unwrap();
+        let mut types = Vec::new();
+        for (pat, ty) in inference_result.type_of_pat.iter() {
+            let syntax_ptr = match body_syntax_mapping.pat_syntax(*pat) {
+                Some(sp) => sp,
+                None => continue,
+            };
+            types.push((syntax_ptr, ty));
+        }
+        for (expr, ty) in inference_result.type_of_expr.iter() {
+            let syntax_ptr = match body_syntax_mapping.expr_syntax(*expr) {
+                Some(sp) => sp,
+                None => continue,
+            };
+            types.push((syntax_ptr, ty));
+        }
+        types.sort_by_key(|(ptr, _)| (ptr.range().start(), ptr.range().end()));
+        for (syntax_ptr, ty) in types {
             let node = syntax_ptr.resolve(&source_file);
             write!(
                 acc,
@@ -182,8 +755,25 @@ fn infer(content: &str) -> String {
 }
 
 fn check_inference(content: &str, data_file: impl AsRef<Path>) {
+    check_inference_result(content, infer(content), data_file)
+}
+
+/// Like `check_inference`, for `/main.rs` of a fixture in which `/core.rs`
+/// is the root of a `core` dependency.
+fn check_inference_with_core(fixture: &str, data_file: impl AsRef<Path>) {
+    let (mut db, source_root) = MockDatabase::with_files(fixture);
+    let main_id = source_root.files[RelativePath::new("/main.rs")];
+    let core_id = source_root.files[RelativePath::new("/core.rs")];
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_id);
+    let core_crate = crate_graph.add_crate_root(core_id);
+    crate_graph.add_dep(main_crate, "core".into(), core_crate);
+    db.set_crate_graph(crate_graph);
+    check_inference_result(fixture, infer_file(&db, main_id), data_file)
+}
+
+fn check_inference_result(content: &str, result: String, data_file: impl AsRef<Path>) {
     let data_file_path = test_data_dir().join(data_file);
-    let result = infer(content);
 
     if !data_file_path.exists() {
         println!("File with expected result doesn't exist, creating...\n");
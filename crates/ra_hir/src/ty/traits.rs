@@ -0,0 +1,175 @@
+//! Trait solving, in a very simple form: to find out whether a type implements
+//! a trait, we look for an impl of the trait whose `Self` type can be made
+//! equal to the type by substituting the impl's generic parameters. There are
+//! no where clauses, blanket impls over bounded parameters or auto traits yet.
+
+use ra_db::Cancelable;
+
+use crate::{
+    DefId, DefKind, Module, ImplBlock, Trait,
+    db::HirDatabase,
+    type_ref::TypeRef,
+};
+use super::{Ty, Substs, method_resolution::def_crate};
+
+/// A trait with the types for its generic parameters, e.g. `Into<u32>` for
+/// `S`. The first parameter is always the `Self` type.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TraitRef {
+    pub trait_: Trait,
+    pub substs: Substs,
+}
+
+impl TraitRef {
+    pub fn self_ty(&self) -> &Ty {
+        &self.substs.0[0]
+    }
+
+    /// The trait implemented by the given impl block, in terms of the impl's
+    /// generic parameters. Returns `None` for inherent impls, or if the trait
+    /// can't be resolved.
+    pub(crate) fn for_impl(
+        db: &impl HirDatabase,
+        module: &Module,
+        impl_block: &ImplBlock,
+    ) -> Cancelable<Option<TraitRef>> {
+        let path = match impl_block.target_trait() {
+            Some(TypeRef::Path(path)) => path,
+            _ => return Ok(None),
+        };
+        let trait_def_id = ctry!(module.resolve_path(db, path)?.take_types());
+        if trait_def_id.loc(db).kind != DefKind::Trait {
+            return Ok(None);
+        }
+        let generics = impl_block.generic_params();
+        let self_ty = Ty::from_hir(
+            db,
+            module,
+            Some(impl_block),
+            &generics,
+            impl_block.target_type(),
+        )?;
+        let substs =
+            Ty::substs_from_path(db, module, Some(impl_block), &generics, path, trait_def_id)?;
+        let mut substs = substs.0.to_vec();
+        substs[0] = self_ty;
+        Ok(Some(TraitRef {
+            trait_: Trait::new(trait_def_id),
+            substs: Substs(substs.into()),
+        }))
+    }
+
+    fn subst(&self, substs: &Substs) -> TraitRef {
+        let trait_substs = self
+            .substs
+            .0
+            .iter()
+            .map(|ty| ty.clone().subst(substs))
+            .collect::<Vec<_>>();
+        TraitRef {
+            trait_: self.trait_,
+            substs: Substs(trait_substs.into()),
+        }
+    }
+}
+
+/// An impl that was found to apply to some type.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Implementation {
+    pub impl_block: ImplBlock,
+    /// The implemented trait, with the generic parameters of the impl
+    /// substituted according to the type.
+    pub trait_ref: TraitRef,
+}
+
+/// Checks whether `ty` implements the trait, returning the impl that applies.
+/// `ty` should not contain inference variables; `Ty::Unknown` in it matches
+/// anything.
+pub(crate) fn implements(
+    db: &impl HirDatabase,
+    ty: Ty,
+    trait_def_id: DefId,
+) -> Cancelable<Option<Implementation>> {
+    let trait_ = Trait::new(trait_def_id);
+    // because of the orphan rules, the impl has to be either in the crate of
+    // the trait, or in the crate of the type
+    let mut krates = Vec::new();
+    krates.extend(trait_def_id.krate(db)?);
+    krates.extend(def_crate(db, &ty)?);
+    krates.dedup();
+    for krate in krates {
+        let impls = db.impls_in_crate(krate)?;
+        for item in impls.lookup_impl_blocks_for_trait(db, &trait_) {
+            let (module, impl_block) = item?;
+            let trait_ref = match TraitRef::for_impl(db, &module, &impl_block)? {
+                Some(it) => it,
+                None => continue,
+            };
+            let param_count = impl_block.generic_params().count_params_including_parent();
+            let mut bindings = vec![None; param_count];
+            if match_impl_ty(trait_ref.self_ty(), &ty, &mut bindings) {
+                let substs = bindings
+                    .into_iter()
+                    .map(|b| b.unwrap_or(Ty::Unknown))
+                    .collect::<Vec<_>>();
+                let trait_ref = trait_ref.subst(&Substs(substs.into()));
+                return Ok(Some(Implementation {
+                    impl_block,
+                    trait_ref,
+                }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Tries to make `impl_ty` (which can contain the impl's type parameters)
+/// equal to `ty`, recording the types for the parameters in `bindings`.
+fn match_impl_ty(impl_ty: &Ty, ty: &Ty, bindings: &mut [Option<Ty>]) -> bool {
+    match (impl_ty, ty) {
+        (Ty::Param { idx, .. }, _) => match bindings.get_mut(*idx as usize) {
+            Some(Some(bound)) => bound == ty,
+            Some(binding) => {
+                *binding = Some(ty.clone());
+                true
+            }
+            None => false,
+        },
+        (_, Ty::Unknown) | (_, Ty::Infer(..)) => true,
+        (
+            Ty::Adt {
+                def_id: def_id1,
+                substs: substs1,
+                ..
+            },
+            Ty::Adt {
+                def_id: def_id2,
+                substs: substs2,
+                ..
+            },
+        ) => def_id1 == def_id2 && match_impl_substs(substs1, substs2, bindings),
+        (Ty::Ref(t1, m1), Ty::Ref(t2, m2)) | (Ty::RawPtr(t1, m1), Ty::RawPtr(t2, m2)) => {
+            m1 == m2 && match_impl_ty(t1, t2, bindings)
+        }
+        (Ty::Slice(t1), Ty::Slice(t2)) | (Ty::Array(t1), Ty::Array(t2)) => {
+            match_impl_ty(t1, t2, bindings)
+        }
+        (Ty::Tuple(ts1), Ty::Tuple(ts2)) => {
+            ts1.len() == ts2.len()
+                && ts1
+                    .iter()
+                    .zip(ts2.iter())
+                    .all(|(t1, t2)| match_impl_ty(t1, t2, bindings))
+        }
+        _ => impl_ty == ty,
+    }
+}
+
+fn match_impl_substs(substs1: &Substs, substs2: &Substs, bindings: &mut [Option<Ty>]) -> bool {
+    substs1.0.len() == substs2.0.len()
+        && substs1
+            .0
+            .iter()
+            .zip(substs2.0.iter())
+            .all(|(t1, t2)| match_impl_ty(t1, t2, bindings))
+}
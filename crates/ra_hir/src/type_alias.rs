@@ -0,0 +1,116 @@
+//! HIR for type aliases, like `type Result<T> = std::result::Result<T, Error>;`.
+//! Aliases are transparent: `Ty::from_hir` expands them to the aliased type,
+//! see `ty::type_for_def`.
+
+use std::sync::Arc;
+
+use ra_db::Cancelable;
+use ra_syntax::ast::{self, AstNode, NameOwner};
+
+use crate::{
+    DefId, DefKind, Name, AsName, Module, Path,
+    db::HirDatabase,
+    path::GenericArg,
+    type_ref::TypeRef,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeAlias {
+    def_id: DefId,
+}
+
+impl TypeAlias {
+    pub(crate) fn new(def_id: DefId) -> TypeAlias {
+        TypeAlias { def_id }
+    }
+
+    pub fn def_id(&self) -> DefId {
+        self.def_id
+    }
+
+    pub fn syntax(&self, db: &impl HirDatabase) -> ast::TypeDefNode {
+        let def_loc = self.def_id.loc(db);
+        assert!(def_loc.kind == DefKind::TypeAlias);
+        let syntax = db.file_item(def_loc.source_item_id);
+        ast::TypeDef::cast(syntax.borrowed()).unwrap().owned()
+    }
+
+    pub fn name(&self, db: &impl HirDatabase) -> Option<Name> {
+        self.syntax(db).borrowed().name().map(|n| n.as_name())
+    }
+
+    /// The aliased type. For associated types in traits, which don't have
+    /// one, this is `TypeRef::Error`.
+    pub fn type_ref(&self, db: &impl HirDatabase) -> Arc<TypeRef> {
+        db.type_alias_ref(self.def_id)
+    }
+
+    pub fn module(&self, db: &impl HirDatabase) -> Cancelable<Module> {
+        self.def_id.module(db)
+    }
+
+    /// Whether expanding the alias would eventually require expanding it
+    /// again, like for `type A = Vec<A>;`. Such aliases are errors and must
+    /// not be expanded.
+    pub(crate) fn is_cyclic(&self, db: &impl HirDatabase) -> Cancelable<bool> {
+        reaches_alias_on_stack(db, self.def_id, &mut Vec::new())
+    }
+}
+
+fn reaches_alias_on_stack(
+    db: &impl HirDatabase,
+    alias: DefId,
+    stack: &mut Vec<DefId>,
+) -> Cancelable<bool> {
+    if stack.contains(&alias) {
+        return Ok(true);
+    }
+    stack.push(alias);
+    let module = alias.module(db)?;
+    let type_ref = db.type_alias_ref(alias);
+    let mut paths = Vec::new();
+    collect_paths(&type_ref, &mut paths);
+    for path in paths {
+        let def_id = match module.resolve_path(db, path)?.take_types() {
+            Some(it) => it,
+            None => continue,
+        };
+        if def_id.loc(db).kind == DefKind::TypeAlias && reaches_alias_on_stack(db, def_id, stack)? {
+            return Ok(true);
+        }
+    }
+    stack.pop();
+    Ok(false)
+}
+
+fn collect_paths<'a>(type_ref: &'a TypeRef, acc: &mut Vec<&'a Path>) {
+    match type_ref {
+        TypeRef::Path(path) => {
+            acc.push(path);
+            let args = path
+                .segments
+                .iter()
+                .filter_map(|segment| segment.args_and_bindings.as_ref())
+                .flat_map(|args| args.args.iter());
+            for arg in args {
+                match arg {
+                    GenericArg::Type(type_ref) => collect_paths(type_ref, acc),
+                }
+            }
+        }
+        TypeRef::Tuple(inner) | TypeRef::Fn(inner) => {
+            inner.iter().for_each(|it| collect_paths(it, acc))
+        }
+        TypeRef::RawPtr(inner, _)
+        | TypeRef::Reference(inner, _)
+        | TypeRef::Array(inner)
+        | TypeRef::Slice(inner) => collect_paths(inner, acc),
+        TypeRef::Never | TypeRef::Placeholder | TypeRef::Error => (),
+    }
+}
+
+pub(crate) fn type_alias_ref(db: &impl HirDatabase, def_id: DefId) -> Arc<TypeRef> {
+    let syntax = TypeAlias::new(def_id).syntax(db);
+    let type_ref = TypeRef::from_ast_opt(syntax.borrowed().type_ref());
+    Arc::new(type_ref)
+}
@@ -80,19 +80,7 @@ impl TypeRef {
                 TypeRef::Reference(Box::new(inner_ty), mutability)
             }
             PlaceholderType(_inner) => TypeRef::Placeholder,
-            FnPointerType(inner) => {
-                let ret_ty = TypeRef::from_ast_opt(inner.ret_type().and_then(|rt| rt.type_ref()));
-                let mut params = if let Some(pl) = inner.param_list() {
-                    pl.params()
-                        .map(|p| p.type_ref())
-                        .map(TypeRef::from_ast_opt)
-                        .collect()
-                } else {
-                    Vec::new()
-                };
-                params.push(ret_ty);
-                TypeRef::Fn(params)
-            }
+            FnPointerType(inner) => TypeRef::fn_from_ast(inner.param_list(), inner.ret_type()),
             // for types are close enough for our purposes to the inner type for now...
             ForType(inner) => TypeRef::from_ast_opt(inner.type_ref()),
             ImplTraitType(_inner) => TypeRef::Error,
@@ -100,6 +88,25 @@ impl TypeRef {
         }
     }
 
+    /// Builds a fn pointer type from the parameters and return type of e.g.
+    /// `fn(i32) -> u32` or `Fn(i32) -> u32`. A missing return type is `()`.
+    pub(crate) fn fn_from_ast(
+        param_list: Option<ast::ParamList>,
+        ret_type: Option<ast::RetType>,
+    ) -> Self {
+        let mut params: Vec<_> = param_list
+            .into_iter()
+            .flat_map(|pl| pl.params())
+            .map(|p| TypeRef::from_ast_opt(p.type_ref()))
+            .collect();
+        let ret_ty = match ret_type {
+            Some(rt) => TypeRef::from_ast_opt(rt.type_ref()),
+            None => TypeRef::unit(),
+        };
+        params.push(ret_ty);
+        TypeRef::Fn(params)
+    }
+
     pub(crate) fn from_ast_opt(node: Option<ast::TypeRef>) -> Self {
         if let Some(node) = node {
             TypeRef::from_ast(node)
@@ -107,4 +114,8 @@ impl TypeRef {
             TypeRef::Error
         }
     }
+
+    pub fn unit() -> TypeRef {
+        TypeRef::Tuple(Vec::new())
+    }
 }
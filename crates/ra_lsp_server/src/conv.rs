@@ -63,8 +63,13 @@ impl Conv for CompletionItemKind {
             CompletionItemKind::Struct => Struct,
             CompletionItemKind::Enum => Enum,
             CompletionItemKind::EnumVariant => EnumMember,
+            CompletionItemKind::Trait => Interface,
+            CompletionItemKind::TypeAlias => Struct,
+            CompletionItemKind::Const => Constant,
+            CompletionItemKind::Static => Value,
             CompletionItemKind::Binding => Variable,
             CompletionItemKind::Field => Field,
+            CompletionItemKind::Method => Method,
         }
     }
 }
@@ -272,12 +277,13 @@ impl TryConvWith for SourceChange {
             }
         };
         let mut document_changes: Vec<DocumentChangeOperation> = Vec::new();
-        for resource_op in self.file_system_edits.try_conv_with(world)? {
-            document_changes.push(DocumentChangeOperation::Op(resource_op));
-        }
+        // the edits refer to the files before they are moved
         for text_document_edit in self.source_file_edits.try_conv_with(world)? {
             document_changes.push(DocumentChangeOperation::Edit(text_document_edit));
         }
+        for resource_op in self.file_system_edits.try_conv_with(world)? {
+            document_changes.push(DocumentChangeOperation::Op(resource_op));
+        }
         let workspace_edit = WorkspaceEdit {
             changes: None,
             document_changes: Some(DocumentChanges::Operations(document_changes)),
@@ -348,6 +354,15 @@ impl TryConvWith for &NavigationTarget {
     }
 }
 
+impl TryConvWith for FileRange {
+    type Ctx = ServerWorld;
+    type Output = Location;
+    fn try_conv_with(self, world: &ServerWorld) -> Result<Location> {
+        let line_index = world.analysis().file_line_index(self.file_id);
+        to_location(self.file_id, self.range, world, &line_index)
+    }
+}
+
 pub fn to_location(
     file_id: FileId,
     range: TextRange,
@@ -6,4 +6,7 @@ pub mod req;
 mod server_world;
 
 pub type Result<T> = ::std::result::Result<T, ::failure::Error>;
-pub use crate::{caps::server_capabilities, main_loop::main_loop, main_loop::LspError};
+pub use crate::{
+    caps::server_capabilities, main_loop::main_loop, main_loop::LspError,
+    project_model::CargoConfig,
+};
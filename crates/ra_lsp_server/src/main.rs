@@ -2,7 +2,7 @@ use serde::Deserialize;
 use flexi_logger::{Duplicate, Logger};
 use gen_lsp_server::{run_server, stdio_transport};
 
-use ra_lsp_server::Result;
+use ra_lsp_server::{CargoConfig, Result};
 
 fn main() -> Result<()> {
     ::std::env::set_var("RUST_BACKTRACE", "short");
@@ -31,6 +31,8 @@ struct InitializationOptions {
     // This is different to the highlightingOn setting, which is whether the user
     // wants our custom highlighting to be used.
     publish_decorations: Option<bool>,
+    // The features and the target to analyze the workspace with.
+    cargo: Option<CargoConfig>,
 }
 
 fn main_inner() -> Result<()> {
@@ -45,12 +47,13 @@ fn main_inner() -> Result<()> {
                 .root_uri
                 .and_then(|it| it.to_file_path().ok())
                 .unwrap_or(cwd);
-            let supports_decorations = params
+            let options = params
                 .initialization_options
-                .and_then(|v| InitializationOptions::deserialize(v).ok())
-                .and_then(|it| it.publish_decorations)
-                == Some(true);
-            ra_lsp_server::main_loop(false, root, supports_decorations, r, s)
+                .and_then(|v| InitializationOptions::deserialize(v).ok());
+            let supports_decorations =
+                options.as_ref().and_then(|it| it.publish_decorations) == Some(true);
+            let cargo_config = options.and_then(|it| it.cargo).unwrap_or_default();
+            ra_lsp_server::main_loop(false, root, supports_decorations, cargo_config, r, s)
         },
     )?;
     log::info!("shutting down IO...");
@@ -23,7 +23,7 @@ use failure_derive::Fail;
 
 use crate::{
     main_loop::subscriptions::Subscriptions,
-    project_model::workspace_loader,
+    project_model::{workspace_loader, CargoConfig},
     req,
     server_world::{ServerWorld, ServerWorldState},
     Result,
@@ -55,12 +55,13 @@ pub fn main_loop(
     internal_mode: bool,
     ws_root: PathBuf,
     supports_decorations: bool,
+    cargo_config: CargoConfig,
     msg_receiver: &Receiver<RawMessage>,
     msg_sender: &Sender<RawMessage>,
 ) -> Result<()> {
     let pool = ThreadPool::new(8);
     let (task_sender, task_receiver) = unbounded::<Task>();
-    let (ws_worker, ws_watcher) = workspace_loader();
+    let (ws_worker, ws_watcher) = workspace_loader(cargo_config);
 
     ws_worker.send(ws_root.clone()).unwrap();
     // FIXME: support dynamic workspace loading.
@@ -280,6 +281,8 @@ fn on_request(
     };
     let req = pool_dispatcher
         .on::<req::SyntaxTree>(handlers::handle_syntax_tree)?
+        .on::<req::ExpandMacro>(handlers::handle_expand_macro)?
+        .on::<req::CallHierarchy>(handlers::handle_call_hierarchy)?
         .on::<req::ExtendSelection>(handlers::handle_extend_selection)?
         .on::<req::FindMatchingBrace>(handlers::handle_find_matching_brace)?
         .on::<req::JoinLines>(handlers::handle_join_lines)?
@@ -288,6 +291,8 @@ fn on_request(
         .on::<req::DocumentSymbolRequest>(handlers::handle_document_symbol)?
         .on::<req::WorkspaceSymbol>(handlers::handle_workspace_symbol)?
         .on::<req::GotoDefinition>(handlers::handle_goto_definition)?
+        .on::<req::GotoTypeDefinition>(handlers::handle_goto_type_definition)?
+        .on::<req::GotoImplementation>(handlers::handle_goto_implementation)?
         .on::<req::ParentModule>(handlers::handle_parent_module)?
         .on::<req::Runnables>(handlers::handle_runnables)?
         .on::<req::DecorationsRequest>(handlers::handle_decorations)?
@@ -30,6 +30,47 @@ pub fn handle_syntax_tree(world: ServerWorld, params: req::SyntaxTreeParams) ->
     Ok(res)
 }
 
+pub fn handle_expand_macro(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<Option<req::ExpandedMacro>> {
+    let position = params.try_conv_with(&world)?;
+    let res = world
+        .analysis()
+        .expand_macro(position)?
+        .map(|it| req::ExpandedMacro {
+            name: it.name,
+            expansion: it.expansion,
+        });
+    Ok(res)
+}
+
+pub fn handle_call_hierarchy(
+    world: ServerWorld,
+    params: req::CallHierarchyParams,
+) -> Result<Vec<req::CallHierarchyItem>> {
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id);
+    let position = FilePosition {
+        file_id,
+        offset: params.position.conv_with(&line_index),
+    };
+    let calls = match params.direction {
+        req::CallHierarchyDirection::Incoming => world.analysis().incoming_calls(position)?,
+        req::CallHierarchyDirection::Outgoing => world.analysis().outgoing_calls(position)?,
+    };
+    calls
+        .into_iter()
+        .map(|call| {
+            Ok(req::CallHierarchyItem {
+                name: call.target.name().to_string(),
+                location: call.target.try_conv_with(&world)?,
+                call_sites: call.call_sites.try_conv_with(&world)?,
+            })
+        })
+        .collect()
+}
+
 pub fn handle_extend_selection(
     world: ServerWorld,
     params: req::ExtendSelectionParams,
@@ -219,6 +260,34 @@ pub fn handle_goto_definition(
     Ok(Some(req::GotoDefinitionResponse::Array(res)))
 }
 
+pub fn handle_goto_type_definition(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<Option<req::GotoTypeDefinitionResponse>> {
+    let position = params.try_conv_with(&world)?;
+    let res = world
+        .analysis()
+        .goto_type_definition(position)?
+        .into_iter()
+        .map(|nav| nav.try_conv_with(&world))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(req::GotoDefinitionResponse::Array(res)))
+}
+
+pub fn handle_goto_implementation(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<Option<req::GotoImplementationResponse>> {
+    let position = params.try_conv_with(&world)?;
+    let res = world
+        .analysis()
+        .goto_implementation(position)?
+        .into_iter()
+        .map(|nav| nav.try_conv_with(&world))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(req::GotoDefinitionResponse::Array(res)))
+}
+
 pub fn handle_parent_module(
     world: ServerWorld,
     params: req::TextDocumentPositionParams,
@@ -342,6 +411,7 @@ pub fn handle_runnables(
                 .read()
                 .file2path(ra_vfs::VfsFile(file_id.0.into()));
             let res = world.workspaces.iter().find_map(|ws| {
+                let ws = &ws.cargo;
                 let tgt = ws.target_by_root(&path)?;
                 let res = CargoTargetSpec {
                     package: tgt.package(ws).name(ws).to_string(),
@@ -533,12 +603,15 @@ pub fn handle_prepare_rename(
     // We support renaming references like handle_rename does.
     // In the future we may want to reject the renaming of things like keywords here too.
     let refs = world.analysis().find_all_refs(position)?;
-    let r = match refs.first() {
+    // the references can be in other files, so we look for the one under the
+    // cursor
+    let r = match refs.iter().find(|(file_id, range)| {
+        *file_id == position.file_id && contains_offset_nonstrict(*range, position.offset)
+    }) {
         Some(r) => r,
         None => return Ok(None),
     };
-    let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.analysis().file_line_index(position.file_id);
     let loc = to_location(r.0, r.1, &world, &line_index)?;
 
     Ok(Some(PrepareRenameResponse::Range(loc.range)))
@@ -549,25 +622,26 @@ pub fn handle_rename(world: ServerWorld, params: RenameParams) -> Result<Option<
     let line_index = world.analysis().file_line_index(file_id);
     let offset = params.position.conv_with(&line_index);
 
-    if params.new_name.is_empty() {
-        return Err(LspError::new(
-            ErrorCode::InvalidParams as i32,
-            "New Name cannot be empty".into(),
-        )
-        .into());
-    }
-
-    let renames = world
+    let change = world
         .analysis()
         .rename(FilePosition { file_id, offset }, &*params.new_name)?;
-    if renames.is_empty() {
-        return Ok(None);
+    let change = match change {
+        Ok(it) => it,
+        Err(e) => {
+            return Err(LspError::new(ErrorCode::InvalidParams as i32, e.to_string()).into());
+        }
+    };
+    // moving files needs `document_changes`
+    if !change.file_system_edits.is_empty() {
+        let change: req::SourceChange = change.try_conv_with(&world)?;
+        return Ok(Some(change.workspace_edit));
     }
 
     let mut changes = HashMap::new();
-    for edit in renames {
+    for edit in change.source_file_edits {
+        let line_index = world.analysis().file_line_index(edit.file_id);
         changes
-            .entry(file_id.try_conv_with(&world)?)
+            .entry(edit.file_id.try_conv_with(&world)?)
             .or_insert_with(Vec::new)
             .extend(edit.edit.conv_with(&line_index));
     }
@@ -594,7 +668,10 @@ pub fn handle_references(
 
     Ok(Some(
         refs.into_iter()
-            .filter_map(|r| to_location(r.0, r.1, &world, &line_index).ok())
+            .filter_map(|r| {
+                let line_index = world.analysis().file_line_index(r.0);
+                to_location(r.0, r.1, &world, &line_index).ok()
+            })
             .collect(),
     ))
 }
@@ -681,6 +758,7 @@ pub fn handle_document_highlight(
 
     Ok(Some(
         refs.into_iter()
+            .filter(|r| r.0 == file_id)
             .map(|r| DocumentHighlight {
                 range: r.1.conv_with(&line_index),
                 kind: None,
@@ -1,13 +1,30 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-use cargo_metadata::{metadata_run, CargoOpt};
+use cargo_metadata::{metadata_run, CargoOpt, Dependency, WorkspaceMember};
+use ra_analysis::CfgOptions;
 use ra_syntax::SmolStr;
 use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Deserialize;
 use failure::{format_err, bail};
 use thread_worker::{WorkerHandle, Worker};
 
 use crate::Result;
 
+mod sysroot;
+
+pub use self::sysroot::Sysroot;
+
+/// Everything we know about the project: the cargo workspace and the
+/// standard library it is compiled against.
+#[derive(Debug, Clone)]
+pub struct ProjectWorkspace {
+    pub cargo: CargoWorkspace,
+    pub sysroot: Sysroot,
+}
+
 /// `CargoWorksapce` represents the logical structure of, well, a Cargo
 /// workspace. It pretty closely mirrors `cargo metadata` output.
 ///
@@ -19,6 +36,23 @@ use crate::Result;
 pub struct CargoWorkspace {
     packages: Vec<PackageData>,
     targets: Vec<TargetData>,
+    target_cfg: CfgOptions,
+}
+
+/// The user's choice of features and of the target, which comes from the
+/// `initializationOptions`. The feature flags have the same meaning as the
+/// corresponding cargo flags and apply to the workspace members.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CargoConfig {
+    pub all_features: bool,
+    pub no_default_features: bool,
+    pub features: Vec<String>,
+    /// The target triple, like `x86_64-pc-windows-msvc`. The host by default.
+    pub target: Option<String>,
+    /// The sysroot with the sources of the standard library, the one of the
+    /// toolchain in use by default.
+    pub sysroot: Option<PathBuf>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -33,6 +67,10 @@ struct PackageData {
     targets: Vec<Target>,
     is_member: bool,
     dependencies: Vec<PackageDependency>,
+    /// Features declared in the manifest, mapped to the features (and the
+    /// optional dependencies) they enable.
+    features: FxHashMap<SmolStr, Vec<SmolStr>>,
+    enabled_features: Vec<SmolStr>,
 }
 
 #[derive(Debug, Clone)]
@@ -79,6 +117,16 @@ impl Package {
     ) -> impl Iterator<Item = &'a PackageDependency> + 'a {
         ws.pkg(self).dependencies.iter()
     }
+    #[allow(unused)]
+    pub fn features<'a>(self, ws: &'a CargoWorkspace) -> impl Iterator<Item = &'a str> + 'a {
+        ws.pkg(self).features.keys().map(|it| it.as_str())
+    }
+    pub fn enabled_features<'a>(
+        self,
+        ws: &'a CargoWorkspace,
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        ws.pkg(self).enabled_features.iter().map(|it| it.as_str())
+    }
 }
 
 impl Target {
@@ -96,9 +144,26 @@ impl Target {
     }
 }
 
+impl ProjectWorkspace {
+    pub fn discover(path: &Path, config: &CargoConfig) -> Result<ProjectWorkspace> {
+        let cargo = CargoWorkspace::from_cargo_metadata(path, config)?;
+        let sysroot = match Sysroot::discover(config.sysroot.as_deref()) {
+            Ok(it) => it,
+            Err(e) => {
+                log::warn!("failed to load the standard library: {}", e);
+                Sysroot::default()
+            }
+        };
+        Ok(ProjectWorkspace { cargo, sysroot })
+    }
+}
+
 impl CargoWorkspace {
-    pub fn from_cargo_metadata(path: &Path) -> Result<CargoWorkspace> {
+    pub fn from_cargo_metadata(path: &Path, config: &CargoConfig) -> Result<CargoWorkspace> {
         let cargo_toml = find_cargo_toml(path)?;
+        // The dependency graph with all features includes every optional
+        // dependency, and `resolve_features` figures out which of them are
+        // actually used with the selected features.
         let meta = metadata_run(
             Some(cargo_toml.as_path()),
             true,
@@ -108,6 +173,7 @@ impl CargoWorkspace {
         let mut pkg_by_id = FxHashMap::default();
         let mut packages = Vec::new();
         let mut targets = Vec::new();
+        let mut manifest_deps = Vec::new();
 
         let ws_members: FxHashSet<String> = meta
             .workspace_members
@@ -125,6 +191,15 @@ impl CargoWorkspace {
                 targets: Vec::new(),
                 is_member,
                 dependencies: Vec::new(),
+                features: meta_pkg
+                    .features
+                    .into_iter()
+                    .map(|(name, enables)| {
+                        let enables = enables.into_iter().map(SmolStr::from).collect();
+                        (name.into(), enables)
+                    })
+                    .collect(),
+                enabled_features: Vec::new(),
             };
             for meta_tgt in meta_pkg.targets {
                 let tgt = Target(targets.len());
@@ -136,24 +211,66 @@ impl CargoWorkspace {
                 });
                 pkg_data.targets.push(tgt);
             }
-            packages.push(pkg_data)
+            packages.push(pkg_data);
+            manifest_deps.push(meta_pkg.dependencies);
         }
         let resolve = meta.resolve.expect("metadata executed with deps");
+        let mut feature_deps = vec![Vec::new(); packages.len()];
         for node in resolve.nodes {
             let source = pkg_by_id[&node.id];
             for id in node.dependencies {
                 let target = pkg_by_id[&id];
-                let name: SmolStr = packages[target.0].name.replace('-', "_").into();
-                let dep = PackageDependency { name, pkg: target };
-                packages[source.0].dependencies.push(dep);
+                // the manifest refers to the package by its name and a version
+                // requirement, even if the dependency is renamed
+                let id = WorkspaceMember { raw: id };
+                let manifest_dep = manifest_deps[source.0]
+                    .iter()
+                    .filter(|it| it.name == id.name() && it.req.matches(&id.version()));
+                for dep in manifest_dep {
+                    feature_deps[source.0].push(FeatureDep::new(target, dep));
+                }
             }
         }
+        let enabled_features = resolve_features(&packages, &feature_deps, config);
+        for (idx, features) in enabled_features.into_iter().enumerate() {
+            // the metadata lists every optional dependency, but only those
+            // whose feature is enabled are used
+            for dep in feature_deps[idx].iter() {
+                let pkg_data = &packages[idx];
+                if dep.optional && !features.contains(&dep.name)
+                    || pkg_data.dependencies.iter().any(|it| it.pkg == dep.pkg)
+                {
+                    continue;
+                }
+                let name = packages[dep.pkg.0].name.replace('-', "_").into();
+                let dep = PackageDependency { name, pkg: dep.pkg };
+                packages[idx].dependencies.push(dep);
+            }
+            let mut features = features.into_iter().collect::<Vec<_>>();
+            features.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+            packages[idx].enabled_features = features;
+        }
+        let target_cfg = match rustc_cfg(config.target.as_deref()) {
+            Ok(it) => it,
+            Err(e) => {
+                log::warn!("failed to get the cfg flags of the target: {}", e);
+                CfgOptions::default()
+            }
+        };
 
-        Ok(CargoWorkspace { packages, targets })
+        Ok(CargoWorkspace {
+            packages,
+            targets,
+            target_cfg,
+        })
     }
     pub fn packages<'a>(&'a self) -> impl Iterator<Item = Package> + 'a {
         (0..self.packages.len()).map(Package)
     }
+    /// The cfg flags of the target, like `unix` and `target_os = "linux"`.
+    pub fn target_cfg(&self) -> &CfgOptions {
+        &self.target_cfg
+    }
     pub fn target_by_root(&self, root: &Path) -> Option<Target> {
         self.packages()
             .filter_map(|pkg| pkg.targets(self).find(|it| it.root(self) == root))
@@ -182,6 +299,152 @@ fn find_cargo_toml(path: &Path) -> Result<PathBuf> {
     bail!("can't find Cargo.toml at {}", path.display())
 }
 
+/// A dependency edge, as far as features are concerned.
+#[derive(Debug, Clone)]
+struct FeatureDep {
+    pkg: Package,
+    /// The name of the dependency in the manifest, which is also the name of
+    /// the implicit feature of an optional dependency.
+    name: SmolStr,
+    optional: bool,
+    uses_default_features: bool,
+    features: Vec<SmolStr>,
+}
+
+impl FeatureDep {
+    fn new(pkg: Package, dep: &Dependency) -> FeatureDep {
+        FeatureDep {
+            pkg,
+            name: dep.name.as_str().into(),
+            optional: dep.optional,
+            uses_default_features: dep.uses_default_features,
+            features: dep.features.iter().map(SmolStr::from).collect(),
+        }
+    }
+}
+
+/// A simplified version of cargo's feature resolution: the features requested
+/// for the workspace members are enabled, together with the features they
+/// imply, and then propagated along the dependency edges of the packages
+/// reachable from the members. An optional dependency only becomes an edge
+/// once its implicit feature is enabled.
+fn resolve_features(
+    packages: &[PackageData],
+    deps: &[Vec<FeatureDep>],
+    config: &CargoConfig,
+) -> Vec<FxHashSet<SmolStr>> {
+    let mut work: Vec<(Package, SmolStr)> = Vec::new();
+    let mut reached: Vec<Package> = Vec::new();
+    for (idx, pkg_data) in packages.iter().enumerate() {
+        if !pkg_data.is_member {
+            continue;
+        }
+        let pkg = Package(idx);
+        reached.push(pkg);
+        if config.all_features {
+            work.extend(pkg_data.features.keys().map(|it| (pkg, it.clone())));
+            let optional = deps[idx].iter().filter(|it| it.optional);
+            work.extend(optional.map(|it| (pkg, it.name.clone())));
+        } else {
+            if !config.no_default_features {
+                work.push((pkg, "default".into()));
+            }
+            work.extend(config.features.iter().map(|it| (pkg, it.as_str().into())));
+        }
+    }
+
+    let mut is_reached = vec![false; packages.len()];
+    let mut res = vec![FxHashSet::default(); packages.len()];
+    loop {
+        // the required dependencies of a package are used as soon as the
+        // package is
+        if let Some(pkg) = reached.pop() {
+            if !is_reached[pkg.0] {
+                is_reached[pkg.0] = true;
+                for dep in deps[pkg.0].iter().filter(|it| !it.optional) {
+                    activate_dep(&mut work, &mut reached, dep);
+                }
+            }
+            continue;
+        }
+        let (pkg, feature) = match work.pop() {
+            Some(it) => it,
+            None => break,
+        };
+        // `dep_name/feature` enables a feature of a dependency
+        if let Some(idx) = feature.find('/') {
+            let (dep_name, dep_feature) = (&feature[..idx], &feature[idx + 1..]);
+            for dep in deps[pkg.0].iter().filter(|it| it.name == dep_name) {
+                if dep.optional {
+                    work.push((pkg, dep.name.clone()));
+                }
+                work.push((dep.pkg, dep_feature.into()));
+            }
+            continue;
+        }
+        let optional_deps = deps[pkg.0]
+            .iter()
+            .filter(|it| it.optional && it.name == feature)
+            .collect::<Vec<_>>();
+        let implied = packages[pkg.0].features.get(&feature);
+        if implied.is_none() && optional_deps.is_empty() {
+            // not a feature of this package
+            continue;
+        }
+        if !res[pkg.0].insert(feature.clone()) {
+            continue;
+        }
+        work.extend(implied.into_iter().flatten().map(|it| (pkg, it.clone())));
+        for dep in optional_deps {
+            activate_dep(&mut work, &mut reached, dep);
+        }
+    }
+    res
+}
+
+fn activate_dep(work: &mut Vec<(Package, SmolStr)>, reached: &mut Vec<Package>, dep: &FeatureDep) {
+    reached.push(dep.pkg);
+    if dep.uses_default_features {
+        work.push((dep.pkg, "default".into()));
+    }
+    work.extend(dep.features.iter().map(|it| (dep.pkg, it.clone())));
+}
+
+/// Asks rustc for the cfg flags of the target, the host if `target` is `None`.
+fn rustc_cfg(target: Option<&str>) -> Result<CfgOptions> {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    let mut cmd = Command::new(rustc);
+    cmd.arg("--print").arg("cfg");
+    if let Some(target) = target {
+        cmd.arg("--target").arg(target);
+    }
+    let output = cmd.output()?;
+    if !output.status.success() {
+        bail!(
+            "rustc --print cfg failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(parse_cfg(&String::from_utf8(output.stdout)?))
+}
+
+/// Parses the output of `rustc --print cfg`, with one atom, like `unix`, or
+/// one key-value pair, like `target_os="linux"`, per line.
+fn parse_cfg(output: &str) -> CfgOptions {
+    let mut res = CfgOptions::default();
+    for line in output.lines() {
+        match line.find('=') {
+            None => res.insert_atom(line.into()),
+            Some(idx) => {
+                let key = &line[..idx];
+                let value = line[idx + 1..].trim_matches('"');
+                res.insert_key_value(key.into(), value.into());
+            }
+        }
+    }
+    res
+}
+
 impl TargetKind {
     fn new(kinds: &[String]) -> TargetKind {
         for kind in kinds {
@@ -198,16 +461,213 @@ impl TargetKind {
     }
 }
 
-pub fn workspace_loader() -> (Worker<PathBuf, Result<CargoWorkspace>>, WorkerHandle) {
-    thread_worker::spawn::<PathBuf, Result<CargoWorkspace>, _>(
+pub fn workspace_loader(
+    config: CargoConfig,
+) -> (Worker<PathBuf, Result<ProjectWorkspace>>, WorkerHandle) {
+    thread_worker::spawn::<PathBuf, Result<ProjectWorkspace>, _>(
         "workspace loader",
         1,
-        |input_receiver, output_sender| {
+        move |input_receiver, output_sender| {
             input_receiver
                 .into_iter()
-                .map(|path| CargoWorkspace::from_cargo_metadata(path.as_path()))
-                .try_for_each(|it| output_sender.send(it))
-                .unwrap()
+                .map(|path| ProjectWorkspace::discover(path.as_path(), &config))
+                .for_each(|it| output_sender.send(it).unwrap())
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, is_member: bool, features: &[(&str, &[&str])]) -> PackageData {
+        PackageData {
+            name: name.into(),
+            manifest: PathBuf::from(format!("/{}/Cargo.toml", name)),
+            targets: Vec::new(),
+            is_member,
+            dependencies: Vec::new(),
+            features: features
+                .iter()
+                .map(|(name, enables)| {
+                    let enables = enables.iter().map(|&it| SmolStr::from(it)).collect();
+                    (SmolStr::from(*name), enables)
+                })
+                .collect(),
+            enabled_features: Vec::new(),
+        }
+    }
+
+    fn dep(pkg: usize, name: &str, optional: bool, uses_default_features: bool) -> FeatureDep {
+        FeatureDep {
+            pkg: Package(pkg),
+            name: name.into(),
+            optional,
+            uses_default_features,
+            features: Vec::new(),
+        }
+    }
+
+    fn check_features(
+        packages: &[PackageData],
+        deps: &[Vec<FeatureDep>],
+        config: &CargoConfig,
+        expected: &[&[&str]],
+    ) {
+        let actual = resolve_features(packages, deps, config)
+            .into_iter()
+            .map(|features| {
+                let mut features = features.iter().map(|it| it.to_string()).collect::<Vec<_>>();
+                features.sort();
+                features
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn default_features_of_members_and_dependencies() {
+        let packages = [
+            package("foo", true, &[("default", &["a"]), ("a", &[]), ("b", &[])]),
+            package("bar", false, &[("default", &["c"]), ("c", &[])]),
+            package("baz", false, &[("default", &["d"]), ("d", &[])]),
+        ];
+        let deps = [
+            vec![dep(1, "bar", false, true), dep(2, "baz", false, false)],
+            Vec::new(),
+            Vec::new(),
+        ];
+        check_features(
+            &packages,
+            &deps,
+            &CargoConfig::default(),
+            &[&["a", "default"], &["c", "default"], &[]],
+        );
+
+        let config = CargoConfig {
+            no_default_features: true,
+            features: vec!["b".to_string()],
+            ..CargoConfig::default()
+        };
+        check_features(&packages, &deps, &config, &[&["b"], &["c", "default"], &[]]);
+    }
+
+    #[test]
+    fn features_of_dependencies_are_enabled_with_slash() {
+        let packages = [
+            package("foo", true, &[("default", &["bar/fast"])]),
+            package(
+                "bar",
+                false,
+                &[("fast", &["simd"]), ("simd", &[]), ("extra", &[])],
+            ),
+        ];
+        // `bar = { default-features = false, features = ["extra"] }`
+        let mut bar = dep(1, "bar", false, false);
+        bar.features.push("extra".into());
+        check_features(
+            &packages,
+            &[vec![bar], Vec::new()],
+            &CargoConfig::default(),
+            &[&["default"], &["extra", "fast", "simd"]],
+        );
+    }
+
+    #[test]
+    fn optional_dependencies_are_enabled_by_features() {
+        let packages = [
+            package("foo", true, &[("default", &[]), ("json", &["serde"])]),
+            package("serde", false, &[("default", &["std"]), ("std", &[])]),
+        ];
+        let deps = [vec![dep(1, "serde", true, true)], Vec::new()];
+        check_features(
+            &packages,
+            &deps,
+            &CargoConfig::default(),
+            &[&["default"], &[]],
+        );
+
+        let config = CargoConfig {
+            features: vec!["json".to_string()],
+            ..CargoConfig::default()
+        };
+        check_features(
+            &packages,
+            &deps,
+            &config,
+            &[&["default", "json", "serde"], &["default", "std"]],
+        );
+
+        let config = CargoConfig {
+            all_features: true,
+            ..CargoConfig::default()
+        };
+        check_features(
+            &packages,
+            &deps,
+            &config,
+            &[&["default", "json", "serde"], &["default", "std"]],
+        );
+    }
+
+    #[test]
+    fn dependencies_of_unused_packages_are_not_enabled() {
+        let packages = [
+            package("foo", true, &[("default", &[]), ("json", &["serde"])]),
+            package("serde", false, &[("default", &[])]),
+            package("itoa", false, &[("default", &["std"]), ("std", &[])]),
+        ];
+        let deps = [
+            vec![dep(1, "serde", true, true)],
+            vec![dep(2, "itoa", false, true)],
+            Vec::new(),
+        ];
+        check_features(
+            &packages,
+            &deps,
+            &CargoConfig::default(),
+            &[&["default"], &[], &[]],
+        );
+
+        let config = CargoConfig {
+            features: vec!["json".to_string()],
+            ..CargoConfig::default()
+        };
+        check_features(
+            &packages,
+            &deps,
+            &config,
+            &[
+                &["default", "json", "serde"],
+                &["default"],
+                &["default", "std"],
+            ],
+        );
+    }
+
+    #[test]
+    fn slash_features_enable_optional_dependencies() {
+        let packages = [
+            package("foo", true, &[("default", &["serde/derive"])]),
+            package("serde", false, &[("derive", &[])]),
+        ];
+        let deps = [vec![dep(1, "serde", true, false)], Vec::new()];
+        check_features(
+            &packages,
+            &deps,
+            &CargoConfig::default(),
+            &[&["default", "serde"], &["derive"]],
+        );
+    }
+
+    #[test]
+    fn parses_rustc_cfg_output() {
+        let cfg = parse_cfg("debug_assertions\nunix\ntarget_os=\"linux\"\nfeature=\"a\"\n");
+        assert!(cfg.check_atom(&"unix".into()));
+        assert!(cfg.check_atom(&"debug_assertions".into()));
+        assert!(!cfg.check_atom(&"windows".into()));
+        assert!(cfg.check_key_value(&"target_os".into(), &"linux".into()));
+        assert!(cfg.check_key_value(&"feature".into(), &"a".into()));
+        assert!(!cfg.check_key_value(&"target_os".into(), &"\"linux\"".into()));
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use ra_syntax::SmolStr;
+use failure::bail;
+
+use crate::Result;
+
+/// The crates of the standard library, found in the `rust-src` component of
+/// the toolchain. Like cargo packages, they are loaded from source and get
+/// their own crates in the `CrateGraph`.
+#[derive(Debug, Clone, Default)]
+pub struct Sysroot {
+    crates: Vec<SysrootCrateData>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SysrootCrate(usize);
+
+#[derive(Debug, Clone)]
+struct SysrootCrateData {
+    name: SmolStr,
+    root: PathBuf,
+    deps: Vec<SysrootCrate>,
+}
+
+/// The crates we load, with their dependencies on the earlier crates.
+const SYSROOT_CRATES: &[(&str, &[&str])] = &[
+    ("core", &[]),
+    ("alloc", &["core"]),
+    ("std", &["alloc", "core"]),
+    ("proc_macro", &["std"]),
+    ("test", &["std"]),
+];
+
+impl Sysroot {
+    /// Finds the sources of the standard library, looking at the configured
+    /// sysroot, then at the `RUST_SRC_PATH` variable and finally at the
+    /// sysroot of the toolchain in use.
+    pub fn discover(sysroot: Option<&Path>) -> Result<Sysroot> {
+        let src = match sysroot {
+            Some(sysroot) => rust_src_of_sysroot(sysroot)?,
+            None => match env::var_os("RUST_SRC_PATH") {
+                Some(it) => PathBuf::from(it),
+                None => rust_src_of_sysroot(&rustc_sysroot()?)?,
+            },
+        };
+        Sysroot::load(&src)
+    }
+
+    /// Loads the crates from the sources of the standard library, with
+    /// either the old (`libstd/lib.rs`) or the new (`std/src/lib.rs`) layout.
+    fn load(src: &Path) -> Result<Sysroot> {
+        let mut crates: Vec<SysrootCrateData> = Vec::new();
+        for &(name, deps) in SYSROOT_CRATES {
+            let candidates = [
+                src.join(format!("lib{}", name)).join("lib.rs"),
+                src.join(name).join("src").join("lib.rs"),
+            ];
+            let root = match candidates.iter().find(|it| it.exists()) {
+                Some(it) => it.clone(),
+                None => continue,
+            };
+            let deps = deps
+                .iter()
+                .filter_map(|&dep| crates.iter().position(|it| it.name == dep))
+                .map(SysrootCrate)
+                .collect();
+            crates.push(SysrootCrateData {
+                name: name.into(),
+                root,
+                deps,
+            });
+        }
+        if crates.is_empty() {
+            bail!(
+                "can't find the standard library sources in {}",
+                src.display()
+            );
+        }
+        Ok(Sysroot { crates })
+    }
+
+    pub fn std(&self) -> Option<SysrootCrate> {
+        self.by_name("std")
+    }
+
+    pub fn core(&self) -> Option<SysrootCrate> {
+        self.by_name("core")
+    }
+
+    pub fn crates<'a>(&'a self) -> impl Iterator<Item = SysrootCrate> + 'a {
+        (0..self.crates.len()).map(SysrootCrate)
+    }
+
+    /// The directories with the sources, which become library roots.
+    pub fn roots<'a>(&'a self) -> impl Iterator<Item = &'a Path> + 'a {
+        self.crates().map(move |it| it.root_dir(self))
+    }
+
+    fn by_name(&self, name: &str) -> Option<SysrootCrate> {
+        self.crates().find(|it| it.name(self) == name)
+    }
+}
+
+impl SysrootCrate {
+    pub fn name(self, sysroot: &Sysroot) -> &str {
+        sysroot.crates[self.0].name.as_str()
+    }
+    pub fn root(self, sysroot: &Sysroot) -> &Path {
+        sysroot.crates[self.0].root.as_path()
+    }
+    pub fn root_dir(self, sysroot: &Sysroot) -> &Path {
+        self.root(sysroot).parent().unwrap()
+    }
+    pub fn deps<'a>(self, sysroot: &'a Sysroot) -> impl Iterator<Item = SysrootCrate> + 'a {
+        sysroot.crates[self.0].deps.iter().cloned()
+    }
+}
+
+fn rustc_sysroot() -> Result<PathBuf> {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    let output = Command::new(rustc).arg("--print").arg("sysroot").output()?;
+    if !output.status.success() {
+        bail!(
+            "rustc --print sysroot failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(PathBuf::from(String::from_utf8(output.stdout)?.trim()))
+}
+
+fn rust_src_of_sysroot(sysroot: &Path) -> Result<PathBuf> {
+    let rust_src = sysroot.join("lib/rustlib/src/rust");
+    match ["src", "library"]
+        .iter()
+        .map(|it| rust_src.join(it))
+        .find(|it| it.exists())
+    {
+        Some(it) => Ok(it),
+        None => bail!(
+            "can't find the standard library sources in {}, is rust-src installed?",
+            sysroot.display()
+        ),
+    }
+}
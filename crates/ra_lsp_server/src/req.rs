@@ -25,6 +25,54 @@ pub struct SyntaxTreeParams {
     pub text_document: TextDocumentIdentifier,
 }
 
+pub enum ExpandMacro {}
+
+impl Request for ExpandMacro {
+    type Params = TextDocumentPositionParams;
+    type Result = Option<ExpandedMacro>;
+    const METHOD: &'static str = "m/expandMacro";
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpandedMacro {
+    pub name: String,
+    pub expansion: String,
+}
+
+pub enum CallHierarchy {}
+
+impl Request for CallHierarchy {
+    type Params = CallHierarchyParams;
+    type Result = Vec<CallHierarchyItem>;
+    const METHOD: &'static str = "m/callHierarchy";
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+    pub direction: CallHierarchyDirection,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum CallHierarchyDirection {
+    /// The callers of the function.
+    Incoming,
+    /// The functions called by the function.
+    Outgoing,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyItem {
+    pub name: String,
+    pub location: Location,
+    pub call_sites: Vec<Location>,
+}
+
 pub enum ExtendSelection {}
 
 impl Request for ExtendSelection {
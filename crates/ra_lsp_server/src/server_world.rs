@@ -9,13 +9,14 @@ use ra_analysis::{
     SourceRootId
 };
 use ra_vfs::{Vfs, VfsChange, VfsFile, VfsRoot};
-use rustc_hash::FxHashMap;
+use ra_syntax::SmolStr;
+use rustc_hash::{FxHashMap, FxHashSet};
 use relative_path::RelativePathBuf;
 use parking_lot::RwLock;
 use failure::{format_err};
 
 use crate::{
-    project_model::{CargoWorkspace, TargetKind},
+    project_model::{ProjectWorkspace, TargetKind},
     Result,
 };
 
@@ -23,26 +24,31 @@ use crate::{
 pub struct ServerWorldState {
     pub roots_to_scan: usize,
     pub root: PathBuf,
-    pub workspaces: Arc<Vec<CargoWorkspace>>,
+    pub workspaces: Arc<Vec<ProjectWorkspace>>,
     pub analysis_host: AnalysisHost,
     pub vfs: Arc<RwLock<Vfs>>,
 }
 
 pub struct ServerWorld {
-    pub workspaces: Arc<Vec<CargoWorkspace>>,
+    pub workspaces: Arc<Vec<ProjectWorkspace>>,
     pub analysis: Analysis,
     pub vfs: Arc<RwLock<Vfs>>,
 }
 
 impl ServerWorldState {
-    pub fn new(root: PathBuf, workspaces: Vec<CargoWorkspace>) -> ServerWorldState {
+    pub fn new(root: PathBuf, workspaces: Vec<ProjectWorkspace>) -> ServerWorldState {
         let mut change = AnalysisChange::new();
 
         let mut roots = Vec::new();
         roots.push(root.clone());
         for ws in workspaces.iter() {
-            for pkg in ws.packages() {
-                roots.push(pkg.root(&ws).to_path_buf());
+            for pkg in ws.cargo.packages() {
+                roots.push(pkg.root(&ws.cargo).to_path_buf());
+            }
+            for root in ws.sysroot.roots() {
+                if !roots.iter().any(|it| it == root) {
+                    roots.push(root.to_path_buf());
+                }
             }
         }
         let roots_to_scan = roots.len();
@@ -53,15 +59,76 @@ impl ServerWorldState {
         }
 
         let mut crate_graph = CrateGraph::default();
-        let mut pkg_to_lib_crate = FxHashMap::default();
-        let mut pkg_crates = FxHashMap::default();
+
+        // The workspaces usually share the sysroot, whose crates are added
+        // once, with the cfg flags of the first workspace using them.
+        let mut sysroot_crate_by_root = FxHashMap::default();
+        let mut ws_implicit_deps = Vec::new();
         for ws in workspaces.iter() {
+            let sysroot = &ws.sysroot;
+            let mut sysroot_crates = FxHashMap::default();
+            let mut new_crates = FxHashSet::default();
+            for krate in sysroot.crates() {
+                if let Some(file_id) = vfs.load(krate.root(sysroot)) {
+                    let file_id = FileId(file_id.0.into());
+                    let crate_id = *sysroot_crate_by_root.entry(file_id).or_insert_with(|| {
+                        let cfg_options = ws.cargo.target_cfg().clone();
+                        let crate_id = crate_graph.add_crate_root_with_cfg(file_id, cfg_options);
+                        new_crates.insert(crate_id);
+                        crate_id
+                    });
+                    sysroot_crates.insert(krate, crate_id);
+                }
+            }
+            for from in sysroot.crates() {
+                for to in from.deps(sysroot) {
+                    let name = to.name(sysroot);
+                    if let (Some(&from), Some(&to)) =
+                        (sysroot_crates.get(&from), sysroot_crates.get(&to))
+                    {
+                        if new_crates.contains(&from) {
+                            crate_graph.add_dep(from, name.into(), to);
+                        }
+                    }
+                }
+            }
+            // every crate implicitly depends on `std` and `core`, `#![no_std]`
+            // is handled by name resolution
+            let implicit_deps = [sysroot.std(), sysroot.core()]
+                .iter()
+                .filter_map(|&krate| {
+                    let krate = krate?;
+                    let crate_id = *sysroot_crates.get(&krate)?;
+                    Some((SmolStr::from(krate.name(sysroot)), crate_id))
+                })
+                .collect::<Vec<_>>();
+            ws_implicit_deps.push(implicit_deps);
+        }
+
+        for (ws, implicit_deps) in workspaces.iter().zip(ws_implicit_deps) {
+            // `Package`s are indices into their workspace
+            let mut pkg_to_lib_crate = FxHashMap::default();
+            let mut pkg_crates = FxHashMap::default();
+            let ws = &ws.cargo;
             for pkg in ws.packages() {
                 for tgt in pkg.targets(ws) {
                     let root = tgt.root(ws);
                     if let Some(file_id) = vfs.load(root) {
                         let file_id = FileId(file_id.0.into());
-                        let crate_id = crate_graph.add_crate_root(file_id);
+                        let mut cfg_options = ws.target_cfg().clone();
+                        for feature in pkg.enabled_features(ws) {
+                            cfg_options.insert_key_value("feature".into(), feature.into());
+                        }
+                        // the workspace is what is edited and tested, so its
+                        // tests are analyzed, like with `cargo test`
+                        if pkg.is_member(ws) {
+                            cfg_options.insert_atom("test".into());
+                            cfg_options.insert_atom("debug_assertions".into());
+                        }
+                        let crate_id = crate_graph.add_crate_root_with_cfg(file_id, cfg_options);
+                        for (name, dep) in implicit_deps.iter() {
+                            crate_graph.add_dep(crate_id, name.clone(), *dep);
+                        }
                         if tgt.kind(ws) == TargetKind::Lib {
                             pkg_to_lib_crate.insert(pkg, crate_id);
                         }
@@ -4,11 +4,12 @@ use languageserver_types::{
     CodeActionContext, DocumentFormattingParams, FormattingOptions, Position, Range,
 };
 use ra_lsp_server::req::{
-    CodeActionParams, CodeActionRequest, Formatting, Runnables, RunnablesParams,
+    CodeActionParams, CodeActionRequest, Completion, CompletionParams, Formatting, Runnables,
+    RunnablesParams,
 };
 use serde_json::json;
 
-use crate::support::project;
+use crate::support::{project, project_with_sysroot};
 
 const LOG: &'static str = "";
 
@@ -233,3 +234,271 @@ fn main() {}
         json!([]),
     );
 }
+
+#[test]
+fn test_completion_from_sysroot() {
+    let server = project_with_sysroot(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+use std::option::;
+use core::option::;
+"#,
+        r#"
+//- lib/rustlib/src/rust/src/libcore/lib.rs
+pub mod option {
+    pub enum Option<T> { None, Some(T) }
+}
+
+//- lib/rustlib/src/rust/src/liballoc/lib.rs
+
+//- lib/rustlib/src/rust/src/libstd/lib.rs
+pub use core::option;
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    let completions = json!([
+      {
+        "filterText": "self",
+        "insertText": "self",
+        "insertTextFormat": 1,
+        "kind": 14,
+        "label": "self"
+      },
+      {
+        "filterText": "super",
+        "insertText": "super",
+        "insertTextFormat": 1,
+        "kind": 14,
+        "label": "super"
+      },
+      {
+        "filterText": "Option",
+        "insertText": "Option",
+        "insertTextFormat": 1,
+        "kind": 13,
+        "label": "Option"
+      }
+    ]);
+    server.request::<Completion>(
+        CompletionParams {
+            text_document: server.doc_id("src/lib.rs"),
+            position: Position::new(0, 17),
+            context: None,
+        },
+        completions.clone(),
+    );
+    server.request::<Completion>(
+        CompletionParams {
+            text_document: server.doc_id("src/lib.rs"),
+            position: Position::new(1, 18),
+            context: None,
+        },
+        completions,
+    );
+}
+
+#[test]
+fn test_completion_from_prelude() {
+    let server = project_with_sysroot(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+struct Vec;
+struct Foo { x:  }
+"#,
+        r#"
+//- lib/rustlib/src/rust/src/libcore/lib.rs
+pub mod option {
+    pub enum Option<T> { None, Some(T) }
+}
+pub mod prelude {
+    pub mod v1 {
+        pub use crate::option::Option;
+    }
+}
+
+//- lib/rustlib/src/rust/src/liballoc/lib.rs
+
+//- lib/rustlib/src/rust/src/libstd/lib.rs
+pub use core::option;
+pub mod vec {
+    pub struct Vec<T>;
+}
+pub mod prelude {
+    pub mod v1 {
+        pub use crate::option::Option;
+        pub use crate::vec::Vec;
+    }
+}
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    server.request::<Completion>(
+        CompletionParams {
+            text_document: server.doc_id("src/lib.rs"),
+            position: Position::new(1, 16),
+            context: None,
+        },
+        // the local `Vec` shadows the one from the prelude
+        json!([
+        {
+          "filterText": "std",
+          "insertText": "std",
+          "insertTextFormat": 1,
+          "kind": 9,
+          "label": "std"
+        },
+        {
+          "filterText": "core",
+          "insertText": "core",
+          "insertTextFormat": 1,
+          "kind": 9,
+          "label": "core"
+        },
+        {
+          "filterText": "Foo",
+          "insertText": "Foo",
+          "insertTextFormat": 1,
+          "kind": 22,
+          "label": "Foo"
+        },
+        {
+          "filterText": "Vec",
+          "insertText": "Vec",
+          "insertTextFormat": 1,
+          "kind": 22,
+          "label": "Vec"
+        },
+        {
+          "filterText": "Option",
+          "insertText": "Option",
+          "insertTextFormat": 1,
+          "kind": 13,
+          "label": "Option"
+        }
+          ]),
+    );
+}
+
+#[test]
+fn test_completion_in_cfg_test_module() {
+    let server = project_with_sysroot(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+pub struct Foo;
+#[cfg(test)]
+mod tests {
+    use super::Foo;
+    fn f() { let _ = F }
+}
+"#,
+        r#"
+//- lib/rustlib/src/rust/src/libcore/lib.rs
+
+//- lib/rustlib/src/rust/src/liballoc/lib.rs
+
+//- lib/rustlib/src/rust/src/libstd/lib.rs
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    server.request::<Completion>(
+        CompletionParams {
+            text_document: server.doc_id("src/lib.rs"),
+            position: Position::new(4, 22),
+            context: None,
+        },
+        json!([
+          {
+            "filterText": "if",
+            "insertText": "if $0 {}",
+            "insertTextFormat": 2,
+            "kind": 14,
+            "label": "if"
+          },
+          {
+            "filterText": "match",
+            "insertText": "match $0 {}",
+            "insertTextFormat": 2,
+            "kind": 14,
+            "label": "match"
+          },
+          {
+            "filterText": "while",
+            "insertText": "while $0 {}",
+            "insertTextFormat": 2,
+            "kind": 14,
+            "label": "while"
+          },
+          {
+            "filterText": "loop",
+            "insertText": "loop {$0}",
+            "insertTextFormat": 2,
+            "kind": 14,
+            "label": "loop"
+          },
+          {
+            "filterText": "return",
+            "insertText": "return",
+            "insertTextFormat": 2,
+            "kind": 14,
+            "label": "return"
+          },
+          {
+            "filterText": "pd",
+            "insertText": "eprintln!(\"$0 = {:?}\", $0);",
+            "insertTextFormat": 2,
+            "kind": 15,
+            "label": "pd"
+          },
+          {
+            "filterText": "ppd",
+            "insertText": "eprintln!(\"$0 = {:#?}\", $0);",
+            "insertTextFormat": 2,
+            "kind": 15,
+            "label": "ppd"
+          },
+          {
+            "filterText": "std",
+            "insertText": "std",
+            "insertTextFormat": 1,
+            "kind": 9,
+            "label": "std"
+          },
+          {
+            "filterText": "Foo",
+            "insertText": "Foo",
+            "insertTextFormat": 1,
+            "kind": 22,
+            "label": "Foo"
+          },
+          {
+            "filterText": "f",
+            "insertText": "f()$0",
+            "insertTextFormat": 2,
+            "kind": 3,
+            "label": "f"
+          },
+          {
+            "filterText": "core",
+            "insertText": "core",
+            "insertTextFormat": 1,
+            "kind": 9,
+            "label": "core"
+          }
+        ]),
+    );
+}
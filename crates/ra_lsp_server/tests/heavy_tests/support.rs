@@ -1,7 +1,7 @@
 use std::{
     cell::{Cell, RefCell},
     fs,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Once,
     time::Duration,
 };
@@ -21,46 +21,84 @@ use thread_worker::{WorkerHandle, Worker};
 use test_utils::{parse_fixture, find_mismatch};
 
 use ra_lsp_server::{
-    main_loop, req,
+    main_loop, req, CargoConfig,
 };
 
+/// The standard library of `project`: empty crates, so that the tests don't
+/// depend on the sources of the installed toolchain.
+const EMPTY_SYSROOT: &str = "
+//- lib/rustlib/src/rust/src/libcore/lib.rs
+
+//- lib/rustlib/src/rust/src/liballoc/lib.rs
+
+//- lib/rustlib/src/rust/src/libstd/lib.rs
+";
+
 pub fn project(fixture: &str) -> Server {
+    project_with_sysroot(fixture, EMPTY_SYSROOT)
+}
+
+/// Like `project`, but the standard library comes from the `sysroot` fixture,
+/// rather than from the toolchain.
+pub fn project_with_sysroot(fixture: &str, sysroot: &str) -> Server {
+    let sysroot_dir = TempDir::new().unwrap();
+    write_fixture(sysroot_dir.path(), sysroot);
+    let config = CargoConfig {
+        sysroot: Some(sysroot_dir.path().to_path_buf()),
+        ..CargoConfig::default()
+    };
+    project_with_config(fixture, Some(sysroot_dir), config)
+}
+
+fn project_with_config(fixture: &str, sysroot_dir: Option<TempDir>, config: CargoConfig) -> Server {
     static INIT: Once = Once::new();
     INIT.call_once(|| Logger::with_env_or_str(crate::LOG).start().unwrap());
 
     let tmp_dir = TempDir::new().unwrap();
-    let mut paths = vec![];
+    let paths = write_fixture(tmp_dir.path(), fixture);
+    Server::new(tmp_dir, sysroot_dir, config, paths)
+}
 
+fn write_fixture(dir: &Path, fixture: &str) -> Vec<(PathBuf, String)> {
+    let mut paths = vec![];
     for entry in parse_fixture(fixture) {
-        let path = tmp_dir.path().join(entry.meta);
+        let path = dir.join(entry.meta);
         fs::create_dir_all(path.parent().unwrap()).unwrap();
         fs::write(path.as_path(), entry.text.as_bytes()).unwrap();
         paths.push((path, entry.text));
     }
-    Server::new(tmp_dir, paths)
+    paths
 }
 
 pub struct Server {
     req_id: Cell<u64>,
     messages: RefCell<Vec<RawMessage>>,
     dir: TempDir,
+    // keeps the fake standard library alive
+    _sysroot_dir: Option<TempDir>,
     worker: Option<Worker<RawMessage, RawMessage>>,
     watcher: Option<WorkerHandle>,
 }
 
 impl Server {
-    fn new(dir: TempDir, files: Vec<(PathBuf, String)>) -> Server {
+    fn new(
+        dir: TempDir,
+        sysroot_dir: Option<TempDir>,
+        config: CargoConfig,
+        files: Vec<(PathBuf, String)>,
+    ) -> Server {
         let path = dir.path().to_path_buf();
         let (worker, watcher) = thread_worker::spawn::<RawMessage, RawMessage, _>(
             "test server",
             128,
             move |mut msg_receiver, mut msg_sender| {
-                main_loop(true, path, true, &mut msg_receiver, &mut msg_sender).unwrap()
+                main_loop(true, path, true, config, &mut msg_receiver, &mut msg_sender).unwrap()
             },
         );
         let res = Server {
             req_id: Cell::new(1),
             dir,
+            _sysroot_dir: sysroot_dir,
             messages: Default::default(),
             worker: Some(worker),
             watcher: Some(watcher),
@@ -0,0 +1,161 @@
+//! `ra_macros` implements `macro_rules!`, also known as "macro by example".
+//!
+//! Macros work on token trees (see the `tt` module) rather than on syntax
+//! trees: the arguments of a call are converted to a token tree, matched
+//! against the rules of the definition, and the matching rule's right hand
+//! side is transcribed into a new token tree.
+//!
+//! This crate knows nothing about name resolution: finding the definition of
+//! a macro and parsing the expansion is the job of `ra_hir`.
+
+pub mod tt;
+mod mbe;
+mod mbe_parser;
+mod mbe_expander;
+mod tt_cursor;
+mod syntax_bridge;
+
+pub use crate::{
+    mbe::MacroRules,
+    syntax_bridge::{ast_to_token_tree, TokenMap},
+};
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::{ast, AstNode, SourceFileNode};
+
+    use super::*;
+
+    fn parse_macro(macro_definition: &str) -> MacroRules {
+        let source_file = SourceFileNode::parse(macro_definition);
+        let macro_definition = source_file
+            .syntax()
+            .descendants()
+            .find_map(ast::MacroCall::cast)
+            .unwrap();
+        let (definition_tt, _) = ast_to_token_tree(macro_definition.token_tree().unwrap()).unwrap();
+        MacroRules::parse(&definition_tt).unwrap()
+    }
+
+    fn expand(rules: &MacroRules, invocation: &str) -> Option<String> {
+        let source_file = SourceFileNode::parse(invocation);
+        let macro_invocation = source_file
+            .syntax()
+            .descendants()
+            .find_map(ast::MacroCall::cast)
+            .unwrap();
+        let (invocation_tt, _) = ast_to_token_tree(macro_invocation.token_tree().unwrap()).unwrap();
+        rules.expand(&invocation_tt).map(|it| it.to_string())
+    }
+
+    fn check(macro_definition: &str, invocation: &str, expansion: &str) {
+        let rules = parse_macro(macro_definition);
+        assert_eq!(expand(&rules, invocation), Some(expansion.to_string()));
+    }
+
+    #[test]
+    fn test_convert_tt() {
+        let source_file = SourceFileNode::parse("foo!{ a::b => (1, 'x') [-] 'a }");
+        let macro_call = source_file
+            .syntax()
+            .descendants()
+            .find_map(ast::MacroCall::cast)
+            .unwrap();
+        let (tt, token_map) = ast_to_token_tree(macro_call.token_tree().unwrap()).unwrap();
+        assert_eq!(tt.to_string(), "{a :: b => (1 , 'x') [-] 'a}");
+        let b = match &tt.token_trees[3] {
+            tt::TokenTree::Leaf(tt::Leaf::Ident(it)) => it,
+            _ => panic!("expected an ident"),
+        };
+        assert_eq!(
+            token_map.relative_range_of(b.id),
+            Some(ra_syntax::TextRange::from_to(5.into(), 6.into()))
+        );
+    }
+
+    #[test]
+    fn test_expand_simple() {
+        check(
+            "macro_rules! foo { ($i:ident) => { fn $i() {} } }",
+            "foo! { bar }",
+            "fn bar () {}",
+        );
+    }
+
+    #[test]
+    fn test_expand_picks_matching_rule() {
+        let rules = parse_macro(
+            "
+            macro_rules! foo {
+                (struct $i:ident) => { struct $i; };
+                (fn $i:ident) => { fn $i() {} };
+            }
+            ",
+        );
+        assert_eq!(expand(&rules, "foo!(fn f)").unwrap(), "fn f () {}");
+        assert_eq!(expand(&rules, "foo!(struct S)").unwrap(), "struct S ;");
+        assert_eq!(expand(&rules, "foo!(enum E)"), None);
+    }
+
+    #[test]
+    fn test_expand_repetitions() {
+        check(
+            "macro_rules! foo { ($($i:ident),*) => { $(fn $i() {})* } }",
+            "foo! { a, b, c }",
+            "fn a () {} fn b () {} fn c () {}",
+        );
+        check(
+            "macro_rules! foo { ($($i:ident),*) => { $(fn $i() {})* } }",
+            "foo! { }",
+            "",
+        );
+        check(
+            "macro_rules! foo { ($($i:ident)+) => { [$($i),+] } }",
+            "foo! { a b }",
+            "[a , b]",
+        );
+    }
+
+    #[test]
+    fn test_expand_nested_repetitions() {
+        check(
+            "
+            macro_rules! foo {
+                ($($s:ident { $($f:ident : $t:ty),* })*) => {
+                    $(struct $s { $($f: $t),* })*
+                }
+            }
+            ",
+            "foo! { A { x: u32, y: Vec<u8> } B { } }",
+            "struct A {x : u32 , y : Vec < u8 >} struct B {}",
+        );
+    }
+
+    #[test]
+    fn test_expand_fragments() {
+        check(
+            "macro_rules! foo { ($e:expr; $t:ty => $b:block) => { fn f() -> $t { $e; $b } } }",
+            "foo!(1 + 2; Option<(u32, i32)> => { 92 })",
+            "fn f () -> Option < (u32 , i32) > {1 + 2 ; {92}}",
+        );
+        check(
+            "macro_rules! foo { ($v:vis $i:ident $($l:literal)?) => { $v const $i: i32 = $($l)?; } }",
+            "foo!(pub(crate) X 92)",
+            "pub (crate) const X : i32 = 92 ;",
+        );
+        check(
+            "macro_rules! foo { ($($i:item)*) => { mod m { $($i)* } } }",
+            "foo! { fn f() {} struct S; }",
+            "mod m {fn f () {} struct S ;}",
+        );
+    }
+
+    #[test]
+    fn test_expand_dollar_crate() {
+        check(
+            "macro_rules! foo { () => { $crate::bar!(); } }",
+            "foo!()",
+            "$crate :: bar ! () ;",
+        );
+    }
+}
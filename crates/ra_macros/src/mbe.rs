@@ -0,0 +1,112 @@
+//! The parsed form of a `macro_rules!` definition. Patterns and templates are
+//! token trees extended with `$var` and `$(...) sep op` nodes.
+
+use ra_syntax::SmolStr;
+
+use crate::tt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MacroRules {
+    pub(crate) rules: Vec<Rule>,
+}
+
+impl MacroRules {
+    /// Parses the body of a `macro_rules!` definition, that is, the token
+    /// tree with the rules.
+    pub fn parse(tt: &tt::Subtree) -> Option<MacroRules> {
+        crate::mbe_parser::parse(tt)
+    }
+
+    /// Expands a call of the macro with the given arguments, using the first
+    /// matching rule. The expansion has `tt::Delimiter::None`, and keeps the
+    /// token ids of the tokens which come from the arguments. `$crate` is
+    /// kept as a `$crate` identifier: it is up to the caller to replace it
+    /// with a path to the crate of the definition.
+    pub fn expand(&self, tt: &tt::Subtree) -> Option<tt::Subtree> {
+        crate::mbe_expander::expand(self, tt)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Rule {
+    pub(crate) lhs: Subtree,
+    pub(crate) rhs: Subtree,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TokenTree {
+    Leaf(Leaf),
+    Subtree(Subtree),
+    Repeat(Repeat),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Leaf {
+    Literal(tt::Literal),
+    Punct(tt::Punct),
+    Ident(tt::Ident),
+    Var(Var),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Subtree {
+    pub(crate) delimiter: tt::Delimiter,
+    pub(crate) token_trees: Vec<TokenTree>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Repeat {
+    pub(crate) subtree: Subtree,
+    pub(crate) kind: RepeatKind,
+    pub(crate) separator: Option<tt::Leaf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RepeatKind {
+    ZeroOrMore,
+    OneOrMore,
+    ZeroOrOne,
+}
+
+/// `$name:kind` in patterns, `$name` in templates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Var {
+    pub(crate) text: SmolStr,
+    pub(crate) kind: Option<SmolStr>,
+}
+
+macro_rules! impl_froms {
+    ($e:ident: $($v:ident),*) => {
+        $(
+            impl From<$v> for $e {
+                fn from(it: $v) -> $e {
+                    $e::$v(it)
+                }
+            }
+        )*
+    }
+}
+
+impl_froms!(TokenTree: Leaf, Subtree, Repeat);
+impl_froms!(Leaf: Var);
+
+impl Subtree {
+    /// Names of all variables in the subtree, including the nested
+    /// repetitions.
+    pub(crate) fn vars(&self) -> Vec<&SmolStr> {
+        let mut acc = Vec::new();
+        collect_vars(self, &mut acc);
+        return acc;
+
+        fn collect_vars<'a>(subtree: &'a Subtree, acc: &mut Vec<&'a SmolStr>) {
+            for tt in subtree.token_trees.iter() {
+                match tt {
+                    TokenTree::Leaf(Leaf::Var(var)) => acc.push(&var.text),
+                    TokenTree::Leaf(_) => (),
+                    TokenTree::Subtree(it) => collect_vars(it, acc),
+                    TokenTree::Repeat(it) => collect_vars(&it.subtree, acc),
+                }
+            }
+        }
+    }
+}
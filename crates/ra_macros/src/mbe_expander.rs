@@ -0,0 +1,385 @@
+use rustc_hash::FxHashMap;
+use ra_syntax::SmolStr;
+
+use crate::{
+    mbe,
+    tt,
+    tt_cursor::TtCursor,
+};
+
+pub(crate) fn expand(rules: &mbe::MacroRules, input: &tt::Subtree) -> Option<tt::Subtree> {
+    rules.rules.iter().find_map(|it| expand_rule(it, input))
+}
+
+fn expand_rule(rule: &mbe::Rule, input: &tt::Subtree) -> Option<tt::Subtree> {
+    let mut input = TtCursor::new(input);
+    let bindings = match_lhs(&rule.lhs, &mut input)?;
+    if !input.is_eof() {
+        return None;
+    }
+    expand_subtree(&rule.rhs, &bindings, &mut Vec::new())
+}
+
+/// The token trees matched by the variables of a rule. A variable inside of
+/// a repetition is bound to a `Binding::Nested`, with one binding per
+/// iteration.
+#[derive(Debug, Default)]
+struct Bindings {
+    inner: FxHashMap<SmolStr, Binding>,
+}
+
+#[derive(Debug)]
+enum Binding {
+    Simple(tt::TokenTree),
+    Nested(Vec<Binding>),
+}
+
+impl Bindings {
+    /// Looks up the variable in the current iteration of each enclosing
+    /// repetition.
+    fn get(&self, name: &SmolStr, nesting: &[usize]) -> Option<&Binding> {
+        let mut b = self.inner.get(name)?;
+        for &idx in nesting.iter() {
+            b = match b {
+                Binding::Simple(_) => break,
+                Binding::Nested(bindings) => bindings.get(idx)?,
+            };
+        }
+        Some(b)
+    }
+
+    fn push_nested(&mut self, nested: Bindings) -> Option<()> {
+        for (key, value) in nested.inner {
+            match self
+                .inner
+                .entry(key)
+                .or_insert_with(|| Binding::Nested(Vec::new()))
+            {
+                Binding::Nested(it) => it.push(value),
+                Binding::Simple(_) => return None,
+            }
+        }
+        Some(())
+    }
+
+    fn merge(&mut self, nested: Bindings) {
+        self.inner.extend(nested.inner);
+    }
+}
+
+fn match_lhs(pattern: &mbe::Subtree, input: &mut TtCursor) -> Option<Bindings> {
+    let mut res = Bindings::default();
+    for pat in pattern.token_trees.iter() {
+        match pat {
+            mbe::TokenTree::Leaf(leaf) => match leaf {
+                mbe::Leaf::Var(mbe::Var { text, kind }) => {
+                    let kind = kind.as_ref()?;
+                    let tt = match_fragment(kind, input)?;
+                    res.inner.insert(text.clone(), Binding::Simple(tt));
+                }
+                mbe::Leaf::Punct(punct) => {
+                    if input.eat_punct()?.char != punct.char {
+                        return None;
+                    }
+                }
+                mbe::Leaf::Ident(ident) => {
+                    if input.eat_ident()?.text != ident.text {
+                        return None;
+                    }
+                }
+                mbe::Leaf::Literal(literal) => {
+                    if input.eat_literal()?.text != literal.text {
+                        return None;
+                    }
+                }
+            },
+            mbe::TokenTree::Subtree(subtree) => {
+                let input_subtree = input.eat_subtree()?;
+                if subtree.delimiter != input_subtree.delimiter {
+                    return None;
+                }
+                let mut input = TtCursor::new(input_subtree);
+                let bindings = match_lhs(subtree, &mut input)?;
+                if !input.is_eof() {
+                    return None;
+                }
+                res.merge(bindings);
+            }
+            mbe::TokenTree::Repeat(mbe::Repeat {
+                subtree,
+                kind,
+                separator,
+            }) => {
+                let mut count = 0;
+                loop {
+                    let mut fork = input.clone();
+                    if count > 0 {
+                        if let Some(separator) = separator {
+                            if !eat_separator(&mut fork, separator) {
+                                break;
+                            }
+                        }
+                    }
+                    let nested = match match_lhs(subtree, &mut fork) {
+                        Some(it) => it,
+                        None => break,
+                    };
+                    // a repetition which doesn't consume anything would
+                    // match forever
+                    if fork.pos() == input.pos() {
+                        break;
+                    }
+                    *input = fork;
+                    res.push_nested(nested)?;
+                    count += 1;
+                    if *kind == mbe::RepeatKind::ZeroOrOne {
+                        break;
+                    }
+                }
+                if *kind == mbe::RepeatKind::OneOrMore && count == 0 {
+                    return None;
+                }
+                // make sure that the template knows that there were zero
+                // iterations
+                for var in subtree.vars() {
+                    res.inner
+                        .entry(var.clone())
+                        .or_insert_with(|| Binding::Nested(Vec::new()));
+                }
+            }
+        }
+    }
+    Some(res)
+}
+
+fn eat_separator(input: &mut TtCursor, separator: &tt::Leaf) -> bool {
+    let matches = match (input.current(), separator) {
+        (Some(tt::TokenTree::Leaf(tt::Leaf::Punct(a))), tt::Leaf::Punct(b)) => a.char == b.char,
+        (Some(tt::TokenTree::Leaf(tt::Leaf::Ident(a))), tt::Leaf::Ident(b)) => a.text == b.text,
+        (Some(tt::TokenTree::Leaf(tt::Leaf::Literal(a))), tt::Leaf::Literal(b)) => a.text == b.text,
+        _ => false,
+    };
+    if matches {
+        input.bump();
+    }
+    matches
+}
+
+/// Matches a `$var:kind` fragment. We don't have a parser for token trees,
+/// so fragments which are syntax nodes are approximated by the tokens up to
+/// the next separator.
+fn match_fragment(kind: &SmolStr, input: &mut TtCursor) -> Option<tt::TokenTree> {
+    let res: tt::TokenTree = match kind.as_str() {
+        "ident" => input.eat_ident()?.clone().into(),
+        "tt" => input.eat()?.clone(),
+        "literal" => input.eat_literal()?.clone().into(),
+        "lifetime" => {
+            let ident = input.at_ident()?;
+            if !ident.text.starts_with('\'') {
+                return None;
+            }
+            input.bump();
+            ident.clone().into()
+        }
+        "block" => {
+            let subtree = input.at_subtree()?;
+            if subtree.delimiter != tt::Delimiter::Brace {
+                return None;
+            }
+            input.bump();
+            subtree.clone().into()
+        }
+        "vis" => {
+            let mut token_trees = Vec::new();
+            if let Some(ident) = input.at_ident() {
+                if ident.text == "pub" {
+                    input.bump();
+                    token_trees.push(ident.clone().into());
+                    if let Some(subtree) = input.at_subtree() {
+                        if subtree.delimiter == tt::Delimiter::Parenthesis {
+                            input.bump();
+                            token_trees.push(subtree.clone().into());
+                        }
+                    }
+                }
+            }
+            tt::Subtree {
+                delimiter: tt::Delimiter::None,
+                token_trees,
+            }
+            .into()
+        }
+        "expr" | "ty" | "path" | "pat" | "stmt" | "item" | "meta" => {
+            let mut token_trees = eat_fragment(kind, input);
+            match token_trees.len() {
+                0 => return None,
+                1 => token_trees.pop().unwrap(),
+                _ => tt::Subtree {
+                    delimiter: tt::Delimiter::None,
+                    token_trees,
+                }
+                .into(),
+            }
+        }
+        _ => return None,
+    };
+    Some(res)
+}
+
+fn eat_fragment(kind: &str, input: &mut TtCursor) -> Vec<tt::TokenTree> {
+    let mut res = Vec::new();
+    // depth of `<>` in types and paths
+    let mut angle_depth = 0usize;
+    while let Some(tt) = input.current() {
+        if let tt::TokenTree::Leaf(tt::Leaf::Punct(punct)) = tt {
+            if at_fat_arrow(input) {
+                break;
+            }
+            match (punct.char, kind) {
+                (',', _) | (';', _) if angle_depth == 0 => {
+                    if kind == "item" && punct.char == ';' {
+                        res.push(tt.clone());
+                        input.bump();
+                    }
+                    break;
+                }
+                ('=', "ty") | ('=', "path") | ('=', "pat") | ('|', "pat") if angle_depth == 0 => {
+                    break
+                }
+                ('<', "ty") | ('<', "path") => angle_depth += 1,
+                ('>', "ty") | ('>', "path") => {
+                    if angle_depth == 0 {
+                        break;
+                    }
+                    angle_depth -= 1;
+                }
+                ('-', _) if at_thin_arrow(input) => {
+                    // don't treat `>` of the `->` as a closing angle bracket
+                    res.push(tt.clone());
+                    input.bump();
+                    res.push(input.eat().unwrap().clone());
+                    continue;
+                }
+                _ => (),
+            }
+        }
+        if let tt::TokenTree::Subtree(subtree) = tt {
+            let is_brace = subtree.delimiter == tt::Delimiter::Brace;
+            if is_brace && angle_depth == 0 && (kind == "ty" || kind == "path") {
+                break;
+            }
+            res.push(tt.clone());
+            input.bump();
+            if is_brace && kind == "item" {
+                break;
+            }
+            continue;
+        }
+        res.push(tt.clone());
+        input.bump();
+    }
+    res
+}
+
+fn at_fat_arrow(input: &TtCursor) -> bool {
+    at_joint_pair(input, '=', '>')
+}
+
+fn at_thin_arrow(input: &TtCursor) -> bool {
+    at_joint_pair(input, '-', '>')
+}
+
+fn at_joint_pair(input: &TtCursor, first: char, second: char) -> bool {
+    match (input.current(), input.nth(1)) {
+        (
+            Some(tt::TokenTree::Leaf(tt::Leaf::Punct(a))),
+            Some(tt::TokenTree::Leaf(tt::Leaf::Punct(b))),
+        ) => a.char == first && a.spacing == tt::Spacing::Joint && b.char == second,
+        _ => false,
+    }
+}
+
+fn expand_subtree(
+    template: &mbe::Subtree,
+    bindings: &Bindings,
+    nesting: &mut Vec<usize>,
+) -> Option<tt::Subtree> {
+    let token_trees = template
+        .token_trees
+        .iter()
+        .map(|it| expand_tt(it, bindings, nesting))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(tt::Subtree {
+        token_trees,
+        delimiter: template.delimiter,
+    })
+}
+
+fn expand_tt(
+    template: &mbe::TokenTree,
+    bindings: &Bindings,
+    nesting: &mut Vec<usize>,
+) -> Option<tt::TokenTree> {
+    let res: tt::TokenTree = match template {
+        mbe::TokenTree::Subtree(subtree) => expand_subtree(subtree, bindings, nesting)?.into(),
+        mbe::TokenTree::Repeat(repeat) => {
+            let count = repeat_count(&repeat.subtree, bindings, nesting)?;
+            let mut token_trees = Vec::new();
+            for idx in 0..count {
+                if idx > 0 {
+                    if let Some(separator) = &repeat.separator {
+                        token_trees.push(without_id(separator.clone()).into());
+                    }
+                }
+                nesting.push(idx);
+                let subtree = expand_subtree(&repeat.subtree, bindings, nesting);
+                nesting.pop();
+                token_trees.push(subtree?.into());
+            }
+            tt::Subtree {
+                token_trees,
+                delimiter: tt::Delimiter::None,
+            }
+            .into()
+        }
+        mbe::TokenTree::Leaf(leaf) => match leaf {
+            mbe::Leaf::Var(mbe::Var { text, .. }) if text == "crate" => tt::Leaf::from(tt::Ident {
+                text: "$crate".into(),
+                id: tt::TokenId::unspecified(),
+            })
+            .into(),
+            mbe::Leaf::Var(mbe::Var { text, .. }) => match bindings.get(text, nesting)? {
+                Binding::Simple(it) => it.clone(),
+                Binding::Nested(_) => return None,
+            },
+            mbe::Leaf::Ident(it) => without_id(it.clone().into()).into(),
+            mbe::Leaf::Punct(it) => without_id((*it).into()).into(),
+            mbe::Leaf::Literal(it) => without_id(it.clone().into()).into(),
+        },
+    };
+    Some(res)
+}
+
+/// The number of iterations of a repetition in the template is the number
+/// of iterations of the repetition its variables were matched by.
+fn repeat_count(template: &mbe::Subtree, bindings: &Bindings, nesting: &[usize]) -> Option<usize> {
+    template
+        .vars()
+        .into_iter()
+        .filter_map(|var| match bindings.get(var, nesting)? {
+            Binding::Nested(it) => Some(it.len()),
+            Binding::Simple(_) => None,
+        })
+        .max()
+}
+
+/// Tokens of the definition should not be mapped to the tokens of the call.
+fn without_id(leaf: tt::Leaf) -> tt::Leaf {
+    let id = tt::TokenId::unspecified();
+    match leaf {
+        tt::Leaf::Literal(it) => tt::Literal { id, ..it }.into(),
+        tt::Leaf::Punct(it) => tt::Punct { id, ..it }.into(),
+        tt::Leaf::Ident(it) => tt::Ident { id, ..it }.into(),
+    }
+}
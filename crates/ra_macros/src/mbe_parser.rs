@@ -0,0 +1,113 @@
+use crate::{
+    mbe,
+    tt,
+    tt_cursor::TtCursor,
+};
+
+/// Whether a `$var` needs a fragment specifier: it does in patterns, and it
+/// doesn't in templates.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Pattern,
+    Template,
+}
+
+pub(crate) fn parse(tt: &tt::Subtree) -> Option<mbe::MacroRules> {
+    let mut parser = TtCursor::new(tt);
+    let mut rules = Vec::new();
+    while !parser.is_eof() {
+        rules.push(parse_rule(&mut parser)?);
+        if parser.expect_char(';').is_none() && !parser.is_eof() {
+            return None;
+        }
+    }
+    Some(mbe::MacroRules { rules })
+}
+
+fn parse_rule(p: &mut TtCursor) -> Option<mbe::Rule> {
+    let lhs = parse_subtree(p.eat_subtree()?, Mode::Pattern)?;
+    p.expect_char('=')?;
+    p.expect_char('>')?;
+    let mut rhs = parse_subtree(p.eat_subtree()?, Mode::Template)?;
+    // the delimiters of the right hand side are not part of the expansion
+    rhs.delimiter = tt::Delimiter::None;
+    Some(mbe::Rule { lhs, rhs })
+}
+
+fn parse_subtree(tt: &tt::Subtree, mode: Mode) -> Option<mbe::Subtree> {
+    let mut token_trees = Vec::new();
+    let mut p = TtCursor::new(tt);
+    while let Some(tt) = p.eat() {
+        let child: mbe::TokenTree = match tt {
+            tt::TokenTree::Leaf(leaf) => match leaf {
+                tt::Leaf::Punct(tt::Punct { char: '$', .. }) => {
+                    if p.at_ident().is_some() {
+                        mbe::Leaf::from(parse_var(&mut p, mode)?).into()
+                    } else {
+                        parse_repeat(&mut p, mode)?.into()
+                    }
+                }
+                tt::Leaf::Punct(it) => mbe::Leaf::Punct(*it).into(),
+                tt::Leaf::Ident(it) => mbe::Leaf::Ident(it.clone()).into(),
+                tt::Leaf::Literal(it) => mbe::Leaf::Literal(it.clone()).into(),
+            },
+            tt::TokenTree::Subtree(subtree) => parse_subtree(subtree, mode)?.into(),
+        };
+        token_trees.push(child);
+    }
+    Some(mbe::Subtree {
+        delimiter: tt.delimiter,
+        token_trees,
+    })
+}
+
+fn parse_var(p: &mut TtCursor, mode: Mode) -> Option<mbe::Var> {
+    let text = p.eat_ident()?.text.clone();
+    let kind = if mode == Mode::Pattern && text != "crate" {
+        p.expect_char(':')?;
+        Some(p.eat_ident()?.text.clone())
+    } else {
+        None
+    };
+    Some(mbe::Var { text, kind })
+}
+
+fn parse_repeat(p: &mut TtCursor, mode: Mode) -> Option<mbe::Repeat> {
+    let mut subtree = parse_subtree(p.eat_subtree()?, mode)?;
+    subtree.delimiter = tt::Delimiter::None;
+    let separator = match p.eat()? {
+        tt::TokenTree::Leaf(tt::Leaf::Punct(punct)) if repeat_kind(punct.char).is_some() => {
+            let kind = repeat_kind(punct.char)?;
+            return Some(mbe::Repeat {
+                subtree,
+                kind,
+                separator: None,
+            });
+        }
+        // the separator is always followed by the repeat operator, but that
+        // doesn't make it `Joint` in the expansion
+        tt::TokenTree::Leaf(tt::Leaf::Punct(punct)) => tt::Punct {
+            spacing: tt::Spacing::Alone,
+            ..*punct
+        }
+        .into(),
+        tt::TokenTree::Leaf(leaf) => leaf.clone(),
+        tt::TokenTree::Subtree(_) => return None,
+    };
+    let kind = repeat_kind(p.eat_punct()?.char)?;
+    Some(mbe::Repeat {
+        subtree,
+        kind,
+        separator: Some(separator),
+    })
+}
+
+fn repeat_kind(char: char) -> Option<mbe::RepeatKind> {
+    let res = match char {
+        '*' => mbe::RepeatKind::ZeroOrMore,
+        '+' => mbe::RepeatKind::OneOrMore,
+        '?' => mbe::RepeatKind::ZeroOrOne,
+        _ => return None,
+    };
+    Some(res)
+}
@@ -0,0 +1,112 @@
+use ra_syntax::{
+    ast, AstNode, SyntaxKind, SyntaxNodeRef, TextRange, TextUnit,
+    SyntaxKind::*,
+};
+
+use crate::tt;
+
+/// Maps the `tt::TokenId`s of a converted token tree to the ranges of the
+/// original tokens, relative to the start of the token tree.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TokenMap {
+    tokens: Vec<TextRange>,
+}
+
+impl TokenMap {
+    pub fn relative_range_of(&self, id: tt::TokenId) -> Option<TextRange> {
+        self.tokens.get(id.0 as usize).cloned()
+    }
+
+    fn alloc(&mut self, relative_range: TextRange) -> tt::TokenId {
+        let id = tt::TokenId(self.tokens.len() as u32);
+        self.tokens.push(relative_range);
+        id
+    }
+}
+
+/// Converts the token tree of a macro call or definition to a `tt::Subtree`.
+/// Returns `None` if the delimiters are unbalanced.
+pub fn ast_to_token_tree(ast: ast::TokenTree) -> Option<(tt::Subtree, TokenMap)> {
+    let mut token_map = TokenMap::default();
+    let node = ast.syntax();
+    let tt = convert_tt(&mut token_map, node.range().start(), node)?;
+    Some((tt, token_map))
+}
+
+fn convert_tt(
+    token_map: &mut TokenMap,
+    global_offset: TextUnit,
+    tt: SyntaxNodeRef,
+) -> Option<tt::Subtree> {
+    let first_child = tt.first_child()?;
+    let last_child = tt.last_child()?;
+    let delimiter = match (first_child.kind(), last_child.kind()) {
+        (L_PAREN, R_PAREN) => tt::Delimiter::Parenthesis,
+        (L_CURLY, R_CURLY) => tt::Delimiter::Brace,
+        (L_BRACK, R_BRACK) => tt::Delimiter::Bracket,
+        _ => return None,
+    };
+    let mut token_trees = Vec::new();
+    let children = tt.children().collect::<Vec<_>>();
+    let inner = &children[1..children.len() - 1];
+    for (i, &child) in inner.iter().enumerate() {
+        if child.kind().is_trivia() {
+            continue;
+        }
+        if child.kind() == TOKEN_TREE {
+            token_trees.push(convert_tt(token_map, global_offset, child)?.into());
+            continue;
+        }
+        let text = child.leaf_text()?;
+        let range = child.range() - global_offset;
+        if is_punct(child.kind(), text) {
+            // the next token is not separated by trivia
+            let next_is_punct = inner
+                .get(i + 1)
+                .and_then(|next| Some(is_punct(next.kind(), next.leaf_text()?)))
+                .unwrap_or(false);
+            let n_chars = text.chars().count();
+            for (j, char) in text.chars().enumerate() {
+                let spacing = if j + 1 < n_chars || next_is_punct {
+                    tt::Spacing::Joint
+                } else {
+                    tt::Spacing::Alone
+                };
+                let char_range = TextRange::offset_len(
+                    range.start() + TextUnit::from(j as u32),
+                    TextUnit::of_char(char),
+                );
+                let id = token_map.alloc(char_range);
+                token_trees.push(tt::Leaf::from(tt::Punct { char, spacing, id }).into());
+            }
+        } else {
+            let id = token_map.alloc(range);
+            let text = text.clone();
+            let leaf: tt::Leaf = if is_literal(child.kind()) {
+                tt::Literal { text, id }.into()
+            } else {
+                tt::Ident { text, id }.into()
+            };
+            token_trees.push(leaf.into());
+        }
+    }
+    Some(tt::Subtree {
+        delimiter,
+        token_trees,
+    })
+}
+
+fn is_literal(kind: SyntaxKind) -> bool {
+    match kind {
+        INT_NUMBER | FLOAT_NUMBER | CHAR | BYTE | STRING | RAW_STRING | BYTE_STRING
+        | RAW_BYTE_STRING | TRUE_KW | FALSE_KW => true,
+        _ => false,
+    }
+}
+
+fn is_punct(kind: SyntaxKind, text: &str) -> bool {
+    if kind.is_trivia() || kind == TOKEN_TREE || is_literal(kind) {
+        return false;
+    }
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_punctuation() && c != '\'')
+}
@@ -0,0 +1,195 @@
+//! Token trees are the input and the output of macros: a sequence of tokens,
+//! where the tokens inside `()`, `[]` and `{}` are grouped into a subtree.
+//!
+//! Unlike in the syntax tree, punctuation is split into single characters
+//! (`::` is two `:` puncts, the first one `Joint`), and there's no trivia.
+
+use std::fmt;
+
+use ra_syntax::{SmolStr, TextRange, TextUnit};
+
+/// Links a token of a token tree to the original token in the source, see
+/// `TokenMap`. Tokens created by the macro itself have an unspecified id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TokenId(pub u32);
+
+impl TokenId {
+    pub const fn unspecified() -> TokenId {
+        TokenId(!0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenTree {
+    Leaf(Leaf),
+    Subtree(Subtree),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Leaf {
+    Literal(Literal),
+    Punct(Punct),
+    Ident(Ident),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subtree {
+    pub delimiter: Delimiter,
+    pub token_trees: Vec<TokenTree>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    Parenthesis,
+    Brace,
+    Bracket,
+    /// An invisible group, produced by macro transcription.
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Literal {
+    pub text: SmolStr,
+    pub id: TokenId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Punct {
+    pub char: char,
+    pub spacing: Spacing,
+    pub id: TokenId,
+}
+
+/// Whether a punct is immediately followed by another punct, like the first
+/// `:` of `::`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Spacing {
+    Alone,
+    Joint,
+}
+
+/// Identifiers, keywords and lifetimes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ident {
+    pub text: SmolStr,
+    pub id: TokenId,
+}
+
+macro_rules! impl_froms {
+    ($e:ident: $($v:ident),*) => {
+        $(
+            impl From<$v> for $e {
+                fn from(it: $v) -> $e {
+                    $e::$v(it)
+                }
+            }
+        )*
+    }
+}
+
+impl_froms!(Leaf: Literal, Punct, Ident);
+
+impl From<Subtree> for TokenTree {
+    fn from(it: Subtree) -> TokenTree {
+        TokenTree::Subtree(it)
+    }
+}
+
+impl<T: Into<Leaf>> From<T> for TokenTree {
+    fn from(it: T) -> TokenTree {
+        TokenTree::Leaf(it.into())
+    }
+}
+
+impl Subtree {
+    /// Converts the tree to source text, together with the ranges of the
+    /// tokens with a specified id in that text.
+    pub fn to_text(&self) -> (String, Vec<(TokenId, TextRange)>) {
+        let mut renderer = Renderer::default();
+        renderer.subtree(self);
+        (renderer.text, renderer.ranges)
+    }
+}
+
+impl Delimiter {
+    fn chars(self) -> Option<(char, char)> {
+        match self {
+            Delimiter::Parenthesis => Some(('(', ')')),
+            Delimiter::Brace => Some(('{', '}')),
+            Delimiter::Bracket => Some(('[', ']')),
+            Delimiter::None => None,
+        }
+    }
+}
+
+/// Puts a space between tokens, unless they are parts of the same punct
+/// or the first and the last tokens of a subtree.
+#[derive(Default)]
+struct Renderer {
+    text: String,
+    ranges: Vec<(TokenId, TextRange)>,
+    needs_space: bool,
+}
+
+impl Renderer {
+    fn subtree(&mut self, subtree: &Subtree) {
+        let delimiters = subtree.delimiter.chars();
+        if let Some((open, _)) = delimiters {
+            self.token(&open.to_string(), TokenId::unspecified());
+            self.needs_space = false;
+        }
+        for tt in subtree.token_trees.iter() {
+            match tt {
+                TokenTree::Subtree(it) => self.subtree(it),
+                TokenTree::Leaf(Leaf::Literal(it)) => self.token(&it.text, it.id),
+                TokenTree::Leaf(Leaf::Ident(it)) => self.token(&it.text, it.id),
+                TokenTree::Leaf(Leaf::Punct(it)) => {
+                    self.token(&it.char.to_string(), it.id);
+                    self.needs_space = it.spacing == Spacing::Alone;
+                }
+            }
+        }
+        if let Some((_, close)) = delimiters {
+            self.needs_space = false;
+            self.token(&close.to_string(), TokenId::unspecified());
+        }
+    }
+
+    fn token(&mut self, text: &str, id: TokenId) {
+        if self.needs_space {
+            self.text.push(' ');
+        }
+        let start = TextUnit::of_str(&self.text);
+        self.text.push_str(text);
+        if id != TokenId::unspecified() {
+            let range = TextRange::offset_len(start, TextUnit::of_str(text));
+            self.ranges.push((id, range));
+        }
+        self.needs_space = true;
+    }
+}
+
+impl fmt::Display for TokenTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenTree::Leaf(it) => fmt::Display::fmt(it, f),
+            TokenTree::Subtree(it) => fmt::Display::fmt(it, f),
+        }
+    }
+}
+
+impl fmt::Display for Subtree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_text().0)
+    }
+}
+
+impl fmt::Display for Leaf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Leaf::Literal(it) => fmt::Display::fmt(&it.text, f),
+            Leaf::Punct(it) => fmt::Display::fmt(&it.char, f),
+            Leaf::Ident(it) => fmt::Display::fmt(&it.text, f),
+        }
+    }
+}
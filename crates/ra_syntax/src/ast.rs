@@ -104,6 +104,9 @@ pub trait AttrsOwner<'a>: AstNode<'a> {
     fn attrs(self) -> AstChildren<'a, Attr<'a>> {
         children(self)
     }
+    fn has_atom_attr(self, atom: &str) -> bool {
+        self.attrs().filter_map(|x| x.as_atom()).any(|x| x == atom)
+    }
 }
 
 pub trait DocCommentsOwner<'a>: AstNode<'a> {
@@ -130,12 +133,6 @@ pub trait DocCommentsOwner<'a>: AstNode<'a> {
     }
 }
 
-impl<'a> FnDef<'a> {
-    pub fn has_atom_attr(&self, atom: &str) -> bool {
-        self.attrs().filter_map(|x| x.as_atom()).any(|x| x == atom)
-    }
-}
-
 impl<'a> Attr<'a> {
     pub fn as_atom(&self) -> Option<SmolStr> {
         let tt = self.value()?;
@@ -311,6 +308,27 @@ impl<'a> Module<'a> {
     }
 }
 
+impl<'a> MacroCall<'a> {
+    /// For `macro_rules! foo { ... }`, returns the name of the defined
+    /// macro, `foo`.
+    pub fn macro_rules_name(self) -> Option<SmolStr> {
+        let path = self.path()?;
+        if path.qualifier().is_some() || path.segment()?.name_ref()?.text() != "macro_rules" {
+            return None;
+        }
+        let name = self
+            .syntax()
+            .children()
+            .skip_while(|it| it.kind() != EXCL)
+            .skip(1)
+            .find(|it| !it.kind().is_trivia())?;
+        if name.kind() != IDENT {
+            return None;
+        }
+        Some(name.leaf_text().unwrap().clone())
+    }
+}
+
 impl<'a> LetStmt<'a> {
     pub fn has_semi(self) -> bool {
         match self.syntax().last_child() {
@@ -361,6 +379,15 @@ impl<'a> PathSegment<'a> {
         };
         Some(res)
     }
+
+    /// `true` for a segment with a leading `::`, like the first one in
+    /// `::foo::bar`.
+    pub fn has_colon_colon(self) -> bool {
+        match self.syntax().first_child() {
+            Some(it) => it.kind() == COLONCOLON,
+            None => false,
+        }
+    }
 }
 
 impl<'a> Path<'a> {
@@ -384,6 +411,34 @@ impl<'a> UseTreeList<'a> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisibilityKind<'a> {
+    In(Path<'a>),
+    PubCrate,
+    PubSuper,
+    PubSelf,
+    Pub,
+}
+
+impl<'a> Visibility<'a> {
+    pub fn kind(self) -> VisibilityKind<'a> {
+        if let Some(path) = self.path() {
+            return VisibilityKind::In(path);
+        }
+        let kw = |kind| self.syntax().children().any(|it| it.kind() == kind);
+        if kw(SUPER_KW) {
+            VisibilityKind::PubSuper
+        } else if kw(SELF_KW) {
+            VisibilityKind::PubSelf
+        } else if kw(CRATE_KW) {
+            // both `pub(crate)` and `crate`
+            VisibilityKind::PubCrate
+        } else {
+            VisibilityKind::Pub
+        }
+    }
+}
+
 fn child_opt<'a, P: AstNode<'a>, C: AstNode<'a>>(parent: P) -> Option<C> {
     children(parent).next()
 }
@@ -467,6 +522,22 @@ impl<'a> RefExpr<'a> {
     }
 }
 
+impl<'a> RefPat<'a> {
+    pub fn is_mut(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == MUT_KW)
+    }
+}
+
+impl<'a> BindPat<'a> {
+    pub fn is_mutable(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == MUT_KW)
+    }
+
+    pub fn is_ref(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == REF_KW)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PrefixOp {
     /// The `*` operator for dereferencing
@@ -488,6 +559,214 @@ impl<'a> PrefixExpr<'a> {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    /// The `||` operator for boolean OR
+    BooleanOr,
+    /// The `&&` operator for boolean AND
+    BooleanAnd,
+    /// The `==` operator for equality testing
+    EqualityTest,
+    /// The `!=` operator for equality testing
+    NegatedEqualityTest,
+    /// The `<=` operator for lesser-equal testing
+    LesserEqualTest,
+    /// The `>=` operator for greater-equal testing
+    GreaterEqualTest,
+    /// The `<` operator for comparison
+    LesserTest,
+    /// The `>` operator for comparison
+    GreaterTest,
+    /// The `+` operator for addition
+    Addition,
+    /// The `*` operator for multiplication
+    Multiplication,
+    /// The `-` operator for subtraction
+    Subtraction,
+    /// The `/` operator for division
+    Division,
+    /// The `%` operator for remainder after division
+    Remainder,
+    /// The `<<` operator for left shift
+    LeftShift,
+    /// The `>>` operator for right shift
+    RightShift,
+    /// The `^` operator for bitwise XOR
+    BitwiseXor,
+    /// The `|` operator for bitwise OR
+    BitwiseOr,
+    /// The `&` operator for bitwise AND
+    BitwiseAnd,
+    /// The `=` operator for assignment
+    Assignment,
+    /// The `+=` operator for assignment after addition
+    AddAssign,
+    /// The `/=` operator for assignment after division
+    DivAssign,
+    /// The `*=` operator for assignment after multiplication
+    MulAssign,
+    /// The `-=` operator for assignment after subtraction
+    SubAssign,
+    /// The `<<=` operator for assignment after shifting left
+    ShlAssign,
+    /// The `>>=` operator for assignment after shifting right
+    ShrAssign,
+    /// The `^=` operator for assignment after bitwise XOR
+    BitXorAssign,
+    /// The `|=` operator for assignment after bitwise OR
+    BitOrAssign,
+    /// The `&=` operator for assignment after bitwise AND
+    BitAndAssign,
+}
+
+impl<'a> BinExpr<'a> {
+    pub fn op(&self) -> Option<BinOp> {
+        self.syntax()
+            .children()
+            .filter_map(|c| match c.kind() {
+                PIPEPIPE => Some(BinOp::BooleanOr),
+                AMPAMP => Some(BinOp::BooleanAnd),
+                EQEQ => Some(BinOp::EqualityTest),
+                NEQ => Some(BinOp::NegatedEqualityTest),
+                LTEQ => Some(BinOp::LesserEqualTest),
+                GTEQ => Some(BinOp::GreaterEqualTest),
+                L_ANGLE => Some(BinOp::LesserTest),
+                R_ANGLE => Some(BinOp::GreaterTest),
+                PLUS => Some(BinOp::Addition),
+                STAR => Some(BinOp::Multiplication),
+                MINUS => Some(BinOp::Subtraction),
+                SLASH => Some(BinOp::Division),
+                PERCENT => Some(BinOp::Remainder),
+                SHL => Some(BinOp::LeftShift),
+                SHR => Some(BinOp::RightShift),
+                CARET => Some(BinOp::BitwiseXor),
+                PIPE => Some(BinOp::BitwiseOr),
+                AMP => Some(BinOp::BitwiseAnd),
+                EQ => Some(BinOp::Assignment),
+                PLUSEQ => Some(BinOp::AddAssign),
+                SLASHEQ => Some(BinOp::DivAssign),
+                STAREQ => Some(BinOp::MulAssign),
+                MINUSEQ => Some(BinOp::SubAssign),
+                SHLEQ => Some(BinOp::ShlAssign),
+                SHREQ => Some(BinOp::ShrAssign),
+                CARETEQ => Some(BinOp::BitXorAssign),
+                PIPEEQ => Some(BinOp::BitOrAssign),
+                AMPEQ => Some(BinOp::BitAndAssign),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn lhs(self) -> Option<Expr<'a>> {
+        children(self).nth(0)
+    }
+
+    pub fn rhs(self) -> Option<Expr<'a>> {
+        children(self).nth(1)
+    }
+}
+
+impl<'a> ArrayExpr<'a> {
+    /// Whether this is a repeat expression like `[0; 16]`; its `exprs` are
+    /// then the element and the length.
+    pub fn is_repeat(self) -> bool {
+        self.syntax().children().any(|n| n.kind() == SEMI)
+    }
+}
+
+impl<'a> IndexExpr<'a> {
+    pub fn base(self) -> Option<Expr<'a>> {
+        children(self).nth(0)
+    }
+
+    pub fn index(self) -> Option<Expr<'a>> {
+        children(self).nth(1)
+    }
+}
+
+impl<'a> RangeExpr<'a> {
+    /// The start of the range, i.e. `a` in `a..b`. Note that `..b` has an end
+    /// but no start, so we need to look at the position of the operator.
+    pub fn start(self) -> Option<Expr<'a>> {
+        let first = self.syntax().first_child()?;
+        if first.kind() == DOTDOT || first.kind() == DOTDOTEQ {
+            return None;
+        }
+        Expr::cast(first)
+    }
+
+    pub fn end(self) -> Option<Expr<'a>> {
+        let last = self.syntax().last_child()?;
+        if last.kind() == DOTDOT || last.kind() == DOTDOTEQ {
+            return None;
+        }
+        Expr::cast(last)
+    }
+
+    pub fn is_inclusive(self) -> bool {
+        self.syntax().children().any(|n| n.kind() == DOTDOTEQ)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LiteralFlavor {
+    String,
+    ByteString,
+    Char,
+    Byte,
+    IntNumber { suffix: Option<SmolStr> },
+    FloatNumber { suffix: Option<SmolStr> },
+    Bool,
+}
+
+impl<'a> Literal<'a> {
+    pub fn token(self) -> SyntaxNodeRef<'a> {
+        self.syntax()
+            .first_child()
+            .expect("literals always contain a token")
+    }
+
+    pub fn flavor(self) -> LiteralFlavor {
+        let token = self.token();
+        match token.kind() {
+            INT_NUMBER => {
+                let text = token.leaf_text().unwrap();
+                let allowed_suffix_list = [
+                    "isize", "i128", "i64", "i32", "i16", "i8", "usize", "u128", "u64", "u32",
+                    "u16", "u8",
+                ];
+                // `1f32` is a float; `0x1f32` is a hexadecimal integer though
+                if !text.starts_with("0x") {
+                    if let Some(suffix) = ["f32", "f64"].iter().find(|&s| text.ends_with(s)) {
+                        return LiteralFlavor::FloatNumber {
+                            suffix: Some(SmolStr::new(*suffix)),
+                        };
+                    }
+                }
+                let suffix = allowed_suffix_list
+                    .iter()
+                    .find(|&s| text.ends_with(s))
+                    .map(|&s| SmolStr::new(s));
+                LiteralFlavor::IntNumber { suffix }
+            }
+            FLOAT_NUMBER => {
+                let text = token.leaf_text().unwrap();
+                let suffix = ["f32", "f64"]
+                    .iter()
+                    .find(|&s| text.ends_with(s))
+                    .map(|&s| SmolStr::new(s));
+                LiteralFlavor::FloatNumber { suffix }
+            }
+            STRING | RAW_STRING => LiteralFlavor::String,
+            TRUE_KW | FALSE_KW => LiteralFlavor::Bool,
+            BYTE_STRING | RAW_BYTE_STRING => LiteralFlavor::ByteString,
+            CHAR => LiteralFlavor::Char,
+            BYTE => LiteralFlavor::Byte,
+            _ => unreachable!(),
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum SelfParamFlavor {
     /// self
@@ -531,3 +810,70 @@ fn test_doc_comment_of_items() {
     let module = file.syntax().descendants().find_map(Module::cast).unwrap();
     assert_eq!("doc", module.doc_comment_text());
 }
+
+#[test]
+fn test_literal_flavor() {
+    let file = SourceFileNode::parse(
+        r#"
+        fn f() { 1; 1u8; 1f32; 0x1f32; 1.0; 1.0f64; "s"; b's'; 'c'; true; }
+        "#,
+    );
+    let flavors: Vec<_> = file
+        .syntax()
+        .descendants()
+        .filter_map(Literal::cast)
+        .map(|l| l.flavor())
+        .collect();
+    let suffix = |s: &str| Some(SmolStr::new(s));
+    assert_eq!(
+        flavors,
+        vec![
+            LiteralFlavor::IntNumber { suffix: None },
+            LiteralFlavor::IntNumber {
+                suffix: suffix("u8")
+            },
+            LiteralFlavor::FloatNumber {
+                suffix: suffix("f32")
+            },
+            LiteralFlavor::IntNumber { suffix: None },
+            LiteralFlavor::FloatNumber { suffix: None },
+            LiteralFlavor::FloatNumber {
+                suffix: suffix("f64")
+            },
+            LiteralFlavor::String,
+            LiteralFlavor::Byte,
+            LiteralFlavor::Char,
+            LiteralFlavor::Bool,
+        ]
+    );
+}
+
+#[test]
+fn test_visibility_kind() {
+    let file = SourceFileNode::parse(
+        r#"
+        pub fn a() {} pub(crate) fn b() {} crate fn c() {} pub(super) fn d() {}
+        pub(self) fn e() {} pub(in foo::bar) fn f() {}
+        "#,
+    );
+    let kinds: Vec<_> = file
+        .syntax()
+        .descendants()
+        .filter_map(Visibility::cast)
+        .map(|vis| match vis.kind() {
+            VisibilityKind::In(path) => format!("in {}", path.syntax().text()),
+            kind => format!("{:?}", kind),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "Pub",
+            "PubCrate",
+            "PubCrate",
+            "PubSuper",
+            "PubSelf",
+            "in foo::bar"
+        ]
+    );
+}
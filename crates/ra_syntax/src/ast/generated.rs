@@ -18,6 +18,44 @@ use crate::{
     SyntaxKind::*,
 };
 
+// Alias
+#[derive(Debug, Clone, Copy,)]
+pub struct AliasNode<R: TreeRoot<RaTypes> = OwnedRoot> {
+    pub(crate) syntax: SyntaxNode<R>,
+}
+pub type Alias<'a> = AliasNode<RefRoot<'a>>;
+
+impl<R1: TreeRoot<RaTypes>, R2: TreeRoot<RaTypes>> PartialEq<AliasNode<R1>> for AliasNode<R2> {
+    fn eq(&self, other: &AliasNode<R1>) -> bool { self.syntax == other.syntax }
+}
+impl<R: TreeRoot<RaTypes>> Eq for AliasNode<R> {}
+impl<R: TreeRoot<RaTypes>> Hash for AliasNode<R> {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.syntax.hash(state) }
+}
+
+impl<'a> AstNode<'a> for Alias<'a> {
+    fn cast(syntax: SyntaxNodeRef<'a>) -> Option<Self> {
+        match syntax.kind() {
+            ALIAS => Some(Alias { syntax }),
+            _ => None,
+        }
+    }
+    fn syntax(self) -> SyntaxNodeRef<'a> { self.syntax }
+}
+
+impl<R: TreeRoot<RaTypes>> AliasNode<R> {
+    pub fn borrowed(&self) -> Alias {
+        AliasNode { syntax: self.syntax.borrowed() }
+    }
+    pub fn owned(&self) -> AliasNode {
+        AliasNode { syntax: self.syntax.owned() }
+    }
+}
+
+
+impl<'a> ast::NameOwner<'a> for Alias<'a> {}
+impl<'a> Alias<'a> {}
+
 // ArgList
 #[derive(Debug, Clone, Copy,)]
 pub struct ArgListNode<R: TreeRoot<RaTypes> = OwnedRoot> {
@@ -94,7 +132,11 @@ impl<R: TreeRoot<RaTypes>> ArrayExprNode<R> {
 }
 
 
-impl<'a> ArrayExpr<'a> {}
+impl<'a> ArrayExpr<'a> {
+    pub fn exprs(self) -> impl Iterator<Item = Expr<'a>> + 'a {
+        super::children(self)
+    }
+}
 
 // ArrayType
 #[derive(Debug, Clone, Copy,)]
@@ -141,6 +183,51 @@ impl<'a> ArrayType<'a> {
     }
 }
 
+// AssocTypeArg
+#[derive(Debug, Clone, Copy,)]
+pub struct AssocTypeArgNode<R: TreeRoot<RaTypes> = OwnedRoot> {
+    pub(crate) syntax: SyntaxNode<R>,
+}
+pub type AssocTypeArg<'a> = AssocTypeArgNode<RefRoot<'a>>;
+
+impl<R1: TreeRoot<RaTypes>, R2: TreeRoot<RaTypes>> PartialEq<AssocTypeArgNode<R1>> for AssocTypeArgNode<R2> {
+    fn eq(&self, other: &AssocTypeArgNode<R1>) -> bool { self.syntax == other.syntax }
+}
+impl<R: TreeRoot<RaTypes>> Eq for AssocTypeArgNode<R> {}
+impl<R: TreeRoot<RaTypes>> Hash for AssocTypeArgNode<R> {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.syntax.hash(state) }
+}
+
+impl<'a> AstNode<'a> for AssocTypeArg<'a> {
+    fn cast(syntax: SyntaxNodeRef<'a>) -> Option<Self> {
+        match syntax.kind() {
+            ASSOC_TYPE_ARG => Some(AssocTypeArg { syntax }),
+            _ => None,
+        }
+    }
+    fn syntax(self) -> SyntaxNodeRef<'a> { self.syntax }
+}
+
+impl<R: TreeRoot<RaTypes>> AssocTypeArgNode<R> {
+    pub fn borrowed(&self) -> AssocTypeArg {
+        AssocTypeArgNode { syntax: self.syntax.borrowed() }
+    }
+    pub fn owned(&self) -> AssocTypeArgNode {
+        AssocTypeArgNode { syntax: self.syntax.owned() }
+    }
+}
+
+
+impl<'a> AssocTypeArg<'a> {
+    pub fn name_ref(self) -> Option<NameRef<'a>> {
+        super::child_opt(self)
+    }
+
+    pub fn type_ref(self) -> Option<TypeRef<'a>> {
+        super::child_opt(self)
+    }
+}
+
 // Attr
 #[derive(Debug, Clone, Copy,)]
 pub struct AttrNode<R: TreeRoot<RaTypes> = OwnedRoot> {
@@ -255,7 +342,11 @@ impl<R: TreeRoot<RaTypes>> BindPatNode<R> {
 
 
 impl<'a> ast::NameOwner<'a> for BindPat<'a> {}
-impl<'a> BindPat<'a> {}
+impl<'a> BindPat<'a> {
+    pub fn pat(self) -> Option<Pat<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // Block
 #[derive(Debug, Clone, Copy,)]
@@ -378,7 +469,11 @@ impl<R: TreeRoot<RaTypes>> BreakExprNode<R> {
 }
 
 
-impl<'a> BreakExpr<'a> {}
+impl<'a> BreakExpr<'a> {
+    pub fn expr(self) -> Option<Expr<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // Byte
 #[derive(Debug, Clone, Copy,)]
@@ -700,7 +795,11 @@ impl<'a> ast::NameOwner<'a> for ConstDef<'a> {}
 impl<'a> ast::TypeParamsOwner<'a> for ConstDef<'a> {}
 impl<'a> ast::AttrsOwner<'a> for ConstDef<'a> {}
 impl<'a> ast::DocCommentsOwner<'a> for ConstDef<'a> {}
-impl<'a> ConstDef<'a> {}
+impl<'a> ConstDef<'a> {
+    pub fn type_ref(self) -> Option<TypeRef<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // ContinueExpr
 #[derive(Debug, Clone, Copy,)]
@@ -925,7 +1024,6 @@ pub enum Expr<'a> {
     MatchExpr(MatchExpr<'a>),
     MatchArmList(MatchArmList<'a>),
     MatchArm(MatchArm<'a>),
-    MatchGuard(MatchGuard<'a>),
     StructLit(StructLit<'a>),
     NamedFieldList(NamedFieldList<'a>),
     NamedField(NamedField<'a>),
@@ -962,7 +1060,6 @@ impl<'a> AstNode<'a> for Expr<'a> {
             MATCH_EXPR => Some(Expr::MatchExpr(MatchExpr { syntax })),
             MATCH_ARM_LIST => Some(Expr::MatchArmList(MatchArmList { syntax })),
             MATCH_ARM => Some(Expr::MatchArm(MatchArm { syntax })),
-            MATCH_GUARD => Some(Expr::MatchGuard(MatchGuard { syntax })),
             STRUCT_LIT => Some(Expr::StructLit(StructLit { syntax })),
             NAMED_FIELD_LIST => Some(Expr::NamedFieldList(NamedFieldList { syntax })),
             NAMED_FIELD => Some(Expr::NamedField(NamedField { syntax })),
@@ -999,7 +1096,6 @@ impl<'a> AstNode<'a> for Expr<'a> {
             Expr::MatchExpr(inner) => inner.syntax(),
             Expr::MatchArmList(inner) => inner.syntax(),
             Expr::MatchArm(inner) => inner.syntax(),
-            Expr::MatchGuard(inner) => inner.syntax(),
             Expr::StructLit(inner) => inner.syntax(),
             Expr::NamedFieldList(inner) => inner.syntax(),
             Expr::NamedField(inner) => inner.syntax(),
@@ -1096,7 +1192,13 @@ impl<R: TreeRoot<RaTypes>> ExternCrateItemNode<R> {
 }
 
 
-impl<'a> ExternCrateItem<'a> {}
+impl<'a> ast::NameOwner<'a> for ExternCrateItem<'a> {}
+impl<'a> ast::VisibilityOwner<'a> for ExternCrateItem<'a> {}
+impl<'a> ExternCrateItem<'a> {
+    pub fn alias(self) -> Option<Alias<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // FieldExpr
 #[derive(Debug, Clone, Copy,)]
@@ -1143,6 +1245,48 @@ impl<'a> FieldExpr<'a> {
     }
 }
 
+// FieldPat
+#[derive(Debug, Clone, Copy,)]
+pub struct FieldPatNode<R: TreeRoot<RaTypes> = OwnedRoot> {
+    pub(crate) syntax: SyntaxNode<R>,
+}
+pub type FieldPat<'a> = FieldPatNode<RefRoot<'a>>;
+
+impl<R1: TreeRoot<RaTypes>, R2: TreeRoot<RaTypes>> PartialEq<FieldPatNode<R1>> for FieldPatNode<R2> {
+    fn eq(&self, other: &FieldPatNode<R1>) -> bool { self.syntax == other.syntax }
+}
+impl<R: TreeRoot<RaTypes>> Eq for FieldPatNode<R> {}
+impl<R: TreeRoot<RaTypes>> Hash for FieldPatNode<R> {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.syntax.hash(state) }
+}
+
+impl<'a> AstNode<'a> for FieldPat<'a> {
+    fn cast(syntax: SyntaxNodeRef<'a>) -> Option<Self> {
+        match syntax.kind() {
+            FIELD_PAT => Some(FieldPat { syntax }),
+            _ => None,
+        }
+    }
+    fn syntax(self) -> SyntaxNodeRef<'a> { self.syntax }
+}
+
+impl<R: TreeRoot<RaTypes>> FieldPatNode<R> {
+    pub fn borrowed(&self) -> FieldPat {
+        FieldPatNode { syntax: self.syntax.borrowed() }
+    }
+    pub fn owned(&self) -> FieldPatNode {
+        FieldPatNode { syntax: self.syntax.owned() }
+    }
+}
+
+
+impl<'a> ast::NameOwner<'a> for FieldPat<'a> {}
+impl<'a> FieldPat<'a> {
+    pub fn pat(self) -> Option<Pat<'a>> {
+        super::child_opt(self)
+    }
+}
+
 // FieldPatList
 #[derive(Debug, Clone, Copy,)]
 pub struct FieldPatListNode<R: TreeRoot<RaTypes> = OwnedRoot> {
@@ -1178,7 +1322,15 @@ impl<R: TreeRoot<RaTypes>> FieldPatListNode<R> {
 }
 
 
-impl<'a> FieldPatList<'a> {}
+impl<'a> FieldPatList<'a> {
+    pub fn field_pats(self) -> impl Iterator<Item = FieldPat<'a>> + 'a {
+        super::children(self)
+    }
+
+    pub fn bind_pats(self) -> impl Iterator<Item = BindPat<'a>> + 'a {
+        super::children(self)
+    }
+}
 
 // FnDef
 #[derive(Debug, Clone, Copy,)]
@@ -1442,6 +1594,7 @@ impl<R: TreeRoot<RaTypes>> ImplBlockNode<R> {
 }
 
 
+impl<'a> ast::TypeParamsOwner<'a> for ImplBlock<'a> {}
 impl<'a> ImplBlock<'a> {
     pub fn item_list(self) -> Option<ItemList<'a>> {
         super::child_opt(self)
@@ -1761,6 +1914,47 @@ impl<R: TreeRoot<RaTypes>> LifetimeNode<R> {
 
 impl<'a> Lifetime<'a> {}
 
+// LifetimeArg
+#[derive(Debug, Clone, Copy,)]
+pub struct LifetimeArgNode<R: TreeRoot<RaTypes> = OwnedRoot> {
+    pub(crate) syntax: SyntaxNode<R>,
+}
+pub type LifetimeArg<'a> = LifetimeArgNode<RefRoot<'a>>;
+
+impl<R1: TreeRoot<RaTypes>, R2: TreeRoot<RaTypes>> PartialEq<LifetimeArgNode<R1>> for LifetimeArgNode<R2> {
+    fn eq(&self, other: &LifetimeArgNode<R1>) -> bool { self.syntax == other.syntax }
+}
+impl<R: TreeRoot<RaTypes>> Eq for LifetimeArgNode<R> {}
+impl<R: TreeRoot<RaTypes>> Hash for LifetimeArgNode<R> {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.syntax.hash(state) }
+}
+
+impl<'a> AstNode<'a> for LifetimeArg<'a> {
+    fn cast(syntax: SyntaxNodeRef<'a>) -> Option<Self> {
+        match syntax.kind() {
+            LIFETIME_ARG => Some(LifetimeArg { syntax }),
+            _ => None,
+        }
+    }
+    fn syntax(self) -> SyntaxNodeRef<'a> { self.syntax }
+}
+
+impl<R: TreeRoot<RaTypes>> LifetimeArgNode<R> {
+    pub fn borrowed(&self) -> LifetimeArg {
+        LifetimeArgNode { syntax: self.syntax.borrowed() }
+    }
+    pub fn owned(&self) -> LifetimeArgNode {
+        LifetimeArgNode { syntax: self.syntax.owned() }
+    }
+}
+
+
+impl<'a> LifetimeArg<'a> {
+    pub fn lifetime(self) -> Option<Lifetime<'a>> {
+        super::child_opt(self)
+    }
+}
+
 // LifetimeParam
 #[derive(Debug, Clone, Copy,)]
 pub struct LifetimeParamNode<R: TreeRoot<RaTypes> = OwnedRoot> {
@@ -1839,6 +2033,47 @@ impl<R: TreeRoot<RaTypes>> LiteralNode<R> {
 
 impl<'a> Literal<'a> {}
 
+// LiteralPat
+#[derive(Debug, Clone, Copy,)]
+pub struct LiteralPatNode<R: TreeRoot<RaTypes> = OwnedRoot> {
+    pub(crate) syntax: SyntaxNode<R>,
+}
+pub type LiteralPat<'a> = LiteralPatNode<RefRoot<'a>>;
+
+impl<R1: TreeRoot<RaTypes>, R2: TreeRoot<RaTypes>> PartialEq<LiteralPatNode<R1>> for LiteralPatNode<R2> {
+    fn eq(&self, other: &LiteralPatNode<R1>) -> bool { self.syntax == other.syntax }
+}
+impl<R: TreeRoot<RaTypes>> Eq for LiteralPatNode<R> {}
+impl<R: TreeRoot<RaTypes>> Hash for LiteralPatNode<R> {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.syntax.hash(state) }
+}
+
+impl<'a> AstNode<'a> for LiteralPat<'a> {
+    fn cast(syntax: SyntaxNodeRef<'a>) -> Option<Self> {
+        match syntax.kind() {
+            LITERAL_PAT => Some(LiteralPat { syntax }),
+            _ => None,
+        }
+    }
+    fn syntax(self) -> SyntaxNodeRef<'a> { self.syntax }
+}
+
+impl<R: TreeRoot<RaTypes>> LiteralPatNode<R> {
+    pub fn borrowed(&self) -> LiteralPat {
+        LiteralPatNode { syntax: self.syntax.borrowed() }
+    }
+    pub fn owned(&self) -> LiteralPatNode {
+        LiteralPatNode { syntax: self.syntax.owned() }
+    }
+}
+
+
+impl<'a> LiteralPat<'a> {
+    pub fn literal(self) -> Option<Literal<'a>> {
+        super::child_opt(self)
+    }
+}
+
 // LoopExpr
 #[derive(Debug, Clone, Copy,)]
 pub struct LoopExprNode<R: TreeRoot<RaTypes> = OwnedRoot> {
@@ -1912,6 +2147,7 @@ impl<R: TreeRoot<RaTypes>> MacroCallNode<R> {
 }
 
 
+impl<'a> ast::AttrsOwner<'a> for MacroCall<'a> {}
 impl<'a> MacroCall<'a> {
     pub fn token_tree(self) -> Option<TokenTree<'a>> {
         super::child_opt(self)
@@ -2092,7 +2328,11 @@ impl<R: TreeRoot<RaTypes>> MatchGuardNode<R> {
 }
 
 
-impl<'a> MatchGuard<'a> {}
+impl<'a> MatchGuard<'a> {
+    pub fn expr(self) -> Option<Expr<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // MethodCallExpr
 #[derive(Debug, Clone, Copy,)]
@@ -2732,6 +2972,7 @@ pub enum Pat<'a> {
     TuplePat(TuplePat<'a>),
     SlicePat(SlicePat<'a>),
     RangePat(RangePat<'a>),
+    LiteralPat(LiteralPat<'a>),
 }
 
 impl<'a> AstNode<'a> for Pat<'a> {
@@ -2747,6 +2988,7 @@ impl<'a> AstNode<'a> for Pat<'a> {
             TUPLE_PAT => Some(Pat::TuplePat(TuplePat { syntax })),
             SLICE_PAT => Some(Pat::SlicePat(SlicePat { syntax })),
             RANGE_PAT => Some(Pat::RangePat(RangePat { syntax })),
+            LITERAL_PAT => Some(Pat::LiteralPat(LiteralPat { syntax })),
             _ => None,
         }
     }
@@ -2762,6 +3004,7 @@ impl<'a> AstNode<'a> for Pat<'a> {
             Pat::TuplePat(inner) => inner.syntax(),
             Pat::SlicePat(inner) => inner.syntax(),
             Pat::RangePat(inner) => inner.syntax(),
+            Pat::LiteralPat(inner) => inner.syntax(),
         }
     }
 }
@@ -2889,7 +3132,11 @@ impl<R: TreeRoot<RaTypes>> PathPatNode<R> {
 }
 
 
-impl<'a> PathPat<'a> {}
+impl<'a> PathPat<'a> {
+    pub fn path(self) -> Option<Path<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // PathSegment
 #[derive(Debug, Clone, Copy,)]
@@ -2930,6 +3177,18 @@ impl<'a> PathSegment<'a> {
     pub fn name_ref(self) -> Option<NameRef<'a>> {
         super::child_opt(self)
     }
+
+    pub fn type_arg_list(self) -> Option<TypeArgList<'a>> {
+        super::child_opt(self)
+    }
+
+    pub fn param_list(self) -> Option<ParamList<'a>> {
+        super::child_opt(self)
+    }
+
+    pub fn ret_type(self) -> Option<RetType<'a>> {
+        super::child_opt(self)
+    }
 }
 
 // PathType
@@ -3363,7 +3622,11 @@ impl<R: TreeRoot<RaTypes>> RefPatNode<R> {
 }
 
 
-impl<'a> RefPat<'a> {}
+impl<'a> RefPat<'a> {
+    pub fn pat(self) -> Option<Pat<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // ReferenceType
 #[derive(Debug, Clone, Copy,)]
@@ -3605,7 +3868,11 @@ impl<R: TreeRoot<RaTypes>> SlicePatNode<R> {
 }
 
 
-impl<'a> SlicePat<'a> {}
+impl<'a> SlicePat<'a> {
+    pub fn args(self) -> impl Iterator<Item = Pat<'a>> + 'a {
+        super::children(self)
+    }
+}
 
 // SliceType
 #[derive(Debug, Clone, Copy,)]
@@ -3731,7 +3998,11 @@ impl<'a> ast::NameOwner<'a> for StaticDef<'a> {}
 impl<'a> ast::TypeParamsOwner<'a> for StaticDef<'a> {}
 impl<'a> ast::AttrsOwner<'a> for StaticDef<'a> {}
 impl<'a> ast::DocCommentsOwner<'a> for StaticDef<'a> {}
-impl<'a> StaticDef<'a> {}
+impl<'a> StaticDef<'a> {
+    pub fn type_ref(self) -> Option<TypeRef<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // Stmt
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -3917,7 +4188,15 @@ impl<R: TreeRoot<RaTypes>> StructPatNode<R> {
 }
 
 
-impl<'a> StructPat<'a> {}
+impl<'a> StructPat<'a> {
+    pub fn field_pat_list(self) -> Option<FieldPatList<'a>> {
+        super::child_opt(self)
+    }
+
+    pub fn path(self) -> Option<Path<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // TokenTree
 #[derive(Debug, Clone, Copy,)]
@@ -3993,9 +4272,14 @@ impl<R: TreeRoot<RaTypes>> TraitDefNode<R> {
 
 impl<'a> ast::VisibilityOwner<'a> for TraitDef<'a> {}
 impl<'a> ast::NameOwner<'a> for TraitDef<'a> {}
+impl<'a> ast::TypeParamsOwner<'a> for TraitDef<'a> {}
 impl<'a> ast::AttrsOwner<'a> for TraitDef<'a> {}
 impl<'a> ast::DocCommentsOwner<'a> for TraitDef<'a> {}
-impl<'a> TraitDef<'a> {}
+impl<'a> TraitDef<'a> {
+    pub fn item_list(self) -> Option<ItemList<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // TryExpr
 #[derive(Debug, Clone, Copy,)]
@@ -4073,7 +4357,11 @@ impl<R: TreeRoot<RaTypes>> TupleExprNode<R> {
 }
 
 
-impl<'a> TupleExpr<'a> {}
+impl<'a> TupleExpr<'a> {
+    pub fn exprs(self) -> impl Iterator<Item = Expr<'a>> + 'a {
+        super::children(self)
+    }
+}
 
 // TuplePat
 #[derive(Debug, Clone, Copy,)]
@@ -4110,7 +4398,11 @@ impl<R: TreeRoot<RaTypes>> TuplePatNode<R> {
 }
 
 
-impl<'a> TuplePat<'a> {}
+impl<'a> TuplePat<'a> {
+    pub fn args(self) -> impl Iterator<Item = Pat<'a>> + 'a {
+        super::children(self)
+    }
+}
 
 // TupleStructPat
 #[derive(Debug, Clone, Copy,)]
@@ -4147,7 +4439,15 @@ impl<R: TreeRoot<RaTypes>> TupleStructPatNode<R> {
 }
 
 
-impl<'a> TupleStructPat<'a> {}
+impl<'a> TupleStructPat<'a> {
+    pub fn args(self) -> impl Iterator<Item = Pat<'a>> + 'a {
+        super::children(self)
+    }
+
+    pub fn path(self) -> Option<Path<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // TupleType
 #[derive(Debug, Clone, Copy,)]
@@ -4190,6 +4490,96 @@ impl<'a> TupleType<'a> {
     }
 }
 
+// TypeArg
+#[derive(Debug, Clone, Copy,)]
+pub struct TypeArgNode<R: TreeRoot<RaTypes> = OwnedRoot> {
+    pub(crate) syntax: SyntaxNode<R>,
+}
+pub type TypeArg<'a> = TypeArgNode<RefRoot<'a>>;
+
+impl<R1: TreeRoot<RaTypes>, R2: TreeRoot<RaTypes>> PartialEq<TypeArgNode<R1>> for TypeArgNode<R2> {
+    fn eq(&self, other: &TypeArgNode<R1>) -> bool { self.syntax == other.syntax }
+}
+impl<R: TreeRoot<RaTypes>> Eq for TypeArgNode<R> {}
+impl<R: TreeRoot<RaTypes>> Hash for TypeArgNode<R> {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.syntax.hash(state) }
+}
+
+impl<'a> AstNode<'a> for TypeArg<'a> {
+    fn cast(syntax: SyntaxNodeRef<'a>) -> Option<Self> {
+        match syntax.kind() {
+            TYPE_ARG => Some(TypeArg { syntax }),
+            _ => None,
+        }
+    }
+    fn syntax(self) -> SyntaxNodeRef<'a> { self.syntax }
+}
+
+impl<R: TreeRoot<RaTypes>> TypeArgNode<R> {
+    pub fn borrowed(&self) -> TypeArg {
+        TypeArgNode { syntax: self.syntax.borrowed() }
+    }
+    pub fn owned(&self) -> TypeArgNode {
+        TypeArgNode { syntax: self.syntax.owned() }
+    }
+}
+
+
+impl<'a> TypeArg<'a> {
+    pub fn type_ref(self) -> Option<TypeRef<'a>> {
+        super::child_opt(self)
+    }
+}
+
+// TypeArgList
+#[derive(Debug, Clone, Copy,)]
+pub struct TypeArgListNode<R: TreeRoot<RaTypes> = OwnedRoot> {
+    pub(crate) syntax: SyntaxNode<R>,
+}
+pub type TypeArgList<'a> = TypeArgListNode<RefRoot<'a>>;
+
+impl<R1: TreeRoot<RaTypes>, R2: TreeRoot<RaTypes>> PartialEq<TypeArgListNode<R1>> for TypeArgListNode<R2> {
+    fn eq(&self, other: &TypeArgListNode<R1>) -> bool { self.syntax == other.syntax }
+}
+impl<R: TreeRoot<RaTypes>> Eq for TypeArgListNode<R> {}
+impl<R: TreeRoot<RaTypes>> Hash for TypeArgListNode<R> {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.syntax.hash(state) }
+}
+
+impl<'a> AstNode<'a> for TypeArgList<'a> {
+    fn cast(syntax: SyntaxNodeRef<'a>) -> Option<Self> {
+        match syntax.kind() {
+            TYPE_ARG_LIST => Some(TypeArgList { syntax }),
+            _ => None,
+        }
+    }
+    fn syntax(self) -> SyntaxNodeRef<'a> { self.syntax }
+}
+
+impl<R: TreeRoot<RaTypes>> TypeArgListNode<R> {
+    pub fn borrowed(&self) -> TypeArgList {
+        TypeArgListNode { syntax: self.syntax.borrowed() }
+    }
+    pub fn owned(&self) -> TypeArgListNode {
+        TypeArgListNode { syntax: self.syntax.owned() }
+    }
+}
+
+
+impl<'a> TypeArgList<'a> {
+    pub fn type_args(self) -> impl Iterator<Item = TypeArg<'a>> + 'a {
+        super::children(self)
+    }
+
+    pub fn lifetime_args(self) -> impl Iterator<Item = LifetimeArg<'a>> + 'a {
+        super::children(self)
+    }
+
+    pub fn assoc_type_args(self) -> impl Iterator<Item = AssocTypeArg<'a>> + 'a {
+        super::children(self)
+    }
+}
+
 // TypeDef
 #[derive(Debug, Clone, Copy,)]
 pub struct TypeDefNode<R: TreeRoot<RaTypes> = OwnedRoot> {
@@ -4230,7 +4620,11 @@ impl<'a> ast::NameOwner<'a> for TypeDef<'a> {}
 impl<'a> ast::TypeParamsOwner<'a> for TypeDef<'a> {}
 impl<'a> ast::AttrsOwner<'a> for TypeDef<'a> {}
 impl<'a> ast::DocCommentsOwner<'a> for TypeDef<'a> {}
-impl<'a> TypeDef<'a> {}
+impl<'a> TypeDef<'a> {
+    pub fn type_ref(self) -> Option<TypeRef<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // TypeParam
 #[derive(Debug, Clone, Copy,)]
@@ -4268,7 +4662,11 @@ impl<R: TreeRoot<RaTypes>> TypeParamNode<R> {
 
 
 impl<'a> ast::NameOwner<'a> for TypeParam<'a> {}
-impl<'a> TypeParam<'a> {}
+impl<'a> TypeParam<'a> {
+    pub fn bounds(self) -> impl Iterator<Item = PathType<'a>> + 'a {
+        super::children(self)
+    }
+}
 
 // TypeParamList
 #[derive(Debug, Clone, Copy,)]
@@ -4408,6 +4806,7 @@ impl<R: TreeRoot<RaTypes>> UseItemNode<R> {
 }
 
 
+impl<'a> ast::VisibilityOwner<'a> for UseItem<'a> {}
 impl<'a> UseItem<'a> {
     pub fn use_tree(self) -> Option<UseTree<'a>> {
         super::child_opt(self)
@@ -4535,7 +4934,11 @@ impl<R: TreeRoot<RaTypes>> VisibilityNode<R> {
 }
 
 
-impl<'a> Visibility<'a> {}
+impl<'a> Visibility<'a> {
+    pub fn path(self) -> Option<Path<'a>> {
+        super::child_opt(self)
+    }
+}
 
 // WhereClause
 #[derive(Debug, Clone, Copy,)]
@@ -54,12 +54,8 @@ fn atom_pat(p: &mut Parser, recovery_set: TokenSet) -> Option<CompletedMarker> {
     //         "hello" => (),
     //     }
     // }
-    if p.at(MINUS) && (p.nth(1) == INT_NUMBER || p.nth(1) == FLOAT_NUMBER) {
-        p.bump();
-    }
-
-    if let Some(m) = expressions::literal(p) {
-        return Some(m);
+    if is_literal_pat_start(p) {
+        return Some(literal_pat(p));
     }
 
     let m = match la0 {
@@ -75,6 +71,21 @@ fn atom_pat(p: &mut Parser, recovery_set: TokenSet) -> Option<CompletedMarker> {
     Some(m)
 }
 
+fn is_literal_pat_start(p: &mut Parser) -> bool {
+    p.at(MINUS) && (p.nth(1) == INT_NUMBER || p.nth(1) == FLOAT_NUMBER)
+        || p.at_ts(expressions::LITERAL_FIRST)
+}
+
+fn literal_pat(p: &mut Parser) -> CompletedMarker {
+    assert!(is_literal_pat_start(p));
+    let m = p.start();
+    if p.at(MINUS) {
+        p.bump();
+    }
+    expressions::literal(p);
+    m.complete(p, LITERAL_PAT)
+}
+
 // test path_part
 // fn foo() {
 //     let foo::Bar = ();
@@ -129,9 +140,11 @@ fn field_pat_list(p: &mut Parser) {
         match p.current() {
             DOTDOT => p.bump(),
             IDENT if p.nth(1) == COLON => {
-                p.bump();
+                let m = p.start();
+                name(p);
                 p.bump();
                 pattern(p);
+                m.complete(p, FIELD_PAT);
             }
             L_CURLY => error_block(p, "expected ident"),
             _ => {
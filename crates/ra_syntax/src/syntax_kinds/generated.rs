@@ -148,9 +148,11 @@ pub enum SyntaxKind {
     REF_PAT,
     BIND_PAT,
     PLACEHOLDER_PAT,
+    LITERAL_PAT,
     PATH_PAT,
     STRUCT_PAT,
     FIELD_PAT_LIST,
+    FIELD_PAT,
     TUPLE_STRUCT_PAT,
     TUPLE_PAT,
     SLICE_PAT,
@@ -409,9 +411,11 @@ impl SyntaxKind {
             REF_PAT => &SyntaxInfo { name: "REF_PAT" },
             BIND_PAT => &SyntaxInfo { name: "BIND_PAT" },
             PLACEHOLDER_PAT => &SyntaxInfo { name: "PLACEHOLDER_PAT" },
+            LITERAL_PAT => &SyntaxInfo { name: "LITERAL_PAT" },
             PATH_PAT => &SyntaxInfo { name: "PATH_PAT" },
             STRUCT_PAT => &SyntaxInfo { name: "STRUCT_PAT" },
             FIELD_PAT_LIST => &SyntaxInfo { name: "FIELD_PAT_LIST" },
+            FIELD_PAT => &SyntaxInfo { name: "FIELD_PAT" },
             TUPLE_STRUCT_PAT => &SyntaxInfo { name: "TUPLE_STRUCT_PAT" },
             TUPLE_PAT => &SyntaxInfo { name: "TUPLE_PAT" },
             SLICE_PAT => &SyntaxInfo { name: "SLICE_PAT" },
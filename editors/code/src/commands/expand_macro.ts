@@ -0,0 +1,74 @@
+import * as vscode from 'vscode';
+import { Position, TextDocumentIdentifier } from 'vscode-languageclient';
+
+import { Server } from '../server';
+
+// The `.rs` extension makes vscode highlight the expansion as Rust
+export const expandMacroUri = vscode.Uri.parse(
+    'ra-lsp-expand-macro://expandmacro/expansion.rs'
+);
+
+export class ExpandMacroContentProvider
+    implements vscode.TextDocumentContentProvider {
+    public eventEmitter = new vscode.EventEmitter<vscode.Uri>();
+
+    public async provideTextDocumentContent(
+        uri: vscode.Uri
+    ): Promise<string> {
+        const editor = vscode.window.activeTextEditor;
+        if (editor == null || editor.document.languageId !== 'rust') {
+            return '';
+        }
+        const request: ExpandMacroParams = {
+            textDocument: { uri: editor.document.uri.toString() },
+            position: Server.client.code2ProtocolConverter.asPosition(
+                editor.selection.active
+            )
+        };
+        const expanded = await Server.client.sendRequest<ExpandMacroResult>(
+            'm/expandMacro',
+            request
+        );
+        if (expanded == null) {
+            return '// No macro call at the cursor';
+        }
+        const header = `// Recursive expansion of ${expanded.name}! macro`;
+        return `${header}\n\n${expanded.expansion}\n`;
+    }
+
+    get onDidChange(): vscode.Event<vscode.Uri> {
+        return this.eventEmitter.event;
+    }
+}
+
+interface ExpandMacroParams {
+    textDocument: TextDocumentIdentifier;
+    position: Position;
+}
+
+interface ExpandedMacro {
+    name: string;
+    expansion: string;
+}
+
+type ExpandMacroResult = ExpandedMacro | null;
+
+// Opens the virtual file that shows the expansion of the macro call at the
+// cursor
+//
+// The contents of the file come from the `ExpandMacroContentProvider`. As
+// vscode caches them, the provider is asked to recompute them for the current
+// cursor first.
+export function createHandler(provider: ExpandMacroContentProvider) {
+    return async () => {
+        provider.eventEmitter.fire(expandMacroUri);
+        const document = await vscode.workspace.openTextDocument(
+            expandMacroUri
+        );
+        return vscode.window.showTextDocument(
+            document,
+            vscode.ViewColumn.Two,
+            true
+        );
+    };
+}
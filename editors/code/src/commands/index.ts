@@ -1,4 +1,5 @@
 import * as applySourceChange from './apply_source_change';
+import * as expandMacro from './expand_macro';
 import * as extendSelection from './extend_selection';
 import * as joinLines from './join_lines';
 import * as matchingBrace from './matching_brace';
@@ -9,6 +10,7 @@ import * as syntaxTree from './syntaxTree';
 
 export {
     applySourceChange,
+    expandMacro,
     extendSelection,
     joinLines,
     matchingBrace,
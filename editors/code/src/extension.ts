@@ -2,6 +2,7 @@ import * as vscode from 'vscode';
 import * as lc from 'vscode-languageclient';
 
 import * as commands from './commands';
+import { ExpandMacroContentProvider } from './commands/expand_macro';
 import { TextDocumentContentProvider } from './commands/syntaxTree';
 import * as events from './events';
 import * as notifications from './notifications';
@@ -49,6 +50,17 @@ export function activate(context: vscode.ExtensionContext) {
     registerCommand('ra-lsp.joinLines', commands.joinLines.handle);
     registerCommand('ra-lsp.parentModule', commands.parentModule.handle);
     registerCommand('ra-lsp.run', commands.runnables.handle);
+    const expandMacroContentProvider = new ExpandMacroContentProvider();
+    disposeOnDeactivation(
+        vscode.workspace.registerTextDocumentContentProvider(
+            'ra-lsp-expand-macro',
+            expandMacroContentProvider
+        )
+    );
+    registerCommand(
+        'ra-lsp.expandMacro',
+        commands.expandMacro.createHandler(expandMacroContentProvider)
+    );
     registerCommand(
         'ra-lsp.applySourceChange',
         commands.applySourceChange.handle